@@ -0,0 +1,143 @@
+use crate::consumer::{reply, settle, trace_context};
+use crate::retry::{self, Retrier};
+use crate::setup::Config;
+use lapin::message::{Delivery, DeliveryResult};
+use phlow_sdk::delivery;
+use phlow_sdk::prelude::*;
+use phlow_sdk::tracing::{Dispatch, Level};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, timeout_at};
+
+/// Hands messages to the flow in batches of up to `batch_size`, waiting at most
+/// `batch_timeout` after the first message of a batch
+pub struct BatchConsumer {
+    pub id: ModuleId,
+    pub main_sender: MainRuntimeSender,
+    pub config: Arc<Config>,
+    pub channel: Arc<lapin::Channel>,
+    pub retrier: Arc<Retrier>,
+    pub dispatch: Dispatch,
+    pub hostname: String,
+}
+
+impl BatchConsumer {
+    pub fn start(self, consumer: lapin::Consumer) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Delivery>();
+
+        consumer.set_delegate(move |delivery: DeliveryResult| {
+            let tx = tx.clone();
+            Box::pin(async move {
+                match delivery {
+                    Ok(Some(delivery)) => {
+                        let _ = tx.send(delivery);
+                    }
+                    Ok(None) => { /* sem mensagens */ }
+                    Err(error) => log::error!("Failed to consume queue message {}", error),
+                }
+            })
+        });
+
+        let this = Arc::new(self);
+        tokio::spawn(async move {
+            while let Some(first) = rx.recv().await {
+                let deadline = Instant::now() + this.config.batch_timeout;
+                let mut batch = vec![first];
+                while batch.len() < this.config.batch_size {
+                    match timeout_at(deadline, rx.recv()).await {
+                        Ok(Some(delivery)) => batch.push(delivery),
+                        Ok(None) | Err(_) => break,
+                    }
+                }
+
+                let this = Arc::clone(&this);
+                tokio::spawn(async move { this.process(batch).await });
+            }
+        });
+    }
+
+    async fn process(&self, batch: Vec<Delivery>) {
+        let _guard = phlow_sdk::tracing::dispatcher::set_default(&self.dispatch);
+        use_log!();
+
+        let span = tracing::span!(
+            Level::INFO,
+            "message_batch_receive",
+            "messaging.system" = "rabbitmq",
+            "messaging.destination.name" = &self.config.queue_name,
+            "messaging.destination.kind" = "queue",
+            "messaging.operation" = "receive",
+            "messaging.protocol" = "AMQP",
+            "messaging.protocol_version" = "0.9.1",
+            "messaging.rabbitmq.consumer_tag" = &self.config.consumer_tag,
+            "messaging.client.id" = &self.hostname,
+            "messaging.batch.message_count" = batch.len(),
+        );
+        // The batch joins the trace of its first message
+        phlow_sdk::propagation::extract(&span, &trace_context(&batch[0].properties));
+        span_enter!(span);
+
+        let items: Vec<Value> = batch
+            .iter()
+            .map(|delivery| {
+                if self.config.envelope {
+                    retry::envelope(delivery, &self.config)
+                } else {
+                    String::from_utf8_lossy(&delivery.data)
+                        .to_string()
+                        .to_value()
+                }
+            })
+            .collect();
+        log::debug!("Received batch of {} messages", items.len());
+
+        let response = sender_package!(
+            span.clone(),
+            self.dispatch.clone(),
+            self.id,
+            self.main_sender.clone(),
+            Some(items.to_value())
+        )
+        .await
+        .ok();
+        log::debug!("Batch response: {:?}", response);
+
+        for (index, delivery) in batch.iter().enumerate() {
+            let result = item_result(response.as_ref(), index);
+            if delivery.properties.reply_to().is_some() {
+                reply(&self.channel, delivery, result).await;
+            } else {
+                let outcome = match (&response, result) {
+                    (Some(_), None) => Err("No result for the message in the batch".to_string()),
+                    (_, result) => delivery::outcome(result),
+                };
+                settle(delivery, outcome, &self.config, &self.retrier).await;
+            }
+        }
+    }
+}
+
+/// Result of the message at `index`: the item of an array response, or the whole
+/// response for every message otherwise
+fn item_result(response: Option<&Value>, index: usize) -> Option<&Value> {
+    match response? {
+        Value::Array(results) => results.values.get(index),
+        response => Some(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_result() {
+        let results = Value::json_to_value(r#"[true, false]"#).unwrap();
+        assert_eq!(item_result(Some(&results), 1), Some(&Value::Boolean(false)));
+        assert_eq!(item_result(Some(&results), 2), None);
+
+        let all = Value::Boolean(false);
+        assert_eq!(item_result(Some(&all), 5), Some(&all));
+        assert_eq!(item_result(None, 0), None);
+    }
+}
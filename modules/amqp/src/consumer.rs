@@ -1,9 +1,14 @@
+use crate::batch::BatchConsumer;
+use crate::retry::{self, Retrier};
+use crate::rpc;
 use crate::setup::Config;
-use lapin::message::DeliveryResult;
+use lapin::message::{Delivery, DeliveryResult};
 use lapin::{BasicProperties, options::*, types::FieldTable};
+use phlow_sdk::delivery;
 use phlow_sdk::prelude::*;
 use phlow_sdk::tracing::{Dispatch, Level, field};
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 // Nota: decisão sobre DLQ foi simplificada para tentar NACK com requeue=false
@@ -26,22 +31,28 @@ pub async fn consumer(
     let config = Arc::new(config);
     let main_sender = Arc::new(main_sender);
     let id = Arc::new(id);
+    let retrier = Arc::new(Retrier::new(channel.clone()));
     let channel = Arc::new(channel);
 
     // Se definido, limita o número de mensagens não confirmadas (concorrência)
-    if config.max_concurrency > 0 {
-        log::debug!(
-            "Setting basic_qos prefetch_count={} (max_concurrency)",
-            config.max_concurrency
+    let mut prefetch = config.prefetch.unwrap_or(config.max_concurrency);
+    // A batch never fills if the broker holds back part of its messages
+    if config.batch_size > 1 && prefetch > 0 && (prefetch as usize) < config.batch_size {
+        log::warn!(
+            "prefetch {} is below batch_size {}, using {}",
+            prefetch,
+            config.batch_size,
+            config.batch_size
         );
+        prefetch = config.batch_size.min(u16::MAX as usize) as u16;
+    }
+    if prefetch > 0 {
+        log::debug!("Setting basic_qos prefetch_count={}", prefetch);
         channel
-            .basic_qos(
-                config.max_concurrency,
-                lapin::options::BasicQosOptions { global: false },
-            )
+            .basic_qos(prefetch, lapin::options::BasicQosOptions { global: false })
             .await?;
     } else {
-        log::debug!("max_concurrency=0 (sem limites), não aplicando basic_qos");
+        log::debug!("prefetch=0 (sem limites), não aplicando basic_qos");
     }
 
     // Declare queue if not already declared
@@ -54,21 +65,41 @@ pub async fn consumer(
         )
         .await?;
 
-    let config_cloned = Arc::clone(&config);
-    let main_sender_cloned = Arc::clone(&main_sender);
-    let id_cloned = Arc::clone(&id);
-    let channel_cloned = Arc::clone(&channel);
     let hostname = match hostname::get() {
         Ok(name) => name.to_string_lossy().into_owned(),
         Err(_) => "unknown".to_string(),
     };
 
+    if config.batch_size > 1 {
+        log::debug!(
+            "Consuming in batches of up to {} messages",
+            config.batch_size
+        );
+        BatchConsumer {
+            id: *id,
+            main_sender: (*main_sender).clone(),
+            config,
+            channel,
+            retrier,
+            dispatch,
+            hostname,
+        }
+        .start(consumer);
+        return Ok(());
+    }
+
+    let config_cloned = Arc::clone(&config);
+    let main_sender_cloned = Arc::clone(&main_sender);
+    let id_cloned = Arc::clone(&id);
+    let channel_cloned = Arc::clone(&channel);
+
     consumer.set_delegate({
         let config = config_cloned;
         let dispatch = dispatch.clone();
         let main_sender = main_sender_cloned;
         let id = id_cloned;
         let channel = channel_cloned;
+        let retrier = retrier;
         let hostname_base = hostname;
 
         move |delivery: DeliveryResult| {
@@ -77,6 +108,7 @@ pub async fn consumer(
             let id = Arc::clone(&id);
             let dispatch = dispatch.clone();
             let channel = Arc::clone(&channel);
+            let retrier = Arc::clone(&retrier);
             let hostname = hostname_base.clone();
 
             Box::pin(async move {
@@ -101,80 +133,61 @@ pub async fn consumer(
                                 "messaging.message.payload_size_bytes" = field::Empty,
                                 "messaging.message.conversation_id" = field::Empty,
                             );
+                            phlow_sdk::propagation::extract(
+                                &span,
+                                &trace_context(&delivery.properties),
+                            );
                             span_enter!(span);
 
                             let sender = (*main_sender).clone();
                             let id_clone = (*id).clone();
-                            let data: Value = String::from_utf8_lossy(&delivery.data).to_string().to_value();
-                            span.record("messaging.message.payload_size_bytes", delivery.data.len());
+                            let data: Value = if config.envelope {
+                                retry::envelope(&delivery, &config)
+                            } else {
+                                String::from_utf8_lossy(&delivery.data)
+                                    .to_string()
+                                    .to_value()
+                            };
+                            span.record(
+                                "messaging.message.payload_size_bytes",
+                                delivery.data.len(),
+                            );
                             span.record("messaging.message.conversation_id", &id_clone.to_string());
 
-                            let retry_count = delivery.properties.headers()
-                                .as_ref()
-                                .and_then(|h| h.inner().get("x-retry-count"))
-                                .and_then(|v| v.as_long_long_int())
-                                .unwrap_or(0);
-                            log::debug!("Received message (retry {}/{}) {:?}", retry_count, config.max_retry, data);
-
-                            let response_value = sender_package!(span.clone(), dispatch.clone(), id_clone, sender, Some(data))
-                                .await
-                                .unwrap_or(Value::Null);
-                            log::debug!("Response: {:?}", response_value);
-
-                            let should_ack = match response_value {
-                                Value::Null => true,
-                                Value::Boolean(false) => false,
-                                Value::Boolean(true) => true,
-                                _ => true,
-                            };
+                            let retry_count = retry::retry_count(&delivery.properties);
+                            log::debug!(
+                                "Received message (retry {}/{}) {:?}",
+                                retry_count,
+                                config.max_retry,
+                                data
+                            );
 
-                            if should_ack {
-                                if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
-                                    log::error!("Failed to ack message: {}", e);
-                                } else {
-                                    log::debug!("Message acknowledged successfully");
-                                }
-                            } else {
-                                // Lógica de retry / DLQ
-                                if retry_count < config.max_retry {
-                                    log::debug!("Retrying message, current retry count: {}, max_retry: {}", retry_count, config.max_retry);
-                                    let mut headers = delivery.properties.headers().as_ref().cloned().unwrap_or_default();
-                                    headers.insert("x-retry-count".into(), (retry_count + 1).into());
-                                    let properties = BasicProperties::default().with_headers(headers);
-                                    match channel.basic_publish(
-                                        "",
-                                        &config.queue_name,
-                                        BasicPublishOptions::default(),
-                                        &delivery.data,
-                                        properties,
-                                    ).await {
-                                        Ok(_) => {
-                                            log::debug!("Message requeued for retry {}/{}", retry_count + 1, config.max_retry);
-                                            let _ = delivery.ack(BasicAckOptions::default()).await;
-                                        }
-                                        Err(e) => {
-                                            log::error!("Failed to requeue message: {}", e);
-                                            let _ = delivery.nack(BasicNackOptions { multiple: false, requeue: true }).await;
-                                        }
-                                    }
-                                } else if config.dlq_enable {
-                                    log::debug!("Max retries exceeded and DLQ enabled");
-                                    match delivery.nack(BasicNackOptions { multiple: false, requeue: false }).await {
-                                        Ok(_) => log::debug!("Message rejected (NACK) after {} retries - sent to DLQ", retry_count),
-                                        Err(e) => {
-                                            log::error!("Failed to nack message to DLQ: {}. Falling back to requeue", e);
-                                            let _ = delivery.nack(BasicNackOptions { multiple: false, requeue: true }).await;
-                                        }
-                                    }
-                                } else {
-                                    log::debug!("Max retries exceeded and DLQ disabled");
-                                    match delivery.ack(BasicAckOptions::default()).await {
-                                        Ok(_) => log::debug!("DLQ disabled - message discarded after {} retries", retry_count),
-                                        Err(e) => log::error!("Failed to ack message for discard: {}", e),
-                                    }
-                                }
+                            let response = sender_package!(
+                                span.clone(),
+                                dispatch.clone(),
+                                id_clone,
+                                sender,
+                                Some(data)
+                            )
+                            .await
+                            .ok();
+                            log::debug!("Response: {:?}", response);
+
+                            // A request: the response goes back as the reply
+                            if delivery.properties.reply_to().is_some() {
+                                reply(&channel, &delivery, response.as_ref()).await;
+                                return;
                             }
-                        }).detach();
+
+                            settle(
+                                &delivery,
+                                delivery::outcome(response.as_ref()),
+                                &config,
+                                &retrier,
+                            )
+                            .await;
+                        })
+                        .detach();
                     }
                     Ok(None) => { /* sem mensagens */ }
                     Err(error) => {
@@ -187,3 +200,158 @@ pub async fn consumer(
 
     Ok(())
 }
+
+/// Publishes the response as the reply of a request and acks it. Requests are never
+/// retried, since the requester only waits for one reply.
+pub async fn reply(channel: &lapin::Channel, delivery: &Delivery, response: Option<&Value>) {
+    let Some(reply_to) = delivery.properties.reply_to() else {
+        return;
+    };
+
+    let mut properties = BasicProperties::default();
+    if let Some(correlation_id) = delivery.properties.correlation_id() {
+        properties = properties.with_correlation_id(correlation_id.clone());
+    }
+    if let Err(e) = channel
+        .basic_publish(
+            "",
+            reply_to.as_str(),
+            BasicPublishOptions::default(),
+            &rpc::encode_reply(response.unwrap_or(&Value::Null)),
+            properties,
+        )
+        .await
+    {
+        log::error!("Failed to publish reply to {}: {}", reply_to, e);
+    }
+    if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+        log::error!("Failed to ack message: {}", e);
+    }
+}
+
+/// Acks a processed message, or retries or dead-letters a failed one
+pub async fn settle(
+    delivery: &Delivery,
+    result: Result<(), String>,
+    config: &Config,
+    retrier: &Retrier,
+) {
+    let error = match result {
+        Ok(()) => {
+            if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+                log::error!("Failed to ack message: {}", e);
+            } else {
+                log::debug!("Message acknowledged successfully");
+            }
+            return;
+        }
+        Err(error) => error,
+    };
+
+    let retry_count = retry::retry_count(&delivery.properties);
+
+    // Lógica de retry / DLQ
+    if retry_count < config.max_retry {
+        let attempt = retry_count + 1;
+        log::debug!(
+            "Retrying message in {}ms ({}/{}): {}",
+            config.retry_delay(attempt).as_millis(),
+            attempt,
+            config.max_retry,
+            error
+        );
+        match retrier.retry(delivery, config, attempt, &error).await {
+            Ok(()) => {
+                let _ = delivery.ack(BasicAckOptions::default()).await;
+            }
+            Err(e) => {
+                log::error!("Failed to requeue message: {}", e);
+                let _ = delivery
+                    .nack(BasicNackOptions {
+                        multiple: false,
+                        requeue: true,
+                    })
+                    .await;
+            }
+        }
+    } else if let (true, Some(dlq_queue)) = (config.dlq_enable, &config.dlq_queue) {
+        match retrier
+            .dead_letter(delivery, dlq_queue, config, retry_count, &error)
+            .await
+        {
+            Ok(()) => {
+                log::debug!(
+                    "Message sent to {} after {} retries: {}",
+                    dlq_queue,
+                    retry_count,
+                    error
+                );
+                let _ = delivery.ack(BasicAckOptions::default()).await;
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to publish message to {}: {}. Falling back to requeue",
+                    dlq_queue,
+                    e
+                );
+                let _ = delivery
+                    .nack(BasicNackOptions {
+                        multiple: false,
+                        requeue: true,
+                    })
+                    .await;
+            }
+        }
+    } else if config.dlq_enable {
+        log::debug!("Max retries exceeded and DLQ enabled");
+        match delivery
+            .nack(BasicNackOptions {
+                multiple: false,
+                requeue: false,
+            })
+            .await
+        {
+            Ok(_) => log::debug!(
+                "Message rejected (NACK) after {} retries - sent to DLQ",
+                retry_count
+            ),
+            Err(e) => {
+                log::error!(
+                    "Failed to nack message to DLQ: {}. Falling back to requeue",
+                    e
+                );
+                let _ = delivery
+                    .nack(BasicNackOptions {
+                        multiple: false,
+                        requeue: true,
+                    })
+                    .await;
+            }
+        }
+    } else {
+        log::debug!("Max retries exceeded and DLQ disabled");
+        match delivery.ack(BasicAckOptions::default()).await {
+            Ok(_) => log::debug!(
+                "DLQ disabled - message discarded after {} retries: {}",
+                retry_count,
+                error
+            ),
+            Err(e) => log::error!("Failed to ack message for discard: {}", e),
+        }
+    }
+}
+
+/// Collects the W3C trace context headers sent by the publisher
+pub fn trace_context(properties: &BasicProperties) -> HashMap<String, String> {
+    let Some(headers) = properties.headers() else {
+        return HashMap::new();
+    };
+
+    ["traceparent", "tracestate"]
+        .into_iter()
+        .filter_map(|name| {
+            let value = headers.inner().get(name)?.as_long_string()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
@@ -1,5 +1,8 @@
+mod batch;
 mod consumer;
 mod produce;
+mod retry;
+mod rpc;
 mod setup;
 use lapin::{Connection, ConnectionProperties};
 use phlow_sdk::prelude::*;
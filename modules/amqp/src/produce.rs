@@ -1,27 +1,60 @@
 use lapin::BasicProperties;
-use lapin::options::BasicPublishOptions;
+use lapin::options::{BasicPublishOptions, ConfirmSelectOptions};
 use lapin::protocol::basic::AMQPProperties;
-use lapin::publisher_confirm::Confirmation;
+use lapin::publisher_confirm::{Confirmation, PublisherConfirm};
 use lapin::types::{AMQPValue, ShortString};
 use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::setup::Config;
+use crate::rpc::{self, DIRECT_REPLY_TO, Rpc};
+use crate::setup::{Config, MANDATORY_WITHOUT_CONFIRM};
+
+#[derive(PartialEq)]
+enum Action {
+    Publish,
+    /// Publishes with `reply_to` and waits for the reply
+    Request,
+}
 
 struct Input {
+    action: Action,
     message: String,
     basic_props: AMQPProperties,
     exchange: Option<String>,
     routing_key: Option<String>,
     vhost: Option<String>,
+    mandatory: Option<bool>,
+    correlation_id: Option<String>,
+    timeout: Option<Duration>,
 }
 
-impl From<&Value> for Input {
-    fn from(value: &Value) -> Self {
+impl TryFrom<&Value> for Input {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let action = match value.get("action").map(|v| v.to_string()).as_deref() {
+            None | Some("publish") => Action::Publish,
+            Some("request") => Action::Request,
+            Some(action) => {
+                return Err(format!(
+                    "Unknown action: {}. Use publish or request",
+                    action
+                ));
+            }
+        };
         let message = value.get("message").unwrap_or(&Value::Null).to_string();
         let headers = value.get("headers").cloned();
         let exchange = value.get("exchange").and_then(|v| Some(v.to_string()));
         let routing_key = value.get("routing_key").and_then(|v| Some(v.to_string()));
         let vhost = value.get("vhost").and_then(|v| Some(v.to_string()));
+        let mandatory = value.get("mandatory").and_then(|v| v.as_bool()).copied();
+        let correlation_id = value.get("correlation_id").map(|v| v.to_string());
+        let reply_to = value.get("reply_to").map(|v| v.to_string());
+        let timeout = value
+            .get("timeout")
+            .and_then(|v| v.to_u64())
+            .map(Duration::from_millis);
 
         let basic_props = if let Some(headers) = headers {
             let mut field_table = lapin::types::FieldTable::default();
@@ -39,13 +72,40 @@ impl From<&Value> for Input {
             BasicProperties::default()
         };
 
-        Self {
+        let basic_props = match &reply_to {
+            Some(reply_to) => basic_props.with_reply_to(reply_to.as_str().into()),
+            None => basic_props,
+        };
+
+        Ok(Self {
+            action,
             message,
             basic_props,
             exchange,
             routing_key,
             vhost,
+            mandatory,
+            correlation_id,
+            timeout,
+        })
+    }
+}
+
+impl Input {
+    /// Adds trace context headers to the message, keeping any the flow set explicitly
+    fn propagate_trace(&mut self, trace_headers: HashMap<String, String>) {
+        if trace_headers.is_empty() {
+            return;
         }
+
+        let mut field_table = self.basic_props.headers().clone().unwrap_or_default();
+        for (key, value) in trace_headers {
+            let amqp_key = ShortString::from(key);
+            if !field_table.inner().contains_key(&amqp_key) {
+                field_table.insert(amqp_key, AMQPValue::LongString(value.into()));
+            }
+        }
+        self.basic_props = self.basic_props.clone().with_headers(field_table);
     }
 }
 
@@ -53,6 +113,10 @@ impl From<&Value> for Input {
 pub struct ProducerResponse {
     pub success: bool,
     pub error_message: Option<String>,
+    /// Reply of a request
+    pub reply: Option<Value>,
+    /// The message, when the broker returned it as unroutable
+    pub returned: Option<Value>,
 }
 
 impl ProducerResponse {
@@ -60,6 +124,66 @@ impl ProducerResponse {
         Self {
             success: false,
             error_message: Some(error_message.to_string()),
+            reply: None,
+            returned: None,
+        }
+    }
+
+    fn published() -> Self {
+        Self {
+            success: true,
+            error_message: None,
+            reply: None,
+            returned: None,
+        }
+    }
+}
+
+/// Waits for the broker to confirm the publish. A mandatory message that no queue
+/// received comes back in the confirmation and fails the publish.
+async fn confirmation(confirm: PublisherConfirm, timeout: Duration) -> ProducerResponse {
+    let confirm = match tokio::time::timeout(timeout, confirm).await {
+        Ok(Ok(confirm)) => confirm,
+        Ok(Err(e)) => {
+            return ProducerResponse::from_error(&format!("Publish confirmation error: {}", e));
+        }
+        Err(_) => {
+            return ProducerResponse::from_error(&format!(
+                "Publish not confirmed within {}ms",
+                timeout.as_millis()
+            ));
+        }
+    };
+
+    match confirm {
+        Confirmation::NotRequested => {
+            log::debug!("Published message without ack");
+            ProducerResponse::published()
+        }
+        Confirmation::Ack(None) => {
+            log::debug!("Ack");
+            ProducerResponse::published()
+        }
+        Confirmation::Ack(Some(returned)) | Confirmation::Nack(Some(returned)) => {
+            let err = format!(
+                "Message returned: {} ({})",
+                returned.reply_text, returned.reply_code
+            );
+            log::debug!("{}", err);
+            ProducerResponse {
+                returned: Some(json!({
+                    "reply_code": returned.reply_code,
+                    "reply_text": returned.reply_text.to_string(),
+                    "exchange": returned.delivery.exchange.to_string(),
+                    "routing_key": returned.delivery.routing_key.to_string(),
+                })),
+                ..ProducerResponse::from_error(&err)
+            }
+        }
+        Confirmation::Nack(None) => {
+            let err = "Nack: the broker rejected the message".to_string();
+            log::debug!("{}", err);
+            ProducerResponse::from_error(&err)
         }
     }
 }
@@ -83,18 +207,33 @@ pub async fn producer(
     };
     let conn = lapin::Connection::connect(&uri, lapin::ConnectionProperties::default()).await?;
 
+    if config.confirm {
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+    }
+
+    // Created on the first request, so flows that only publish never consume replies
+    let mut rpc: Option<Rpc> = None;
+
     for package in rx {
         log::debug!("Received package");
 
         let input = match package.input {
-            Some(input) => Input::from(&input),
-            None => {
-                let response = ProducerResponse::from_error("No input provided");
+            Some(ref input) => Input::try_from(input),
+            None => Err("No input provided".to_string()),
+        };
+        let mut input = match input {
+            Ok(input) => input,
+            Err(e) => {
+                let response = ProducerResponse::from_error(&e);
                 let _ = package.sender.send(response.to_value().into());
                 continue;
             }
         };
 
+        input.propagate_trace(package.trace_headers());
+
         // Determine effective vhost (input overrides config)
         let effective_vhost = input
             .vhost
@@ -121,10 +260,115 @@ pub async fn producer(
         };
         let routing_key = effective_routing_key_owned.as_str();
 
+        // Requests without a route fail right away instead of waiting for the timeout
+        let mandatory = input
+            .mandatory
+            .unwrap_or(config.mandatory || input.action == Action::Request);
+        let options = BasicPublishOptions {
+            mandatory,
+            ..Default::default()
+        };
+
+        if mandatory && !config.confirm && input.action != Action::Request {
+            let response = ProducerResponse::from_error(MANDATORY_WITHOUT_CONFIRM);
+            let _ = package.sender.send(response.to_value().into());
+            continue;
+        }
+
+        if input.action == Action::Request {
+            if effective_vhost != config.vhost {
+                let response = ProducerResponse::from_error("Requests use the vhost of the module");
+                let _ = package.sender.send(response.to_value().into());
+                continue;
+            }
+
+            if !rpc.as_ref().is_some_and(Rpc::is_connected) {
+                match Rpc::new(&conn).await {
+                    Ok(new_rpc) => rpc = Some(new_rpc),
+                    Err(e) => {
+                        let response = ProducerResponse::from_error(&format!(
+                            "Failed to open the reply channel: {}",
+                            e
+                        ));
+                        let _ = package.sender.send(response.to_value().into());
+                        continue;
+                    }
+                }
+            }
+            let rpc = rpc.as_ref().expect("reply channel");
+
+            let correlation_id = input
+                .correlation_id
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let reply = rpc.register(&correlation_id);
+            let properties = input
+                .basic_props
+                .clone()
+                .with_reply_to(DIRECT_REPLY_TO.into())
+                .with_correlation_id(correlation_id.as_str().into());
+
+            let published = match rpc
+                .channel
+                .basic_publish(
+                    &effective_exchange,
+                    routing_key,
+                    options,
+                    input.message.as_bytes(),
+                    properties,
+                )
+                .await
+            {
+                Ok(confirm) => confirmation(confirm, config.confirm_timeout).await,
+                Err(e) => ProducerResponse::from_error(&format!("Publish error: {}", e)),
+            };
+            if !published.success {
+                rpc.forget(&correlation_id);
+                let _ = package.sender.send(published.to_value().into());
+                continue;
+            }
+
+            log::debug!(
+                "Sent request {} to {} ({})",
+                correlation_id,
+                effective_exchange,
+                routing_key
+            );
+
+            // Waits on its own task, so other packages go on while the reply comes
+            let timeout = input.timeout.unwrap_or(config.rpc_timeout);
+            let rpc = rpc.clone();
+            tokio::spawn(async move {
+                let response = match tokio::time::timeout(timeout, reply).await {
+                    Ok(Ok(data)) => ProducerResponse {
+                        reply: Some(rpc::decode_reply(&data)),
+                        ..ProducerResponse::published()
+                    },
+                    Ok(Err(_)) => ProducerResponse::from_error("Reply channel closed"),
+                    Err(_) => {
+                        rpc.forget(&correlation_id);
+                        ProducerResponse::from_error(&format!(
+                            "No reply within {}ms",
+                            timeout.as_millis()
+                        ))
+                    }
+                };
+                let _ = package.sender.send(response.to_value().into());
+            });
+            continue;
+        }
+
         // Check if channel is closed and recreate if needed
         if !channel.status().connected() {
             log::debug!("Channel is closed, recreating...");
-            match conn.create_channel().await {
+            let new_channel = match conn.create_channel().await {
+                Ok(new_channel) if config.confirm => new_channel
+                    .confirm_select(ConfirmSelectOptions::default())
+                    .await
+                    .map(|_| new_channel),
+                result => result,
+            };
+            match new_channel {
                 Ok(new_channel) => {
                     channel = new_channel;
                     log::debug!("Channel recreated successfully");
@@ -163,15 +407,27 @@ pub async fn producer(
             {
                 Ok(temp_conn) => match temp_conn.create_channel().await {
                     Ok(temp_channel) => {
-                        temp_channel
-                            .basic_publish(
-                                &effective_exchange,
-                                routing_key,
-                                BasicPublishOptions::default(),
-                                input.message.as_bytes(),
-                                input.basic_props.clone(),
-                            )
-                            .await
+                        let selected = if config.confirm {
+                            temp_channel
+                                .confirm_select(ConfirmSelectOptions::default())
+                                .await
+                        } else {
+                            Ok(())
+                        };
+                        match selected {
+                            Ok(()) => {
+                                temp_channel
+                                    .basic_publish(
+                                        &effective_exchange,
+                                        routing_key,
+                                        options,
+                                        input.message.as_bytes(),
+                                        input.basic_props.clone(),
+                                    )
+                                    .await
+                            }
+                            Err(e) => Err(e),
+                        }
                     }
                     Err(e) => Err(e.into()),
                 },
@@ -182,28 +438,16 @@ pub async fn producer(
                 .basic_publish(
                     &effective_exchange,
                     routing_key,
-                    BasicPublishOptions::default(),
+                    options,
                     input.message.as_bytes(),
                     input.basic_props,
                 )
                 .await
         };
 
-        let confirm = match publish_result {
-            Ok(confirm_future) => match confirm_future.await {
-                Ok(confirm) => confirm,
-                Err(e) => {
-                    let response =
-                        ProducerResponse::from_error(&format!("Publish confirmation error: {}", e));
-                    let _ = package.sender.send(response.to_value().into());
-                    continue;
-                }
-            },
-            Err(e) => {
-                let response = ProducerResponse::from_error(&format!("Publish error: {}", e));
-                let _ = package.sender.send(response.to_value().into());
-                continue;
-            }
+        let response = match publish_result {
+            Ok(confirm) => confirmation(confirm, config.confirm_timeout).await,
+            Err(e) => ProducerResponse::from_error(&format!("Publish error: {}", e)),
         };
 
         log::debug!(
@@ -213,30 +457,7 @@ pub async fn producer(
             effective_vhost
         );
 
-        let (success, error_message) = match confirm {
-            Confirmation::NotRequested => {
-                log::debug!("Published message without ack");
-                (true, None)
-            }
-            Confirmation::Ack(msg) => {
-                log::debug!("Ack: {:?}", msg);
-                (true, None)
-            }
-            Confirmation::Nack(msg) => {
-                let err = format!("Nack: {:?}", msg);
-                log::debug!("{}", err);
-                (false, Some(err))
-            }
-        };
-
-        let _ = package.sender.send(
-            ProducerResponse {
-                success,
-                error_message,
-            }
-            .to_value()
-            .into(),
-        );
+        let _ = package.sender.send(response.to_value().into());
     }
 
     Ok(())
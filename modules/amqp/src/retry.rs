@@ -0,0 +1,221 @@
+use lapin::message::Delivery;
+use lapin::options::{BasicPublishOptions, QueueDeclareOptions};
+use lapin::types::{AMQPValue, FieldTable, ShortString};
+use lapin::{BasicProperties, Channel};
+use phlow_sdk::delivery::{LAST_ERROR_HEADER, RETRY_COUNT_HEADER};
+use phlow_sdk::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::setup::Config;
+
+pub fn retry_count(properties: &BasicProperties) -> i64 {
+    header(properties, RETRY_COUNT_HEADER)
+        .and_then(|v| v.as_long_long_int())
+        .unwrap_or(0)
+}
+
+pub fn last_error(properties: &BasicProperties) -> Option<String> {
+    header(properties, LAST_ERROR_HEADER)
+        .and_then(|v| v.as_long_string())
+        .map(|v| v.to_string())
+}
+
+fn header<'a>(properties: &'a BasicProperties, name: &str) -> Option<&'a AMQPValue> {
+    properties.headers().as_ref()?.inner().get(name)
+}
+
+/// Message as the flow receives it when `envelope` is enabled
+pub fn envelope(delivery: &Delivery, config: &Config) -> Value {
+    let headers: HashMap<String, Value> = delivery
+        .properties
+        .headers()
+        .as_ref()
+        .map(|headers| {
+            headers
+                .inner()
+                .iter()
+                .map(|(key, value)| (key.to_string(), amqp_to_value(value)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let retry = json!({
+        "count": retry_count(&delivery.properties),
+        "max": config.max_retry,
+        "last_error": last_error(&delivery.properties),
+    });
+
+    json!({
+        "body": String::from_utf8_lossy(&delivery.data).to_string(),
+        "headers": headers,
+        "exchange": delivery.exchange.to_string(),
+        "routing_key": delivery.routing_key.to_string(),
+        "redelivered": delivery.redelivered,
+        "retry": retry,
+    })
+}
+
+fn amqp_to_value(value: &AMQPValue) -> Value {
+    match value {
+        AMQPValue::Boolean(b) => b.to_value(),
+        AMQPValue::ShortShortInt(n) => (*n as i64).to_value(),
+        AMQPValue::ShortShortUInt(n) => (*n as i64).to_value(),
+        AMQPValue::ShortInt(n) => (*n as i64).to_value(),
+        AMQPValue::ShortUInt(n) => (*n as i64).to_value(),
+        AMQPValue::LongInt(n) => (*n as i64).to_value(),
+        AMQPValue::LongUInt(n) => (*n as i64).to_value(),
+        AMQPValue::LongLongInt(n) => n.to_value(),
+        AMQPValue::Float(n) => (*n as f64).to_value(),
+        AMQPValue::Double(n) => n.to_value(),
+        AMQPValue::ShortString(s) => s.to_string().to_value(),
+        AMQPValue::LongString(s) => s.to_string().to_value(),
+        AMQPValue::Void => Value::Null,
+        value => format!("{:?}", value).to_value(),
+    }
+}
+
+/// Republishes failed messages: after a delay through TTL queues, or to the
+/// dead-letter queue once the retries are over
+pub struct Retrier {
+    channel: Channel,
+    /// Retry queues already declared on this channel
+    declared: Mutex<HashSet<String>>,
+}
+
+impl Retrier {
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            channel,
+            declared: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Publishes the message for the attempt `attempt`. With a delay, it waits in
+    /// `<queue>.retry.<delay>ms`, whose TTL dead-letters it back to the queue.
+    pub async fn retry(
+        &self,
+        delivery: &Delivery,
+        config: &Config,
+        attempt: i64,
+        error: &str,
+    ) -> lapin::Result<()> {
+        let mut headers = delivery
+            .properties
+            .headers()
+            .as_ref()
+            .cloned()
+            .unwrap_or_default();
+        headers.insert(RETRY_COUNT_HEADER.into(), attempt.into());
+        headers.insert(
+            LAST_ERROR_HEADER.into(),
+            AMQPValue::LongString(error.into()),
+        );
+        let properties = delivery.properties.clone().with_headers(headers);
+
+        let delay = config.retry_delay(attempt);
+        let routing_key = if delay.is_zero() {
+            config.queue_name.clone()
+        } else {
+            let queue = format!("{}.retry.{}ms", config.queue_name, delay.as_millis());
+            self.declare_retry_queue(&queue, &config.queue_name, delay.as_millis())
+                .await?;
+            queue
+        };
+
+        self.channel
+            .basic_publish(
+                "",
+                &routing_key,
+                BasicPublishOptions::default(),
+                &delivery.data,
+                properties,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn declare_retry_queue(&self, queue: &str, target: &str, ttl: u128) -> lapin::Result<()> {
+        if self.declared.lock().unwrap().contains(queue) {
+            return Ok(());
+        }
+
+        let mut arguments = FieldTable::default();
+        arguments.insert("x-message-ttl".into(), AMQPValue::LongLongInt(ttl as i64));
+        arguments.insert(
+            "x-dead-letter-exchange".into(),
+            AMQPValue::LongString("".into()),
+        );
+        arguments.insert(
+            "x-dead-letter-routing-key".into(),
+            AMQPValue::LongString(target.into()),
+        );
+        self.channel
+            .queue_declare(
+                queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                arguments,
+            )
+            .await?;
+
+        log::debug!("Declared retry queue {}", queue);
+        self.declared.lock().unwrap().insert(queue.to_string());
+        Ok(())
+    }
+
+    /// Publishes the message to `dlq_queue` with the last error and the attempts
+    /// in its headers, keeping the body as it was received
+    pub async fn dead_letter(
+        &self,
+        delivery: &Delivery,
+        queue: &str,
+        config: &Config,
+        attempts: i64,
+        error: &str,
+    ) -> lapin::Result<()> {
+        let failed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+
+        let mut headers = delivery
+            .properties
+            .headers()
+            .as_ref()
+            .cloned()
+            .unwrap_or_default();
+        headers.insert(RETRY_COUNT_HEADER.into(), attempts.into());
+        headers.insert(
+            LAST_ERROR_HEADER.into(),
+            AMQPValue::LongString(error.into()),
+        );
+        headers.insert(
+            ShortString::from("x-original-queue"),
+            AMQPValue::LongString(config.queue_name.as_str().into()),
+        );
+        headers.insert(
+            ShortString::from("x-original-routing-key"),
+            AMQPValue::LongString(delivery.routing_key.as_str().into()),
+        );
+        headers.insert(
+            ShortString::from("x-failed-at"),
+            AMQPValue::LongLongInt(failed_at),
+        );
+        let properties = delivery.properties.clone().with_headers(headers);
+
+        self.channel
+            .basic_publish(
+                "",
+                queue,
+                BasicPublishOptions::default(),
+                &delivery.data,
+                properties,
+            )
+            .await?;
+        Ok(())
+    }
+}
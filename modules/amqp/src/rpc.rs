@@ -0,0 +1,121 @@
+use lapin::message::DeliveryResult;
+use lapin::options::{BasicConsumeOptions, ConfirmSelectOptions};
+use lapin::types::FieldTable;
+use lapin::{Channel, Connection};
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Pseudo-queue of RabbitMQ's direct reply-to: replies go straight to the channel
+/// consuming from it, without declaring a reply queue
+pub const DIRECT_REPLY_TO: &str = "amq.rabbitmq.reply-to";
+
+type Pending = Arc<Mutex<HashMap<String, oneshot::Sender<Vec<u8>>>>>;
+
+/// Channel for request/reply. Requests must be published on the same channel that
+/// consumes the replies, which are matched to their request by correlation id.
+#[derive(Clone)]
+pub struct Rpc {
+    pub channel: Channel,
+    pending: Pending,
+}
+
+impl Rpc {
+    /// The channel is always in confirm mode: a request that no queue receives only
+    /// comes back with its confirmation, whatever the module's `confirm`
+    pub async fn new(conn: &Connection) -> lapin::Result<Self> {
+        let channel = conn.create_channel().await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+
+        let consumer = channel
+            .basic_consume(
+                DIRECT_REPLY_TO,
+                "",
+                BasicConsumeOptions {
+                    no_ack: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        consumer.set_delegate({
+            let pending = Arc::clone(&pending);
+            move |delivery: DeliveryResult| {
+                let pending = Arc::clone(&pending);
+                Box::pin(async move {
+                    let Ok(Some(delivery)) = delivery else {
+                        return;
+                    };
+                    let waiting = delivery
+                        .properties
+                        .correlation_id()
+                        .as_ref()
+                        .and_then(|id| pending.lock().unwrap().remove(id.as_str()));
+                    match waiting {
+                        Some(sender) => {
+                            let _ = sender.send(delivery.data);
+                        }
+                        None => log::warn!("Discarding a reply without a pending request"),
+                    }
+                })
+            }
+        });
+
+        Ok(Self { channel, pending })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.channel.status().connected()
+    }
+
+    /// Waits for the reply with the correlation id. Register before publishing, so a
+    /// fast reply is never missed.
+    pub fn register(&self, correlation_id: &str) -> oneshot::Receiver<Vec<u8>> {
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(correlation_id.to_string(), tx);
+        rx
+    }
+
+    pub fn forget(&self, correlation_id: &str) {
+        self.pending.lock().unwrap().remove(correlation_id);
+    }
+}
+
+/// Body of a reply: strings as they are, any other value as JSON
+pub fn encode_reply(value: &Value) -> Vec<u8> {
+    match value {
+        Value::String(_) => value.to_string().into_bytes(),
+        value => value.to_json(JsonMode::Inline).into_bytes(),
+    }
+}
+
+/// Reply as delivered to the flow, parsed when it is JSON
+pub fn decode_reply(data: &[u8]) -> Value {
+    let text = String::from_utf8_lossy(data);
+    Value::json_to_value(&text).unwrap_or_else(|_| text.to_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_round_trip() {
+        let object = Value::json_to_value(r#"{ "id": 7, "ok": true }"#).unwrap();
+        let decoded = decode_reply(&encode_reply(&object));
+        assert_eq!(decoded.get("id").and_then(Value::to_i64), Some(7));
+        assert_eq!(decoded.get("ok"), Some(&Value::Boolean(true)));
+
+        assert_eq!(encode_reply(&"plain text".to_value()), b"plain text");
+        assert_eq!(decode_reply(b"plain text"), "plain text".to_value());
+        assert_eq!(decode_reply(b"false"), Value::Boolean(false));
+    }
+}
@@ -1,5 +1,11 @@
 use phlow_sdk::prelude::*;
 use std::fmt::Display;
+use std::time::Duration;
+
+/// Returned messages are reported with the publish confirmation, so without it a
+/// mandatory message that no queue receives would still be a success
+pub const MANDATORY_WITHOUT_CONFIRM: &str =
+    "mandatory needs confirm: true, returned messages are reported with the confirmation";
 
 #[derive(Debug)]
 pub enum Error {
@@ -35,10 +41,40 @@ pub struct Config {
     pub vhost: String,
     pub max_retry: i64,
     pub dlq_enable: bool,
+    /// Queue that receives messages out of retries, with the last error. Without
+    /// it, they are rejected to the dead-letter exchange of the queue.
+    pub dlq_queue: Option<String>,
+    /// Delay before the first retry. Zero requeues right away.
+    pub retry_delay: Duration,
+    /// Multiplier of the delay on each further retry
+    pub retry_backoff: f64,
+    pub retry_max_delay: Duration,
+    /// Delivers the message to the flow with its headers and retry metadata
+    pub envelope: bool,
     pub max_concurrency: u16,
+    /// Prefetch of the consumer, `max_concurrency` when not set
+    pub prefetch: Option<u16>,
+    /// Messages handed to the flow at once. Up to 1, each message runs the flow alone.
+    pub batch_size: usize,
+    /// Time a batch waits to fill after its first message
+    pub batch_timeout: Duration,
+    /// Waits for the broker to confirm each publish
+    pub confirm: bool,
+    pub confirm_timeout: Duration,
+    /// Asks the broker to return messages that no queue receives
+    pub mandatory: bool,
+    /// Time a request waits for its reply
+    pub rpc_timeout: Duration,
 }
 
 impl Config {
+    /// Delay before the retry number `attempt`, starting at 1
+    pub fn retry_delay(&self, attempt: i64) -> Duration {
+        let exponent = (attempt - 1).clamp(0, i32::MAX as i64) as i32;
+        let delay = self.retry_delay.as_millis() as f64 * self.retry_backoff.powi(exponent);
+        Duration::from_millis(delay.min(self.retry_max_delay.as_millis() as f64) as u64)
+    }
+
     pub fn to_connection_string(&self) -> String {
         format!(
             "amqp://{}:{}@{}:{}/{}",
@@ -164,6 +200,37 @@ impl TryFrom<&Value> for Config {
             .copied()
             .unwrap_or(true);
 
+        let dlq_queue = value
+            .get("dlq_queue")
+            .map(|v| v.to_string())
+            .filter(|s| !s.is_empty());
+
+        let retry_delay = Duration::from_millis(
+            value
+                .get("retry_delay")
+                .and_then(|v| v.to_u64())
+                .unwrap_or(1000),
+        );
+
+        let retry_backoff = value
+            .get("retry_backoff")
+            .and_then(|v| v.to_f64())
+            .unwrap_or(2.0)
+            .max(1.0);
+
+        let retry_max_delay = Duration::from_millis(
+            value
+                .get("retry_max_delay")
+                .and_then(|v| v.to_u64())
+                .unwrap_or(60000),
+        );
+
+        let envelope = value
+            .get("envelope")
+            .and_then(|v| v.as_bool())
+            .copied()
+            .unwrap_or(false);
+
         // Limite de concorrência (0 = sem limites)
         let max_concurrency = value
             .get("max_concurrency")
@@ -171,6 +238,53 @@ impl TryFrom<&Value> for Config {
             .unwrap_or(10)
             .clamp(0, u16::MAX as i64) as u16;
 
+        let prefetch = value
+            .get("prefetch")
+            .and_then(|v| v.to_i64())
+            .map(|v| v.clamp(0, u16::MAX as i64) as u16);
+
+        let batch_size = value
+            .get("batch_size")
+            .and_then(|v| v.to_i64())
+            .unwrap_or(0)
+            .max(0) as usize;
+
+        let batch_timeout = Duration::from_millis(
+            value
+                .get("batch_timeout")
+                .and_then(|v| v.to_u64())
+                .unwrap_or(1000),
+        );
+
+        let confirm = value
+            .get("confirm")
+            .and_then(|v| v.as_bool())
+            .copied()
+            .unwrap_or(true);
+
+        let confirm_timeout = Duration::from_millis(
+            value
+                .get("confirm_timeout")
+                .and_then(|v| v.to_u64())
+                .unwrap_or(5000),
+        );
+
+        let mandatory = value
+            .get("mandatory")
+            .and_then(|v| v.as_bool())
+            .copied()
+            .unwrap_or(false);
+        if mandatory && !confirm {
+            return Err(Error::GenericError(MANDATORY_WITHOUT_CONFIRM.to_string()));
+        }
+
+        let rpc_timeout = Duration::from_millis(
+            value
+                .get("rpc_timeout")
+                .and_then(|v| v.to_u64())
+                .unwrap_or(30000),
+        );
+
         // Parse RabbitMQ definition if available and import
         if let Some(definition) = value.get("definition") {
             log::debug!("Found definition in config, importing...");
@@ -205,7 +319,19 @@ impl TryFrom<&Value> for Config {
             uri,
             max_retry,
             dlq_enable,
+            dlq_queue,
+            retry_delay,
+            retry_backoff,
+            retry_max_delay,
+            envelope,
             max_concurrency,
+            prefetch,
+            batch_size,
+            batch_timeout,
+            confirm,
+            confirm_timeout,
+            mandatory,
+            rpc_timeout,
         })
     }
 }
@@ -536,3 +662,38 @@ fn import_definition(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let config = Config::try_from(
+            &Value::json_to_value(
+                r#"{ "routing_key": "jobs", "retry_delay": 1000, "retry_max_delay": 5000 }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let delays: Vec<u128> = (1..=5)
+            .map(|attempt| config.retry_delay(attempt).as_millis())
+            .collect();
+        assert_eq!(delays, vec![1000, 2000, 4000, 5000, 5000]);
+    }
+
+    #[test]
+    fn test_mandatory_needs_confirm() {
+        let config = Config::try_from(
+            &Value::json_to_value(r#"{ "mandatory": true, "confirm": false }"#).unwrap(),
+        );
+        assert!(
+            matches!(config, Err(Error::GenericError(msg)) if msg == MANDATORY_WITHOUT_CONFIRM)
+        );
+
+        assert!(
+            Config::try_from(&Value::json_to_value(r#"{ "mandatory": true }"#).unwrap()).is_ok()
+        );
+    }
+}
@@ -1,10 +1,29 @@
 use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+
+/// What goes when a namespace is full. Expired items always go first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eviction {
+    /// Least recently used
+    Lru,
+    /// Least frequently used, the least recently used among ties
+    Lfu,
+    /// Closest to expire, then the least recently used of the items without ttl
+    TtlFirst,
+}
 
 /// Configuration for the cache module
 #[derive(Debug, Clone)]
 pub struct CacheConfig {
     pub capacity: usize,
     pub default_ttl: Option<u64>,
+    pub eviction: Eviction,
+    /// Capacity of each configured namespace; the others get `capacity`
+    pub namespaces: HashMap<String, usize>,
+    /// File the cache is loaded from on start and saved to
+    pub snapshot_path: Option<String>,
+    /// Seconds between saves; without it, the cache is only saved when the module stops
+    pub snapshot_interval: Option<u64>,
 }
 
 impl Default for CacheConfig {
@@ -12,10 +31,22 @@ impl Default for CacheConfig {
         Self {
             capacity: 1000,
             default_ttl: None,
+            eviction: Eviction::Lru,
+            namespaces: HashMap::new(),
+            snapshot_path: None,
+            snapshot_interval: None,
         }
     }
 }
 
+fn positive(value: &Value, field: &str) -> Result<u64, String> {
+    match value.to_i64() {
+        Some(number) if number > 0 => Ok(number as u64),
+        Some(_) => Err(format!("{} must be a positive number", field)),
+        None => Err(format!("Invalid {} value", field)),
+    }
+}
+
 impl TryFrom<&Value> for CacheConfig {
     type Error = String;
 
@@ -50,6 +81,79 @@ impl TryFrom<&Value> for CacheConfig {
             }
         }
 
+        if let Some(eviction) = value.get("eviction") {
+            config.eviction = match eviction.to_string().as_str() {
+                "lru" => Eviction::Lru,
+                "lfu" => Eviction::Lfu,
+                "ttl" => Eviction::TtlFirst,
+                other => {
+                    return Err(format!(
+                        "Invalid eviction '{}'. Must be one of: lru, lfu, ttl",
+                        other
+                    ));
+                }
+            };
+        }
+
+        match value.get("namespaces") {
+            Some(Value::Object(namespaces)) => {
+                for (name, namespace) in namespaces.iter() {
+                    let capacity = namespace
+                        .get("capacity")
+                        .ok_or(format!("Missing capacity of namespace '{}'", name))?;
+                    config.namespaces.insert(
+                        name.to_string(),
+                        positive(capacity, &format!("Capacity of namespace '{}'", name))? as usize,
+                    );
+                }
+            }
+            Some(_) => return Err("namespaces must be an object".to_string()),
+            None => {}
+        }
+
+        config.snapshot_path = value.get("snapshot_path").map(|v| v.to_string());
+
+        if let Some(interval) = value.get("snapshot_interval") {
+            if config.snapshot_path.is_none() {
+                return Err("snapshot_interval requires snapshot_path".to_string());
+            }
+            config.snapshot_interval = Some(positive(interval, "snapshot_interval")?);
+        }
+
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<CacheConfig, String> {
+        CacheConfig::try_from(&Value::json_to_value(json).unwrap())
+    }
+
+    #[test]
+    fn test_cache_config() {
+        let config = parse(
+            r#"{
+                "eviction": "lfu",
+                "namespaces": { "sessions": { "capacity": 50 } },
+                "snapshot_path": "cache.json",
+                "snapshot_interval": 30
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.capacity, 1000);
+        assert_eq!(config.eviction, Eviction::Lfu);
+        assert_eq!(config.namespaces.get("sessions"), Some(&50));
+        assert_eq!(config.snapshot_interval, Some(30));
+
+        assert_eq!(parse("{}").unwrap().eviction, Eviction::Lru);
+        assert!(parse(r#"{ "eviction": "fifo" }"#).is_err());
+        assert!(parse(r#"{ "namespaces": { "sessions": { "capacity": 0 } } }"#).is_err());
+        assert_eq!(
+            parse(r#"{ "snapshot_interval": 30 }"#).unwrap_err(),
+            "snapshot_interval requires snapshot_path"
+        );
+    }
+}
@@ -7,34 +7,71 @@ use serde::{Deserialize, Serialize};
 pub enum CacheInput {
     #[serde(rename = "set")]
     Set {
+        namespace: Option<String>,
         key: String,
         value: Value,
         ttl: Option<u64>,
     },
     #[serde(rename = "get")]
-    Get { key: String },
+    Get {
+        namespace: Option<String>,
+        key: String,
+    },
     #[serde(rename = "remove")]
-    Remove { key: String },
+    Remove {
+        namespace: Option<String>,
+        key: String,
+    },
     #[serde(rename = "clear")]
-    Clear,
+    Clear { namespace: Option<String> },
     #[serde(rename = "exists")]
-    Exists { key: String },
-    #[serde(rename = "list")]
-    List {
-        filter_type: String,
-        filter_value: Option<String>,
-        filter_prefix: Option<String>,
-        filter_suffix: Option<String>,
-        order: String,
-        limit: Option<u64>,
-        offset: u64,
+    Exists {
+        namespace: Option<String>,
+        key: String,
     },
+    #[serde(rename = "incr")]
+    Incr {
+        namespace: Option<String>,
+        key: String,
+        by: i64,
+        ttl: Option<u64>,
+    },
+    #[serde(rename = "get_or_set")]
+    GetOrSet {
+        namespace: Option<String>,
+        key: String,
+        value: Value,
+        ttl: Option<u64>,
+    },
+    #[serde(rename = "compare_and_swap")]
+    CompareAndSwap {
+        namespace: Option<String>,
+        key: String,
+        expected: Value,
+        value: Value,
+        ttl: Option<u64>,
+    },
+    #[serde(rename = "list")]
+    List(ListInput),
     #[serde(rename = "cleanup")]
     Cleanup,
     #[serde(rename = "stats")]
     Stats,
 }
 
+/// Filters, order and page of a list action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListInput {
+    pub namespace: Option<String>,
+    pub filter_type: String,
+    pub filter_value: Option<String>,
+    pub filter_prefix: Option<String>,
+    pub filter_suffix: Option<String>,
+    pub order: String,
+    pub limit: Option<u64>,
+    pub offset: u64,
+}
+
 impl TryFrom<Option<Value>> for CacheInput {
     type Error = String;
 
@@ -52,6 +89,9 @@ impl TryFrom<Option<Value>> for CacheInput {
             None => return Err("Missing required 'action' field in cache input".to_string()),
         };
 
+        // Keys without a namespace belong to the default one
+        let namespace = input_value.get("namespace").map(|v| v.to_string());
+
         match action.as_str() {
             "set" => {
                 let key = input_value
@@ -70,7 +110,12 @@ impl TryFrom<Option<Value>> for CacheInput {
 
                 let ttl = input_value.get("ttl").and_then(|v| v.to_u64());
 
-                Ok(CacheInput::Set { key, value, ttl })
+                Ok(CacheInput::Set {
+                    namespace,
+                    key,
+                    value,
+                    ttl,
+                })
             }
             "get" => {
                 let key = input_value
@@ -82,7 +127,7 @@ impl TryFrom<Option<Value>> for CacheInput {
                     return Err("Key cannot be empty for get action".to_string());
                 }
 
-                Ok(CacheInput::Get { key })
+                Ok(CacheInput::Get { namespace, key })
             }
             "remove" => {
                 let key = input_value
@@ -94,9 +139,9 @@ impl TryFrom<Option<Value>> for CacheInput {
                     return Err("Key cannot be empty for remove action".to_string());
                 }
 
-                Ok(CacheInput::Remove { key })
+                Ok(CacheInput::Remove { namespace, key })
             }
-            "clear" => Ok(CacheInput::Clear),
+            "clear" => Ok(CacheInput::Clear { namespace }),
             "exists" => {
                 let key = input_value
                     .get("key")
@@ -107,7 +152,85 @@ impl TryFrom<Option<Value>> for CacheInput {
                     return Err("Key cannot be empty for exists action".to_string());
                 }
 
-                Ok(CacheInput::Exists { key })
+                Ok(CacheInput::Exists { namespace, key })
+            }
+            "incr" => {
+                let key = input_value
+                    .get("key")
+                    .ok_or("Missing 'key' field for incr action")?
+                    .to_string();
+
+                if key.is_empty() {
+                    return Err("Key cannot be empty for incr action".to_string());
+                }
+
+                let by = match input_value.get("by") {
+                    Some(by) => by
+                        .to_i64()
+                        .ok_or("'by' must be an integer for incr action")?,
+                    None => 1,
+                };
+
+                let ttl = input_value.get("ttl").and_then(|v| v.to_u64());
+
+                Ok(CacheInput::Incr {
+                    namespace,
+                    key,
+                    by,
+                    ttl,
+                })
+            }
+            "get_or_set" => {
+                let key = input_value
+                    .get("key")
+                    .ok_or("Missing 'key' field for get_or_set action")?
+                    .to_string();
+
+                if key.is_empty() {
+                    return Err("Key cannot be empty for get_or_set action".to_string());
+                }
+
+                let value = input_value
+                    .get("value")
+                    .ok_or("Missing 'value' field for get_or_set action")?
+                    .clone();
+
+                let ttl = input_value.get("ttl").and_then(|v| v.to_u64());
+
+                Ok(CacheInput::GetOrSet {
+                    namespace,
+                    key,
+                    value,
+                    ttl,
+                })
+            }
+            "compare_and_swap" => {
+                let key = input_value
+                    .get("key")
+                    .ok_or("Missing 'key' field for compare_and_swap action")?
+                    .to_string();
+
+                if key.is_empty() {
+                    return Err("Key cannot be empty for compare_and_swap action".to_string());
+                }
+
+                // A missing or null 'expected' swaps only when the key is not set
+                let expected = input_value.get("expected").cloned().unwrap_or(Value::Null);
+
+                let value = input_value
+                    .get("value")
+                    .ok_or("Missing 'value' field for compare_and_swap action")?
+                    .clone();
+
+                let ttl = input_value.get("ttl").and_then(|v| v.to_u64());
+
+                Ok(CacheInput::CompareAndSwap {
+                    namespace,
+                    key,
+                    expected,
+                    value,
+                    ttl,
+                })
             }
             "list" => {
                 let filter_type = input_value
@@ -130,7 +253,8 @@ impl TryFrom<Option<Value>> for CacheInput {
                     .and_then(|v| v.to_u64())
                     .unwrap_or(0);
 
-                Ok(CacheInput::List {
+                Ok(CacheInput::List(ListInput {
+                    namespace,
                     filter_type,
                     filter_value,
                     filter_prefix,
@@ -138,12 +262,12 @@ impl TryFrom<Option<Value>> for CacheInput {
                     order,
                     limit,
                     offset,
-                })
+                }))
             }
             "cleanup" => Ok(CacheInput::Cleanup),
             "stats" => Ok(CacheInput::Stats),
             _ => Err(format!(
-                "Invalid action '{}'. Must be one of: set, get, remove, clear, exists, list, cleanup, stats",
+                "Invalid action '{}'. Must be one of: set, get, remove, clear, exists, incr, get_or_set, compare_and_swap, list, cleanup, stats",
                 action
             )),
         }
@@ -165,7 +289,13 @@ mod tests {
 
         let input = CacheInput::try_from(Some(value)).unwrap();
         match input {
-            CacheInput::Set { key, value, ttl } => {
+            CacheInput::Set {
+                namespace,
+                key,
+                value,
+                ttl,
+            } => {
+                assert_eq!(namespace, None);
                 assert_eq!(key, "test_key");
                 assert_eq!(value.to_string(), "test_value");
                 assert_eq!(ttl, Some(3600));
@@ -183,7 +313,7 @@ mod tests {
 
         let input = CacheInput::try_from(Some(value)).unwrap();
         match input {
-            CacheInput::Get { key } => {
+            CacheInput::Get { key, .. } => {
                 assert_eq!(key, "test_key");
             }
             _ => panic!("Expected Get variant"),
@@ -203,14 +333,14 @@ mod tests {
 
         let input = CacheInput::try_from(Some(value)).unwrap();
         match input {
-            CacheInput::List {
+            CacheInput::List(ListInput {
                 filter_type,
                 filter_prefix,
                 order,
                 limit,
                 offset,
                 ..
-            } => {
+            }) => {
                 assert_eq!(filter_type, "prefix");
                 assert_eq!(filter_prefix, Some("user:".to_string()));
                 assert_eq!(order, "desc");
@@ -221,6 +351,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cache_input_atomic_actions() {
+        let value = json!({
+            "action": "incr",
+            "namespace": "rate",
+            "key": "client:1",
+            "ttl": 60
+        });
+
+        match CacheInput::try_from(Some(value)).unwrap() {
+            CacheInput::Incr {
+                namespace,
+                key,
+                by,
+                ttl,
+            } => {
+                assert_eq!(namespace, Some("rate".to_string()));
+                assert_eq!(key, "client:1");
+                assert_eq!(by, 1);
+                assert_eq!(ttl, Some(60));
+            }
+            _ => panic!("Expected Incr variant"),
+        }
+
+        let value = json!({
+            "action": "compare_and_swap",
+            "key": "version",
+            "value": 2
+        });
+
+        match CacheInput::try_from(Some(value)).unwrap() {
+            CacheInput::CompareAndSwap {
+                expected, value, ..
+            } => {
+                assert_eq!(expected, Value::Null);
+                assert_eq!(value, 2.to_value());
+            }
+            _ => panic!("Expected CompareAndSwap variant"),
+        }
+
+        let value = json!({
+            "action": "get_or_set",
+            "key": "config"
+        });
+
+        let result = CacheInput::try_from(Some(value));
+        assert_eq!(
+            result.unwrap_err(),
+            "Missing 'value' field for get_or_set action"
+        );
+    }
+
     #[test]
     fn test_cache_input_invalid_action() {
         let value = json!({
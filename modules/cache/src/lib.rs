@@ -1,20 +1,23 @@
 mod config;
 mod input;
+mod snapshot;
 mod stats;
+mod store;
 
 use config::CacheConfig;
-use input::CacheInput;
+use input::{CacheInput, ListInput};
 use stats::CacheStats;
 use phlow_sdk::prelude::*;
-use quickleaf::{Quickleaf, Filter, ListProps, Order, Duration};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use store::{Store, DEFAULT_NAMESPACE};
 
 create_step!(cache_handler(setup));
 
 /// Global cache instance wrapped in Arc<Mutex> for thread safety
-type CacheInstance = Arc<Mutex<Quickleaf>>;
+type CacheInstance = Arc<Mutex<Store>>;
 
-/// Cache handler that manages a QuickLeaf cache instance
+/// Cache handler that manages the in-memory store and its snapshots
 pub async fn cache_handler(
     setup: ModuleSetup,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -24,15 +27,42 @@ pub async fn cache_handler(
     let config = CacheConfig::try_from(&setup.with)?;
     log::debug!("Cache module started with config: {:?}", config);
 
-    // Initialize cache instance
-    let cache = if let Some(default_ttl) = config.default_ttl {
-        Arc::new(Mutex::new(Quickleaf::with_default_ttl(
-            config.capacity,
-            Duration::from_secs(default_ttl),
-        )))
-    } else {
-        Arc::new(Mutex::new(Quickleaf::new(config.capacity)))
-    };
+    // Initialize cache instance, restoring the last snapshot if there is one
+    let mut store = Store::new(&config);
+    if let Some(path) = &config.snapshot_path {
+        match snapshot::load(path).and_then(|loaded| match loaded {
+            Some(value) => store.restore(&value),
+            None => Ok(0),
+        }) {
+            Ok(loaded) => log::info!("Loaded {} cache items from {}", loaded, path),
+            Err(e) => log::error!("Failed to load cache snapshot: {}", e),
+        }
+    }
+    let cache = Arc::new(Mutex::new(store));
+
+    if let (Some(path), Some(interval)) = (config.snapshot_path.clone(), config.snapshot_interval) {
+        let cache = cache.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                save_snapshot(&cache, &path);
+            }
+        });
+    }
+
+    // The runtime waits for this save before exiting on SIGINT/SIGTERM
+    if let Some(path) = config.snapshot_path.clone() {
+        let cache = cache.clone();
+        let hook = setup.shutdown.register();
+        tokio::spawn(async move {
+            if let Ok(done) = hook.await {
+                save_snapshot(&cache, &path);
+                let _ = done.send(());
+            }
+        });
+    }
 
     // Initialize statistics
     let stats = Arc::new(Mutex::new(CacheStats::new()));
@@ -60,32 +90,40 @@ pub async fn cache_handler(
 
         // Process based on action
         let result = match input {
-            CacheInput::Set { key, value, ttl } => handle_set(cache, stats, key, value, ttl).await,
-            CacheInput::Get { key } => handle_get(cache, stats, key).await,
-            CacheInput::Remove { key } => handle_remove(cache, stats, key).await,
-            CacheInput::Clear => handle_clear(cache, stats).await,
-            CacheInput::Exists { key } => handle_exists(cache, stats, key).await,
-            CacheInput::List {
-                filter_type,
-                filter_value,
-                filter_prefix,
-                filter_suffix,
-                order,
-                limit,
-                offset,
-            } => {
-                handle_list(
-                    cache,
-                    filter_type,
-                    filter_value,
-                    filter_prefix,
-                    filter_suffix,
-                    order,
-                    limit,
-                    offset,
-                )
-                .await
+            CacheInput::Set {
+                namespace,
+                key,
+                value,
+                ttl,
+            } => handle_set(cache, stats, namespace, key, value, ttl).await,
+            CacheInput::Get { namespace, key } => handle_get(cache, stats, namespace, key).await,
+            CacheInput::Remove { namespace, key } => {
+                handle_remove(cache, stats, namespace, key).await
             }
+            CacheInput::Clear { namespace } => handle_clear(cache, stats, namespace).await,
+            CacheInput::Exists { namespace, key } => {
+                handle_exists(cache, stats, namespace, key).await
+            }
+            CacheInput::Incr {
+                namespace,
+                key,
+                by,
+                ttl,
+            } => handle_incr(cache, stats, namespace, key, by, ttl).await,
+            CacheInput::GetOrSet {
+                namespace,
+                key,
+                value,
+                ttl,
+            } => handle_get_or_set(cache, stats, namespace, key, value, ttl).await,
+            CacheInput::CompareAndSwap {
+                namespace,
+                key,
+                expected,
+                value,
+                ttl,
+            } => handle_compare_and_swap(cache, stats, namespace, key, expected, value, ttl).await,
+            CacheInput::List(list) => handle_list(cache, list).await,
             CacheInput::Cleanup => handle_cleanup(cache).await,
             CacheInput::Stats => handle_stats(cache, stats).await,
         };
@@ -107,13 +145,35 @@ pub async fn cache_handler(
         }
     }
 
+    // The runtime stopped on its own and closed the channel
+    if let Some(path) = &config.snapshot_path {
+        save_snapshot(&cache, path);
+    }
+
     Ok(())
 }
 
+/// Save the cache to its snapshot file, releasing the lock before writing
+fn save_snapshot(cache: &CacheInstance, path: &str) {
+    let snapshot = match cache.lock() {
+        Ok(cache_guard) => cache_guard.snapshot(),
+        Err(e) => {
+            log::error!("Cache lock error: {}", e);
+            return;
+        }
+    };
+
+    match snapshot::save(path, &snapshot) {
+        Ok(()) => log::debug!("Saved cache snapshot to {}", path),
+        Err(e) => log::error!("{}", e),
+    }
+}
+
 /// Handle set action
 async fn handle_set(
     cache: CacheInstance,
     stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
     key: String,
     value: Value,
     ttl: Option<u64>,
@@ -122,14 +182,8 @@ async fn handle_set(
         .lock()
         .map_err(|e| format!("Cache lock error: {}", e))?;
 
-    // Convert phlow-sdk Value to quickleaf Value
-    let quickleaf_value = quickleaf::valu3::value::Value::from(value.clone());
-    
-    if let Some(ttl_secs) = ttl {
-        cache_guard.insert_with_ttl(&key, quickleaf_value, Duration::from_secs(ttl_secs));
-    } else {
-        cache_guard.insert(&key, quickleaf_value);
-    }
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    cache_guard.insert(namespace, &key, value.clone(), ttl);
 
     // Update statistics
     if let Ok(mut stats_guard) = stats.lock() {
@@ -150,13 +204,15 @@ async fn handle_set(
 async fn handle_get(
     cache: CacheInstance,
     stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
     key: String,
 ) -> Result<Value, String> {
     let mut cache_guard = cache
         .lock()
         .map_err(|e| format!("Cache lock error: {}", e))?;
 
-    match cache_guard.get(&key) {
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    match cache_guard.get(namespace, &key) {
         Some(value) => {
             // Cache hit
             if let Ok(mut stats_guard) = stats.lock() {
@@ -169,7 +225,7 @@ async fn handle_get(
                 ("success", true.to_value()),
                 ("found", true.to_value()),
                 ("key", key.to_value()),
-                ("value", value.clone()),
+                ("value", value),
             ])
             .to_value())
         }
@@ -196,14 +252,16 @@ async fn handle_get(
 async fn handle_remove(
     cache: CacheInstance,
     stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
     key: String,
 ) -> Result<Value, String> {
     let mut cache_guard = cache
         .lock()
         .map_err(|e| format!("Cache lock error: {}", e))?;
 
-    match cache_guard.remove(&key) {
-        Ok(()) => {
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    match cache_guard.remove(namespace, &key) {
+        true => {
             // Update statistics
             if let Ok(mut stats_guard) = stats.lock() {
                 stats_guard.record_remove();
@@ -218,7 +276,7 @@ async fn handle_remove(
             ])
             .to_value())
         }
-        Err(_) => {
+        false => {
             log::debug!("Key '{}' not found for removal", key);
 
             Ok(std::collections::HashMap::from([
@@ -236,13 +294,14 @@ async fn handle_remove(
 async fn handle_clear(
     cache: CacheInstance,
     stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
 ) -> Result<Value, String> {
     let mut cache_guard = cache
         .lock()
         .map_err(|e| format!("Cache lock error: {}", e))?;
 
-    let previous_size = cache_guard.len();
-    cache_guard.clear();
+    // Without a namespace, every namespace is cleared
+    let previous_size = cache_guard.clear(namespace.as_deref());
 
     // Update statistics
     if let Ok(mut stats_guard) = stats.lock() {
@@ -263,13 +322,15 @@ async fn handle_clear(
 async fn handle_exists(
     cache: CacheInstance,
     stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
     key: String,
 ) -> Result<Value, String> {
     let mut cache_guard = cache
         .lock()
         .map_err(|e| format!("Cache lock error: {}", e))?;
 
-    let exists = cache_guard.contains_key(&key);
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    let exists = cache_guard.contains_key(namespace, &key);
 
     // Update statistics (consider this a type of get operation)
     if let Ok(mut stats_guard) = stats.lock() {
@@ -290,17 +351,141 @@ async fn handle_exists(
     .to_value())
 }
 
-/// Handle list action
-async fn handle_list(
+/// Handle incr action
+async fn handle_incr(
+    cache: CacheInstance,
+    stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
+    key: String,
+    by: i64,
+    ttl: Option<u64>,
+) -> Result<Value, String> {
+    let mut cache_guard = cache
+        .lock()
+        .map_err(|e| format!("Cache lock error: {}", e))?;
+
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    let value = cache_guard.incr(namespace, &key, by, ttl)?;
+
+    // Update statistics
+    if let Ok(mut stats_guard) = stats.lock() {
+        stats_guard.record_set();
+    }
+
+    log::debug!("Incremented key '{}' to {}", key, value);
+
+    Ok(std::collections::HashMap::from([
+        ("success", true.to_value()),
+        ("key", key.to_value()),
+        ("value", value.to_value()),
+    ])
+    .to_value())
+}
+
+/// Handle get_or_set action
+async fn handle_get_or_set(
     cache: CacheInstance,
-    filter_type: String,
-    filter_value: Option<String>,
-    filter_prefix: Option<String>,
-    filter_suffix: Option<String>,
-    order: String,
-    limit: Option<u64>,
-    offset: u64,
+    stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
+    key: String,
+    value: Value,
+    ttl: Option<u64>,
 ) -> Result<Value, String> {
+    let mut cache_guard = cache
+        .lock()
+        .map_err(|e| format!("Cache lock error: {}", e))?;
+
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    let (found, value) = cache_guard.get_or_set(namespace, &key, value, ttl);
+
+    // Update statistics: a miss also sets the key
+    if let Ok(mut stats_guard) = stats.lock() {
+        if found {
+            stats_guard.record_hit();
+        } else {
+            stats_guard.record_miss();
+            stats_guard.record_set();
+        }
+    }
+
+    log::debug!("get_or_set key '{}' found: {}", key, found);
+
+    Ok(std::collections::HashMap::from([
+        ("success", true.to_value()),
+        ("found", found.to_value()),
+        ("key", key.to_value()),
+        ("value", value),
+    ])
+    .to_value())
+}
+
+/// Handle compare_and_swap action
+async fn handle_compare_and_swap(
+    cache: CacheInstance,
+    stats: Arc<Mutex<CacheStats>>,
+    namespace: Option<String>,
+    key: String,
+    expected: Value,
+    value: Value,
+    ttl: Option<u64>,
+) -> Result<Value, String> {
+    let mut cache_guard = cache
+        .lock()
+        .map_err(|e| format!("Cache lock error: {}", e))?;
+
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    let (swapped, value) = cache_guard.compare_and_swap(namespace, &key, &expected, value, ttl);
+
+    // Update statistics
+    if swapped && let Ok(mut stats_guard) = stats.lock() {
+        stats_guard.record_set();
+    }
+
+    log::debug!("compare_and_swap key '{}' swapped: {}", key, swapped);
+
+    Ok(std::collections::HashMap::from([
+        ("success", true.to_value()),
+        ("key", key.to_value()),
+        ("swapped", swapped.to_value()),
+        ("value", value),
+    ])
+    .to_value())
+}
+
+/// Key filter of the list action
+enum Filter {
+    None,
+    StartWith(String),
+    EndWith(String),
+    StartAndEndWith(String, String),
+}
+
+impl Filter {
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            Filter::None => true,
+            Filter::StartWith(prefix) => key.starts_with(prefix.as_str()),
+            Filter::EndWith(suffix) => key.ends_with(suffix.as_str()),
+            Filter::StartAndEndWith(prefix, suffix) => {
+                key.starts_with(prefix.as_str()) && key.ends_with(suffix.as_str())
+            }
+        }
+    }
+}
+
+/// Handle list action
+async fn handle_list(cache: CacheInstance, list: ListInput) -> Result<Value, String> {
+    let ListInput {
+        namespace,
+        filter_type,
+        filter_value,
+        filter_prefix,
+        filter_suffix,
+        order,
+        limit,
+        offset,
+    } = list;
+
     let mut cache_guard = cache
         .lock()
         .map_err(|e| format!("Cache lock error: {}", e))?;
@@ -331,19 +516,17 @@ async fn handle_list(
         _ => Filter::None,
     };
 
-    // Determine order
-    let list_order = match order.as_str() {
-        "desc" => Order::Desc,
-        _ => Order::Asc,
-    };
-
-    // Build list properties
-    let list_props = ListProps::default().filter(filter).order(list_order);
+    // Get items from cache, ordered by key
+    let namespace = namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    let mut items: Vec<(String, Value)> = cache_guard
+        .list(namespace)
+        .into_iter()
+        .filter(|(key, _)| filter.matches(key))
+        .collect();
 
-    // Get items from cache
-    let items = cache_guard
-        .list(list_props)
-        .map_err(|e| format!("List operation failed: {:?}", e))?;
+    if order == "desc" {
+        items.reverse();
+    }
 
     // Apply pagination
     let total_count = items.len();
@@ -421,6 +604,21 @@ async fn handle_stats(
     let capacity = cache_guard.capacity();
     let hit_rate = stats_guard.get_hit_rate();
     let estimated_memory = estimate_memory_usage(current_size, capacity);
+    let namespaces = cache_guard
+        .namespaces()
+        .into_iter()
+        .map(|(name, size, capacity)| {
+            (
+                name,
+                std::collections::HashMap::from([
+                    ("size", size.to_value()),
+                    ("capacity", capacity.to_value()),
+                ])
+                .to_value(),
+            )
+        })
+        .collect::<std::collections::HashMap<String, Value>>()
+        .to_value();
 
     log::debug!(
         "Cache stats - Size: {}, Capacity: {}, Hit rate: {:.2}%",
@@ -438,6 +636,8 @@ async fn handle_stats(
         ("total_hits", stats_guard.get_total_hits().to_value()),
         ("total_sets", stats_guard.get_total_sets().to_value()),
         ("total_removes", stats_guard.get_total_removes().to_value()),
+        ("total_evictions", cache_guard.evictions().to_value()),
+        ("namespaces", namespaces),
     ])
     .to_value();
 
@@ -461,3 +661,68 @@ fn estimate_memory_usage(current_size: usize, capacity: usize) -> u64 {
 
     base_overhead + (current_size as u64 * per_item_overhead) + capacity_overhead
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phlow_sdk::tokio::sync::oneshot;
+
+    /// The shutdown hook saves what was cached since the last periodic snapshot
+    #[tokio::test]
+    async fn test_shutdown_hook_saves_snapshot() {
+        let path = std::env::temp_dir().join(format!("phlow-cache-{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let hooks = ShutdownHooks::default();
+        let (setup_sender, setup_receiver) = oneshot::channel();
+        let setup = ModuleSetup {
+            id: 0,
+            setup_sender,
+            main_sender: None,
+            with: json!({ "snapshot_path": path.clone() }),
+            dispatch: phlow_sdk::tracing::Dispatch::default(),
+            app_data: ApplicationData {
+                name: None,
+                version: None,
+                environment: None,
+                description: None,
+                author: None,
+                license: None,
+                repository: None,
+                homepage: None,
+            },
+            is_test_mode: false,
+            step_ids: Vec::new(),
+            script_arg_index: None,
+            shutdown: hooks.clone(),
+        };
+
+        // Runs on its own runtime, as the plugin entry point does
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let _ = runtime.block_on(cache_handler(setup));
+        });
+        let module = setup_receiver.await.unwrap().unwrap();
+
+        let (sender, response) = oneshot::channel();
+        module
+            .send(ModulePackage {
+                input: Some(json!({ "action": "set", "key": "user:1", "value": "Alice" })),
+                payload: None,
+                sender,
+                span: None,
+            })
+            .unwrap();
+        response.await.unwrap();
+
+        hooks.run(Duration::from_secs(5)).await;
+
+        let saved = snapshot::load(&path).unwrap().unwrap();
+        let mut store = Store::new(&CacheConfig::default());
+        assert_eq!(store.restore(&saved).unwrap(), 1);
+        assert_eq!(store.get(DEFAULT_NAMESPACE, "user:1"), Some("Alice".to_value()));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
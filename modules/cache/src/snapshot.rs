@@ -0,0 +1,54 @@
+use phlow_sdk::prelude::*;
+use std::path::Path;
+
+/// Reads the snapshot at `path`, or None when it doesn't exist yet
+pub fn load(path: &str) -> Result<Option<Value>, String> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read snapshot {}: {}", path, e))?;
+    Value::json_to_value(&content)
+        .map(Some)
+        .map_err(|e| format!("Invalid snapshot {}: {:?}", path, e))
+}
+
+/// Writes the snapshot to a temporary file and renames it over `path`, so a crash
+/// in the middle of a save never leaves a truncated snapshot behind
+pub fn save(path: &str, snapshot: &Value) -> Result<(), String> {
+    let temp = format!("{}.tmp", path);
+
+    if let Some(parent) = Path::new(path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    std::fs::write(&temp, snapshot.to_json(JsonMode::Inline))
+        .map_err(|e| format!("Failed to write snapshot {}: {}", temp, e))?;
+    std::fs::rename(&temp, path).map_err(|e| format!("Failed to replace snapshot {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("phlow-cache-{}", std::process::id()));
+        let path = dir.join("snapshots/cache.json");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(load(path), Ok(None));
+
+        let snapshot = Value::json_to_value(r#"{ "namespaces": { "default": [] } }"#).unwrap();
+        save(path, &snapshot).unwrap();
+        assert_eq!(load(path), Ok(Some(snapshot)));
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
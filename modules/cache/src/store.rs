@@ -0,0 +1,598 @@
+use phlow_sdk::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{CacheConfig, Eviction};
+
+/// Namespace of keys set without one
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Current time in milliseconds since the Unix epoch, so expirations survive a restart
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    value: Value,
+    expires_at: Option<u64>,
+    hits: u64,
+    last_access: u64,
+    created: u64,
+}
+
+impl Entry {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Keys of one namespace, with the indexes that pick what to evict in O(log n)
+#[derive(Debug)]
+struct Namespace {
+    capacity: usize,
+    eviction: Eviction,
+    entries: HashMap<String, Entry>,
+    /// Eviction order, first to go first. The access tick makes every rank unique.
+    ranks: BTreeMap<(u64, u64), String>,
+    /// Keys with a ttl, soonest to expire first
+    expirations: BTreeMap<(u64, u64), String>,
+}
+
+impl Namespace {
+    fn new(capacity: usize, eviction: Eviction) -> Self {
+        Self {
+            capacity,
+            eviction,
+            entries: HashMap::new(),
+            ranks: BTreeMap::new(),
+            expirations: BTreeMap::new(),
+        }
+    }
+
+    fn rank(&self, entry: &Entry) -> (u64, u64) {
+        match self.eviction {
+            Eviction::Lru => (0, entry.last_access),
+            Eviction::Lfu => (entry.hits, entry.last_access),
+            Eviction::TtlFirst => (entry.expires_at.unwrap_or(u64::MAX), entry.last_access),
+        }
+    }
+
+    fn index(&mut self, key: &str, entry: &Entry) {
+        self.ranks.insert(self.rank(entry), key.to_string());
+        if let Some(expires_at) = entry.expires_at {
+            self.expirations
+                .insert((expires_at, entry.created), key.to_string());
+        }
+    }
+
+    fn unindex(&mut self, entry: &Entry) {
+        self.ranks.remove(&self.rank(entry));
+        if let Some(expires_at) = entry.expires_at {
+            self.expirations.remove(&(expires_at, entry.created));
+        }
+    }
+
+    fn insert(&mut self, key: &str, entry: Entry) {
+        if let Some(previous) = self.entries.remove(key) {
+            self.unindex(&previous);
+        }
+        self.index(key, &entry);
+        self.entries.insert(key.to_string(), entry);
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.unindex(&entry);
+        Some(entry)
+    }
+
+    /// Live entry of the key; an expired one is dropped on the way
+    fn live(&mut self, key: &str, now: u64) -> Option<&mut Entry> {
+        if self.entries.get(key)?.is_expired(now) {
+            self.remove(key);
+            return None;
+        }
+        self.entries.get_mut(key)
+    }
+
+    fn touch(&mut self, key: &str, tick: u64) {
+        if let Some(mut entry) = self.entries.remove(key) {
+            self.unindex(&entry);
+            entry.hits += 1;
+            entry.last_access = tick;
+            self.index(key, &entry);
+            self.entries.insert(key.to_string(), entry);
+        }
+    }
+
+    fn cleanup_expired(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while let Some((&(expires_at, _), key)) = self.expirations.first_key_value() {
+            if expires_at > now {
+                break;
+            }
+            let key = key.clone();
+            self.remove(&key);
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Frees a slot for a new key: expired keys go first, then the eviction policy's pick.
+    /// Returns whether a live key had to be evicted.
+    fn make_room(&mut self, now: u64) -> bool {
+        if self.entries.len() < self.capacity || self.cleanup_expired(now) > 0 {
+            return false;
+        }
+        match self.ranks.first_key_value() {
+            Some((_, key)) => {
+                let key = key.clone();
+                self.remove(&key);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// In-memory store of the cache module, split in namespaces with their own capacity
+#[derive(Debug)]
+pub struct Store {
+    eviction: Eviction,
+    capacity: usize,
+    default_ttl: Option<u64>,
+    capacities: HashMap<String, usize>,
+    namespaces: HashMap<String, Namespace>,
+    tick: u64,
+    evictions: u64,
+}
+
+impl Store {
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            eviction: config.eviction,
+            capacity: config.capacity,
+            default_ttl: config.default_ttl,
+            capacities: config.namespaces.clone(),
+            namespaces: HashMap::new(),
+            tick: 0,
+            evictions: 0,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn namespace(&mut self, name: &str) -> &mut Namespace {
+        let capacity = *self.capacities.get(name).unwrap_or(&self.capacity);
+        let eviction = self.eviction;
+        self.namespaces
+            .entry(name.to_string())
+            .or_insert_with(|| Namespace::new(capacity, eviction))
+    }
+
+    /// Live entry of the key, without creating the namespace on reads
+    fn live(&mut self, namespace: &str, key: &str) -> Option<&mut Entry> {
+        self.namespaces.get_mut(namespace)?.live(key, now_millis())
+    }
+
+    /// Value of the key, counted as an access by the eviction policy
+    pub fn get(&mut self, namespace: &str, key: &str) -> Option<Value> {
+        let value = self.live(namespace, key)?.value.clone();
+        let tick = self.next_tick();
+        self.namespace(namespace).touch(key, tick);
+        Some(value)
+    }
+
+    pub fn contains_key(&mut self, namespace: &str, key: &str) -> bool {
+        self.live(namespace, key).is_some()
+    }
+
+    /// Stores the value for `ttl` seconds, or the default ttl when not given
+    pub fn insert(&mut self, namespace: &str, key: &str, value: Value, ttl: Option<u64>) {
+        let now = now_millis();
+        let expires_at = ttl.or(self.default_ttl).map(|ttl| now + ttl * 1000);
+        self.insert_entry(namespace, key, value, expires_at, 0, now);
+    }
+
+    fn insert_entry(
+        &mut self,
+        namespace: &str,
+        key: &str,
+        value: Value,
+        expires_at: Option<u64>,
+        hits: u64,
+        now: u64,
+    ) {
+        let tick = self.next_tick();
+        let ns = self.namespace(namespace);
+
+        // Overwriting a key keeps its access count
+        let (hits, created, evicted) = match ns.live(key, now) {
+            Some(existing) => (existing.hits.max(hits), existing.created, false),
+            None => (hits, tick, ns.make_room(now)),
+        };
+
+        ns.insert(
+            key,
+            Entry {
+                value,
+                expires_at,
+                hits,
+                last_access: tick,
+                created,
+            },
+        );
+
+        if evicted {
+            self.evictions += 1;
+        }
+    }
+
+    pub fn remove(&mut self, namespace: &str, key: &str) -> bool {
+        let found = self.live(namespace, key).is_some();
+        if found {
+            self.namespace(namespace).remove(key);
+        }
+        found
+    }
+
+    /// Empties the namespace, or every namespace when none is given. Returns the keys removed.
+    pub fn clear(&mut self, namespace: Option<&str>) -> usize {
+        match namespace {
+            Some(name) => self
+                .namespaces
+                .remove(name)
+                .map_or(0, |ns| ns.entries.len()),
+            None => self
+                .namespaces
+                .drain()
+                .map(|(_, ns)| ns.entries.len())
+                .sum(),
+        }
+    }
+
+    /// Live keys and values of the namespace, ordered by key
+    pub fn list(&mut self, namespace: &str) -> Vec<(String, Value)> {
+        let Some(ns) = self.namespaces.get_mut(namespace) else {
+            return Vec::new();
+        };
+        ns.cleanup_expired(now_millis());
+
+        let mut items: Vec<(String, Value)> = ns
+            .entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.value.clone()))
+            .collect();
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        items
+    }
+
+    pub fn cleanup_expired(&mut self) -> usize {
+        let now = now_millis();
+        self.namespaces
+            .values_mut()
+            .map(|ns| ns.cleanup_expired(now))
+            .sum()
+    }
+
+    /// Adds `by` to the integer of the key, starting from 0. The ttl only applies
+    /// when the key is created, so the counter expires a fixed time after its first use.
+    pub fn incr(
+        &mut self,
+        namespace: &str,
+        key: &str,
+        by: i64,
+        ttl: Option<u64>,
+    ) -> Result<i64, String> {
+        let now = now_millis();
+        let tick = self.next_tick();
+        let ns = self.namespace(namespace);
+
+        if let Some(entry) = ns.live(key, now) {
+            let current = entry
+                .value
+                .to_i64()
+                .ok_or_else(|| format!("Value of key '{}' is not an integer", key))?;
+            let value = current
+                .checked_add(by)
+                .ok_or_else(|| format!("Increment of key '{}' overflows", key))?;
+
+            entry.value = value.to_value();
+            ns.touch(key, tick);
+            return Ok(value);
+        }
+
+        self.insert(namespace, key, by.to_value(), ttl);
+        Ok(by)
+    }
+
+    /// Value of the key, storing `value` first when the key is missing.
+    /// Returns whether the key was found along with its value.
+    pub fn get_or_set(
+        &mut self,
+        namespace: &str,
+        key: &str,
+        value: Value,
+        ttl: Option<u64>,
+    ) -> (bool, Value) {
+        match self.get(namespace, key) {
+            Some(existing) => (true, existing),
+            None => {
+                self.insert(namespace, key, value.clone(), ttl);
+                (false, value)
+            }
+        }
+    }
+
+    /// Stores `value` only when the key holds `expected`; a null `expected` means the
+    /// key must be missing. Returns whether it swapped and the value the key ends with.
+    pub fn compare_and_swap(
+        &mut self,
+        namespace: &str,
+        key: &str,
+        expected: &Value,
+        value: Value,
+        ttl: Option<u64>,
+    ) -> (bool, Value) {
+        let current = self.live(namespace, key).map(|entry| entry.value.clone());
+
+        let matches = match &current {
+            Some(current) => current == expected,
+            None => matches!(expected, Value::Null),
+        };
+
+        if matches {
+            self.insert(namespace, key, value.clone(), ttl);
+            (true, value)
+        } else {
+            (false, current.unwrap_or(Value::Null))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.namespaces.values().map(|ns| ns.entries.len()).sum()
+    }
+
+    /// Capacity of the namespaces in use, or of the default namespace when none is
+    pub fn capacity(&self) -> usize {
+        if self.namespaces.is_empty() {
+            return *self
+                .capacities
+                .get(DEFAULT_NAMESPACE)
+                .unwrap_or(&self.capacity);
+        }
+        self.namespaces.values().map(|ns| ns.capacity).sum()
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Size and capacity of each namespace in use
+    pub fn namespaces(&self) -> Vec<(String, usize, usize)> {
+        let mut namespaces: Vec<_> = self
+            .namespaces
+            .iter()
+            .map(|(name, ns)| (name.clone(), ns.entries.len(), ns.capacity))
+            .collect();
+        namespaces.sort();
+        namespaces
+    }
+
+    /// Live entries as a snapshot. Each namespace lists its entries from the least to
+    /// the most recently used, so loading them back keeps the eviction order.
+    pub fn snapshot(&self) -> Value {
+        let now = now_millis();
+        let namespaces: HashMap<String, Value> = self
+            .namespaces
+            .iter()
+            .map(|(name, ns)| {
+                let mut entries: Vec<(&String, &Entry)> = ns
+                    .entries
+                    .iter()
+                    .filter(|(_, entry)| !entry.is_expired(now))
+                    .collect();
+                entries.sort_by_key(|(_, entry)| entry.last_access);
+
+                let items: Vec<Value> = entries
+                    .into_iter()
+                    .map(|(key, entry)| {
+                        HashMap::from([
+                            ("key", key.to_value()),
+                            ("value", entry.value.clone()),
+                            ("expires_at", entry.expires_at.to_value()),
+                            ("hits", entry.hits.to_value()),
+                        ])
+                        .to_value()
+                    })
+                    .collect();
+
+                (name.clone(), items.to_value())
+            })
+            .collect();
+
+        HashMap::from([("namespaces", namespaces.to_value())]).to_value()
+    }
+
+    /// Loads the entries of a snapshot, skipping those that expired meanwhile.
+    /// Returns how many were loaded.
+    pub fn restore(&mut self, snapshot: &Value) -> Result<usize, String> {
+        let namespaces = match snapshot.get("namespaces") {
+            Some(Value::Object(namespaces)) => namespaces,
+            _ => return Err("Invalid snapshot: missing namespaces".to_string()),
+        };
+
+        let now = now_millis();
+        let mut loaded = 0;
+
+        for (name, entries) in namespaces.iter() {
+            let Value::Array(entries) = entries else {
+                return Err(format!("Invalid snapshot: entries of '{}'", name));
+            };
+
+            for item in entries.values.iter() {
+                let (Some(key), Some(value)) = (item.get("key"), item.get("value")) else {
+                    return Err(format!("Invalid snapshot: entry of '{}'", name));
+                };
+                let expires_at = item.get("expires_at").and_then(|v| v.to_u64());
+                if expires_at.is_some_and(|expires_at| expires_at <= now) {
+                    continue;
+                }
+                let hits = item.get("hits").and_then(|v| v.to_u64()).unwrap_or(0);
+
+                self.insert_entry(
+                    &name.to_string(),
+                    &key.to_string(),
+                    value.clone(),
+                    expires_at,
+                    hits,
+                    now,
+                );
+                loaded += 1;
+            }
+        }
+
+        Ok(loaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(json: &str) -> Store {
+        Store::new(&CacheConfig::try_from(&Value::json_to_value(json).unwrap()).unwrap())
+    }
+
+    fn keys(store: &mut Store, namespace: &str) -> Vec<String> {
+        store
+            .list(namespace)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut store = store(r#"{ "capacity": 2 }"#);
+        store.insert("default", "a", 1.to_value(), None);
+        store.insert("default", "b", 2.to_value(), None);
+        store.get("default", "a");
+        store.insert("default", "c", 3.to_value(), None);
+
+        assert_eq!(keys(&mut store, "default"), vec!["a", "c"]);
+        assert_eq!(store.evictions(), 1);
+    }
+
+    #[test]
+    fn test_lfu_eviction() {
+        let mut store = store(r#"{ "capacity": 2, "eviction": "lfu" }"#);
+        store.insert("default", "a", 1.to_value(), None);
+        store.insert("default", "b", 2.to_value(), None);
+        store.get("default", "a");
+        store.get("default", "a");
+        store.get("default", "b");
+        store.insert("default", "c", 3.to_value(), None);
+
+        assert_eq!(keys(&mut store, "default"), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_ttl_first_eviction() {
+        let mut store = store(r#"{ "capacity": 3, "eviction": "ttl" }"#);
+        store.insert("default", "forever", 1.to_value(), None);
+        store.insert("default", "long", 2.to_value(), Some(3600));
+        store.insert("default", "short", 3.to_value(), Some(60));
+        store.get("default", "short");
+        store.insert("default", "new", 4.to_value(), None);
+
+        assert_eq!(keys(&mut store, "default"), vec!["forever", "long", "new"]);
+    }
+
+    #[test]
+    fn test_namespace_capacities() {
+        let mut store =
+            store(r#"{ "capacity": 10, "namespaces": { "sessions": { "capacity": 1 } } }"#);
+        store.insert("sessions", "s1", 1.to_value(), None);
+        store.insert("sessions", "s2", 2.to_value(), None);
+        store.insert("default", "s1", 3.to_value(), None);
+
+        assert_eq!(keys(&mut store, "sessions"), vec!["s2"]);
+        assert_eq!(store.get("default", "s1"), Some(3.to_value()));
+        assert_eq!(store.capacity(), 11);
+        assert_eq!(store.clear(Some("sessions")), 1);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_atomic_operations() {
+        let mut store = store("{}");
+        assert_eq!(store.incr("default", "hits", 1, Some(60)), Ok(1));
+        assert_eq!(store.incr("default", "hits", 5, None), Ok(6));
+        store.insert("default", "name", "Ana".to_value(), None);
+        assert!(store.incr("default", "name", 1, None).is_err());
+
+        assert_eq!(
+            store.get_or_set("default", "config", "a".to_value(), None),
+            (false, "a".to_value())
+        );
+        assert_eq!(
+            store.get_or_set("default", "config", "b".to_value(), None),
+            (true, "a".to_value())
+        );
+
+        assert_eq!(
+            store.compare_and_swap("default", "v", &Value::Null, 1.to_value(), None),
+            (true, 1.to_value())
+        );
+        assert_eq!(
+            store.compare_and_swap("default", "v", &2.to_value(), 3.to_value(), None),
+            (false, 1.to_value())
+        );
+        assert_eq!(
+            store.compare_and_swap("default", "v", &1.to_value(), 3.to_value(), None),
+            (true, 3.to_value())
+        );
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut original = store(r#"{ "capacity": 2 }"#);
+        original.insert("default", "a", 1.to_value(), None);
+        original.insert("default", "b", "x".to_value(), Some(3600));
+        original.insert("sessions", "s", 2.to_value(), None);
+        original.get("default", "a");
+        original.namespace("default").insert(
+            "expired",
+            Entry {
+                value: 0.to_value(),
+                expires_at: Some(1),
+                hits: 0,
+                last_access: 0,
+                created: 0,
+            },
+        );
+
+        let snapshot = original.snapshot();
+        let json = snapshot.to_json(JsonMode::Inline);
+
+        let mut restored = store(r#"{ "capacity": 2 }"#);
+        let loaded = restored
+            .restore(&Value::json_to_value(&json).unwrap())
+            .unwrap();
+        assert_eq!(loaded, 3);
+        assert_eq!(restored.get("default", "b"), Some("x".to_value()));
+        assert_eq!(restored.get("sessions", "s"), Some(2.to_value()));
+
+        // "b" was used after "a" was restored, so "a" is the least recently used
+        restored.insert("default", "c", 3.to_value(), None);
+        assert_eq!(keys(&mut restored, "default"), vec!["b", "c"]);
+    }
+}
@@ -1,8 +1,9 @@
 use colored::*;
 use phlow_sdk::prelude::*;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     env,
+    io::{self, Read},
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -10,6 +11,8 @@ pub enum InputType {
     String,
     Number,
     Boolean,
+    /// The value is a path (or `-` for stdin) and the argument receives the file content
+    File,
 }
 
 #[derive(Debug, Clone)]
@@ -22,261 +25,588 @@ pub struct Arg {
     pub required: bool,
     pub index: Option<usize>,
     pub input_type: InputType,
+    /// Repeated flags, or every positional from `index` on, collected into an array
+    pub multiple: bool,
+    pub choices: Vec<String>,
+    /// Environment variable read when the argument is not given
+    pub env: Option<String>,
 }
 
+impl Arg {
+    fn parse(item: &Value, error: &mut Vec<String>) -> Option<Self> {
+        if !item.is_object() {
+            error.push("Invalid argument: each argument must be an object.".to_string());
+            return None;
+        }
+
+        let name = match item.get("name").map(Value::to_string) {
+            Some(n) => n,
+            None => {
+                error.push("Missing required 'name' field in schema.".to_string());
+                return None;
+            }
+        };
+
+        let long = item.get("long").map(Value::to_string);
+        let short = item.get("short").map(Value::to_string);
+        let help = item.get("help").map(Value::to_string).unwrap_or_default();
+        // Array defaults of repeated arguments are kept comma separated, like env values
+        let default = item.get("default").map(|value| match value {
+            Value::Array(array) => array
+                .values
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            value => value.to_string(),
+        });
+        let required_flag = *item
+            .get("required")
+            .and_then(Value::as_bool)
+            .unwrap_or(&false);
+        let index = item
+            .get("index")
+            .and_then(Value::to_u64)
+            .map(|i| i as usize);
+        let mut multiple = *item
+            .get("multiple")
+            .and_then(Value::as_bool)
+            .unwrap_or(&false);
+        let input_type = match item.get("type").map(Value::to_string) {
+            Some(ref t) if t == "number" => InputType::Number,
+            Some(ref t) if t == "boolean" => InputType::Boolean,
+            Some(ref t) if t == "file" => InputType::File,
+            Some(ref t) if t == "array" => {
+                multiple = true;
+                InputType::String
+            }
+            _ => InputType::String,
+        };
+        let choices = match item.get("choices") {
+            Some(Value::Array(array)) => array.values.iter().map(Value::to_string).collect(),
+            _ => Vec::new(),
+        };
+        let env = item.get("env").map(Value::to_string);
+
+        Some(Arg {
+            name,
+            long,
+            short,
+            help,
+            default,
+            required: required_flag,
+            index,
+            input_type,
+            multiple,
+            choices,
+            env,
+        })
+    }
+
+    fn matches_flag(&self, flag: &str) -> bool {
+        match flag.strip_prefix("--") {
+            Some(long) => self.long.as_deref() == Some(long),
+            None => self.short.as_deref() == flag.strip_prefix('-'),
+        }
+    }
+
+    /// Values from env vars and defaults are split on commas for repeated arguments
+    fn split(&self, value: String) -> Vec<String> {
+        if self.multiple {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        } else {
+            vec![value]
+        }
+    }
+
+    fn convert(&self, value_str: &str, error: &mut Vec<String>) -> Option<Value> {
+        if !self.choices.is_empty() && !self.choices.iter().any(|choice| choice == value_str) {
+            error.push(format!(
+                "Invalid value for {}: {}. Possible values: {}",
+                self.name,
+                value_str,
+                self.choices.join(", ")
+            ));
+            return None;
+        }
+
+        match self.input_type {
+            InputType::String => Some(Value::from(value_str.to_string())),
+            InputType::Number => match value_str.parse::<f64>() {
+                Ok(v) => Some(Value::from(v)),
+                Err(_) => {
+                    error.push(format!("Invalid value for {}: {}", self.name, value_str));
+                    None
+                }
+            },
+            InputType::Boolean => match value_str {
+                "" | "true" | "1" => Some(Value::Boolean(true)),
+                "false" | "0" => Some(Value::Boolean(false)),
+                _ => {
+                    error.push(format!("Invalid value for {}: {}", self.name, value_str));
+                    None
+                }
+            },
+            InputType::File => {
+                let content = if value_str == "-" {
+                    let mut content = String::new();
+                    io::stdin().read_to_string(&mut content).map(|_| content)
+                } else {
+                    std::fs::read_to_string(value_str)
+                };
+                match content {
+                    Ok(content) => Some(Value::from(content)),
+                    Err(err) => {
+                        error.push(format!(
+                            "Unable to read file for {}: {}: {}",
+                            self.name, value_str, err
+                        ));
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A (sub)command with its own arguments. The root command is the application itself.
 #[derive(Debug, Clone)]
-pub struct Args {
-    pub args: HashMap<String, Value>,
-    pub schema: Vec<Arg>,
-    pub app_data: ApplicationData,
-    pub error: Vec<String>,
+pub struct Command {
+    pub name: String,
+    pub help: String,
+    /// Id of the step where the flow starts when this command is run
+    pub step: Option<String>,
+    pub args: Vec<Arg>,
+    pub commands: Vec<Command>,
 }
 
-impl Args {
-    pub fn new(value: Value, app_data: ApplicationData, additional_args: bool) -> Self {
-        let mut arg_defs: Vec<Arg> = Vec::new();
-        let mut error = Vec::new();
+impl Command {
+    fn root(with: &Value, app_data: &ApplicationData, error: &mut Vec<String>) -> Self {
+        let mut root = Command {
+            name: app_data.name.clone().unwrap_or("phlow".to_string()),
+            help: app_data.description.clone().unwrap_or_default(),
+            step: None,
+            args: Vec::new(),
+            commands: Vec::new(),
+        };
 
-        if value.is_null() {
-            return Self {
-                args: HashMap::new(),
-                schema: Vec::new(),
-                app_data,
-                error,
-            };
+        if with.is_object() {
+            root.args = Self::parse_args(with.get("args"), error);
+            root.commands = Self::parse_commands(with.get("commands"), error);
+        }
+
+        root
+    }
+
+    fn parse(item: &Value, error: &mut Vec<String>) -> Option<Self> {
+        if !item.is_object() {
+            error.push("Invalid command: each command must be an object.".to_string());
+            return None;
         }
 
-        let array = match value.as_array() {
-            Some(arr) => arr.clone(),
+        let name = match item.get("name").map(Value::to_string) {
+            Some(n) => n,
             None => {
-                error.push("Invalid input: schema must be an array.".to_string());
-                return Self {
-                    args: HashMap::new(),
-                    schema: Vec::new(),
-                    app_data,
-                    error,
-                };
+                error.push("Missing required 'name' field in command.".to_string());
+                return None;
             }
         };
 
-        for item in array {
-            let name = match item.get("name").map(Value::to_string) {
-                Some(n) => n,
-                None => {
-                    error.push("Missing required 'name' field in schema.".to_string());
-                    continue;
-                }
-            };
+        Some(Command {
+            name,
+            help: item.get("help").map(Value::to_string).unwrap_or_default(),
+            step: item.get("step").map(Value::to_string),
+            args: Self::parse_args(item.get("args"), error),
+            commands: Self::parse_commands(item.get("commands"), error),
+        })
+    }
 
-            let long = item.get("long").map(Value::to_string);
-            let short = item.get("short").map(Value::to_string);
-            let help = item.get("help").map(Value::to_string).unwrap_or_default();
-            let default = item.get("default").map(Value::to_string).map(String::from);
-            let required_flag = *item
-                .get("required")
+    fn parse_args(value: Option<&Value>, error: &mut Vec<String>) -> Vec<Arg> {
+        match value {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(array)) => array
+                .values
+                .iter()
+                .filter_map(|item| Arg::parse(item, error))
+                .collect(),
+            Some(_) => {
+                error.push("Invalid input: schema must be an array.".to_string());
+                Vec::new()
+            }
+        }
+    }
+
+    fn parse_commands(value: Option<&Value>, error: &mut Vec<String>) -> Vec<Command> {
+        match value {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(array)) => array
+                .values
+                .iter()
+                .filter_map(|item| Command::parse(item, error))
+                .collect(),
+            Some(_) => {
+                error.push("Invalid input: commands must be an array.".to_string());
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+}
+
+/// Output requested with `--generate-completions <shell>` or `--generate-man`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generate {
+    Completions(String),
+    Man,
+}
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub args: HashMap<String, Value>,
+    pub root: Command,
+    /// Names of the subcommands given on the command line, outermost first
+    pub path: Vec<String>,
+    pub app_data: ApplicationData,
+    pub error: Vec<String>,
+    pub help: bool,
+    pub generate: Option<Generate>,
+    /// `--no-input`: prompts answer from their `value` or `default` instead of asking
+    pub no_input: bool,
+}
+
+fn is_help_flag(raw: &str) -> bool {
+    raw == "--help" || raw == "-h" || raw == "-H"
+}
+
+fn is_flag(raw: &str) -> bool {
+    raw.len() > 1 && raw.starts_with('-') && raw.parse::<f64>().is_err()
+}
+
+impl Args {
+    pub fn new(with: &Value, app_data: ApplicationData, script_arg_index: Option<usize>) -> Self {
+        let process_args: Vec<String> = env::args().collect();
+        // When started as `phlow main.phlow ...` the runtime tells where the flow file is.
+        // It keeps index 0 of the root positionals, but commands may still follow it,
+        // and the runtime flags before it are not ours
+        let (script, raw_args) = match script_arg_index {
+            Some(index) if index < process_args.len() => (
+                Some(process_args[index].clone()),
+                process_args[index + 1..].to_vec(),
+            ),
+            _ => (None, process_args.into_iter().skip(1).collect()),
+        };
+        Self::parse(with, app_data, script, raw_args)
+    }
+
+    pub fn parse(
+        with: &Value,
+        app_data: ApplicationData,
+        script: Option<String>,
+        raw_args: Vec<String>,
+    ) -> Self {
+        let mut error = Vec::new();
+        let root = Command::root(with, &app_data, &mut error);
+        let additional_args = with.is_object()
+            && *with
+                .get("additional_args")
                 .and_then(Value::as_bool)
                 .unwrap_or(&false);
-            let index = item
-                .get("index")
-                .and_then(Value::to_u64)
-                .map(|i| i as usize);
-            let input_type = match item.get("type").map(Value::to_string) {
-                Some(ref t) if t == "number" => InputType::Number,
-                Some(ref t) if t == "boolean" => InputType::Boolean,
-                _ => InputType::String,
-            };
 
-            arg_defs.push(Arg {
-                name,
-                long,
-                short,
-                help,
-                default,
-                required: required_flag,
-                index,
-                input_type,
-            });
-        }
+        let mut path: Vec<String> = Vec::new();
+        let mut command = &root;
+        let mut positionals: Vec<String> = script.into_iter().collect();
+        // Arguments of the parent commands stay valid inside subcommands. The index of
+        // an argument counts the positionals given after its command
+        let mut active: Vec<(&Arg, usize)> = root.args.iter().map(|arg| (arg, 0)).collect();
+        let offset = positionals.len();
+        let mut flags: HashMap<String, Vec<String>> = HashMap::new();
+        let mut help = false;
+        let mut generate = None;
+        let mut no_input = false;
+        let mut only_positionals = false;
+
+        let mut tokens = raw_args.iter().peekable();
+        while let Some(raw) = tokens.next() {
+            if only_positionals {
+                positionals.push(raw.clone());
+                continue;
+            }
 
-        let raw_args: Vec<String> = env::args().skip(1).collect();
-        let mut parsed_args: HashMap<String, Value> = HashMap::new();
+            if raw == "--" {
+                only_positionals = true;
+                continue;
+            }
 
-        // 🔍 Construir conjunto de flags válidas
-        let mut valid_flags: HashSet<String> = HashSet::new();
-        for arg in &arg_defs {
-            if let Some(l) = &arg.long {
-                valid_flags.insert(format!("--{}", l));
+            if is_help_flag(raw) {
+                help = true;
+                continue;
             }
-            if let Some(s) = &arg.short {
-                valid_flags.insert(format!("-{}", s));
+
+            if raw == "--no-input" {
+                no_input = true;
+                continue;
             }
-        }
 
-        let mut error = Vec::new();
-        if !additional_args {
-            for (_, raw) in raw_args.iter().enumerate() {
-                if raw.starts_with('-') {
-                    // Se for "--flag=valor", considera apenas a flag
-                    let flag = raw.split('=').next().unwrap_or(raw);
-                    if !valid_flags.contains(flag)
-                        && flag != "--help"
-                        && flag != "-H"
-                        && flag != "-h"
-                    {
-                        error.push(format!(
-                            "Unknown flag: {}. Use --help to see the available flags.",
-                            flag
-                        ));
-                    }
+            if raw == "--generate-man" {
+                generate = Some(Generate::Man);
+                continue;
+            }
+
+            if raw == "--generate-completions" {
+                match tokens.next() {
+                    Some(shell) => generate = Some(Generate::Completions(shell.clone())),
+                    None => error.push("Missing value for --generate-completions.".to_string()),
                 }
+                continue;
             }
-        }
 
-        for arg_def in &arg_defs {
-            let mut found: Option<String> = None;
-
-            if let Some(long_flag) = &arg_def.long {
-                let flag = format!("--{}", long_flag);
-                if let Some(pos) = raw_args.iter().position(|a| a == &flag) {
-                    if arg_def.input_type == InputType::Boolean {
-                        let next = raw_args.get(pos + 1);
-                        if next.is_none() || next.unwrap().starts_with('-') {
-                            found = Some("".to_string());
-                        } else {
-                            found = Some(next.unwrap().to_string());
+            if is_flag(raw) {
+                // Se for "--flag=valor", separa a flag do valor
+                let (flag, inline) = match raw.split_once('=') {
+                    Some((flag, value)) => (flag, Some(value.to_string())),
+                    None => (raw.as_str(), None),
+                };
+
+                let arg_def = match active.iter().find(|(arg, _)| arg.matches_flag(flag)) {
+                    Some((arg, _)) => *arg,
+                    None => {
+                        if !additional_args {
+                            error.push(format!(
+                                "Unknown flag: {}. Use --help to see the available flags.",
+                                flag
+                            ));
                         }
-                    } else {
-                        found = raw_args.get(pos + 1).cloned();
+                        continue;
                     }
-                }
-            }
+                };
 
-            if found.is_none() {
-                if let Some(short_flag) = &arg_def.short {
-                    let flag = format!("-{}", short_flag);
-                    if let Some(pos) = raw_args.iter().position(|a| a == &flag) {
-                        if arg_def.input_type == InputType::Boolean {
-                            let next = raw_args.get(pos + 1);
-                            if next.is_none() || next.unwrap().starts_with('-') {
-                                found = Some("".to_string());
-                            } else {
-                                found = Some(next.unwrap().to_string());
-                            }
-                        } else {
-                            found = raw_args.get(pos + 1).cloned();
+                let value = if arg_def.input_type == InputType::Boolean {
+                    // Only literal booleans are taken as the value, so commands and
+                    // positionals can follow a boolean flag
+                    inline
+                        .or_else(|| {
+                            tokens
+                                .next_if(|next| {
+                                    matches!(next.as_str(), "true" | "false" | "1" | "0")
+                                })
+                                .cloned()
+                        })
+                        .unwrap_or_default()
+                } else {
+                    match inline.or_else(|| tokens.next().cloned()) {
+                        Some(value) => value,
+                        None => {
+                            error.push(format!("Missing value for {}", arg_def.name));
+                            continue;
                         }
                     }
-                }
+                };
+
+                flags.entry(arg_def.name.clone()).or_default().push(value);
+                continue;
             }
 
-            if found.is_none() {
-                if let Some(idx) = arg_def.index {
-                    if raw_args.len() > idx {
-                        let value = raw_args[idx].clone();
-                        if value.starts_with('-') {
-                            error.push(format!(
-                                "Invalid value for positional argument {}: cannot start with '-' or '--'. Found '{}'",
-                                arg_def.name, value
-                            ));
-                        } else {
-                            found = Some(value);
-                        }
+            if positionals.len() == offset && !command.commands.is_empty() {
+                match command.find(raw) {
+                    Some(subcommand) => {
+                        path.push(subcommand.name.clone());
+                        active.extend(subcommand.args.iter().map(|arg| (arg, offset)));
+                        command = subcommand;
                     }
+                    None => error.push(format!(
+                        "Unknown command: {}. Use --help to see the available commands.",
+                        raw
+                    )),
                 }
+                continue;
             }
 
-            if found.is_none() {
-                found = arg_def.default.clone();
+            positionals.push(raw.clone());
+        }
+
+        let skip_validation = help || generate.is_some();
+
+        if !command.commands.is_empty() && !skip_validation {
+            error.push(format!(
+                "Missing command. Available commands: {}",
+                command
+                    .commands
+                    .iter()
+                    .map(|command| command.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        let mut parsed_args: HashMap<String, Value> = HashMap::new();
+
+        for (arg_def, start) in active {
+            let mut found: Vec<String> = flags.remove(&arg_def.name).unwrap_or_default();
+
+            if found.is_empty()
+                && let Some(index) = arg_def.index
+            {
+                let idx = start + index;
+                if arg_def.multiple {
+                    found = positionals.iter().skip(idx).cloned().collect();
+                } else if let Some(value) = positionals.get(idx) {
+                    found = vec![value.clone()];
+                }
             }
 
-            if found.is_none() && arg_def.required {
-                error.push(format!("Missing required argument: {}", arg_def.name));
+            if found.is_empty()
+                && let Some(var) = &arg_def.env
+                && let Ok(value) = env::var(var)
+            {
+                found = arg_def.split(value);
             }
 
-            if let Some(value_str) = found {
-                let value = match arg_def.input_type {
-                    InputType::String => Value::from(value_str),
-                    InputType::Number => match value_str.parse::<f64>().map(Value::from) {
-                        Ok(v) => v,
-                        Err(_) => {
-                            error
-                                .push(format!("Invalid value for {}: {}", arg_def.name, value_str));
+            if found.is_empty()
+                && let Some(default) = &arg_def.default
+            {
+                found = arg_def.split(default.clone());
+            }
 
-                            Value::Null
-                        }
-                    },
-                    InputType::Boolean => {
-                        let v = match value_str.as_str() {
-                            "" => Value::Boolean(true),
-                            "true" | "1" => Value::Boolean(true),
-                            "false" | "0" => Value::Boolean(false),
-                            _ => {
-                                error.push(format!(
-                                    "Invalid value for {}: {}",
-                                    arg_def.name, value_str
-                                ));
-                                Value::Null
-                            }
-                        };
-                        v
-                    }
-                };
-                parsed_args.insert(arg_def.name.clone(), value);
+            if found.is_empty() {
+                if arg_def.required && !skip_validation {
+                    error.push(format!("Missing required argument: {}", arg_def.name));
+                }
+                continue;
+            }
+
+            if skip_validation {
+                continue;
             }
+
+            let mut values: Vec<Value> = found
+                .iter()
+                .filter_map(|value_str| arg_def.convert(value_str, &mut error))
+                .collect();
+
+            let value = if arg_def.multiple {
+                values.to_value()
+            } else {
+                // A repeated single-value flag keeps the last occurrence
+                values.pop().unwrap_or(Value::Null)
+            };
+            parsed_args.insert(arg_def.name.clone(), value);
+        }
+
+        if !root.commands.is_empty() {
+            parsed_args.insert("command".to_string(), path.join(" ").to_value());
         }
 
         Self {
             args: parsed_args,
-            schema: arg_defs,
+            root,
+            path,
             app_data,
             error,
+            help,
+            generate,
+            no_input,
         }
     }
 
-    pub fn is_help(&self) -> bool {
-        let raw_args: Vec<String> = env::args().skip(1).collect();
-
-        raw_args.contains(&"--help".to_string())
-            || raw_args.contains(&"-h".to_string())
-            || raw_args.contains(&"-H".to_string())
+    /// The innermost command given on the command line
+    pub fn command(&self) -> &Command {
+        let mut command = &self.root;
+        for name in &self.path {
+            match command.find(name) {
+                Some(subcommand) => command = subcommand,
+                None => break,
+            }
+        }
+        command
     }
 
-    pub fn print_help(self, extra: Option<String>) {
-        println!(
-            "{}: {}",
-            "Usage".bold().underline(),
-            self.app_data
-                .name
-                .unwrap_or("Phlow Cli".to_string())
-                .bold()
-                .blue()
-        );
-
-        if let Some(version) = self.app_data.version {
-            println!("       {}: {}", "Version", version);
+    /// Step of the innermost command that declares one
+    pub fn start_step(&self) -> Option<String> {
+        let mut command = &self.root;
+        let mut step = None;
+        for name in &self.path {
+            match command.find(name) {
+                Some(subcommand) => {
+                    command = subcommand;
+                    if subcommand.step.is_some() {
+                        step = subcommand.step.clone();
+                    }
+                }
+                None => break,
+            }
         }
+        step
+    }
 
-        if let Some(description) = self.app_data.description {
-            println!("       {}: {}", "Description", description);
+    /// Arguments accepted by the innermost command, including the ones of its parents
+    fn active_args(&self) -> Vec<&Arg> {
+        let mut args: Vec<&Arg> = self.root.args.iter().collect();
+        let mut command = &self.root;
+        for name in &self.path {
+            match command.find(name) {
+                Some(subcommand) => {
+                    args.extend(subcommand.args.iter());
+                    command = subcommand;
+                }
+                None => break,
+            }
         }
+        args
+    }
 
-        if let Some(license) = self.app_data.license {
-            println!("       {}: {}", "License", license);
-        }
+    pub fn is_help(&self) -> bool {
+        self.help
+    }
 
-        if let Some(author) = self.app_data.author {
-            println!("       {}: {}", "Author", author);
+    pub fn print_help(self, extra: Option<String>) {
+        let mut usage = self
+            .app_data
+            .name
+            .clone()
+            .unwrap_or("Phlow Cli".to_string());
+        for name in &self.path {
+            usage.push(' ');
+            usage.push_str(name);
         }
 
-        if let Some(homepage) = self.app_data.homepage {
-            println!("       {}: {}", "Homepage", homepage);
-        }
+        println!("{}: {}", "Usage".bold().underline(), usage.bold().blue());
 
-        if let Some(repository) = self.app_data.repository {
-            println!("       {}: {}", "Repository", repository);
+        if self.path.is_empty() {
+            if let Some(version) = &self.app_data.version {
+                println!("       {}: {}", "Version", version);
+            }
+
+            if let Some(description) = &self.app_data.description {
+                println!("       {}: {}", "Description", description);
+            }
+
+            if let Some(license) = &self.app_data.license {
+                println!("       {}: {}", "License", license);
+            }
+
+            if let Some(author) = &self.app_data.author {
+                println!("       {}: {}", "Author", author);
+            }
+
+            if let Some(homepage) = &self.app_data.homepage {
+                println!("       {}: {}", "Homepage", homepage);
+            }
+
+            if let Some(repository) = &self.app_data.repository {
+                println!("       {}: {}", "Repository", repository);
+            }
+        } else if !self.command().help.is_empty() {
+            println!("       Description: {}", self.command().help);
         }
 
-        println!("");
+        println!();
 
         if let Some(extra) = extra {
             println!("{}", extra);
@@ -285,11 +615,14 @@ impl Args {
         let mut arguments = Vec::new();
         let mut options = Vec::new();
 
-        for arg in &self.schema {
+        for arg in self.active_args() {
             let long = arg.long.as_deref().unwrap_or("");
             let short = arg.short.as_deref().unwrap_or("");
-            let name = &arg.name;
-            let help = &arg.help;
+            let name = if arg.multiple {
+                format!("{}...", arg.name)
+            } else {
+                arg.name.clone()
+            };
             let required = if arg.required {
                 format!("{}", "[required]".yellow().bold())
             } else {
@@ -297,6 +630,14 @@ impl Args {
             };
             let default = arg.default.as_deref().unwrap_or("");
 
+            let mut help = arg.help.clone();
+            if !arg.choices.is_empty() {
+                help.push_str(&format!(" [possible values: {}]", arg.choices.join(", ")));
+            }
+            if let Some(env) = &arg.env {
+                help.push_str(&format!(" [env: {}]", env));
+            }
+
             if arg.index.is_some() {
                 arguments.push(format!("{} {} {} {}", name.bold(), required, default, help));
             } else {
@@ -315,6 +656,15 @@ impl Args {
             }
         }
 
+        let commands = &self.command().commands;
+        if !commands.is_empty() {
+            println!("{}:", "Commands".bold().underline());
+            for command in commands {
+                println!("  {} {}", command.name.bold(), command.help);
+            }
+            println!();
+        }
+
         if !arguments.is_empty() {
             println!("{}:", "Arguments".bold().underline());
             for arg in arguments {
@@ -351,3 +701,183 @@ impl Args {
         self.print_help(Some(err));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with() -> Value {
+        let yaml = r#"{
+            "args": [
+                { "name": "verbose", "long": "verbose", "short": "v", "type": "boolean" },
+                { "name": "region", "long": "region", "env": "PHLOW_CLI_TEST_REGION", "default": "us" }
+            ],
+            "commands": [
+                {
+                    "name": "users",
+                    "commands": [
+                        {
+                            "name": "add",
+                            "step": "add_user",
+                            "args": [
+                                { "name": "email", "index": 0, "required": true },
+                                { "name": "role", "long": "role", "choices": ["admin", "user"], "default": "user" },
+                                { "name": "tags", "long": "tag", "multiple": true },
+                                { "name": "age", "long": "age", "type": "number" }
+                            ]
+                        },
+                        { "name": "list", "step": "list_users" }
+                    ]
+                },
+                {
+                    "name": "import",
+                    "args": [{ "name": "files", "index": 0, "type": "array" }]
+                }
+            ]
+        }"#;
+        Value::json_to_value(yaml).unwrap()
+    }
+
+    fn app_data() -> ApplicationData {
+        ApplicationData {
+            name: Some("tool".to_string()),
+            version: None,
+            environment: None,
+            description: None,
+            author: None,
+            license: None,
+            repository: None,
+            homepage: None,
+        }
+    }
+
+    fn parse(raw: &[&str]) -> Args {
+        Args::parse(
+            &with(),
+            app_data(),
+            None,
+            raw.iter().map(|s| s.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_subcommand_with_args() {
+        let args = parse(&[
+            "--verbose",
+            "users",
+            "add",
+            "ana@example.com",
+            "--tag",
+            "a",
+            "--tag=b",
+            "--role",
+            "admin",
+            "--age",
+            "30",
+        ]);
+        assert!(!args.is_error(), "{:?}", args.error);
+        assert_eq!(args.path, vec!["users", "add"]);
+        assert_eq!(args.start_step(), Some("add_user".to_string()));
+
+        let value = args.args.to_value();
+        assert_eq!(value.get("command").unwrap().to_string(), "users add");
+        assert_eq!(value.get("email").unwrap().to_string(), "ana@example.com");
+        assert_eq!(value.get("role").unwrap().to_string(), "admin");
+        assert_eq!(
+            value.get("tags").unwrap().to_json(JsonMode::Inline),
+            "[\"a\",\"b\"]"
+        );
+        assert_eq!(value.get("age").unwrap().to_f64(), Some(30.0));
+        assert_eq!(value.get("verbose"), Some(&Value::Boolean(true)));
+        assert_eq!(value.get("region").unwrap().to_string(), "us");
+    }
+
+    #[test]
+    fn test_repeated_positionals() {
+        let args = parse(&["import", "a.csv", "b.csv"]);
+        assert!(!args.is_error(), "{:?}", args.error);
+        assert_eq!(args.start_step(), None);
+        assert_eq!(
+            args.args.get("files").unwrap().to_json(JsonMode::Inline),
+            "[\"a.csv\",\"b.csv\"]"
+        );
+    }
+
+    #[test]
+    fn test_command_after_script() {
+        let args = Args::parse(
+            &with(),
+            app_data(),
+            Some("main.phlow".to_string()),
+            vec![
+                "users".to_string(),
+                "add".to_string(),
+                "ana@example.com".to_string(),
+            ],
+        );
+        assert!(!args.is_error(), "{:?}", args.error);
+        assert_eq!(args.start_step(), Some("add_user".to_string()));
+        assert_eq!(
+            args.args.get("email").unwrap().to_string(),
+            "ana@example.com"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let args = parse(&["users", "add", "ana@example.com", "--role", "owner"]);
+        assert_eq!(
+            args.error,
+            vec!["Invalid value for role: owner. Possible values: admin, user"]
+        );
+
+        let args = parse(&["users"]);
+        assert_eq!(
+            args.error,
+            vec!["Missing command. Available commands: add, list"]
+        );
+
+        let args = parse(&["groups"]);
+        assert!(args.error[0].starts_with("Unknown command: groups"));
+
+        // Flags of a subcommand are not accepted before it
+        let args = parse(&["--role", "admin", "users", "list"]);
+        assert!(args.error[0].starts_with("Unknown flag: --role"));
+    }
+
+    #[test]
+    fn test_help_skips_validation() {
+        let args = parse(&["users", "add", "--help"]);
+        assert!(args.is_help());
+        assert!(!args.is_error());
+        assert_eq!(args.command().name, "add");
+    }
+
+    #[test]
+    fn test_file_argument() {
+        let path = std::env::temp_dir().join(format!("phlow-cli-{}.txt", std::process::id()));
+        std::fs::write(&path, "content").unwrap();
+
+        let with = Value::json_to_value(
+            r#"{ "args": [{ "name": "body", "long": "body", "type": "file" }] }"#,
+        )
+        .unwrap();
+        let args = Args::parse(
+            &with,
+            app_data(),
+            None,
+            vec!["--body".to_string(), path.to_string_lossy().to_string()],
+        );
+        assert_eq!(args.args.get("body").unwrap().to_string(), "content");
+        // Without commands, no `command` key is added
+        assert!(!args.args.contains_key("command"));
+
+        let args = Args::parse(
+            &with,
+            app_data(),
+            None,
+            vec!["--body".to_string(), "/nonexistent/phlow".to_string()],
+        );
+        assert!(args.error[0].starts_with("Unable to read file for body"));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::args::{Arg, Command, InputType};
+use phlow_sdk::prelude::*;
+
+/// Every command reachable from `root` with its path of subcommand names
+fn walk<'a>(command: &'a Command, path: Vec<&'a str>, out: &mut Vec<(Vec<&'a str>, &'a Command)>) {
+    out.push((path.clone(), command));
+    for subcommand in &command.commands {
+        let mut path = path.clone();
+        path.push(&subcommand.name);
+        walk(subcommand, path, out);
+    }
+}
+
+/// Arguments accepted at each command path, including the ones inherited from parents
+fn commands(root: &Command) -> Vec<(String, &Command, Vec<&Arg>)> {
+    let mut all = Vec::new();
+    walk(root, Vec::new(), &mut all);
+
+    all.into_iter()
+        .map(|(path, command)| {
+            let mut args: Vec<&Arg> = root.args.iter().collect();
+            let mut current = root;
+            for name in &path {
+                if let Some(subcommand) = current.find(name) {
+                    args.extend(subcommand.args.iter());
+                    current = subcommand;
+                }
+            }
+            (path.join(" "), command, args)
+        })
+        .collect()
+}
+
+fn flags(arg: &Arg) -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(long) = &arg.long {
+        flags.push(format!("--{}", long));
+    }
+    if let Some(short) = &arg.short {
+        flags.push(format!("-{}", short));
+    }
+    flags
+}
+
+/// Bash `case` patterns moving from a command path to one of its subcommands
+fn transitions(commands: &[(String, &Command, Vec<&Arg>)]) -> String {
+    commands
+        .iter()
+        .flat_map(|(path, command, _)| {
+            command
+                .commands
+                .iter()
+                .map(move |subcommand| format!("\"{}:{}\"", path, subcommand.name))
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Name the generated scripts complete, derived from the application name
+pub fn bin_name(app_data: &ApplicationData) -> String {
+    app_data
+        .name
+        .clone()
+        .unwrap_or("phlow".to_string())
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub fn completions(root: &Command, bin: &str, shell: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash(root, bin)),
+        "zsh" => Ok(zsh(root, bin)),
+        "fish" => Ok(fish(root, bin)),
+        other => Err(format!(
+            "Unsupported shell: {}. Supported shells: bash, zsh, fish",
+            other
+        )),
+    }
+}
+
+fn function_name(bin: &str) -> String {
+    format!(
+        "_{}",
+        bin.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    )
+}
+
+fn bash(root: &Command, bin: &str) -> String {
+    let commands = commands(root);
+    let function = function_name(bin);
+    let mut script = String::new();
+
+    script.push_str(&format!("{}() {{\n", function));
+    script.push_str("    local cur prev cmd_path word i\n");
+    script.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    cmd_path=\"\"\n");
+    script.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    script.push_str("        word=\"${COMP_WORDS[i]}\"\n");
+    script.push_str("        case \"${cmd_path}:${word}\" in\n");
+    let transitions = transitions(&commands);
+    if !transitions.is_empty() {
+        script.push_str(&format!(
+            "            {}) cmd_path=\"${{cmd_path:+$cmd_path }}${{word}}\" ;;\n",
+            transitions
+        ));
+    }
+    script.push_str("        esac\n");
+    script.push_str("    done\n\n");
+
+    script.push_str("    case \"${cmd_path}:${prev}\" in\n");
+    for (path, _, args) in &commands {
+        for arg in args {
+            let patterns = flags(arg)
+                .iter()
+                .map(|flag| format!("\"{}:{}\"", path, flag))
+                .collect::<Vec<_>>();
+            if patterns.is_empty() {
+                continue;
+            }
+            let reply = if !arg.choices.is_empty() {
+                format!("compgen -W \"{}\" -- \"$cur\"", arg.choices.join(" "))
+            } else if arg.input_type == InputType::File {
+                "compgen -f -- \"$cur\"".to_string()
+            } else {
+                continue;
+            };
+            script.push_str(&format!(
+                "        {}) COMPREPLY=($({})); return ;;\n",
+                patterns.join("|"),
+                reply
+            ));
+        }
+    }
+    script.push_str("    esac\n\n");
+
+    script.push_str("    case \"$cmd_path\" in\n");
+    for (path, command, args) in &commands {
+        let mut words: Vec<String> = command
+            .commands
+            .iter()
+            .map(|command| command.name.clone())
+            .collect();
+        words.extend(args.iter().flat_map(|arg| flags(arg)));
+        words.push("--help".to_string());
+        script.push_str(&format!(
+            "        \"{}\") COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+            path,
+            words.join(" ")
+        ));
+    }
+    script.push_str("    esac\n");
+    script.push_str("}\n\n");
+    script.push_str(&format!("complete -F {} {}\n", function, bin));
+    script
+}
+
+fn zsh(root: &Command, bin: &str) -> String {
+    let commands = commands(root);
+    let function = function_name(bin);
+    let mut script = String::new();
+
+    // `path` is tied to `PATH` in zsh, hence `cmd_path`
+    script.push_str(&format!("#compdef {}\n\n", bin));
+    script.push_str(&format!("{}() {{\n", function));
+    script.push_str("    local cmd_path=\"\" word i\n");
+    script.push_str("    for ((i = 2; i < CURRENT; i++)); do\n");
+    script.push_str("        word=\"${words[i]}\"\n");
+    script.push_str("        case \"${cmd_path}:${word}\" in\n");
+    let transitions = transitions(&commands);
+    if !transitions.is_empty() {
+        script.push_str(&format!(
+            "            {}) cmd_path=\"${{cmd_path:+$cmd_path }}${{word}}\" ;;\n",
+            transitions
+        ));
+    }
+    script.push_str("        esac\n");
+    script.push_str("    done\n\n");
+
+    script.push_str("    case \"${cmd_path}:${words[CURRENT-1]}\" in\n");
+    for (path, _, args) in &commands {
+        for arg in args {
+            let patterns = flags(arg)
+                .iter()
+                .map(|flag| format!("\"{}:{}\"", path, flag))
+                .collect::<Vec<_>>();
+            if patterns.is_empty() {
+                continue;
+            }
+            let reply = if !arg.choices.is_empty() {
+                format!("compadd -- {}", arg.choices.join(" "))
+            } else if arg.input_type == InputType::File {
+                "_files".to_string()
+            } else {
+                continue;
+            };
+            script.push_str(&format!(
+                "        {}) {}; return ;;\n",
+                patterns.join("|"),
+                reply
+            ));
+        }
+    }
+    script.push_str("    esac\n\n");
+
+    script.push_str("    case \"$cmd_path\" in\n");
+    for (path, command, args) in &commands {
+        let mut words: Vec<String> = command
+            .commands
+            .iter()
+            .map(|command| command.name.clone())
+            .collect();
+        words.extend(args.iter().flat_map(|arg| flags(arg)));
+        words.push("--help".to_string());
+        script.push_str(&format!(
+            "        \"{}\") compadd -- {} ;;\n",
+            path,
+            words.join(" ")
+        ));
+    }
+    script.push_str("    esac\n");
+    script.push_str("}\n\n");
+    script.push_str(&format!("compdef {} {}\n", function, bin));
+    script
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(root: &Command, bin: &str) -> String {
+    let commands = commands(root);
+    // Succeeds when the words typed so far select the command path given as argument
+    let function = format!("{}_at", function_name(bin));
+    let mut script = String::new();
+
+    script.push_str(&format!("function {}\n", function));
+    script.push_str("    set -l cmd_path \"\"\n");
+    script.push_str("    for word in (commandline -opc)[2..-1]\n");
+    script.push_str("        switch \"$cmd_path:$word\"\n");
+    let transitions = transitions(&commands).replace('|', " ");
+    if !transitions.is_empty() {
+        script.push_str(&format!("            case {}\n", transitions));
+        script.push_str("                set cmd_path (string trim -- \"$cmd_path $word\")\n");
+    }
+    script.push_str("        end\n");
+    script.push_str("    end\n");
+    script.push_str("    test \"$cmd_path\" = \"$argv[1]\"\n");
+    script.push_str("end\n\n");
+
+    script.push_str(&format!("complete -c {} -f\n", bin));
+    for (path, command, args) in &commands {
+        let condition = format!("-n '{} \"{}\"'", function, path);
+
+        for subcommand in &command.commands {
+            script.push_str(&format!(
+                "complete -c {} {} -a {} -d {}\n",
+                bin,
+                condition,
+                fish_quote(&subcommand.name),
+                fish_quote(&subcommand.help)
+            ));
+        }
+
+        for arg in args {
+            if arg.long.is_none() && arg.short.is_none() {
+                continue;
+            }
+            let mut line = format!("complete -c {} {}", bin, condition);
+            if let Some(long) = &arg.long {
+                line.push_str(&format!(" -l {}", long));
+            }
+            if let Some(short) = &arg.short {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if arg.input_type == InputType::File {
+                line.push_str(" -r -F");
+            } else if arg.input_type != InputType::Boolean {
+                line.push_str(" -r");
+            }
+            if !arg.choices.is_empty() {
+                line.push_str(&format!(" -a {}", fish_quote(&arg.choices.join(" "))));
+            }
+            line.push_str(&format!(" -d {}", fish_quote(&arg.help)));
+            script.push_str(&line);
+            script.push('\n');
+        }
+    }
+    script
+}
+
+/// Escapes text for roff, where `-` and `\` are special and lines starting with `.` or `'`
+/// are requests
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    match escaped.chars().next() {
+        Some('.') | Some('\'') => format!("\\&{}", escaped),
+        _ => escaped,
+    }
+}
+
+fn man_options(page: &mut String, args: &[&Arg]) {
+    for arg in args {
+        page.push_str(".TP\n");
+        let mut names = flags(arg)
+            .iter()
+            .map(|flag| format!("\\fB{}\\fR", roff(flag)))
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            names.push(format!("\\fI{}\\fR", roff(&arg.name)));
+        } else if arg.input_type != InputType::Boolean {
+            names.push(format!("\\fI<{}>\\fR", roff(&arg.name)));
+        }
+        page.push_str(&names.join(" "));
+        page.push('\n');
+
+        let mut help = roff(&arg.help);
+        if arg.required {
+            help.push_str(" [required]");
+        }
+        if arg.multiple {
+            help.push_str(" [repeatable]");
+        }
+        if let Some(default) = &arg.default {
+            help.push_str(&format!(" [default: {}]", roff(default)));
+        }
+        if let Some(env) = &arg.env {
+            help.push_str(&format!(" [env: {}]", roff(env)));
+        }
+        if !arg.choices.is_empty() {
+            help.push_str(&format!(
+                " [possible values: {}]",
+                roff(&arg.choices.join(", "))
+            ));
+        }
+        page.push_str(help.trim_start());
+        page.push('\n');
+    }
+}
+
+pub fn man(root: &Command, app_data: &ApplicationData) -> String {
+    let bin = bin_name(app_data);
+    let mut page = String::new();
+
+    page.push_str(&format!(
+        ".TH {} 1 \"\" \"{} {}\"\n",
+        roff(&bin.to_uppercase()),
+        roff(&bin),
+        roff(app_data.version.as_deref().unwrap_or(""))
+    ));
+
+    page.push_str(".SH NAME\n");
+    match &app_data.description {
+        Some(description) => page.push_str(&format!("{} \\- {}\n", roff(&bin), roff(description))),
+        None => page.push_str(&format!("{}\n", roff(&bin))),
+    }
+
+    page.push_str(".SH SYNOPSIS\n");
+    let mut synopsis = format!("\\fB{}\\fR [OPTIONS]", roff(&bin));
+    if !root.commands.is_empty() {
+        synopsis.push_str(" <COMMAND>");
+    }
+    for arg in root.args.iter().filter(|arg| arg.index.is_some()) {
+        synopsis.push_str(&format!(" <{}>", roff(&arg.name)));
+    }
+    page.push_str(&synopsis);
+    page.push('\n');
+
+    let root_args: Vec<&Arg> = root.args.iter().collect();
+    if !root_args.is_empty() {
+        page.push_str(".SH OPTIONS\n");
+        man_options(&mut page, &root_args);
+    }
+
+    let subcommands: Vec<_> = commands(root)
+        .into_iter()
+        .filter(|(path, _, _)| !path.is_empty())
+        .collect();
+    if !subcommands.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        for (path, command, _) in subcommands {
+            page.push_str(&format!(".SS \"{} {}\"\n", roff(&bin), roff(&path)));
+            if !command.help.is_empty() {
+                page.push_str(&roff(&command.help));
+                page.push('\n');
+            }
+            let args: Vec<&Arg> = command.args.iter().collect();
+            man_options(&mut page, &args);
+        }
+    }
+
+    if let Some(author) = &app_data.author {
+        page.push_str(".SH AUTHOR\n");
+        page.push_str(&roff(author));
+        page.push('\n');
+    }
+
+    let links: Vec<&String> = [&app_data.homepage, &app_data.repository]
+        .into_iter()
+        .flatten()
+        .collect();
+    if !links.is_empty() {
+        page.push_str(".SH SEE ALSO\n");
+        for link in links {
+            page.push_str(&roff(link));
+            page.push_str("\n.br\n");
+        }
+    }
+
+    page
+}
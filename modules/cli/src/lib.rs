@@ -1,19 +1,30 @@
 mod args;
+mod generate;
+mod output;
+mod prompt;
 mod resolve;
+mod terminal;
 use std::env;
 
-use args::Args;
+use args::{Args, Generate};
 use phlow_sdk::{
     prelude::*,
-    tracing::{field, Level},
+    tracing::{Level, field},
 };
 use resolve::resolve;
+use terminal::Terminal;
 
 create_main!(cli(setup));
 
 pub async fn cli(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    
-    sender_safe!(setup.setup_sender, None);
+    let is_main = setup.is_main();
+    let rx = module_channel!(setup);
+
+    // Only used in steps, e.g. with another main module
+    if !is_main {
+        Terminal::new(env::args().any(|arg| arg == "--no-input")).listen(rx);
+        return Ok(());
+    }
 
     let _ = phlow_sdk::tracing::dispatcher::with_default(&setup.dispatch.clone(), || async move {
         let span = tracing::span!(
@@ -34,16 +45,29 @@ pub async fn cli(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Error + S
 
         span_enter!(span);
 
-        let with_args = setup.with.get("args").cloned().unwrap_or(Value::Null);
-        let additional_args = *setup
-            .with
-            .get("additional_args")
-            .cloned()
-            .unwrap_or(Value::Boolean(false))
-            .as_bool()
-            .unwrap_or(&false);
+        let args = Args::new(&setup.with, setup.app_data.clone(), setup.script_arg_index);
 
-        let args = Args::new(with_args, setup.app_data.clone(), additional_args);
+        if let Some(generate) = &args.generate {
+            let output = match generate {
+                Generate::Completions(shell) => {
+                    generate::completions(&args.root, &generate::bin_name(&args.app_data), shell)
+                }
+                Generate::Man => Ok(generate::man(&args.root, &args.app_data)),
+            };
+
+            match output {
+                Ok(output) => {
+                    span.record("process.exit.code", 0);
+                    print!("{}", output);
+                }
+                Err(err) => {
+                    span.record("error.type", "invalid_input");
+                    span.record("process.exit.code", 1);
+                    eprintln!("Error: {}", err);
+                }
+            }
+            return Ok::<(), Box<dyn std::error::Error + Send + Sync>>(());
+        }
 
         if args.is_error() {
             span.record("error.type", "invalid_input");
@@ -58,6 +82,10 @@ pub async fn cli(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Error + S
             return Ok::<(), Box<dyn std::error::Error + Send + Sync>>(());
         }
 
+        // Prompts block on the terminal, so step actions run on their own thread
+        let terminal = Terminal::new(args.no_input);
+        std::thread::spawn(move || terminal.listen(rx));
+
         let context = resolve::RequestContext {
             args: args.clone(),
             span: span.clone(),
@@ -0,0 +1,220 @@
+use colored::*;
+use comfy_table::{Table, presets::UTF8_FULL};
+use indicatif::{ProgressBar, ProgressStyle};
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Object(_) | Value::Array(_) => value.to_json(JsonMode::Indented),
+        Value::Null | Value::Undefined => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Prints a message. `level` adds a coloured marker, and `error` and `warn` go to stderr.
+pub fn print(input: &Value) -> Result<(), String> {
+    let message = text(input.get("message").unwrap_or(&Value::Null));
+    let level = input
+        .get("level")
+        .map(Value::to_string)
+        .unwrap_or("plain".to_string());
+
+    let (marker, color) = match level.as_str() {
+        "plain" => ("", None),
+        "info" => ("ℹ ", Some(Color::Blue)),
+        "success" => ("✔ ", Some(Color::Green)),
+        "warn" => ("⚠ ", Some(Color::Yellow)),
+        "error" => ("✖ ", Some(Color::Red)),
+        other => {
+            return Err(format!(
+                "Invalid level: {}. Possible values: plain, info, success, warn, error",
+                other
+            ));
+        }
+    };
+
+    let color = match input.get("color") {
+        Some(color) => Some(
+            color
+                .to_string()
+                .parse::<Color>()
+                .map_err(|_| format!("Invalid color: {}", color))?,
+        ),
+        None => color,
+    };
+
+    let mut line = format!("{}{}", marker, message).normal();
+    if let Some(color) = color {
+        line = line.color(color);
+    }
+    if *input.get("bold").and_then(Value::as_bool).unwrap_or(&false) {
+        line = line.bold();
+    }
+
+    match level.as_str() {
+        "warn" | "error" => eprintln!("{}", line),
+        _ => println!("{}", line),
+    }
+    Ok(())
+}
+
+/// Header and cells of a table. Rows are objects or arrays; object rows take their
+/// columns from `columns` or, when it is missing, from the keys of the rows in sorted order.
+fn table_cells(input: &Value) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let rows = match input.get("rows") {
+        Some(Value::Array(array)) => &array.values,
+        _ => return Err("Invalid input: rows must be an array.".to_string()),
+    };
+
+    let mut columns: Vec<String> = match input.get("columns") {
+        Some(Value::Array(array)) => array.values.iter().map(Value::to_string).collect(),
+        _ => Vec::new(),
+    };
+    if columns.is_empty() {
+        for row in rows {
+            if let Value::Object(object) = row {
+                for (key, _) in object.iter() {
+                    let key = key.to_string();
+                    if !columns.contains(&key) {
+                        columns.push(key);
+                    }
+                }
+            }
+        }
+        columns.sort();
+    }
+
+    let cell = |value: Option<&Value>| match value {
+        Some(value @ (Value::Object(_) | Value::Array(_))) => value.to_json(JsonMode::Inline),
+        Some(value) => text(value),
+        None => String::new(),
+    };
+
+    let cells = rows
+        .iter()
+        .map(|row| match row {
+            Value::Array(array) => array.values.iter().map(|item| cell(Some(item))).collect(),
+            row if row.is_object() => columns
+                .iter()
+                .map(|column| cell(row.get(column.as_str())))
+                .collect(),
+            row => vec![cell(Some(row))],
+        })
+        .collect();
+
+    Ok((columns, cells))
+}
+
+pub fn table(input: &Value) -> Result<(), String> {
+    let (columns, cells) = table_cells(input)?;
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    if !columns.is_empty() {
+        table.set_header(columns);
+    }
+    for row in cells {
+        table.add_row(row);
+    }
+
+    println!("{}", table);
+    Ok(())
+}
+
+/// Progress bars that live across steps, by `id`
+#[derive(Default)]
+pub struct Progress {
+    bars: HashMap<String, ProgressBar>,
+}
+
+impl Progress {
+    /// `total` starts a bar (a spinner when there is no bar yet and no total), `position`
+    /// and `increment` move it and `finish` completes it.
+    pub fn update(&mut self, input: &Value) -> Result<(), String> {
+        let id = input
+            .get("id")
+            .map(Value::to_string)
+            .unwrap_or("progress".to_string());
+        let message = input.get("message").map(Value::to_string);
+
+        if let Some(total) = input.get("total") {
+            let total = total
+                .to_u64()
+                .ok_or_else(|| format!("Invalid total: {}", total))?;
+            let bar = ProgressBar::new(total).with_style(
+                ProgressStyle::with_template("{msg} [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
+                    .map_err(|e| e.to_string())?
+                    .progress_chars("=> "),
+            );
+            if let Some(old) = self.bars.insert(id.clone(), bar) {
+                old.finish_and_clear();
+            }
+        }
+
+        let bar = self
+            .bars
+            .entry(id.clone())
+            .or_insert_with(ProgressBar::new_spinner);
+
+        if let Some(message) = &message {
+            bar.set_message(message.clone());
+        }
+
+        if let Some(position) = input.get("position").and_then(Value::to_u64) {
+            bar.set_position(position);
+        } else if let Some(increment) = input.get("increment").and_then(Value::to_u64) {
+            bar.inc(increment);
+        } else {
+            bar.tick();
+        }
+
+        if *input
+            .get("finish")
+            .and_then(Value::as_bool)
+            .unwrap_or(&false)
+            && let Some(bar) = self.bars.remove(&id)
+        {
+            match message {
+                Some(message) => bar.finish_with_message(message),
+                None => bar.finish(),
+            }
+            // The finished bar stays on screen; keep the next output off its line
+            if !bar.is_hidden() {
+                eprintln!();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_cells() {
+        let input = Value::json_to_value(
+            r#"{
+                "rows": [
+                    { "name": "ana", "age": 31 },
+                    { "name": "bruno", "tags": ["admin"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let (columns, cells) = table_cells(&input).unwrap();
+        assert_eq!(columns, vec!["age", "name", "tags"]);
+        assert_eq!(cells[0], vec!["31", "ana", ""]);
+        assert_eq!(cells[1], vec!["", "bruno", "[\"admin\"]"]);
+
+        let input = Value::json_to_value(
+            r#"{ "columns": ["age"], "rows": [{ "name": "ana", "age": 31 }] }"#,
+        )
+        .unwrap();
+        let (columns, cells) = table_cells(&input).unwrap();
+        assert_eq!(columns, vec!["age"]);
+        assert_eq!(cells[0], vec!["31"]);
+    }
+}
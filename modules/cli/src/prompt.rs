@@ -0,0 +1,308 @@
+use dialoguer::{Confirm, Input, MultiSelect, Password, Select, theme::ColorfulTheme};
+use phlow_sdk::prelude::*;
+
+/// An option of a `select` prompt. Options given as plain values use the value as label.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    pub label: String,
+    pub value: Value,
+}
+
+impl Choice {
+    fn parse(item: &Value) -> Self {
+        if item.is_object() {
+            let value = item.get("value").cloned().unwrap_or(Value::Null);
+            let label = item
+                .get("label")
+                .map(Value::to_string)
+                .unwrap_or_else(|| value.to_string());
+            Self { label, value }
+        } else {
+            Self {
+                label: item.to_string(),
+                value: item.clone(),
+            }
+        }
+    }
+
+    fn matches(&self, answer: &str) -> bool {
+        self.value.to_string() == answer || self.label == answer
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub message: String,
+    /// Answer given by the flow, usually mapped from a cli argument. The prompt is not shown
+    pub value: Option<Value>,
+    pub default: Option<Value>,
+    pub options: Vec<Choice>,
+    pub multiple: bool,
+    /// Asks a password twice
+    pub confirmation: bool,
+}
+
+impl From<&Value> for Prompt {
+    fn from(input: &Value) -> Self {
+        let present = |key: &str| match input.get(key) {
+            None | Some(Value::Null) | Some(Value::Undefined) => None,
+            Some(value) => Some(value.clone()),
+        };
+
+        let options = match input.get("options") {
+            Some(Value::Array(array)) => array.values.iter().map(Choice::parse).collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            message: input
+                .get("message")
+                .map(Value::to_string)
+                .unwrap_or_default(),
+            value: present("value"),
+            default: present("default"),
+            options,
+            multiple: *input
+                .get("multiple")
+                .and_then(Value::as_bool)
+                .unwrap_or(&false),
+            confirmation: *input
+                .get("confirmation")
+                .and_then(Value::as_bool)
+                .unwrap_or(&false),
+        }
+    }
+}
+
+impl Prompt {
+    /// Answer used without asking: the given value, or the default when the terminal
+    /// is not interactive
+    fn answer(&self, interactive: bool) -> Result<Option<Value>, String> {
+        if let Some(value) = &self.value {
+            return Ok(Some(value.clone()));
+        }
+
+        if interactive {
+            return Ok(None);
+        }
+
+        match &self.default {
+            Some(default) => Ok(Some(default.clone())),
+            None => Err(format!(
+                "No answer for '{}': input is not interactive. Pass a value or set a default.",
+                self.message
+            )),
+        }
+    }
+
+    pub fn text(&self, interactive: bool) -> Result<Value, String> {
+        if let Some(answer) = self.answer(interactive)? {
+            return Ok(answer.to_string().to_value());
+        }
+
+        let theme = ColorfulTheme::default();
+        let mut input = Input::<String>::with_theme(&theme).with_prompt(&self.message);
+        if let Some(default) = &self.default {
+            input = input.default(default.to_string());
+        }
+
+        input
+            .interact_text()
+            .map(|text| text.to_value())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn password(&self, interactive: bool) -> Result<Value, String> {
+        if let Some(answer) = self.answer(interactive)? {
+            return Ok(answer.to_string().to_value());
+        }
+
+        let theme = ColorfulTheme::default();
+        let mut input = Password::with_theme(&theme).with_prompt(&self.message);
+        if self.confirmation {
+            input = input.with_confirmation("Confirm password", "Passwords do not match");
+        }
+
+        input
+            .interact()
+            .map(|password| password.to_value())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn confirm(&self, interactive: bool) -> Result<Value, String> {
+        if let Some(answer) = self.answer(interactive)? {
+            return self.to_bool(&answer).map(Value::Boolean);
+        }
+
+        let theme = ColorfulTheme::default();
+        let mut input = Confirm::with_theme(&theme).with_prompt(&self.message);
+        if let Some(default) = &self.default {
+            input = input.default(self.to_bool(default)?);
+        }
+
+        input
+            .interact()
+            .map(Value::Boolean)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn select(&self, interactive: bool) -> Result<Value, String> {
+        if self.options.is_empty() {
+            return Err(format!("Missing options for '{}'", self.message));
+        }
+
+        if let Some(answer) = self.answer(interactive)? {
+            let choices = self.choices(&answer)?;
+            return Ok(if self.multiple {
+                choices.to_value()
+            } else {
+                choices.into_iter().next().unwrap_or(Value::Null)
+            });
+        }
+
+        let defaults = match &self.default {
+            Some(default) => self.indexes(default)?,
+            None => Vec::new(),
+        };
+        let labels: Vec<&str> = self.options.iter().map(|c| c.label.as_str()).collect();
+        let theme = ColorfulTheme::default();
+
+        if self.multiple {
+            let checked: Vec<bool> = (0..labels.len()).map(|i| defaults.contains(&i)).collect();
+            MultiSelect::with_theme(&theme)
+                .with_prompt(&self.message)
+                .items(&labels)
+                .defaults(&checked)
+                .interact()
+                .map(|selected| {
+                    selected
+                        .into_iter()
+                        .map(|i| self.options[i].value.clone())
+                        .collect::<Vec<_>>()
+                        .to_value()
+                })
+                .map_err(|e| e.to_string())
+        } else {
+            Select::with_theme(&theme)
+                .with_prompt(&self.message)
+                .items(&labels)
+                .default(defaults.first().copied().unwrap_or(0))
+                .interact()
+                .map(|i| self.options[i].value.clone())
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    fn to_bool(&self, value: &Value) -> Result<bool, String> {
+        if let Some(value) = value.as_bool() {
+            return Ok(*value);
+        }
+
+        match value.to_string().to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Ok(true),
+            "false" | "no" | "n" | "0" => Ok(false),
+            other => Err(format!("Invalid value for '{}': {}", self.message, other)),
+        }
+    }
+
+    /// Indexes of the options named by an answer. Several options are given as an array
+    /// or a comma separated string.
+    fn indexes(&self, answer: &Value) -> Result<Vec<usize>, String> {
+        let answers: Vec<String> = match answer {
+            Value::Array(array) => array.values.iter().map(Value::to_string).collect(),
+            value if self.multiple => value
+                .to_string()
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+            value => vec![value.to_string()],
+        };
+
+        answers
+            .iter()
+            .map(|answer| {
+                self.options
+                    .iter()
+                    .position(|choice| choice.matches(answer))
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid value for '{}': {}. Possible values: {}",
+                            self.message,
+                            answer,
+                            self.options
+                                .iter()
+                                .map(|choice| choice.value.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    fn choices(&self, answer: &Value) -> Result<Vec<Value>, String> {
+        Ok(self
+            .indexes(answer)?
+            .into_iter()
+            .map(|i| self.options[i].value.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(json: &str) -> Prompt {
+        Prompt::from(&Value::json_to_value(json).unwrap())
+    }
+
+    #[test]
+    fn test_value_skips_the_prompt() {
+        let name = prompt(r#"{ "message": "Name", "value": "ana", "default": "bruno" }"#);
+        assert_eq!(name.text(true).unwrap().to_string(), "ana");
+        assert_eq!(name.text(false).unwrap().to_string(), "ana");
+    }
+
+    #[test]
+    fn test_non_interactive_fallback() {
+        let name = prompt(r#"{ "message": "Name", "default": "bruno" }"#);
+        assert_eq!(name.text(false).unwrap().to_string(), "bruno");
+
+        let confirm = prompt(r#"{ "message": "Continue?", "default": "yes" }"#);
+        assert_eq!(confirm.confirm(false).unwrap(), Value::Boolean(true));
+
+        let missing = prompt(r#"{ "message": "Token" }"#);
+        assert_eq!(
+            missing.password(false).unwrap_err(),
+            "No answer for 'Token': input is not interactive. Pass a value or set a default."
+        );
+    }
+
+    #[test]
+    fn test_select_answers() {
+        let env = prompt(
+            r#"{
+                "message": "Environment",
+                "options": [{ "label": "Production", "value": "prod" }, "staging"],
+                "value": "Production"
+            }"#,
+        );
+        assert_eq!(env.select(false).unwrap().to_string(), "prod");
+
+        let regions = prompt(
+            r#"{ "message": "Regions", "options": ["us", "eu", "br"], "multiple": true, "default": "eu, br" }"#,
+        );
+        assert_eq!(
+            regions.select(false).unwrap().to_json(JsonMode::Inline),
+            "[\"eu\",\"br\"]"
+        );
+
+        let invalid = prompt(r#"{ "message": "Region", "options": ["us", "eu"], "value": "ap" }"#);
+        assert_eq!(
+            invalid.select(false).unwrap_err(),
+            "Invalid value for 'Region': ap. Possible values: us, eu"
+        );
+    }
+}
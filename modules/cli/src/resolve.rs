@@ -15,12 +15,15 @@ pub struct RequestContext {
 }
 
 pub async fn resolve(context: RequestContext) -> Result<Value, Infallible> {
+    // Subcommands with a `step` start the flow there
+    let start_step = context.args.start_step();
     let response_value = sender_package!(
         context.span.clone(),
         context.dispatch.clone(),
         context.id,
         context.sender,
-        Some(context.args.args.to_value())
+        Some(context.args.args.to_value()),
+        start_step
     )
     .await
     .unwrap_or(Value::Null);
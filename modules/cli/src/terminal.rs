@@ -0,0 +1,64 @@
+use crate::output::{self, Progress};
+use crate::prompt::Prompt;
+use phlow_sdk::prelude::*;
+use std::io::{self, IsTerminal};
+
+/// Runs the step actions of the module: prompts and terminal output.
+pub struct Terminal {
+    /// Prompts are only shown on a terminal and without `--no-input`. Otherwise they
+    /// answer from `value` or `default`, so the same flow runs in CI.
+    interactive: bool,
+    progress: Progress,
+}
+
+impl Terminal {
+    pub fn new(no_input: bool) -> Self {
+        Self {
+            interactive: !no_input && io::stdin().is_terminal() && io::stderr().is_terminal(),
+            progress: Progress::default(),
+        }
+    }
+
+    /// Handles packages one at a time, so prompts and output never interleave
+    pub fn listen(mut self, rx: ModuleReceiver) {
+        for package in rx {
+            let input = package.input().unwrap_or(Value::Null);
+            let payload = package.payload().unwrap_or(Value::Null);
+
+            let response = match self.run(&input, payload) {
+                Ok(value) => value.into(),
+                Err(err) => {
+                    log::debug!("Cli step failed: {}", err);
+                    ModuleResponse::from_error(err)
+                }
+            };
+            sender_safe!(package.sender, response);
+        }
+    }
+
+    /// Prompts return the answer; output actions pass the payload through
+    fn run(&mut self, input: &Value, payload: Value) -> Result<Value, String> {
+        if !input.is_object() {
+            return Err("Invalid input: expected an object with an action.".to_string());
+        }
+
+        let action = input
+            .get("action")
+            .map(Value::to_string)
+            .unwrap_or_default();
+
+        match action.as_str() {
+            "text" => Prompt::from(input).text(self.interactive),
+            "password" => Prompt::from(input).password(self.interactive),
+            "confirm" => Prompt::from(input).confirm(self.interactive),
+            "select" => Prompt::from(input).select(self.interactive),
+            "print" => output::print(input).map(|_| payload),
+            "table" => output::table(input).map(|_| payload),
+            "progress" => self.progress.update(input).map(|_| payload),
+            other => Err(format!(
+                "Unknown action: {}. Available actions: text, password, confirm, select, print, table, progress",
+                other
+            )),
+        }
+    }
+}
@@ -88,6 +88,7 @@ mod tests {
             input: Some(Value::from("test message")),
             payload: None,
             sender: result_tx,
+            span: None,
         };
 
         // Enviar pacote
@@ -117,6 +118,7 @@ mod tests {
             input: None,
             payload: None,
             sender: result_tx,
+            span: None,
         };
 
         // Enviar pacote
@@ -148,6 +150,7 @@ mod tests {
             input: Some(input.clone()),
             payload: None,
             sender: result_tx,
+            span: None,
         };
 
         // Enviar pacote
@@ -0,0 +1,176 @@
+use crate::codec::DynamicCodec;
+use crate::convert::{ConvertError, message_to_value, value_to_message};
+use crate::descriptors::{self, DescriptorError};
+use crate::setup::Config;
+use crate::status::error_message;
+use phlow_sdk::prelude::*;
+use prost_reflect::DescriptorPool;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::time::Duration;
+use tonic::client::Grpc;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+#[derive(Debug)]
+pub enum ClientError {
+    InvalidAddress(String),
+    MissingMethod,
+    Descriptor(DescriptorError),
+    Convert(ConvertError),
+    InvalidMetadata(String),
+    Status(Status),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidAddress(address) => write!(f, "Invalid gRPC address: {}", address),
+            Self::MissingMethod => write!(f, "The `method` to call is required"),
+            Self::Descriptor(e) => write!(f, "{}", e),
+            Self::Convert(e) => write!(f, "Invalid request message: {}", e),
+            Self::InvalidMetadata(key) => write!(f, "Invalid metadata entry: {}", key),
+            Self::Status(status) => write!(f, "{}", error_message(status)),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Calls methods of the configured `.proto` services on `address`. The channel connects
+/// lazily and is shared by every step of the module.
+#[derive(Clone)]
+pub struct GrpcClient {
+    channel: Channel,
+    pool: DescriptorPool,
+    service: Option<String>,
+    timeout: Duration,
+}
+
+impl GrpcClient {
+    pub fn new(config: &Config, pool: DescriptorPool) -> Result<Self, ClientError> {
+        let channel = Endpoint::from_shared(config.address.clone())
+            .map_err(|_| ClientError::InvalidAddress(config.address.clone()))?
+            .connect_lazy();
+
+        Ok(Self {
+            channel,
+            pool,
+            service: config.service.clone(),
+            timeout: Duration::from_millis(config.timeout_ms),
+        })
+    }
+
+    /// Calls `input.method` with `input.message`. Server-streaming responses are collected
+    /// into an array.
+    pub async fn call(
+        &self,
+        input: &Value,
+        trace_headers: HashMap<String, String>,
+    ) -> Result<Value, ClientError> {
+        let method_name = input
+            .get("method")
+            .map(|v| v.to_string())
+            .ok_or(ClientError::MissingMethod)?;
+        let service = input
+            .get("service")
+            .map(|v| v.to_string())
+            .or_else(|| self.service.clone());
+        let method = descriptors::find_method(&self.pool, service.as_deref(), &method_name)
+            .map_err(ClientError::Descriptor)?;
+
+        if method.is_client_streaming() {
+            return Err(ClientError::Status(Status::unimplemented(
+                "Client-streaming calls are not supported",
+            )));
+        }
+
+        let message = value_to_message(
+            input.get("message").unwrap_or(&Value::Null),
+            &method.input(),
+        )
+        .map_err(ClientError::Convert)?;
+
+        let timeout = input
+            .get("timeout_ms")
+            .and_then(|v| v.to_i64())
+            .map(|v| Duration::from_millis(v as u64))
+            .unwrap_or(self.timeout);
+
+        let mut request = Request::new(message);
+        request.set_timeout(timeout);
+        let metadata = request.metadata_mut();
+        if let Some(Value::Object(entries)) = input.get("metadata") {
+            for (key, value) in entries.iter() {
+                insert_metadata(metadata, &key.to_string(), &value.to_string())?;
+            }
+        }
+        // Trace context from the flow is added unless the step already sets it
+        for (key, value) in trace_headers {
+            if !metadata.contains_key(key.as_str()) {
+                insert_metadata(metadata, &key, &value)?;
+            }
+        }
+
+        let path = descriptors::path(&method)
+            .parse()
+            .map_err(|_| ClientError::Descriptor(DescriptorError::UnknownMethod(method_name)))?;
+        let codec = DynamicCodec::new(method.output());
+        let mut grpc = Grpc::new(self.channel.clone());
+
+        let call = async {
+            grpc.ready()
+                .await
+                .map_err(|e| Status::unavailable(format!("Service not ready: {}", e)))?;
+
+            if method.is_server_streaming() {
+                let response = grpc.server_streaming(request, path, codec).await?;
+                let metadata = metadata_entries(response.metadata()).to_value();
+                let mut stream = response.into_inner();
+                let mut messages = Vec::new();
+                while let Some(message) = stream.message().await? {
+                    messages.push(message_to_value(&message));
+                }
+                Ok::<_, Status>((messages.to_value(), metadata))
+            } else {
+                let response = grpc.unary(request, path, codec).await?;
+                let metadata = metadata_entries(response.metadata()).to_value();
+                Ok((message_to_value(response.get_ref()), metadata))
+            }
+        };
+
+        let (response, metadata) = tokio::time::timeout(timeout, call)
+            .await
+            .map_err(|_| ClientError::Status(Status::deadline_exceeded("Call timed out")))?
+            .map_err(ClientError::Status)?;
+
+        Ok(HashMap::from([("response", response), ("metadata", metadata)]).to_value())
+    }
+}
+
+fn insert_metadata(metadata: &mut MetadataMap, key: &str, value: &str) -> Result<(), ClientError> {
+    let invalid = || ClientError::InvalidMetadata(key.to_string());
+    let key = MetadataKey::from_bytes(key.to_lowercase().as_bytes()).map_err(|_| invalid())?;
+    let value = MetadataValue::try_from(value).map_err(|_| invalid())?;
+    metadata.insert(key, value);
+    Ok(())
+}
+
+/// ASCII metadata entries. Binary (`-bin`) entries and the headers reserved by the
+/// gRPC protocol (`te`, `content-type`, `grpc-*`) are skipped.
+pub fn metadata_entries(metadata: &MetadataMap) -> HashMap<String, String> {
+    metadata
+        .clone()
+        .into_headers()
+        .iter()
+        .filter(|(key, _)| {
+            let key = key.as_str();
+            !key.ends_with("-bin")
+                && !key.starts_with("grpc-")
+                && key != "te"
+                && key != "content-type"
+        })
+        .filter_map(|(key, value)| Some((key.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
@@ -0,0 +1,389 @@
+//! Mapping between protobuf messages and `Value`.
+//!
+//! Fields use their `.proto` names (the JSON names are also accepted on input), enums are
+//! written as their value names, `bytes` as base64 strings, repeated fields as arrays and
+//! map fields as objects. 64-bit integers are also accepted as numeric strings.
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use phlow_sdk::prelude::*;
+use prost_reflect::{
+    DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor, ReflectMessage,
+    Value as ProtoValue,
+};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq)]
+pub struct ConvertError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ConvertError {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// Converts a message into an object with every field. Fields with explicit presence
+/// (`optional`, messages, oneof members) are left out when unset.
+pub fn message_to_value(message: &DynamicMessage) -> Value {
+    let mut object = HashMap::new();
+    for field in message.descriptor().fields() {
+        if field.supports_presence() && !message.has_field(&field) {
+            continue;
+        }
+        let value = message.get_field(&field);
+        object.insert(field.name().to_string(), field_to_value(&value, &field));
+    }
+    object.to_value()
+}
+
+fn field_to_value(value: &ProtoValue, field: &FieldDescriptor) -> Value {
+    match value {
+        ProtoValue::List(items) => items
+            .iter()
+            .map(|item| scalar_to_value(item, &field.kind()))
+            .collect::<Vec<_>>()
+            .to_value(),
+        ProtoValue::Map(entries) => {
+            let value_kind = match field.kind() {
+                Kind::Message(entry) => entry.map_entry_value_field().kind(),
+                kind => kind,
+            };
+            entries
+                .iter()
+                .map(|(key, value)| (map_key_to_string(key), scalar_to_value(value, &value_kind)))
+                .collect::<HashMap<_, _>>()
+                .to_value()
+        }
+        value => scalar_to_value(value, &field.kind()),
+    }
+}
+
+fn scalar_to_value(value: &ProtoValue, kind: &Kind) -> Value {
+    match value {
+        ProtoValue::Bool(v) => v.to_value(),
+        ProtoValue::I32(v) => v.to_value(),
+        ProtoValue::I64(v) => v.to_value(),
+        ProtoValue::U32(v) => v.to_value(),
+        ProtoValue::U64(v) => v.to_value(),
+        ProtoValue::F32(v) => (*v as f64).to_value(),
+        ProtoValue::F64(v) => v.to_value(),
+        ProtoValue::String(v) => v.to_value(),
+        ProtoValue::Bytes(v) => BASE64.encode(v).to_value(),
+        ProtoValue::EnumNumber(number) => match kind {
+            Kind::Enum(descriptor) => match descriptor.get_value(*number) {
+                Some(enum_value) => enum_value.name().to_value(),
+                None => number.to_value(),
+            },
+            _ => number.to_value(),
+        },
+        ProtoValue::Message(message) => message_to_value(message),
+        ProtoValue::List(_) | ProtoValue::Map(_) => Value::Null,
+    }
+}
+
+fn map_key_to_string(key: &MapKey) -> String {
+    match key {
+        MapKey::Bool(v) => v.to_string(),
+        MapKey::I32(v) => v.to_string(),
+        MapKey::I64(v) => v.to_string(),
+        MapKey::U32(v) => v.to_string(),
+        MapKey::U64(v) => v.to_string(),
+        MapKey::String(v) => v.clone(),
+    }
+}
+
+/// Builds a message of type `descriptor` from an object. Null fields are left unset and
+/// unknown fields are rejected.
+pub fn value_to_message(
+    value: &Value,
+    descriptor: &MessageDescriptor,
+) -> Result<DynamicMessage, ConvertError> {
+    object_to_message(value, descriptor, "message")
+}
+
+fn object_to_message(
+    value: &Value,
+    descriptor: &MessageDescriptor,
+    path: &str,
+) -> Result<DynamicMessage, ConvertError> {
+    let mut message = DynamicMessage::new(descriptor.clone());
+
+    let object = match value {
+        Value::Object(object) => object,
+        Value::Null | Value::Undefined => return Ok(message),
+        _ => {
+            return Err(ConvertError::new(
+                path,
+                format!("expected an object for {}", descriptor.full_name()),
+            ));
+        }
+    };
+
+    for (key, item) in object.iter() {
+        let key = key.to_string();
+        let field_path = format!("{}.{}", path, key);
+        let field = descriptor
+            .get_field_by_name(&key)
+            .or_else(|| descriptor.get_field_by_json_name(&key))
+            .ok_or_else(|| ConvertError::new(&field_path, "unknown field"))?;
+
+        if matches!(item, Value::Null | Value::Undefined) {
+            continue;
+        }
+
+        let value = if field.is_map() {
+            value_to_map(item, &field, &field_path)?
+        } else if field.is_list() {
+            match item {
+                Value::Array(array) => ProtoValue::List(
+                    array
+                        .values
+                        .iter()
+                        .enumerate()
+                        .map(|(index, item)| {
+                            value_to_scalar(
+                                item,
+                                &field.kind(),
+                                &format!("{}[{}]", field_path, index),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                ),
+                _ => return Err(ConvertError::new(&field_path, "expected an array")),
+            }
+        } else {
+            value_to_scalar(item, &field.kind(), &field_path)?
+        };
+
+        message.set_field(&field, value);
+    }
+
+    Ok(message)
+}
+
+fn value_to_map(
+    value: &Value,
+    field: &FieldDescriptor,
+    path: &str,
+) -> Result<ProtoValue, ConvertError> {
+    let entry = match field.kind() {
+        Kind::Message(entry) => entry,
+        _ => return Err(ConvertError::new(path, "invalid map field")),
+    };
+    let key_kind = entry.map_entry_key_field().kind();
+    let value_kind = entry.map_entry_value_field().kind();
+
+    let object = match value {
+        Value::Object(object) => object,
+        _ => return Err(ConvertError::new(path, "expected an object")),
+    };
+
+    let mut map = HashMap::new();
+    for (key, item) in object.iter() {
+        let key = key.to_string();
+        let entry_path = format!("{}.{}", path, key);
+        let map_key = string_to_map_key(&key, &key_kind)
+            .ok_or_else(|| ConvertError::new(&entry_path, "invalid map key"))?;
+        map.insert(map_key, value_to_scalar(item, &value_kind, &entry_path)?);
+    }
+
+    Ok(ProtoValue::Map(map))
+}
+
+fn string_to_map_key(key: &str, kind: &Kind) -> Option<MapKey> {
+    Some(match kind {
+        Kind::Bool => MapKey::Bool(key.parse().ok()?),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => MapKey::I32(key.parse().ok()?),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => MapKey::I64(key.parse().ok()?),
+        Kind::Uint32 | Kind::Fixed32 => MapKey::U32(key.parse().ok()?),
+        Kind::Uint64 | Kind::Fixed64 => MapKey::U64(key.parse().ok()?),
+        Kind::String => MapKey::String(key.to_string()),
+        _ => return None,
+    })
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(_) => value.to_i64(),
+        Value::String(_) => value.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+fn unsigned(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(_) => value.to_u64(),
+        Value::String(_) => value.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(_) => value.to_f64(),
+        Value::String(_) => value.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+fn value_to_scalar(value: &Value, kind: &Kind, path: &str) -> Result<ProtoValue, ConvertError> {
+    let invalid = |expected: &str| ConvertError::new(path, format!("expected {}", expected));
+
+    Ok(match kind {
+        Kind::Double => ProtoValue::F64(float(value).ok_or_else(|| invalid("a number"))?),
+        Kind::Float => ProtoValue::F32(float(value).ok_or_else(|| invalid("a number"))? as f32),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => ProtoValue::I32(
+            integer(value)
+                .and_then(|v| i32::try_from(v).ok())
+                .ok_or_else(|| invalid("a 32-bit integer"))?,
+        ),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+            ProtoValue::I64(integer(value).ok_or_else(|| invalid("an integer"))?)
+        }
+        Kind::Uint32 | Kind::Fixed32 => ProtoValue::U32(
+            unsigned(value)
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| invalid("an unsigned 32-bit integer"))?,
+        ),
+        Kind::Uint64 | Kind::Fixed64 => {
+            ProtoValue::U64(unsigned(value).ok_or_else(|| invalid("an unsigned integer"))?)
+        }
+        Kind::Bool => match value {
+            Value::Boolean(v) => ProtoValue::Bool(*v),
+            _ => return Err(invalid("a boolean")),
+        },
+        Kind::String => match value {
+            Value::String(_) => ProtoValue::String(value.to_string()),
+            _ => return Err(invalid("a string")),
+        },
+        Kind::Bytes => match value {
+            Value::String(_) => ProtoValue::Bytes(
+                BASE64
+                    .decode(value.to_string())
+                    .map_err(|_| invalid("a base64 string"))?
+                    .into(),
+            ),
+            _ => return Err(invalid("a base64 string")),
+        },
+        Kind::Enum(descriptor) => match value {
+            Value::String(_) => {
+                let name = value.to_string();
+                let enum_value = descriptor.get_value_by_name(&name).ok_or_else(|| {
+                    ConvertError::new(
+                        path,
+                        format!("unknown value '{}' for {}", name, descriptor.full_name()),
+                    )
+                })?;
+                ProtoValue::EnumNumber(enum_value.number())
+            }
+            _ => ProtoValue::EnumNumber(
+                integer(value)
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or_else(|| invalid("an enum name or number"))?,
+            ),
+        },
+        Kind::Message(descriptor) => {
+            ProtoValue::Message(object_to_message(value, descriptor, path)?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptors::tests::pool;
+
+    fn user() -> Value {
+        HashMap::from([
+            ("id", "42".to_value()),
+            ("name", "Ana".to_value()),
+            ("status", "STATUS_ACTIVE".to_value()),
+            ("tags", vec!["admin", "ops"].to_value()),
+            ("scores", HashMap::from([("math", 9)]).to_value()),
+            ("address", HashMap::from([("city", "Recife")]).to_value()),
+            ("avatar", "aGk=".to_value()),
+            ("rating", 4.5.to_value()),
+        ])
+        .to_value()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let descriptor = pool().get_message_by_name("demo.v1.User").unwrap();
+        let message = value_to_message(&user(), &descriptor).unwrap();
+        let value = message_to_value(&message);
+
+        assert_eq!(value.get("id").unwrap().to_i64(), Some(42));
+        assert_eq!(value.get("status").unwrap().to_string(), "STATUS_ACTIVE");
+        assert_eq!(
+            value.get("tags").unwrap().to_json(JsonMode::Inline),
+            "[\"admin\",\"ops\"]"
+        );
+        assert_eq!(
+            value.get("scores").unwrap().get("math").unwrap().to_i64(),
+            Some(9)
+        );
+        assert_eq!(
+            value
+                .get("address")
+                .unwrap()
+                .get("city")
+                .unwrap()
+                .to_string(),
+            "Recife"
+        );
+        assert_eq!(value.get("avatar").unwrap().to_string(), "aGk=");
+        assert_eq!(value.get("rating").unwrap().to_f64(), Some(4.5));
+        // Unset optional fields are omitted
+        assert!(value.get("nickname").is_none());
+    }
+
+    #[test]
+    fn test_defaults_are_present() {
+        let descriptor = pool().get_message_by_name("demo.v1.User").unwrap();
+        let value = message_to_value(&DynamicMessage::new(descriptor));
+
+        assert_eq!(value.get("id").unwrap().to_i64(), Some(0));
+        assert_eq!(
+            value.get("status").unwrap().to_string(),
+            "STATUS_UNSPECIFIED"
+        );
+        assert!(value.get("address").is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let descriptor = pool().get_message_by_name("demo.v1.User").unwrap();
+
+        let value = HashMap::from([("unknown", 1)]).to_value();
+        assert_eq!(
+            value_to_message(&value, &descriptor).unwrap_err().path,
+            "message.unknown"
+        );
+
+        let value = HashMap::from([("tags", vec![1])]).to_value();
+        assert_eq!(
+            value_to_message(&value, &descriptor)
+                .unwrap_err()
+                .to_string(),
+            "message.tags[0]: expected a string"
+        );
+
+        let value = HashMap::from([("status", "STATUS_DELETED")]).to_value();
+        assert!(value_to_message(&value, &descriptor).is_err());
+    }
+}
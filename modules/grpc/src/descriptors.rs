@@ -0,0 +1,169 @@
+use crate::setup::Config;
+use prost_reflect::{DescriptorPool, MethodDescriptor, ServiceDescriptor};
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum DescriptorError {
+    Compile(protox::Error),
+    Descriptor(prost_reflect::DescriptorError),
+    UnknownService(String),
+    UnknownMethod(String),
+    MissingService(String),
+}
+
+impl Display for DescriptorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Compile(e) => write!(f, "Failed to compile proto files: {}", e),
+            Self::Descriptor(e) => write!(f, "Invalid proto descriptors: {}", e),
+            Self::UnknownService(name) => write!(f, "Unknown gRPC service: {}", name),
+            Self::UnknownMethod(name) => write!(f, "Unknown gRPC method: {}", name),
+            Self::MissingService(method) => write!(
+                f,
+                "Method '{}' needs a service, use `service` or `package.Service/Method`",
+                method
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DescriptorError {}
+
+/// Compiles the configured `.proto` files in process, so no generated code or server
+/// reflection is needed.
+pub fn load(config: &Config) -> Result<DescriptorPool, DescriptorError> {
+    let files =
+        protox::compile(&config.proto, &config.includes).map_err(DescriptorError::Compile)?;
+    DescriptorPool::from_file_descriptor_set(files).map_err(DescriptorError::Descriptor)
+}
+
+/// Services exposed by the server: the configured one, or every service in the files
+pub fn services(
+    pool: &DescriptorPool,
+    service: Option<&str>,
+) -> Result<Vec<ServiceDescriptor>, DescriptorError> {
+    match service {
+        Some(name) => pool
+            .get_service_by_name(name)
+            .map(|service| vec![service])
+            .ok_or_else(|| DescriptorError::UnknownService(name.to_string())),
+        None => Ok(pool.services().collect()),
+    }
+}
+
+/// Resolves `package.Service/Method` (optionally with a leading `/`), or a bare
+/// method name on `service`. A bare name also resolves when the files declare a
+/// single service.
+pub fn find_method(
+    pool: &DescriptorPool,
+    service: Option<&str>,
+    method: &str,
+) -> Result<MethodDescriptor, DescriptorError> {
+    let method = method.trim_start_matches('/');
+    let (service, name) = match method.split_once('/') {
+        Some((service, name)) => (service.to_string(), name),
+        None => match service {
+            Some(service) => (service.to_string(), method),
+            None => {
+                let mut services = pool.services();
+                match (services.next(), services.next()) {
+                    (Some(service), None) => (service.full_name().to_string(), method),
+                    _ => return Err(DescriptorError::MissingService(method.to_string())),
+                }
+            }
+        },
+    };
+
+    let descriptor = pool
+        .get_service_by_name(&service)
+        .ok_or_else(|| DescriptorError::UnknownService(service.clone()))?;
+
+    descriptor
+        .methods()
+        .find(|candidate| candidate.name() == name)
+        .ok_or_else(|| DescriptorError::UnknownMethod(format!("{}/{}", service, name)))
+}
+
+/// HTTP/2 path of a method, as sent on the wire
+pub fn path(method: &MethodDescriptor) -> String {
+    format!("/{}/{}", method.parent_service().full_name(), method.name())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    pub const PROTO: &str = r#"
+syntax = "proto3";
+package demo.v1;
+
+enum Status {
+  STATUS_UNSPECIFIED = 0;
+  STATUS_ACTIVE = 1;
+  STATUS_BLOCKED = 2;
+}
+
+message Address {
+  string city = 1;
+}
+
+message User {
+  int64 id = 1;
+  string name = 2;
+  Status status = 3;
+  repeated string tags = 4;
+  map<string, int32> scores = 5;
+  Address address = 6;
+  bytes avatar = 7;
+  optional string nickname = 8;
+  double rating = 9;
+}
+
+message GetUserRequest {
+  int64 id = 1;
+}
+
+service Users {
+  rpc GetUser(GetUserRequest) returns (User);
+  rpc ListUsers(GetUserRequest) returns (stream User);
+}
+"#;
+
+    /// Writes the test schema to a unique directory and compiles it
+    pub fn pool() -> DescriptorPool {
+        let dir: PathBuf = std::env::temp_dir().join(format!(
+            "phlow-grpc-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("demo.proto");
+        std::fs::write(&file, PROTO).unwrap();
+
+        let files = protox::compile([&file], [&dir]).unwrap();
+        DescriptorPool::from_file_descriptor_set(files).unwrap()
+    }
+
+    #[test]
+    fn test_find_method() {
+        let pool = pool();
+
+        let method = find_method(&pool, None, "demo.v1.Users/GetUser").unwrap();
+        assert_eq!(path(&method), "/demo.v1.Users/GetUser");
+        assert!(!method.is_server_streaming());
+
+        // A single service lets steps name only the method
+        let method = find_method(&pool, None, "ListUsers").unwrap();
+        assert!(method.is_server_streaming());
+
+        assert!(matches!(
+            find_method(&pool, Some("demo.v1.Users"), "Delete"),
+            Err(DescriptorError::UnknownMethod(_))
+        ));
+        assert!(matches!(
+            find_method(&pool, None, "demo.v1.Groups/List"),
+            Err(DescriptorError::UnknownService(_))
+        ));
+    }
+}
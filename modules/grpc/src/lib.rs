@@ -0,0 +1,65 @@
+mod client;
+mod codec;
+mod convert;
+mod descriptors;
+mod server;
+mod setup;
+mod status;
+
+use client::GrpcClient;
+use phlow_sdk::prelude::*;
+use server::start_grpc_server;
+use setup::Config;
+
+create_main!(start_grpc_module(setup));
+
+pub async fn start_grpc_module(
+    setup: ModuleSetup,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::try_from(&setup.with).map_err(|e| format!("{}", e))?;
+
+    log::debug!("Starting gRPC module with config: {:?}", config);
+
+    let pool = descriptors::load(&config)?;
+
+    // Without a main sender (--test, --migrate) grpc is only used as a client step
+    if let Some(main_sender) = setup.main_sender.clone() {
+        log::info!("Starting gRPC server as main module");
+        let dispatch = setup.dispatch.clone();
+        let id = setup.id;
+        let config = config.clone();
+        let pool = pool.clone();
+
+        tokio::task::spawn(async move {
+            if let Err(e) = start_grpc_server(config, pool, dispatch, main_sender, id).await {
+                log::error!("gRPC server error: {}", e);
+            }
+        });
+    }
+
+    let rx = module_channel!(setup);
+    let client = GrpcClient::new(&config, pool)?;
+
+    for package in rx {
+        let client = client.clone();
+        let trace_headers = package.trace_headers();
+
+        tokio::task::spawn(async move {
+            let response = match package.input() {
+                Some(input) => match client.call(&input, trace_headers).await {
+                    Ok(value) => value.into(),
+                    Err(e) => {
+                        log::error!("gRPC call failed: {}", e);
+                        ModuleResponse::from_error(e.to_string())
+                    }
+                },
+                None => ModuleResponse::from_error("No input provided".to_string()),
+            };
+
+            sender_safe!(package.sender, response);
+        });
+    }
+
+    log::debug!("gRPC module finished");
+    Ok(())
+}
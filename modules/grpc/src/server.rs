@@ -0,0 +1,272 @@
+use crate::client::metadata_entries;
+use crate::codec::DynamicCodec;
+use crate::convert::{message_to_value, value_to_message};
+use crate::descriptors;
+use crate::setup::Config;
+use crate::status;
+use futures_util::Stream;
+use futures_util::future::BoxFuture;
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use phlow_sdk::prelude::*;
+use phlow_sdk::tracing::{Dispatch, Level};
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tonic::body::Body;
+use tonic::server::{Grpc, ServerStreamingService, UnaryService};
+use tonic::{Request, Response, Status};
+
+type MessageStream = Pin<Box<dyn Stream<Item = Result<DynamicMessage, Status>> + Send>>;
+
+/// Hands every call to the flow. Routes are built from the descriptors, so no
+/// generated service code is involved.
+struct ServerContext {
+    routes: HashMap<String, MethodDescriptor>,
+    dispatch: Dispatch,
+    main_sender: MainRuntimeSender,
+    id: ModuleId,
+}
+
+impl ServerContext {
+    /// Runs the flow with `{ service, method, message, metadata }` and returns its result.
+    /// The runtime drops the request without a response when the flow fails, which is
+    /// answered with `INTERNAL`.
+    async fn run_flow(
+        &self,
+        method: &MethodDescriptor,
+        request: Request<DynamicMessage>,
+    ) -> Result<Value, Status> {
+        let metadata = metadata_entries(request.metadata());
+        let service_name = method.parent_service().full_name().to_string();
+        let data = HashMap::from([
+            ("service", service_name.to_value()),
+            ("method", method.name().to_value()),
+            ("message", message_to_value(request.get_ref())),
+            ("metadata", metadata.to_value()),
+        ])
+        .to_value();
+
+        let dispatch = self.dispatch.clone();
+        let id = self.id;
+        let main_sender = self.main_sender.clone();
+        let method_name = method.name().to_string();
+        let full_name = method.full_name().to_string();
+
+        let result = phlow_sdk::tracing::dispatcher::with_default(&self.dispatch, || {
+            let span = tracing::span!(
+                Level::INFO,
+                "grpc_call",
+                "rpc.system" = "grpc",
+                "rpc.service" = service_name,
+                "rpc.method" = method_name,
+            );
+            phlow_sdk::propagation::extract(&span, &metadata);
+
+            span_enter!(span);
+
+            Box::pin(async move {
+                sender_package!(span.clone(), dispatch, id, main_sender, Some(data)).await
+            })
+        })
+        .await;
+
+        result.map_err(|_| {
+            log::warn!("gRPC method {} failed in the flow", full_name);
+            Status::internal(format!("Method {} failed to execute", full_name))
+        })
+    }
+}
+
+fn invalid_response(method: &MethodDescriptor, error: impl std::fmt::Display) -> Status {
+    log::warn!(
+        "Flow returned an invalid {} response: {}",
+        method.output().full_name(),
+        error
+    );
+    Status::internal(format!("Invalid response message: {}", error))
+}
+
+#[derive(Clone)]
+struct FlowService {
+    context: Arc<ServerContext>,
+    method: MethodDescriptor,
+}
+
+impl UnaryService<DynamicMessage> for FlowService {
+    type Response = DynamicMessage;
+    type Future = BoxFuture<'static, Result<Response<DynamicMessage>, Status>>;
+
+    fn call(&mut self, request: Request<DynamicMessage>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let result = service.context.run_flow(&service.method, request).await?;
+            if let Some(status) = status::from_result(&result) {
+                return Err(status);
+            }
+
+            value_to_message(&result, &service.method.output())
+                .map(Response::new)
+                .map_err(|e| invalid_response(&service.method, e))
+        })
+    }
+}
+
+impl ServerStreamingService<DynamicMessage> for FlowService {
+    type Response = DynamicMessage;
+    type ResponseStream = MessageStream;
+    type Future = BoxFuture<'static, Result<Response<MessageStream>, Status>>;
+
+    /// An array result is streamed one message per element
+    fn call(&mut self, request: Request<DynamicMessage>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let result = service.context.run_flow(&service.method, request).await?;
+            if let Some(status) = status::from_result(&result) {
+                return Err(status);
+            }
+
+            let items = match result {
+                Value::Array(array) => array.values,
+                Value::Null | Value::Undefined => Vec::new(),
+                value => vec![value],
+            };
+            let messages = items
+                .iter()
+                .map(|item| value_to_message(item, &service.method.output()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid_response(&service.method, e))?;
+
+            let stream: MessageStream =
+                Box::pin(futures_util::stream::iter(messages.into_iter().map(Ok)));
+            Ok(Response::new(stream))
+        })
+    }
+}
+
+async fn handle(
+    context: Arc<ServerContext>,
+    request: http::Request<hyper::body::Incoming>,
+) -> Result<http::Response<Body>, Infallible> {
+    let method = match context.routes.get(request.uri().path()) {
+        Some(method) => method.clone(),
+        None => {
+            log::debug!("Unknown gRPC method: {}", request.uri().path());
+            return Ok(
+                Status::unimplemented(format!("Unknown method {}", request.uri().path()))
+                    .into_http(),
+            );
+        }
+    };
+
+    if method.is_client_streaming() {
+        return Ok(Status::unimplemented("Client-streaming methods are not supported").into_http());
+    }
+
+    let mut grpc = Grpc::new(DynamicCodec::new(method.input()));
+    let service = FlowService { context, method };
+
+    let response = if service.method.is_server_streaming() {
+        grpc.server_streaming(service, request).await
+    } else {
+        grpc.unary(service, request).await
+    };
+    Ok(response)
+}
+
+pub async fn start_grpc_server(
+    config: Config,
+    pool: DescriptorPool,
+    dispatch: Dispatch,
+    main_sender: MainRuntimeSender,
+    id: ModuleId,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let routes: HashMap<String, MethodDescriptor> =
+        descriptors::services(&pool, config.service.as_deref())?
+            .iter()
+            .flat_map(|service| service.methods())
+            .map(|method| (descriptors::path(&method), method))
+            .collect();
+
+    for path in routes.keys() {
+        log::debug!("Serving gRPC method {}", path);
+    }
+
+    let context = Arc::new(ServerContext {
+        routes,
+        dispatch,
+        main_sender,
+        id,
+    });
+
+    let listener = TcpListener::bind(config.server_address()).await?;
+    log::info!("gRPC server listening on {}", config.server_address());
+
+    loop {
+        let (stream, remote) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::warn!("Failed to accept gRPC connection: {}", e);
+                continue;
+            }
+        };
+
+        let context = context.clone();
+        tokio::task::spawn(async move {
+            let service = service_fn(move |request| handle(context.clone(), request));
+            if let Err(e) = http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("gRPC connection from {} closed: {}", remote, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptors::tests::pool;
+    use phlow_sdk::crossbeam::channel;
+
+    /// Drops every request like the runtime does when the flow fails
+    fn failing_context(method: &MethodDescriptor) -> Arc<ServerContext> {
+        let (main_sender, receiver) = channel::unbounded::<Package>();
+        std::thread::spawn(move || for _package in receiver {});
+
+        Arc::new(ServerContext {
+            routes: HashMap::from([(descriptors::path(method), method.clone())]),
+            dispatch: Dispatch::default(),
+            main_sender,
+            id: 0,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_failed_flow_is_internal() {
+        let pool = pool();
+        for name in ["GetUser", "ListUsers"] {
+            let method = descriptors::find_method(&pool, None, name).unwrap();
+            let mut service = FlowService {
+                context: failing_context(&method),
+                method: method.clone(),
+            };
+            let request = || Request::new(DynamicMessage::new(method.input()));
+
+            let status = if method.is_server_streaming() {
+                ServerStreamingService::call(&mut service, request())
+                    .await
+                    .err()
+                    .unwrap()
+            } else {
+                UnaryService::call(&mut service, request()).await.unwrap_err()
+            };
+            assert_eq!(status.code(), tonic::Code::Internal);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use phlow_sdk::prelude::*;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum Error {
+    MissingProto,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingProto => write!(f, "At least one .proto file is required in `proto`"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// `.proto` files compiled at startup
+    pub proto: Vec<String>,
+    /// Import paths; defaults to the directories of the `proto` files
+    pub includes: Vec<String>,
+    pub host: String,
+    pub port: u16,
+    /// Target of the calls made by steps, such as `http://localhost:50051`
+    pub address: String,
+    /// Service used by steps that only name the method, and the only one served when set
+    pub service: Option<String>,
+    pub timeout_ms: u64,
+}
+
+impl Config {
+    pub fn server_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(array)) => array.values.iter().map(|v| v.to_string()).collect(),
+        Some(Value::Null) | Some(Value::Undefined) | None => Vec::new(),
+        Some(value) => vec![value.to_string()],
+    }
+}
+
+impl TryFrom<&Value> for Config {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Error> {
+        let proto = strings(value.get("proto"));
+        if proto.is_empty() {
+            return Err(Error::MissingProto);
+        }
+
+        let mut includes = strings(value.get("includes"));
+        if includes.is_empty() {
+            for file in &proto {
+                let dir = std::path::Path::new(file)
+                    .parent()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .filter(|dir| !dir.is_empty())
+                    .unwrap_or_else(|| ".".to_string());
+                if !includes.contains(&dir) {
+                    includes.push(dir);
+                }
+            }
+        }
+
+        let host = value
+            .get("host")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "0.0.0.0".to_string());
+
+        let port = value
+            .get("port")
+            .and_then(|v| v.to_i64())
+            .map(|v| v as u16)
+            .unwrap_or(50051);
+
+        let address = value
+            .get("address")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| format!("http://localhost:{}", port));
+
+        let service = value.get("service").map(|v| v.to_string());
+
+        let timeout_ms = value
+            .get("timeout_ms")
+            .and_then(|v| v.to_i64())
+            .map(|v| v as u64)
+            .unwrap_or(5000);
+
+        Ok(Self {
+            proto,
+            includes,
+            host,
+            port,
+            address,
+            service,
+            timeout_ms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::try_from(
+            &HashMap::from([("proto", "protos/users.proto".to_value())]).to_value(),
+        )
+        .unwrap();
+
+        assert_eq!(config.proto, vec!["protos/users.proto"]);
+        assert_eq!(config.includes, vec!["protos"]);
+        assert_eq!(config.server_address(), "0.0.0.0:50051");
+        assert_eq!(config.address, "http://localhost:50051");
+        assert_eq!(config.service, None);
+    }
+
+    #[test]
+    fn test_missing_proto() {
+        assert!(matches!(
+            Config::try_from(&HashMap::from([("port", 9000)]).to_value()),
+            Err(Error::MissingProto)
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use phlow_sdk::prelude::*;
+use tonic::{Code, Status};
+
+const CODES: [(Code, &str); 17] = [
+    (Code::Ok, "OK"),
+    (Code::Cancelled, "CANCELLED"),
+    (Code::Unknown, "UNKNOWN"),
+    (Code::InvalidArgument, "INVALID_ARGUMENT"),
+    (Code::DeadlineExceeded, "DEADLINE_EXCEEDED"),
+    (Code::NotFound, "NOT_FOUND"),
+    (Code::AlreadyExists, "ALREADY_EXISTS"),
+    (Code::PermissionDenied, "PERMISSION_DENIED"),
+    (Code::ResourceExhausted, "RESOURCE_EXHAUSTED"),
+    (Code::FailedPrecondition, "FAILED_PRECONDITION"),
+    (Code::Aborted, "ABORTED"),
+    (Code::OutOfRange, "OUT_OF_RANGE"),
+    (Code::Unimplemented, "UNIMPLEMENTED"),
+    (Code::Internal, "INTERNAL"),
+    (Code::Unavailable, "UNAVAILABLE"),
+    (Code::DataLoss, "DATA_LOSS"),
+    (Code::Unauthenticated, "UNAUTHENTICATED"),
+];
+
+/// Canonical name of a status code, such as `NOT_FOUND`
+pub fn code_name(code: Code) -> &'static str {
+    CODES
+        .iter()
+        .find(|(candidate, _)| *candidate == code)
+        .map(|(_, name)| *name)
+        .unwrap_or("UNKNOWN")
+}
+
+/// Parses a code from its name (case insensitive) or number
+pub fn parse_code(value: &Value) -> Option<Code> {
+    match value {
+        Value::Number(_) => value.to_i64().map(|number| Code::from_i32(number as i32)),
+        Value::String(_) => {
+            let name = value.to_string().to_uppercase();
+            CODES
+                .iter()
+                .find(|(_, candidate)| *candidate == name)
+                .map(|(code, _)| *code)
+        }
+        _ => None,
+    }
+}
+
+/// Message reported to the flow when a call ends with a non-OK status
+pub fn error_message(status: &Status) -> String {
+    format!(
+        "gRPC status {} ({}): {}",
+        code_name(status.code()),
+        status.code() as i32,
+        status.message()
+    )
+}
+
+/// A flow result of `{ grpc_status, grpc_message }` is answered with that status
+/// instead of a message.
+pub fn from_result(value: &Value) -> Option<Status> {
+    if !value.is_object() {
+        return None;
+    }
+    let code = parse_code(value.get("grpc_status")?)?;
+    if code == Code::Ok {
+        return None;
+    }
+    let message = value
+        .get("grpc_message")
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    Some(Status::new(code, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!(parse_code(&"not_found".to_value()), Some(Code::NotFound));
+        assert_eq!(parse_code(&7.to_value()), Some(Code::PermissionDenied));
+        assert_eq!(parse_code(&"TEAPOT".to_value()), None);
+        assert_eq!(code_name(Code::Unavailable), "UNAVAILABLE");
+    }
+
+    #[test]
+    fn test_status_from_result() {
+        let value = HashMap::from([
+            ("grpc_status", "NOT_FOUND".to_value()),
+            ("grpc_message", "user 1 not found".to_value()),
+        ])
+        .to_value();
+        let status = from_result(&value).unwrap();
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(
+            error_message(&status),
+            "gRPC status NOT_FOUND (5): user 1 not found"
+        );
+
+        assert!(from_result(&HashMap::from([("id", 1)]).to_value()).is_none());
+        assert!(from_result(&HashMap::from([("grpc_status", "OK")]).to_value()).is_none());
+    }
+}
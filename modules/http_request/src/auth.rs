@@ -0,0 +1,198 @@
+use md5::{Digest, Md5};
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Auth {
+    Basic { username: String, password: String },
+    Bearer(String),
+    Digest { username: String, password: String },
+}
+
+impl Auth {
+    /// `auth` is an object with a `type` of `basic`, `bearer` or `digest`
+    pub fn parse(value: Option<&Value>) -> Option<Self> {
+        let value = value.filter(|v| v.is_object())?;
+        let field = |key: &str| value.get(key).map(|v| v.to_string());
+
+        match field("type")
+            .unwrap_or_else(|| "basic".to_string())
+            .as_str()
+        {
+            "basic" => Some(Auth::Basic {
+                username: field("username")?,
+                password: field("password").unwrap_or_default(),
+            }),
+            "bearer" => Some(Auth::Bearer(field("token")?)),
+            "digest" => Some(Auth::Digest {
+                username: field("username")?,
+                password: field("password").unwrap_or_default(),
+            }),
+            other => {
+                log::warn!("Unknown auth type '{}', ignoring auth", other);
+                None
+            }
+        }
+    }
+}
+
+/// Parameters of a `WWW-Authenticate: Digest ...` challenge
+#[derive(Debug, PartialEq)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub qop: Option<String>,
+    pub opaque: Option<String>,
+    pub algorithm: Option<String>,
+}
+
+impl DigestChallenge {
+    pub fn parse(header: &str) -> Option<Self> {
+        let params = header.trim().strip_prefix("Digest")?;
+        let mut fields: HashMap<String, String> = HashMap::new();
+
+        let mut rest = params.trim_start();
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq]
+                .trim()
+                .trim_start_matches(',')
+                .trim()
+                .to_lowercase();
+            rest = &rest[eq + 1..];
+            let value = if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"')?;
+                let value = &quoted[..end];
+                rest = &quoted[end + 1..];
+                value
+            } else {
+                let end = rest.find(',').unwrap_or(rest.len());
+                let value = rest[..end].trim();
+                rest = &rest[end..];
+                value
+            };
+            fields.insert(key, value.to_string());
+            rest = rest.trim_start().trim_start_matches(',').trim_start();
+        }
+
+        Some(DigestChallenge {
+            realm: fields.remove("realm")?,
+            nonce: fields.remove("nonce")?,
+            qop: fields.remove("qop"),
+            opaque: fields.remove("opaque"),
+            algorithm: fields.remove("algorithm"),
+        })
+    }
+
+    /// Builds the `Authorization` header value answering this challenge (RFC 7616, MD5)
+    pub fn authorization(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        cnonce: &str,
+    ) -> String {
+        let nc = "00000001";
+        let session = self
+            .algorithm
+            .as_deref()
+            .is_some_and(|a| a.eq_ignore_ascii_case("MD5-sess"));
+
+        let mut ha1 = md5_hex(&format!("{}:{}:{}", username, self.realm, password));
+        if session {
+            ha1 = md5_hex(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = md5_hex(&format!("{}:{}", method, uri));
+
+        // Servers may offer several qop values; only `auth` is supported
+        let qop = self
+            .qop
+            .as_deref()
+            .filter(|qop| qop.split(',').any(|q| q.trim() == "auth"))
+            .map(|_| "auth");
+
+        let response = match qop {
+            Some(qop) => md5_hex(&format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, self.nonce, nc, cnonce, qop, ha2
+            )),
+            None => md5_hex(&format!("{}:{}:{}", ha1, self.nonce, ha2)),
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", response=\"{}\"",
+            username, self.realm, self.nonce, uri, response
+        );
+        if let Some(qop) = qop {
+            header.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        if let Some(algorithm) = &self.algorithm {
+            header.push_str(&format!(", algorithm={}", algorithm));
+        }
+        header
+    }
+}
+
+fn md5_hex(input: &str) -> String {
+    Md5::digest(input.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_auth() {
+        let value = HashMap::from([("type", "bearer"), ("token", "abc")]).to_value();
+        assert_eq!(
+            Auth::parse(Some(&value)),
+            Some(Auth::Bearer("abc".to_string()))
+        );
+
+        let value = HashMap::from([("username", "user"), ("password", "pass")]).to_value();
+        assert_eq!(
+            Auth::parse(Some(&value)),
+            Some(Auth::Basic {
+                username: "user".to_string(),
+                password: "pass".to_string()
+            })
+        );
+
+        let value = HashMap::from([("type", "bearer")]).to_value();
+        assert_eq!(Auth::parse(Some(&value)), None);
+    }
+
+    #[test]
+    fn test_digest_rfc2617_example() {
+        let challenge = DigestChallenge::parse(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+             opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        )
+        .unwrap();
+        assert_eq!(challenge.realm, "testrealm@host.com");
+        assert_eq!(challenge.qop.as_deref(), Some("auth,auth-int"));
+
+        let header = challenge.authorization(
+            "Mufasa",
+            "Circle Of Life",
+            "GET",
+            "/dir/index.html",
+            "0a4f113b",
+        );
+        assert!(header.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(header.contains("qop=auth, nc=00000001, cnonce=\"0a4f113b\""));
+        assert!(header.contains("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+    }
+
+    #[test]
+    fn test_digest_rejects_other_schemes() {
+        assert_eq!(DigestChallenge::parse("Basic realm=\"x\""), None);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::config::{Config, RedirectPolicy};
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy, redirect};
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Request(reqwest::Error),
+    File(String, std::io::Error),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Request(e) => write!(f, "Error creating client: {}", e),
+            ClientError::File(path, e) => write!(f, "Error reading {}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Clients kept for redirect policies other than the configured one
+const MAX_CLIENTS: usize = 8;
+
+/// Redirect handling is fixed when a `reqwest::Client` is built, so one client is kept
+/// per redirect policy requested by the flow. Every other setting comes from `with`.
+/// Policies come from each request, so at most `MAX_CLIENTS` of them are kept besides
+/// the configured one.
+pub struct ClientPool {
+    config: Config,
+    clients: HashMap<RedirectPolicy, Client>,
+}
+
+impl ClientPool {
+    pub fn new(config: Config) -> Result<Self, ClientError> {
+        let mut pool = ClientPool {
+            config,
+            clients: HashMap::new(),
+        };
+        // Fail at startup on invalid TLS or proxy settings instead of on the first request
+        pool.get(pool.config.redirect)?;
+        Ok(pool)
+    }
+
+    pub fn default_redirect(&self) -> RedirectPolicy {
+        self.config.redirect
+    }
+
+    pub fn get(&mut self, policy: RedirectPolicy) -> Result<Client, ClientError> {
+        if let Some(client) = self.clients.get(&policy) {
+            return Ok(client.clone());
+        }
+
+        let client = self.build(policy)?;
+        if self.clients.len() > MAX_CLIENTS {
+            let default = self.config.redirect;
+            self.clients.retain(|cached, _| *cached == default);
+        }
+        self.clients.insert(policy, client.clone());
+        Ok(client)
+    }
+
+    fn build(&self, policy: RedirectPolicy) -> Result<Client, ClientError> {
+        let config = &self.config;
+        let mut builder = Client::builder()
+            .danger_accept_invalid_certs(!config.verify_ssl)
+            .timeout(Duration::from_secs(config.timeout))
+            .redirect(if policy.follow {
+                redirect::Policy::limited(policy.max)
+            } else {
+                redirect::Policy::none()
+            });
+
+        if let Some(proxy) = &config.proxy {
+            let mut reqwest_proxy = Proxy::all(&proxy.url).map_err(ClientError::Request)?;
+            if let Some(username) = &proxy.username {
+                reqwest_proxy =
+                    reqwest_proxy.basic_auth(username, proxy.password.as_deref().unwrap_or(""));
+            }
+            if let Some(no_proxy) = &proxy.no_proxy {
+                reqwest_proxy = reqwest_proxy.no_proxy(NoProxy::from_string(no_proxy));
+            }
+            builder = builder.proxy(reqwest_proxy);
+        }
+
+        if let Some(ca_cert) = &config.ca_cert {
+            for certificate in
+                Certificate::from_pem_bundle(&read(ca_cert)?).map_err(ClientError::Request)?
+            {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(client_cert) = &config.client_cert {
+            // The key may live in the certificate file or in `client_key`
+            let mut pem = read(client_cert)?;
+            if let Some(client_key) = &config.client_key {
+                pem.push(b'\n');
+                pem.extend(read(client_key)?);
+            }
+            let identity = Identity::from_pem(&pem).map_err(ClientError::Request)?;
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+
+        builder.build().map_err(ClientError::Request)
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, ClientError> {
+    std::fs::read(path).map_err(|e| ClientError::File(path.to_string(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phlow_sdk::prelude::Value;
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let mut pool = ClientPool::new(Config::from(Value::Null)).unwrap();
+        let default = pool.default_redirect();
+
+        for max in 0..100 {
+            pool.get(RedirectPolicy { follow: true, max }).unwrap();
+            assert!(pool.clients.len() <= MAX_CLIENTS + 1);
+        }
+        assert!(pool.clients.contains_key(&default));
+    }
+}
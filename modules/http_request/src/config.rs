@@ -1,16 +1,90 @@
 use phlow_sdk::prelude::{NumberBehavior, Value};
 
+/// Whether redirects are followed and how many hops are allowed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RedirectPolicy {
+    pub follow: bool,
+    pub max: usize,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        RedirectPolicy {
+            follow: true,
+            max: 10,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Applies `follow_redirects` / `max_redirects` found in `value` on top of `self`
+    pub fn merge(&self, value: &Value) -> Self {
+        let mut policy = *self;
+        if !value.is_object() {
+            return policy;
+        }
+        if let Some(follow) = value.get("follow_redirects").and_then(Value::as_bool) {
+            policy.follow = *follow;
+        }
+        if let Some(max) = value.get("max_redirects").and_then(Value::to_u64) {
+            policy.max = max as usize;
+        }
+        policy
+    }
+}
+
+pub struct ProxyConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    /// `proxy` may be a URL string or an object with `url`, `username`, `password` and `no_proxy`
+    fn parse(value: Option<&Value>) -> Option<Self> {
+        match value {
+            Some(Value::String(url)) => Some(ProxyConfig {
+                url: url.to_string(),
+                username: None,
+                password: None,
+                no_proxy: None,
+            }),
+            Some(proxy @ Value::Object(_)) => {
+                let field = |key: &str| proxy.get(key).map(|v| v.to_string());
+                Some(ProxyConfig {
+                    url: field("url")?,
+                    username: field("username"),
+                    password: field("password"),
+                    no_proxy: field("no_proxy"),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
 pub struct Config {
     pub timeout: u64,
     pub verify_ssl: bool,
+    pub redirect: RedirectPolicy,
+    pub proxy: Option<ProxyConfig>,
+    pub ca_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
 }
 
 impl From<Value> for Config {
     fn from(value: Value) -> Self {
-        if value.is_null() {
+        if !value.is_object() {
             return Config {
                 timeout: 29,
                 verify_ssl: true,
+                redirect: RedirectPolicy::default(),
+                proxy: None,
+                ca_cert: None,
+                client_cert: None,
+                client_key: None,
             };
         }
 
@@ -19,10 +93,79 @@ impl From<Value> for Config {
             .get("verify_ssl")
             .and_then(Value::as_bool)
             .unwrap_or(&true);
+        let path = |key: &str| value.get(key).map(|v| v.to_string());
 
         Config {
             timeout,
             verify_ssl,
+            redirect: RedirectPolicy::default().merge(&value),
+            proxy: ProxyConfig::parse(value.get("proxy")),
+            ca_cert: path("ca_cert"),
+            client_cert: path("client_cert"),
+            client_key: path("client_key"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phlow_sdk::prelude::ToValueBehavior;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::from(Value::Null);
+        assert_eq!(config.timeout, 29);
+        assert!(config.verify_ssl);
+        assert_eq!(config.redirect, RedirectPolicy::default());
+        assert!(config.proxy.is_none());
+    }
+
+    #[test]
+    fn test_proxy_and_redirect_forms() {
+        let config = Config::from(
+            HashMap::from([
+                ("proxy", "http://proxy:3128".to_value()),
+                ("follow_redirects", false.to_value()),
+            ])
+            .to_value(),
+        );
+        assert_eq!(config.proxy.unwrap().url, "http://proxy:3128");
+        assert!(!config.redirect.follow);
+        assert_eq!(config.redirect.max, 10);
+
+        let config = Config::from(
+            HashMap::from([(
+                "proxy",
+                HashMap::from([
+                    ("url", "http://proxy:3128"),
+                    ("username", "user"),
+                    ("no_proxy", "localhost"),
+                ])
+                .to_value(),
+            )])
+            .to_value(),
+        );
+        let proxy = config.proxy.unwrap();
+        assert_eq!(proxy.username.as_deref(), Some("user"));
+        assert_eq!(proxy.password, None);
+        assert_eq!(proxy.no_proxy.as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn test_redirect_merge_keeps_unset_fields() {
+        let base = RedirectPolicy {
+            follow: false,
+            max: 3,
+        };
+        let merged = base.merge(&HashMap::from([("max_redirects", 5)]).to_value());
+        assert_eq!(
+            merged,
+            RedirectPolicy {
+                follow: false,
+                max: 5
+            }
+        );
+    }
+}
@@ -1,15 +1,46 @@
+use crate::auth::Auth;
+use crate::config::RedirectPolicy;
+use crate::response::ResponseType;
 use phlow_sdk::prelude::*;
-use reqwest::header::{self, HeaderMap};
 use reqwest::Method;
+use reqwest::header::{self, HeaderMap};
+use std::collections::HashMap;
+
+pub enum MultipartField {
+    Text(String),
+    File {
+        path: String,
+        filename: Option<String>,
+        content_type: Option<String>,
+    },
+}
+
+pub enum Body {
+    Empty,
+    Raw(String),
+    Json(Value),
+    Form(Vec<(String, String)>),
+    Multipart(Vec<(String, MultipartField)>),
+}
 
 pub struct Input {
     pub method: Method,
     pub url: String,
+    pub query: Vec<(String, String)>,
     pub headers: HeaderMap,
-    pub body: Option<String>,
+    pub auth: Option<Auth>,
+    pub body: Body,
+    pub timeout: Option<u64>,
+    pub redirect: RedirectPolicy,
+    pub response_type: ResponseType,
+    pub output_file: Option<String>,
 }
 impl Input {
-    pub fn new(value: Value, default_user_agent: &Option<String>) -> Self {
+    pub fn new(
+        value: Value,
+        default_user_agent: &Option<String>,
+        default_redirect: RedirectPolicy,
+    ) -> Self {
         let method = match value.get("method") {
             Some(Value::String(method)) => match method.as_str() {
                 "GET" => Method::GET,
@@ -56,26 +87,205 @@ impl Input {
             );
         }
 
-        let body = value.get("body").map(|v| v.to_string());
-
-        if let Some(body) = &body {
-            if body.starts_with('{')
-                && body.ends_with('}')
-                && headers.get("Content-Type").is_none()
-                && headers.get("content-type").is_none()
-            {
-                headers.insert(
-                    "content-type",
-                    header::HeaderValue::from_static("application/json"),
-                );
+        // `json`, `form` and `multipart` set their own Content-Type, so only a raw
+        // `body` goes through the JSON sniffing below
+        let body = if let Some(json) = value.get("json") {
+            Body::Json(json.clone())
+        } else if let Some(form) = value.get("form") {
+            Body::Form(pairs(form))
+        } else if let Some(Value::Object(fields)) = value.get("multipart") {
+            Body::Multipart(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.to_string(), MultipartField::from(field)))
+                    .collect(),
+            )
+        } else {
+            match value.get("body") {
+                Some(body) => Body::Raw(body.to_string()),
+                None => Body::Empty,
             }
+        };
+
+        if let Body::Raw(body) = &body
+            && body.starts_with('{')
+            && body.ends_with('}')
+            && headers.get("Content-Type").is_none()
+            && headers.get("content-type").is_none()
+        {
+            headers.insert(
+                "content-type",
+                header::HeaderValue::from_static("application/json"),
+            );
         }
 
+        let query = value.get("query").map(pairs).unwrap_or_default();
+        let auth = Auth::parse(value.get("auth"));
+        let timeout = value.get("timeout").and_then(Value::to_u64);
+        let redirect = default_redirect.merge(&value);
+
+        let response_type = match value.get("response_type") {
+            Some(Value::String(response_type)) => ResponseType::from_str(response_type.as_str())
+                .unwrap_or_else(|| {
+                    log::warn!("Unknown response_type '{}', using auto", response_type);
+                    ResponseType::Auto
+                }),
+            _ => ResponseType::Auto,
+        };
+
+        let output_file = match value.get("output_file") {
+            Some(Value::String(path)) if !path.as_str().is_empty() => Some(path.to_string()),
+            _ => None,
+        };
+
         Input {
             method,
             url,
+            query,
             headers,
+            auth,
             body,
+            timeout,
+            redirect,
+            response_type,
+            output_file,
+        }
+    }
+
+    /// Adds trace context headers, keeping any the flow set explicitly
+    pub fn propagate_trace(&mut self, trace_headers: HashMap<String, String>) {
+        for (key, value) in trace_headers {
+            if let (Ok(name), Ok(value)) = (
+                header::HeaderName::from_bytes(key.as_bytes()),
+                header::HeaderValue::from_str(&value),
+            ) {
+                self.headers.entry(name).or_insert(value);
+            }
+        }
+    }
+}
+
+impl From<&Value> for MultipartField {
+    /// A field is either a plain value or `{ file, filename?, content_type? }`
+    fn from(value: &Value) -> Self {
+        match value.is_object().then(|| value.get("file")).flatten() {
+            Some(path) => MultipartField::File {
+                path: path.to_string(),
+                filename: value.get("filename").map(|v| v.to_string()),
+                content_type: value.get("content_type").map(|v| v.to_string()),
+            },
+            _ => MultipartField::Text(value.to_string()),
+        }
+    }
+}
+
+/// Flattens an object into key/value pairs; array values repeat the key
+fn pairs(value: &Value) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    if let Value::Object(object) = value {
+        for (key, value) in object.iter() {
+            match value {
+                Value::Array(array) => {
+                    for item in array.values.iter() {
+                        pairs.push((key.to_string(), item.to_string()));
+                    }
+                }
+                Value::Null | Value::Undefined => {}
+                _ => pairs.push((key.to_string(), value.to_string())),
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(value: Value) -> Input {
+        Input::new(value, &None, RedirectPolicy::default())
+    }
+
+    #[test]
+    fn test_query_repeats_array_keys() {
+        let input = input(
+            HashMap::from([
+                ("url", "http://localhost".to_value()),
+                (
+                    "query",
+                    HashMap::from([("tag", vec!["a", "b"].to_value())]).to_value(),
+                ),
+            ])
+            .to_value(),
+        );
+        assert_eq!(
+            input.query,
+            vec![
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_body_kinds() {
+        let json =
+            input(HashMap::from([("json", HashMap::from([("a", 1)]).to_value())]).to_value());
+        assert!(matches!(json.body, Body::Json(_)));
+        assert!(json.headers.get("content-type").is_none());
+
+        let raw = input(HashMap::from([("body", "{\"a\":1}")]).to_value());
+        assert!(matches!(raw.body, Body::Raw(_)));
+        assert_eq!(raw.headers.get("content-type").unwrap(), "application/json");
+
+        let multipart = input(
+            HashMap::from([(
+                "multipart",
+                HashMap::from([
+                    ("name", "report".to_value()),
+                    ("file", HashMap::from([("file", "/tmp/a.csv")]).to_value()),
+                ])
+                .to_value(),
+            )])
+            .to_value(),
+        );
+        match multipart.body {
+            Body::Multipart(fields) => {
+                assert_eq!(fields.len(), 2);
+                assert!(fields.iter().any(|(name, field)| name == "file"
+                    && matches!(field, MultipartField::File { path, .. } if path == "/tmp/a.csv")));
+            }
+            _ => panic!("expected multipart body"),
         }
     }
+
+    #[test]
+    fn test_propagate_trace_keeps_explicit_headers() {
+        let mut input = input(
+            HashMap::from([(
+                "headers",
+                HashMap::from([("tracestate", "vendor=explicit")]).to_value(),
+            )])
+            .to_value(),
+        );
+        input.propagate_trace(HashMap::from([
+            ("traceparent".to_string(), "00-abc-def-01".to_string()),
+            ("tracestate".to_string(), "vendor=span".to_string()),
+        ]));
+        assert_eq!(input.headers.get("traceparent").unwrap(), "00-abc-def-01");
+        assert_eq!(input.headers.get("tracestate").unwrap(), "vendor=explicit");
+    }
+
+    #[test]
+    fn test_timeout_and_redirect_override() {
+        let input = input(
+            HashMap::from([
+                ("timeout", 5.to_value()),
+                ("follow_redirects", false.to_value()),
+            ])
+            .to_value(),
+        );
+        assert_eq!(input.timeout, Some(5));
+        assert!(!input.redirect.follow);
+    }
 }
@@ -1,6 +1,10 @@
+mod auth;
+mod client;
 mod config;
 mod input;
 mod request;
+mod response;
+use client::ClientPool;
 use config::Config;
 use input::Input;
 use phlow_sdk::prelude::*;
@@ -30,14 +34,10 @@ pub async fn http_request(
         }
     };
 
-    let client = match reqwest::Client::builder()
-        .danger_accept_invalid_certs(!config.verify_ssl)
-        .timeout(std::time::Duration::from_secs(config.timeout))
-        .build()
-    {
-        Ok(client) => client,
+    let mut clients = match ClientPool::new(config) {
+        Ok(clients) => clients,
         Err(e) => {
-            log::error!("Error creating client: {:?}", e);
+            log::error!("{}", e);
             return Err(Box::new(e));
         }
     };
@@ -45,8 +45,13 @@ pub async fn http_request(
     for package in rx {
         let response = match package.input() {
             Some(value) => {
-                let input = Input::new(value, &default_user_agent);
-                match request::request(input, client.clone()).await {
+                let mut input = Input::new(value, &default_user_agent, clients.default_redirect());
+                input.propagate_trace(package.trace_headers());
+                let result = match clients.get(input.redirect) {
+                    Ok(client) => request::request(input, client).await,
+                    Err(e) => Err(request::Error::Client(e)),
+                };
+                match result {
                     Ok(value) => HashMap::from([
                         ("response", value),
                         ("is_success", true.to_value()),
@@ -1,23 +1,35 @@
-use crate::input::Input;
+use crate::auth::{Auth, DigestChallenge};
+use crate::client::ClientError;
+use crate::input::{Body, Input, MultipartField};
+use crate::response;
+use bytes::Bytes;
 use phlow_sdk::prelude::*;
-use reqwest::{header, Client, Method};
+use phlow_sdk::tokio::fs;
+use phlow_sdk::tokio::io::AsyncWriteExt;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, Request, Response, StatusCode, header};
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum Error {
-    RequestError(reqwest::Error),
-    HeaderError(header::InvalidHeaderName),
-    HeaderValueError(header::InvalidHeaderValue),
-    ValueError(phlow_sdk::valu3::Error),
+    Request(reqwest::Error),
+    Header(header::InvalidHeaderName),
+    HeaderValue(header::InvalidHeaderValue),
+    Value(phlow_sdk::valu3::Error),
+    Decode(String),
+    File(std::io::Error),
+    Client(ClientError),
 }
 
 impl From<Error> for Value {
     fn from(error: Error) -> Self {
         match error {
-            Error::RequestError(e) => format!("Request error: {}", e).to_value(),
-            Error::HeaderError(e) => format!("Header error: {}", e).to_value(),
-            Error::HeaderValueError(e) => format!("Header value error: {}", e).to_value(),
-            Error::ValueError(e) => format!(
+            Error::Request(e) => format!("Request error: {}", e).to_value(),
+            Error::Header(e) => format!("Header error: {}", e).to_value(),
+            Error::HeaderValue(e) => format!("Header value error: {}", e).to_value(),
+            Error::Value(e) => format!(
                 "Value error: {}",
                 match e {
                     phlow_sdk::valu3::Error::NonParsebleMsg(msg) => msg,
@@ -26,23 +38,191 @@ impl From<Error> for Value {
                 }
             )
             .to_value(),
+            Error::Decode(e) => format!("Decode error: {}", e).to_value(),
+            Error::File(e) => format!("File error: {}", e).to_value(),
+            Error::Client(e) => e.to_string().to_value(),
         }
     }
 }
 
-pub async fn request(input: Input, client: Client) -> Result<Value, Error> {
-    let request_builder = match input.method {
-        Method::GET => client.get(&input.url),
-        Method::POST => client.post(&input.url),
-        Method::PUT => client.put(&input.url),
-        Method::PATCH => client.patch(&input.url),
-        Method::DELETE => client.delete(&input.url),
-        _ => client.get(&input.url),
+/// A multipart field with its file already loaded, so the form can be rebuilt
+/// when a digest challenge forces the request to be sent again. File contents are
+/// shared between builds instead of copied.
+enum LoadedField {
+    Text(String),
+    File {
+        bytes: Bytes,
+        filename: String,
+        content_type: Option<String>,
+    },
+}
+
+async fn load_multipart(
+    fields: &[(String, MultipartField)],
+) -> Result<Vec<(String, LoadedField)>, Error> {
+    let mut loaded = Vec::with_capacity(fields.len());
+    for (name, field) in fields {
+        let field = match field {
+            MultipartField::Text(text) => LoadedField::Text(text.clone()),
+            MultipartField::File {
+                path,
+                filename,
+                content_type,
+            } => {
+                let bytes = fs::read(path).await.map_err(Error::File)?;
+                let filename = filename.clone().unwrap_or_else(|| {
+                    std::path::Path::new(path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| path.clone())
+                });
+                LoadedField::File {
+                    bytes: Bytes::from(bytes),
+                    filename,
+                    content_type: content_type.clone(),
+                }
+            }
+        };
+        loaded.push((name.clone(), field));
+    }
+    Ok(loaded)
+}
+
+fn build(
+    client: &Client,
+    input: &Input,
+    multipart: &[(String, LoadedField)],
+    authorization: Option<&str>,
+) -> Result<Request, Error> {
+    let mut builder = client
+        .request(input.method.clone(), &input.url)
+        .headers(input.headers.clone());
+
+    if !input.query.is_empty() {
+        builder = builder.query(&input.query);
+    }
+
+    if let Some(timeout) = input.timeout {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+
+    builder = match &input.auth {
+        Some(Auth::Basic { username, password }) => builder.basic_auth(username, Some(password)),
+        Some(Auth::Bearer(token)) => builder.bearer_auth(token),
+        _ => builder,
+    };
+
+    if let Some(authorization) = authorization {
+        builder = builder.header(header::AUTHORIZATION, authorization);
     }
-    .headers(input.headers)
-    .body(input.body.unwrap_or_default());
 
-    let response = request_builder.send().await.map_err(Error::RequestError)?;
+    builder = match &input.body {
+        Body::Empty => builder,
+        Body::Raw(body) => builder.body(body.clone()),
+        Body::Json(json) => builder
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json.to_json(JsonMode::Inline)),
+        Body::Form(pairs) => builder.form(pairs),
+        Body::Multipart(_) => {
+            let mut form = Form::new();
+            for (name, field) in multipart {
+                form = match field {
+                    LoadedField::Text(text) => form.text(name.clone(), text.clone()),
+                    LoadedField::File {
+                        bytes,
+                        filename,
+                        content_type,
+                    } => {
+                        let mut part = Part::stream_with_length(
+                            reqwest::Body::from(bytes.clone()),
+                            bytes.len() as u64,
+                        )
+                        .file_name(filename.clone());
+                        if let Some(content_type) = content_type {
+                            part = part.mime_str(content_type).map_err(Error::Request)?;
+                        }
+                        form.part(name.clone(), part)
+                    }
+                };
+            }
+            builder.multipart(form)
+        }
+    };
+
+    builder.build().map_err(Error::Request)
+}
+
+fn cnonce() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}{:x}", nanos, std::process::id())
+}
+
+/// Streams the body to a temporary file next to `path`, so large downloads never sit
+/// in memory, and moves it into place only once the whole body arrived. A failed
+/// download leaves neither a partial file nor the temporary one behind.
+async fn download(response: &mut Response, path: &str) -> Result<u64, Error> {
+    let target = Path::new(path);
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let partial = target.with_file_name(format!(".{}.{}.part", name, cnonce()));
+
+    let result = match write_body(response, &partial).await {
+        Ok(size) => fs::rename(&partial, target)
+            .await
+            .map(|_| size)
+            .map_err(Error::File),
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        let _ = fs::remove_file(&partial).await;
+    }
+    result
+}
+
+async fn write_body(response: &mut Response, path: &Path) -> Result<u64, Error> {
+    let mut file = fs::File::create(path).await.map_err(Error::File)?;
+    let mut size: u64 = 0;
+    while let Some(chunk) = response.chunk().await.map_err(Error::Request)? {
+        file.write_all(&chunk).await.map_err(Error::File)?;
+        size += chunk.len() as u64;
+    }
+    file.flush().await.map_err(Error::File)?;
+    Ok(size)
+}
+
+pub async fn request(input: Input, client: Client) -> Result<Value, Error> {
+    let multipart = match &input.body {
+        Body::Multipart(fields) => load_multipart(fields).await?,
+        _ => Vec::new(),
+    };
+
+    let request = build(&client, &input, &multipart, None)?;
+    let mut response = client.execute(request).await.map_err(Error::Request)?;
+
+    if let Some(Auth::Digest { username, password }) = &input.auth {
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(DigestChallenge::parse);
+
+        if let (StatusCode::UNAUTHORIZED, Some(challenge)) = (response.status(), challenge) {
+            let url = response.url().clone();
+            let uri = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            let authorization =
+                challenge.authorization(username, password, input.method.as_str(), &uri, &cnonce());
+            let request = build(&client, &input, &multipart, Some(&authorization))?;
+            response = client.execute(request).await.map_err(Error::Request)?;
+        }
+    }
 
     let status_code = response.status().as_u16();
 
@@ -52,8 +232,18 @@ pub async fn request(input: Input, client: Client) -> Result<Value, Error> {
         headers_map.insert(key.to_string(), value.to_str().unwrap_or("").to_string());
     }
 
-    let body = response.text().await.map_err(Error::RequestError)?;
-    let body_value = Value::json_to_value(&body).map_err(Error::ValueError)?;
+    let body_value = match input.output_file {
+        // Error bodies are decoded as usual instead of taking the place of the download
+        Some(path) if response.status().is_success() => {
+            let size = download(&mut response, &path).await?;
+            HashMap::from([("path", path.to_value()), ("size", size.to_value())]).to_value()
+        }
+        _ => {
+            let content_type = headers_map.get("content-type").cloned().unwrap_or_default();
+            let body = response.bytes().await.map_err(Error::Request)?;
+            response::decode(&body, &content_type, input.response_type).map_err(Error::Decode)?
+        }
+    };
 
     let response = HashMap::from([
         ("headers", headers_map.to_value()),
@@ -64,3 +254,97 @@ pub async fn request(input: Input, client: Client) -> Result<Value, Error> {
 
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedirectPolicy;
+    use phlow_sdk::tokio::io::AsyncReadExt;
+    use phlow_sdk::tokio::net::TcpListener;
+    use std::path::PathBuf;
+
+    /// Answers a single request with `raw` and closes the connection
+    async fn serve_once(raw: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        phlow_sdk::tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 4096];
+            let _ = stream.read(&mut buffer).await;
+            let _ = stream.write_all(raw.as_bytes()).await;
+        });
+        format!("http://{}/file", address)
+    }
+
+    fn download_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("phlow-http-request-{}-{}", name, cnonce()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn get(url: String, output_file: &Path) -> Result<Value, Error> {
+        let input = Input::new(
+            HashMap::from([
+                ("url", url.to_value()),
+                ("output_file", output_file.to_string_lossy().to_value()),
+            ])
+            .to_value(),
+            &None,
+            RedirectPolicy::default(),
+        );
+        request(input, Client::new()).await
+    }
+
+    #[phlow_sdk::tokio::test]
+    async fn test_output_file() {
+        let dir = download_dir("ok");
+        let path = dir.join("report.csv");
+        let url =
+            serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\na,b,c")
+                .await;
+
+        let response = get(url, &path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b,c");
+        assert_eq!(
+            response
+                .get("body")
+                .unwrap()
+                .get("size")
+                .unwrap()
+                .to_string(),
+            "5"
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[phlow_sdk::tokio::test]
+    async fn test_output_file_skips_error_bodies() {
+        let dir = download_dir("error");
+        let path = dir.join("report.csv");
+        std::fs::write(&path, "previous").unwrap();
+        let url = serve_once(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: 9\r\nConnection: close\r\n\r\nnot found",
+        )
+        .await;
+
+        let response = get(url, &path).await.unwrap();
+        assert_eq!(response.get("body").unwrap().to_string(), "not found");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[phlow_sdk::tokio::test]
+    async fn test_output_file_is_not_left_partial() {
+        let dir = download_dir("partial");
+        let path = dir.join("report.csv");
+        // The connection closes before the promised 100 bytes arrive
+        let url =
+            serve_once("HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\na,b,c")
+                .await;
+
+        assert!(get(url, &path).await.is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
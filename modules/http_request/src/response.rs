@@ -0,0 +1,356 @@
+use base64::Engine;
+use phlow_sdk::prelude::*;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+
+/// How the response body is turned into a `Value`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseType {
+    /// Decided from the response `Content-Type`
+    Auto,
+    Json,
+    Text,
+    Form,
+    Xml,
+    /// Base64 encoded string
+    Binary,
+}
+
+impl ResponseType {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Some(ResponseType::Auto),
+            "json" => Some(ResponseType::Json),
+            "text" => Some(ResponseType::Text),
+            "form" => Some(ResponseType::Form),
+            "xml" => Some(ResponseType::Xml),
+            "binary" | "base64" => Some(ResponseType::Binary),
+            _ => None,
+        }
+    }
+
+    /// Picks a decoder from a `Content-Type` header value
+    pub fn from_content_type(content_type: &str) -> Self {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+
+        if mime == "application/json" || mime.ends_with("+json") {
+            ResponseType::Json
+        } else if mime == "application/x-www-form-urlencoded" {
+            ResponseType::Form
+        } else if mime == "application/xml" || mime == "text/xml" || mime.ends_with("+xml") {
+            ResponseType::Xml
+        } else if mime.starts_with("text/")
+            || mime == "application/javascript"
+            || mime == "application/yaml"
+            || mime == "application/x-yaml"
+        {
+            ResponseType::Text
+        } else if mime.is_empty() {
+            ResponseType::Auto
+        } else {
+            ResponseType::Binary
+        }
+    }
+}
+
+/// Decodes a response body.
+/// With `ResponseType::Auto` the decoder follows the `Content-Type` and falls back to text
+/// when a JSON body does not parse; explicit types fail instead.
+pub fn decode(
+    body: &[u8],
+    content_type: &str,
+    response_type: ResponseType,
+) -> Result<Value, String> {
+    if response_type != ResponseType::Auto {
+        return decode_as(body, response_type);
+    }
+
+    if body.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    match ResponseType::from_content_type(content_type) {
+        ResponseType::Json => {
+            decode_as(body, ResponseType::Json).or_else(|_| decode_as(body, ResponseType::Text))
+        }
+        // Without a Content-Type, guess: JSON, then UTF-8 text, then binary
+        ResponseType::Auto => decode_as(body, ResponseType::Json)
+            .or_else(|_| decode_as(body, ResponseType::Text))
+            .or_else(|_| decode_as(body, ResponseType::Binary)),
+        ResponseType::Binary => decode_as(body, ResponseType::Binary),
+        other => decode_as(body, other),
+    }
+}
+
+fn decode_as(body: &[u8], response_type: ResponseType) -> Result<Value, String> {
+    match response_type {
+        ResponseType::Binary => Ok(base64::engine::general_purpose::STANDARD
+            .encode(body)
+            .to_value()),
+        ResponseType::Text => text(body).map(|text| text.to_value()),
+        ResponseType::Form => text(body).map(|text| form_to_value(&text)),
+        ResponseType::Xml => xml_to_value(&text(body)?),
+        ResponseType::Json | ResponseType::Auto => {
+            let text = text(body)?;
+            if text.trim().is_empty() {
+                return Ok(Value::Null);
+            }
+            Value::json_to_value(&text).map_err(|e| format!("Invalid JSON body: {:?}", e))
+        }
+    }
+}
+
+fn text(body: &[u8]) -> Result<String, String> {
+    String::from_utf8(body.to_vec()).map_err(|_| "Body is not valid UTF-8".to_string())
+}
+
+/// `a=1&b=2&b=3` becomes `{a: "1", b: ["2", "3"]}`
+fn form_to_value(body: &str) -> Value {
+    let mut fields: HashMap<String, Value> = HashMap::new();
+
+    for (key, value) in url::form_urlencoded::parse(body.trim().as_bytes()) {
+        let key = key.into_owned();
+        let value = value.into_owned().to_value();
+        match fields.remove(&key) {
+            Some(Value::Array(mut array)) => {
+                array.push(value);
+                fields.insert(key, Value::Array(array));
+            }
+            Some(existing) => {
+                fields.insert(key, vec![existing, value].to_value());
+            }
+            None => {
+                fields.insert(key, value);
+            }
+        }
+    }
+
+    fields.to_value()
+}
+
+/// Element being built while walking the document
+struct Node {
+    name: String,
+    children: Vec<(String, Value)>,
+    text: String,
+}
+
+impl Node {
+    fn new(start: &BytesStart) -> Result<Self, String> {
+        let mut children = Vec::new();
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|e| format!("Invalid XML attribute: {}", e))?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|e| format!("Invalid XML attribute: {}", e))?;
+            children.push((
+                format!("@{}", String::from_utf8_lossy(attribute.key.as_ref())),
+                value.into_owned().to_value(),
+            ));
+        }
+
+        Ok(Node {
+            name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+            children,
+            text: String::new(),
+        })
+    }
+
+    /// Text-only elements become strings; everything else becomes an object where
+    /// attributes are prefixed with `@`, mixed text lives under `#text` and repeated
+    /// children are grouped into arrays.
+    fn into_value(self) -> Value {
+        let text = self.text.trim().to_string();
+
+        if self.children.is_empty() {
+            return text.to_value();
+        }
+
+        let mut object: HashMap<String, Value> = HashMap::new();
+        for (key, value) in self.children {
+            match object.remove(&key) {
+                Some(Value::Array(mut array)) if !key.starts_with('@') => {
+                    array.push(value);
+                    object.insert(key, Value::Array(array));
+                }
+                Some(existing) => {
+                    object.insert(key, vec![existing, value].to_value());
+                }
+                None => {
+                    object.insert(key, value);
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            object.insert("#text".to_string(), text.to_value());
+        }
+
+        object.to_value()
+    }
+}
+
+/// Converts an XML document into `{root_name: ...}`
+pub fn xml_to_value(xml: &str) -> Result<Value, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Node> = Vec::new();
+    let mut root: Option<(String, Value)> = None;
+
+    let mut close = |stack: &mut Vec<Node>, node: Node| {
+        let name = node.name.clone();
+        let value = node.into_value();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push((name, value)),
+            None => root = Some((name, value)),
+        }
+    };
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => stack.push(Node::new(&start)?),
+            Ok(Event::Empty(start)) => {
+                let node = Node::new(&start)?;
+                close(&mut stack, node);
+            }
+            Ok(Event::End(_)) => {
+                if let Some(node) = stack.pop() {
+                    close(&mut stack, node);
+                }
+            }
+            Ok(Event::Text(text)) => {
+                if let Some(node) = stack.last_mut() {
+                    let text = text
+                        .unescape()
+                        .map_err(|e| format!("Invalid XML text: {}", e))?;
+                    node.text.push_str(&text);
+                }
+            }
+            Ok(Event::CData(data)) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!(
+                    "Invalid XML at position {}: {}",
+                    reader.error_position(),
+                    e
+                ));
+            }
+        }
+    }
+
+    match root {
+        Some((name, value)) if stack.is_empty() => Ok(HashMap::from([(name, value)]).to_value()),
+        _ => Err("Invalid XML: document has no complete root element".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_type_detection() {
+        assert_eq!(
+            ResponseType::from_content_type("application/json; charset=utf-8"),
+            ResponseType::Json
+        );
+        assert_eq!(
+            ResponseType::from_content_type("application/problem+json"),
+            ResponseType::Json
+        );
+        assert_eq!(
+            ResponseType::from_content_type("text/html"),
+            ResponseType::Text
+        );
+        assert_eq!(
+            ResponseType::from_content_type("application/atom+xml"),
+            ResponseType::Xml
+        );
+        assert_eq!(
+            ResponseType::from_content_type("image/png"),
+            ResponseType::Binary
+        );
+        assert_eq!(ResponseType::from_content_type(""), ResponseType::Auto);
+    }
+
+    #[test]
+    fn test_auto_decoding() {
+        let value = decode(b"{\"id\": 1}", "application/json", ResponseType::Auto).unwrap();
+        assert_eq!(value.get("id"), Some(&1.to_value()));
+
+        let value = decode(b"<html></html>", "text/html", ResponseType::Auto).unwrap();
+        assert_eq!(value, "<html></html>".to_value());
+
+        // A JSON content type with a broken body is kept as text
+        let value = decode(b"not json", "application/json", ResponseType::Auto).unwrap();
+        assert_eq!(value, "not json".to_value());
+
+        let value = decode(&[0xff, 0x00, 0x01], "", ResponseType::Auto).unwrap();
+        assert_eq!(value, "/wAB".to_value());
+
+        assert_eq!(
+            decode(b"", "text/plain", ResponseType::Auto).unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_explicit_type_overrides_content_type() {
+        let value = decode(b"{\"id\": 1}", "application/json", ResponseType::Text).unwrap();
+        assert_eq!(value, "{\"id\": 1}".to_value());
+        assert!(decode(b"not json", "text/plain", ResponseType::Json).is_err());
+        assert_eq!(
+            decode(b"abc", "text/plain", ResponseType::Binary).unwrap(),
+            "YWJj".to_value()
+        );
+    }
+
+    #[test]
+    fn test_form_decoding() {
+        let value = decode(
+            b"name=Jo%C3%A3o+Silva&tag=a&tag=b",
+            "application/x-www-form-urlencoded",
+            ResponseType::Auto,
+        )
+        .unwrap();
+        assert_eq!(value.get("name"), Some(&"João Silva".to_value()));
+        assert_eq!(
+            value.get("tag"),
+            Some(&vec!["a".to_value(), "b".to_value()].to_value())
+        );
+    }
+
+    #[test]
+    fn test_xml_decoding() {
+        let value = xml_to_value(
+            r#"<?xml version="1.0"?>
+            <users count="2">
+                <user id="1"><name>Ana</name></user>
+                <user id="2"><name>Bruno &amp; Cia</name></user>
+                <empty/>
+            </users>"#,
+        )
+        .unwrap();
+
+        let users = value.get("users").unwrap();
+        assert_eq!(users.get("@count"), Some(&"2".to_value()));
+        assert_eq!(users.get("empty"), Some(&"".to_value()));
+
+        let list = users.get("user").unwrap().as_array().unwrap();
+        assert_eq!(list.values.len(), 2);
+        assert_eq!(list.values[1].get("@id"), Some(&"2".to_value()));
+        assert_eq!(list.values[1].get("name"), Some(&"Bruno & Cia".to_value()));
+
+        assert!(xml_to_value("<open>").is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{HeaderMap, Response};
+use phlow_sdk::prelude::*;
+use std::io::{Read, Write};
+
+/// Bodies from this size on are compressed on the blocking pool, so a large response
+/// does not hold an executor thread. Smaller ones compress faster than the hand-off.
+const BLOCKING_MIN_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Brotli,
+    Zstd,
+    Gzip,
+    Deflate,
+}
+
+impl Algorithm {
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token.trim().to_lowercase().as_str() {
+            "br" | "brotli" => Some(Algorithm::Brotli),
+            "zstd" => Some(Algorithm::Zstd),
+            "gzip" | "x-gzip" => Some(Algorithm::Gzip),
+            "deflate" => Some(Algorithm::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Brotli => "br",
+            Algorithm::Zstd => "zstd",
+            Algorithm::Gzip => "gzip",
+            Algorithm::Deflate => "deflate",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub algorithms: Vec<Algorithm>,
+    pub min_size: usize,
+    pub content_types: Vec<String>,
+    pub decompress_requests: bool,
+    pub max_decompressed_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            algorithms: vec![Algorithm::Brotli, Algorithm::Zstd, Algorithm::Gzip],
+            min_size: 1024,
+            content_types: vec![
+                "text/".to_string(),
+                "application/json".to_string(),
+                "application/javascript".to_string(),
+                "application/xml".to_string(),
+                "image/svg+xml".to_string(),
+                "+json".to_string(),
+                "+xml".to_string(),
+            ],
+            decompress_requests: true,
+            max_decompressed_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl From<&Value> for CompressionConfig {
+    fn from(value: &Value) -> Self {
+        let mut config = CompressionConfig {
+            enabled: true,
+            ..Default::default()
+        };
+
+        // `compression: true` / `compression: false` toggles response compression with defaults
+        if let Value::Boolean(enabled) = value {
+            config.enabled = *enabled;
+            return config;
+        }
+
+        if !value.is_object() {
+            log::warn!("Invalid compression configuration, using defaults");
+            return config;
+        }
+
+        if let Some(enabled) = value.get("enabled").and_then(|v| v.as_bool()) {
+            config.enabled = *enabled;
+        }
+
+        if let Some(algorithms) = value.get("algorithms").and_then(|v| v.as_array()) {
+            let parsed: Vec<Algorithm> = algorithms
+                .values
+                .iter()
+                .filter_map(|v| {
+                    let token = v.to_string();
+                    let algorithm = Algorithm::from_token(&token);
+                    if algorithm.is_none() {
+                        log::warn!("Unknown compression algorithm ignored: {}", token);
+                    }
+                    algorithm
+                })
+                .collect();
+            if !parsed.is_empty() {
+                config.algorithms = parsed;
+            }
+        }
+
+        if let Some(min_size) = value.get("min_size").and_then(|v| v.to_u64()) {
+            config.min_size = min_size as usize;
+        }
+
+        if let Some(content_types) = value.get("content_types").and_then(|v| v.as_array()) {
+            config.content_types = content_types
+                .values
+                .iter()
+                .map(|v| v.to_string().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+
+        if let Some(decompress) = value.get("decompress_requests").and_then(|v| v.as_bool()) {
+            config.decompress_requests = *decompress;
+        }
+
+        if let Some(max) = value.get("max_decompressed_size").and_then(|v| v.to_u64()) {
+            config.max_decompressed_size = max as usize;
+        }
+
+        log::debug!(
+            "Compression configuration parsed: enabled={} algorithms={:?} min_size={} decompress_requests={}",
+            config.enabled,
+            config.algorithms,
+            config.min_size,
+            config.decompress_requests
+        );
+
+        config
+    }
+}
+
+/// Returns true when `token` is listed in an Accept-Encoding header with a non-zero quality
+pub fn accepts(accept_encoding: &str, token: &str) -> bool {
+    accept_encoding.split(',').any(|part| {
+        let mut pieces = part.trim().split(';');
+        let name = pieces.next().unwrap_or_default().trim();
+        let refused = pieces.any(|p| {
+            p.trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .map(|q| q <= 0.0)
+                .unwrap_or(false)
+        });
+        (name.eq_ignore_ascii_case(token) || name == "*") && !refused
+    })
+}
+
+impl CompressionConfig {
+    /// Picks the first configured algorithm accepted by the client
+    pub fn negotiate(&self, headers: &HeaderMap) -> Option<Algorithm> {
+        let accept_encoding = headers.get("accept-encoding")?.to_str().ok()?;
+        self.algorithms
+            .iter()
+            .find(|algorithm| accepts(accept_encoding, algorithm.name()))
+            .copied()
+    }
+
+    fn is_compressible(&self, content_type: &str) -> bool {
+        let content_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+
+        self.content_types.iter().any(|allowed| {
+            if allowed.starts_with('+') {
+                content_type.ends_with(allowed.as_str())
+            } else {
+                content_type.starts_with(allowed.as_str())
+            }
+        })
+    }
+
+    /// Compresses the response body when the client, content type and size allow it
+    pub async fn encode_response(
+        &self,
+        request_headers: &HeaderMap,
+        response: Response<Full<Bytes>>,
+    ) -> (Response<Full<Bytes>>, Option<Algorithm>) {
+        if !self.enabled {
+            return (response, None);
+        }
+
+        let status = response.status().as_u16();
+        if status < 200 || status == 204 || status == 206 || status == 304 {
+            return (response, None);
+        }
+
+        let headers = response.headers();
+        if headers.contains_key("content-encoding") {
+            return (response, None);
+        }
+
+        let content_type = headers
+            .get("content-type")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default();
+        if !self.is_compressible(content_type) {
+            return (response, None);
+        }
+
+        // The representation depends on Accept-Encoding from here on, even when left uncompressed
+        let mut response = response;
+        response.headers_mut().insert(
+            "vary",
+            hyper::header::HeaderValue::from_static("accept-encoding"),
+        );
+
+        let Some(algorithm) = self.negotiate(request_headers) else {
+            return (response, None);
+        };
+
+        let (mut parts, body) = response.into_parts();
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(_) => Bytes::new(),
+        };
+
+        if body.len() < self.min_size {
+            return (Response::from_parts(parts, Full::new(body)), None);
+        }
+
+        let compressed = if body.len() >= BLOCKING_MIN_SIZE {
+            let data = body.clone();
+            tokio::task::spawn_blocking(move || compress(&data, algorithm))
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+        } else {
+            compress(&body, algorithm)
+        };
+
+        match compressed {
+            Ok(compressed) => {
+                log::debug!(
+                    "Response compressed with {}: {} -> {} bytes",
+                    algorithm.name(),
+                    body.len(),
+                    compressed.len()
+                );
+                parts.headers.insert(
+                    "content-encoding",
+                    hyper::header::HeaderValue::from_static(algorithm.name()),
+                );
+                parts.headers.insert(
+                    "content-length",
+                    hyper::header::HeaderValue::from(compressed.len()),
+                );
+                (
+                    Response::from_parts(parts, Full::new(Bytes::from(compressed))),
+                    Some(algorithm),
+                )
+            }
+            Err(e) => {
+                log::error!(
+                    "Error compressing response with {}: {}",
+                    algorithm.name(),
+                    e
+                );
+                (Response::from_parts(parts, Full::new(body)), None)
+            }
+        }
+    }
+}
+
+pub fn compress(data: &[u8], algorithm: Algorithm) -> std::io::Result<Vec<u8>> {
+    match algorithm {
+        Algorithm::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Algorithm::Deflate => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Algorithm::Brotli => {
+            let mut output = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                writer.write_all(data)?;
+            }
+            Ok(output)
+        }
+        Algorithm::Zstd => zstd::stream::encode_all(data, 3),
+    }
+}
+
+/// Decodes a request body, refusing to inflate past `max_size` bytes
+pub fn decompress(data: &[u8], algorithm: Algorithm, max_size: usize) -> std::io::Result<Vec<u8>> {
+    let limit = max_size as u64 + 1;
+    let mut output = Vec::new();
+
+    match algorithm {
+        Algorithm::Gzip => {
+            flate2::read::MultiGzDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output)?;
+        }
+        Algorithm::Deflate => {
+            flate2::read::ZlibDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output)?;
+        }
+        Algorithm::Brotli => {
+            brotli::Decompressor::new(data, 4096)
+                .take(limit)
+                .read_to_end(&mut output)?;
+        }
+        Algorithm::Zstd => {
+            zstd::stream::read::Decoder::new(data)?
+                .take(limit)
+                .read_to_end(&mut output)?;
+        }
+    }
+
+    if output.len() > max_size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::FileTooLarge,
+            format!("decompressed body exceeds {} bytes", max_size),
+        ));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_respects_quality() {
+        assert!(accepts("gzip, deflate, br", "br"));
+        assert!(accepts("gzip;q=0.5", "gzip"));
+        assert!(!accepts("gzip;q=0", "gzip"));
+        assert!(!accepts("gzip", "br"));
+        assert!(accepts("*", "zstd"));
+    }
+
+    #[test]
+    fn test_negotiate_follows_server_preference() {
+        let config = CompressionConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-encoding", "gzip, zstd".parse().unwrap());
+        assert_eq!(config.negotiate(&headers), Some(Algorithm::Zstd));
+
+        headers.insert("accept-encoding", "identity".parse().unwrap());
+        assert_eq!(config.negotiate(&headers), None);
+    }
+
+    #[test]
+    fn test_content_type_filter() {
+        let config = CompressionConfig::default();
+        assert!(config.is_compressible("application/json; charset=utf-8"));
+        assert!(config.is_compressible("text/html"));
+        assert!(config.is_compressible("application/problem+json"));
+        assert!(!config.is_compressible("image/png"));
+    }
+
+    #[test]
+    fn test_roundtrip_all_algorithms() {
+        let data = "phlow ".repeat(500);
+        for algorithm in [
+            Algorithm::Brotli,
+            Algorithm::Zstd,
+            Algorithm::Gzip,
+            Algorithm::Deflate,
+        ] {
+            let compressed = compress(data.as_bytes(), algorithm).unwrap();
+            assert!(compressed.len() < data.len());
+            let decompressed = decompress(&compressed, algorithm, 1024 * 1024).unwrap();
+            assert_eq!(decompressed, data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let data = vec![0u8; 10_000];
+        let compressed = compress(&data, Algorithm::Gzip).unwrap();
+        assert!(decompress(&compressed, Algorithm::Gzip, 1000).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encode_response_skips_small_bodies() {
+        let config = CompressionConfig::from(
+            &std::collections::HashMap::from([("min_size", 100.to_value())]).to_value(),
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-encoding", "gzip".parse().unwrap());
+
+        let small = Response::builder()
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from("{}")))
+            .unwrap();
+        let (_, algorithm) = config.encode_response(&headers, small).await;
+        assert_eq!(algorithm, None);
+
+        let large = Response::builder()
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from("[1,2,3]".repeat(100))))
+            .unwrap();
+        let (response, algorithm) = config.encode_response(&headers, large).await;
+        assert_eq!(algorithm, Some(Algorithm::Gzip));
+        assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+    }
+
+    #[tokio::test]
+    async fn test_encode_response_compresses_large_bodies_off_the_executor() {
+        let config = CompressionConfig::from(&Value::Boolean(true));
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-encoding", "zstd".parse().unwrap());
+
+        let body = "[1,2,3]".repeat(BLOCKING_MIN_SIZE);
+        let large = Response::builder()
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from(body.clone())))
+            .unwrap();
+        let (response, algorithm) = config.encode_response(&headers, large).await;
+        assert_eq!(algorithm, Some(Algorithm::Zstd));
+
+        let compressed = response.into_body().collect().await.unwrap().to_bytes();
+        let decoded = decompress(&compressed, Algorithm::Zstd, body.len()).unwrap();
+        assert_eq!(decoded, body.as_bytes());
+    }
+}
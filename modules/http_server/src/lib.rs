@@ -1,3 +1,6 @@
+mod compression;
+mod http3;
+mod limits;
 mod middleware;
 mod openapi;
 mod resolver;
@@ -5,14 +8,24 @@ mod response;
 mod router;
 mod settings;
 mod setup;
-use hyper::{server::conn::http1, service::service_fn};
+mod static_files;
+mod tls;
+mod transport;
+use bytes::Bytes;
+use compression::CompressionConfig;
+use http_body_util::Full;
+use hyper::{Request, body::Incoming, header::HeaderValue, service::service_fn};
 use hyper_util::rt::TokioIo;
+use limits::{ConnectionCounter, HeaderTimeoutIo, IdleTracker, LimitsConfig, Rejection};
 use middleware::TracingMiddleware;
 use phlow_sdk::{prelude::*, tokio::net::TcpListener};
 use resolver::proxy;
-use settings::Settings;
-use setup::Config;
-use std::{net::SocketAddr, sync::Arc};
+use router::Router;
+use settings::{AuthorizationSpanMode, Settings};
+use setup::{Config, CorsConfig};
+use static_files::StaticConfig;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tls::TlsConfig;
 #[cfg(test)]
 mod openapi_tests;
 create_main!(start_server(setup));
@@ -46,11 +59,82 @@ pub async fn start_server(
     }
 
     log::debug!("Loading server configuration from setup.with");
+    let tls = match setup.with.get("tls") {
+        Some(tls_value) => Some(TlsConfig::try_from(tls_value)?),
+        None => None,
+    };
     let config: Config = Config::from(setup.with);
     let settings = Arc::new(Settings::load());
 
     let addr: SocketAddr = format!("{}:{}", config.host, config.port).parse()?;
 
+    let sender = match setup.main_sender.clone() {
+        Some(sender) => sender,
+        None => {
+            return Err("Main sender is None".into());
+        }
+    };
+
+    let server_context = ServerContext {
+        id: setup.id,
+        dispatch: setup.dispatch.clone(),
+        sender,
+        authorization_span_mode: settings.authorization_span_mode.clone(),
+        router: config.router.clone(),
+        cors: config.cors.clone(),
+        static_files: config.static_files.clone(),
+        compression: config.compression.clone(),
+        limits: config.limits.clone(),
+    };
+
+    let tls_acceptor = match &tls {
+        Some(tls) => {
+            let server_config = tls.server_config(config.protocols.alpn_protocols())?;
+            Some(tokio_rustls::TlsAcceptor::from(Arc::new(server_config)))
+        }
+        None => None,
+    };
+
+    let alt_svc = match &config.protocols.http3 {
+        Some(http3) => {
+            let Some(tls) = &tls else {
+                return Err("HTTP/3 requires the 'tls' configuration (cert and key)".into());
+            };
+            let http3_addr = SocketAddr::new(addr.ip(), http3.port.unwrap_or(config.port));
+            let server_config = tls.server_config(vec![b"h3".to_vec()])?;
+            let http3_config = http3.clone();
+            let http3_context = server_context.clone();
+            let http3_limits = http3::RequestLimits {
+                max_body_size: config.limits.largest_body_size(),
+                body_read_timeout: config.limits.body_read_timeout,
+                dispatch: setup.dispatch.clone(),
+            };
+
+            tokio::task::spawn(async move {
+                let make_service = move |peer_addr: SocketAddr| {
+                    http3_context.middleware(service_fn(proxy::<Full<Bytes>>), peer_addr)
+                };
+                if let Err(e) = http3::serve(
+                    http3_addr,
+                    server_config,
+                    http3_config,
+                    http3_limits,
+                    make_service,
+                )
+                .await
+                {
+                    log::error!("HTTP/3 server error: {}", e);
+                }
+            });
+
+            config
+                .protocols
+                .alt_svc(config.port)
+                .and_then(|value| HeaderValue::from_str(&value).ok())
+        }
+        None => None,
+    };
+
     log::debug!("Binding to {}", addr);
 
     let listener = match TcpListener::bind(addr).await {
@@ -66,54 +150,141 @@ pub async fn start_server(
         Err(e) => return Err(e.into()),
     };
 
-    log::debug!("Listening on {}", listener.local_addr()?);
+    log::debug!(
+        "Listening on {} (tls={} http1={} http2={} max_connections={:?})",
+        listener.local_addr()?,
+        tls_acceptor.is_some(),
+        config.protocols.http1.enabled,
+        config.protocols.http2.enabled,
+        config.limits.max_connections
+    );
+
+    let connection_builder = Arc::new(config.protocols.connection_builder(&config.limits));
+    let connections = ConnectionCounter::default();
 
     sender_safe!(setup.setup_sender, None);
 
     loop {
-        let dispatch = setup.dispatch.clone();
-        let authorization_span_mode = settings.authorization_span_mode.clone();
-        let router = config.router.clone();
-        let cors_config = config.cors.clone();
-        let sender = match setup.main_sender.clone() {
-            Some(sender) => sender,
-            None => {
-                return Err("Main sender is None".into());
-            }
-        };
-
         log::debug!("Waiting for incoming TCP connection...");
         let (tcp, peer_addr) = listener.accept().await?;
         log::debug!("Accepted connection from {}", peer_addr);
-        let io: TokioIo<tokio::net::TcpStream> = TokioIo::new(tcp);
+
+        let connection_builder = connection_builder.clone();
+        let tls_acceptor = tls_acceptor.clone();
+        let alt_svc = alt_svc.clone();
+        let idle_timeout = config.limits.idle_timeout;
+        let header_read_timeout = config.limits.header_read_timeout;
+        let dispatch = setup.dispatch.clone();
+        let idle_tracker = IdleTracker::default();
+
+        // Connections above the limit are still served, but only to answer 503 and close
+        let permit = connections.acquire(config.limits.max_connections);
+        let over_capacity = permit.is_none();
+        if over_capacity {
+            Rejection::MaxConnections.record(&setup.dispatch);
+        }
+
+        let service = service_fn({
+            let idle_tracker = idle_tracker.clone();
+            move |req: Request<Incoming>| {
+                let alt_svc = alt_svc.clone();
+                let active_request = idle_tracker.begin();
+                async move {
+                    let _active_request = active_request;
+                    if over_capacity {
+                        return Ok(limits::service_unavailable());
+                    }
+                    let mut response = proxy(req).await?;
+                    if let Some(alt_svc) = alt_svc {
+                        response.headers_mut().insert("alt-svc", alt_svc);
+                    }
+                    Ok::<_, Infallible>(response)
+                }
+            }
+        });
+        let middleware = server_context.middleware(service, peer_addr);
 
         tokio::task::spawn(async move {
             log::debug!(
                 "Spawning connection handler task for peer {} with tracing middleware",
                 peer_addr
             );
-            let service = service_fn(proxy);
-
-            let middleware = TracingMiddleware {
-                inner: service,
-                dispatch: dispatch.clone(),
-                sender: sender.clone(),
-                id: setup.id,
-                peer_addr,
-                authorization_span_mode,
-                router: router.clone(),
-                openapi_validator: router.openapi_validator.clone(),
-                cors: cors_config,
+
+            let result = match tls_acceptor {
+                Some(acceptor) => match acceptor.accept(tcp).await {
+                    Ok(stream) => {
+                        let io = HeaderTimeoutIo::new(
+                            stream,
+                            header_read_timeout,
+                            idle_tracker.clone(),
+                            dispatch,
+                        );
+                        limits::serve_with_idle_timeout(
+                            connection_builder.serve_connection(TokioIo::new(io), middleware),
+                            idle_tracker,
+                            idle_timeout,
+                        )
+                        .await
+                    }
+                    Err(e) => {
+                        log::debug!("TLS handshake with {} failed: {}", peer_addr, e);
+                        return;
+                    }
+                },
+                None => {
+                    let io = HeaderTimeoutIo::new(
+                        tcp,
+                        header_read_timeout,
+                        idle_tracker.clone(),
+                        dispatch,
+                    );
+                    limits::serve_with_idle_timeout(
+                        connection_builder.serve_connection(TokioIo::new(io), middleware),
+                        idle_tracker,
+                        idle_timeout,
+                    )
+                    .await
+                }
             };
 
-            if let Err(e) = http1::Builder::new()
-                .keep_alive(true)
-                .serve_connection(io, middleware)
-                .await
-            {
+            if let Err(e) = result {
                 log::debug!("Error serving connection: {}", e);
             }
+            drop(permit);
             log::debug!("Connection handler for {} finished", peer_addr);
         });
     }
 }
+
+/// Shared state cloned into the tracing middleware of every connection
+#[derive(Clone)]
+struct ServerContext {
+    id: ModuleId,
+    dispatch: tracing::Dispatch,
+    sender: MainRuntimeSender,
+    authorization_span_mode: AuthorizationSpanMode,
+    router: Router,
+    cors: Option<CorsConfig>,
+    static_files: Arc<StaticConfig>,
+    compression: Arc<CompressionConfig>,
+    limits: Arc<LimitsConfig>,
+}
+
+impl ServerContext {
+    fn middleware<S>(&self, inner: S, peer_addr: SocketAddr) -> TracingMiddleware<S> {
+        TracingMiddleware {
+            inner,
+            dispatch: self.dispatch.clone(),
+            sender: self.sender.clone(),
+            id: self.id,
+            peer_addr,
+            authorization_span_mode: self.authorization_span_mode.clone(),
+            router: self.router.clone(),
+            openapi_validator: self.router.openapi_validator.clone(),
+            cors: self.cors.clone(),
+            static_files: self.static_files.clone(),
+            compression: self.compression.clone(),
+            limits: self.limits.clone(),
+        }
+    }
+}
@@ -0,0 +1,704 @@
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::Response;
+use hyper_util::server::graceful::GracefulConnection;
+use phlow_sdk::{prelude::*, tracing::Dispatch};
+use std::io;
+use std::pin::{Pin, pin};
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+/// Smallest read buffer hyper accepts for HTTP/1 connections
+pub const MIN_HTTP1_BUF_SIZE: usize = 8192;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteLimit {
+    pub method: Option<String>,
+    pub path: String,
+    pub max_body_size: usize,
+}
+
+impl RouteLimit {
+    fn from_value(value: &Value) -> Option<Self> {
+        if !value.is_object() {
+            log::error!("Route limit ignored: expected an object, got {}", value);
+            return None;
+        }
+
+        let path = match value.get("path") {
+            Some(path) => path.to_string(),
+            None => {
+                log::error!("Route limit ignored: missing 'path'");
+                return None;
+            }
+        };
+
+        let max_body_size = match value.get("max_body_size").and_then(|v| v.to_u64()) {
+            Some(max_body_size) => max_body_size as usize,
+            None => {
+                log::error!("Route limit for {} ignored: missing 'max_body_size'", path);
+                return None;
+            }
+        };
+
+        let method = value
+            .get("method")
+            .map(|v| v.to_string().to_uppercase())
+            .filter(|m| !m.is_empty() && m != "*");
+
+        Some(RouteLimit {
+            method,
+            path,
+            max_body_size,
+        })
+    }
+
+    /// Matches `:param`/`{param}` segments and a trailing `*` wildcard
+    fn matches(&self, method: &str, path: &str) -> bool {
+        if let Some(expected) = &self.method
+            && !expected.eq_ignore_ascii_case(method)
+        {
+            return false;
+        }
+
+        let mut pattern = self.path.trim_matches('/').split('/');
+        let mut segments = path.trim_matches('/').split('/');
+
+        loop {
+            match (pattern.next(), segments.next()) {
+                (Some("*"), _) => return true,
+                (Some(expected), Some(segment)) => {
+                    let is_param = expected.starts_with(':')
+                        || (expected.starts_with('{') && expected.ends_with('}'));
+                    if !is_param && expected != segment {
+                        return false;
+                    }
+                }
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Body, header and connection limits protecting the server from slow or oversized clients.
+/// Without a `limits` block nothing is limited; with one, unset fields take the
+/// recommended values of [`LimitsConfig::recommended`].
+#[derive(Clone, Debug, Default)]
+pub struct LimitsConfig {
+    pub max_body_size: Option<usize>,
+    pub max_header_size: Option<usize>,
+    pub header_read_timeout: Option<Duration>,
+    pub body_read_timeout: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub max_connections: Option<usize>,
+    pub routes: Vec<RouteLimit>,
+}
+
+impl LimitsConfig {
+    pub fn recommended() -> Self {
+        Self {
+            max_body_size: Some(16 * 1024 * 1024),
+            max_header_size: None,
+            header_read_timeout: Some(Duration::from_secs(30)),
+            body_read_timeout: Some(Duration::from_secs(60)),
+            idle_timeout: Some(Duration::from_secs(60)),
+            max_connections: None,
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// Timeouts are given in seconds; `0` disables the timeout
+fn get_timeout(value: &Value, key: &str, default: Option<Duration>) -> Option<Duration> {
+    match value.get(key).and_then(|v| v.to_f64()) {
+        Some(seconds) if seconds <= 0.0 => None,
+        Some(seconds) => Some(Duration::from_secs_f64(seconds)),
+        None => default,
+    }
+}
+
+impl From<&Value> for LimitsConfig {
+    fn from(value: &Value) -> Self {
+        let mut config = LimitsConfig::recommended();
+
+        if !value.is_object() {
+            log::warn!("Invalid limits configuration, using recommended limits");
+            return config;
+        }
+
+        if let Some(max_body_size) = value.get("max_body_size").and_then(|v| v.to_u64()) {
+            config.max_body_size = Some(max_body_size as usize);
+        }
+
+        config.max_header_size = value
+            .get("max_header_size")
+            .and_then(|v| v.to_u64())
+            .map(|v| v as usize);
+
+        config.header_read_timeout =
+            get_timeout(value, "header_read_timeout", config.header_read_timeout);
+        config.body_read_timeout =
+            get_timeout(value, "body_read_timeout", config.body_read_timeout);
+        config.idle_timeout = get_timeout(value, "idle_timeout", config.idle_timeout);
+
+        config.max_connections = value
+            .get("max_connections")
+            .and_then(|v| v.to_u64())
+            .filter(|v| *v > 0)
+            .map(|v| v as usize);
+
+        if let Some(routes) = value.get("routes").and_then(|v| v.as_array()) {
+            config.routes = routes
+                .values
+                .iter()
+                .filter_map(RouteLimit::from_value)
+                .collect();
+        }
+
+        log::debug!(
+            "Limits configuration parsed: max_body_size={:?} header_read_timeout={:?} body_read_timeout={:?} idle_timeout={:?} max_connections={:?} routes={}",
+            config.max_body_size,
+            config.header_read_timeout,
+            config.body_read_timeout,
+            config.idle_timeout,
+            config.max_connections,
+            config.routes.len()
+        );
+
+        config
+    }
+}
+
+impl LimitsConfig {
+    /// Body size limit for a request; the first matching route wins
+    pub fn max_body_size_for(&self, method: &str, path: &str) -> Option<usize> {
+        self.routes
+            .iter()
+            .find(|route| route.matches(method, path))
+            .map(|route| route.max_body_size)
+            .or(self.max_body_size)
+    }
+
+    /// Upper bound across the global and per-route limits, used where the route is not known yet
+    pub fn largest_body_size(&self) -> Option<usize> {
+        let global = self.max_body_size?;
+        Some(
+            self.routes
+                .iter()
+                .map(|route| route.max_body_size)
+                .fold(global, usize::max),
+        )
+    }
+
+    /// HTTP/1 read buffer size derived from `max_header_size`, clamped to hyper's minimum
+    pub fn http1_max_buf_size(&self) -> Option<usize> {
+        self.max_header_size
+            .map(|size| size.max(MIN_HTTP1_BUF_SIZE))
+    }
+}
+
+/// Why a request or connection was refused, reported as a metric attribute
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rejection {
+    BodyTooLarge,
+    BodyTimeout,
+    HeaderTimeout,
+    MaxConnections,
+}
+
+impl Rejection {
+    pub fn from_status(status_code: u16) -> Option<Self> {
+        match status_code {
+            413 => Some(Rejection::BodyTooLarge),
+            408 => Some(Rejection::BodyTimeout),
+            503 => Some(Rejection::MaxConnections),
+            _ => None,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rejection::BodyTooLarge => "body_too_large",
+            Rejection::BodyTimeout => "body_timeout",
+            Rejection::HeaderTimeout => "header_timeout",
+            Rejection::MaxConnections => "max_connections",
+        }
+    }
+
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Rejection::BodyTooLarge => 413,
+            Rejection::BodyTimeout | Rejection::HeaderTimeout => 408,
+            Rejection::MaxConnections => 503,
+        }
+    }
+
+    /// Counts the rejection through the metrics layer of the runtime subscriber
+    pub fn record(&self, dispatch: &Dispatch) {
+        phlow_sdk::tracing::dispatcher::with_default(dispatch, || {
+            tracing::info!(
+                monotonic_counter.http.server.rejected_requests = 1_u64,
+                reason = self.reason(),
+                http.response.status_code = self.status_code() as u64,
+            );
+        });
+        log::debug!(
+            "Request rejected: reason={} status={}",
+            self.reason(),
+            self.status_code()
+        );
+    }
+}
+
+/// Response sent on connections accepted above `max_connections`
+pub fn service_unavailable() -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(503)
+        .header("content-type", "application/json")
+        .header("retry-after", "1")
+        .header("connection", "close")
+        .body(Full::new(Bytes::from(
+            r#"{"error":"Service Unavailable","message":"Too many open connections"}"#,
+        )))
+        .expect("Failed to build response")
+}
+
+/// Counts open TCP connections for `max_connections`
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionCounter {
+    open: Arc<AtomicUsize>,
+}
+
+pub struct ConnectionPermit {
+    open: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConnectionCounter {
+    /// Returns a permit while the server is below `max`; the slot is released when dropped
+    pub fn acquire(&self, max: Option<usize>) -> Option<ConnectionPermit> {
+        let open = self.open.fetch_add(1, Ordering::SeqCst) + 1;
+        let permit = ConnectionPermit {
+            open: self.open.clone(),
+        };
+
+        match max {
+            Some(max) if open > max => None,
+            _ => Some(permit),
+        }
+    }
+}
+
+/// Tracks in-flight requests on a connection so idle keep-alive connections can be closed
+#[derive(Clone, Debug)]
+pub struct IdleTracker {
+    in_flight: Arc<AtomicUsize>,
+    started: Arc<AtomicUsize>,
+    last_activity: Arc<Mutex<Instant>>,
+}
+
+pub struct ActiveRequest {
+    tracker: IdleTracker,
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.tracker.touch();
+        self.tracker.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        Self {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            started: Arc::new(AtomicUsize::new(0)),
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl IdleTracker {
+    pub fn begin(&self) -> ActiveRequest {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        self.started.fetch_add(1, Ordering::SeqCst);
+        self.touch();
+        ActiveRequest {
+            tracker: self.clone(),
+        }
+    }
+
+    fn touch(&self) {
+        if let Ok(mut last_activity) = self.last_activity.lock() {
+            *last_activity = Instant::now();
+        }
+    }
+
+    /// Time left before the connection counts as idle, `None` once it is
+    fn remaining(&self, idle_timeout: Duration) -> Option<Duration> {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return Some(idle_timeout);
+        }
+
+        let elapsed = self
+            .last_activity
+            .lock()
+            .map(|last_activity| last_activity.elapsed())
+            .unwrap_or_default();
+        idle_timeout.checked_sub(elapsed).filter(|d| !d.is_zero())
+    }
+}
+
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0";
+
+const REQUEST_TIMEOUT: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+/// Enforces `header_read_timeout` on HTTP/1 connections in place of hyper's, which
+/// drops the connection without a response. The deadline starts whenever the
+/// connection waits for a request head. A client that sent part of a head by then
+/// gets a `408` and is counted as rejected; a connection that sent nothing is just
+/// closed. HTTP/2 connections are left alone.
+pub struct HeaderTimeoutIo<T> {
+    inner: T,
+    timeout: Option<Duration>,
+    tracker: IdleTracker,
+    dispatch: Dispatch,
+    deadline: Option<Pin<Box<Sleep>>>,
+    /// Requests started when the deadline was set, to notice heads read since then
+    armed_at: usize,
+    received: bool,
+    first_read: bool,
+}
+
+impl<T> HeaderTimeoutIo<T> {
+    pub fn new(
+        inner: T,
+        timeout: Option<Duration>,
+        tracker: IdleTracker,
+        dispatch: Dispatch,
+    ) -> Self {
+        Self {
+            inner,
+            timeout,
+            tracker,
+            dispatch,
+            deadline: None,
+            armed_at: 0,
+            received: false,
+            first_read: true,
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> HeaderTimeoutIo<T> {
+    /// Writes what it can of the 408 without waiting, the connection is closed right after
+    fn reject(&mut self, cx: &mut Context<'_>) {
+        Rejection::HeaderTimeout.record(&self.dispatch);
+
+        let mut written = 0;
+        while written < REQUEST_TIMEOUT.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &REQUEST_TIMEOUT[written..]) {
+                Poll::Ready(Ok(n)) if n > 0 => written += n,
+                _ => break,
+            }
+        }
+        let _ = Pin::new(&mut self.inner).poll_flush(cx);
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for HeaderTimeoutIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        if let Some(timeout) = this.timeout {
+            let started = this.tracker.started.load(Ordering::SeqCst);
+            if this.tracker.in_flight.load(Ordering::SeqCst) > 0 {
+                this.deadline = None;
+            } else if this.deadline.is_none() || this.armed_at != started {
+                this.deadline = Some(Box::pin(tokio::time::sleep(timeout)));
+                this.armed_at = started;
+                this.received = false;
+            }
+
+            if let Some(deadline) = this.deadline.as_mut()
+                && deadline.as_mut().poll(cx).is_ready()
+            {
+                this.deadline = None;
+                this.timeout = None;
+                if this.received {
+                    log::debug!("Request headers not received within {:?}", timeout);
+                    this.reject(cx);
+                } else {
+                    log::debug!("No request within {:?}, closing connection", timeout);
+                }
+                // End of stream, hyper closes the connection
+                return Poll::Ready(Ok(()));
+            }
+        }
+
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = result {
+            let read = &buf.filled()[filled..];
+            if !read.is_empty() {
+                this.received = true;
+                if this.first_read {
+                    this.first_read = false;
+                    if read.starts_with(HTTP2_PREFACE) {
+                        this.timeout = None;
+                        this.deadline = None;
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for HeaderTimeoutIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// Drives a connection and starts a graceful shutdown once it has had no requests
+/// in flight for `idle_timeout`
+pub async fn serve_with_idle_timeout<C>(
+    connection: C,
+    tracker: IdleTracker,
+    idle_timeout: Option<Duration>,
+) -> Result<(), C::Error>
+where
+    C: GracefulConnection,
+{
+    let mut connection = pin!(connection);
+
+    let Some(idle_timeout) = idle_timeout else {
+        return connection.await;
+    };
+
+    let mut shutting_down = false;
+    loop {
+        let wait = tracker.remaining(idle_timeout);
+        tokio::select! {
+            result = connection.as_mut() => return result,
+            _ = tokio::time::sleep(wait.unwrap_or(idle_timeout)), if !shutting_down => {
+                if wait.is_none() || tracker.remaining(idle_timeout).is_none() {
+                    log::debug!("Closing idle connection after {:?}", idle_timeout);
+                    connection.as_mut().graceful_shutdown();
+                    shutting_down = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_route_limits_override_global_limit() {
+        let config = LimitsConfig::from(
+            &HashMap::from([
+                ("max_body_size", 1024.to_value()),
+                (
+                    "routes",
+                    vec![
+                        HashMap::from([
+                            ("path", "/upload/*".to_value()),
+                            ("method", "post".to_value()),
+                            ("max_body_size", 1048576.to_value()),
+                        ])
+                        .to_value(),
+                        HashMap::from([
+                            ("path", "/users/:id/avatar".to_value()),
+                            ("max_body_size", 4096.to_value()),
+                        ])
+                        .to_value(),
+                    ]
+                    .to_value(),
+                ),
+            ])
+            .to_value(),
+        );
+
+        assert_eq!(
+            config.max_body_size_for("POST", "/upload/files/a.png"),
+            Some(1048576)
+        );
+        assert_eq!(
+            config.max_body_size_for("PUT", "/upload/files/a.png"),
+            Some(1024)
+        );
+        assert_eq!(
+            config.max_body_size_for("PUT", "/users/42/avatar"),
+            Some(4096)
+        );
+        assert_eq!(config.max_body_size_for("PUT", "/users/42"), Some(1024));
+        assert_eq!(config.largest_body_size(), Some(1048576));
+    }
+
+    #[test]
+    fn test_no_limits_without_limits_block() {
+        let config = LimitsConfig::default();
+        assert_eq!(config.max_body_size_for("POST", "/upload"), None);
+        assert_eq!(config.largest_body_size(), None);
+        assert_eq!(config.header_read_timeout, None);
+        assert_eq!(config.body_read_timeout, None);
+        assert_eq!(config.idle_timeout, None);
+
+        let config = LimitsConfig::from(&HashMap::<String, Value>::new().to_value());
+        assert_eq!(config.max_body_size, Some(16 * 1024 * 1024));
+        assert_eq!(config.idle_timeout, Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_timeouts_in_seconds_and_zero_disables() {
+        let config = LimitsConfig::from(
+            &HashMap::from([
+                ("header_read_timeout", 5.to_value()),
+                ("idle_timeout", 0.to_value()),
+                ("max_header_size", 1024.to_value()),
+            ])
+            .to_value(),
+        );
+
+        assert_eq!(config.header_read_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(config.idle_timeout, None);
+        assert_eq!(config.body_read_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(config.http1_max_buf_size(), Some(MIN_HTTP1_BUF_SIZE));
+    }
+
+    #[test]
+    fn test_connection_counter_releases_slots() {
+        let counter = ConnectionCounter::default();
+        let first = counter.acquire(Some(1));
+        assert!(first.is_some());
+        assert!(counter.acquire(Some(1)).is_none());
+
+        drop(first);
+        assert!(counter.acquire(Some(1)).is_some());
+        assert!(counter.acquire(None).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_header_timeout_replies_408_to_partial_heads() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let timeout = Some(Duration::from_millis(50));
+
+        let (client, server) = tokio::io::duplex(1024);
+        let mut io = HeaderTimeoutIo::new(
+            server,
+            timeout,
+            IdleTracker::default(),
+            Dispatch::none(),
+        );
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+        client_write.write_all(b"GET / HTTP/1.1\r\nHost").await.unwrap();
+
+        let mut buf = vec![0; 64];
+        assert!(io.read(&mut buf).await.unwrap() > 0);
+        assert_eq!(io.read(&mut buf).await.unwrap(), 0);
+
+        let mut response = vec![0; REQUEST_TIMEOUT.len()];
+        client_read.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, REQUEST_TIMEOUT);
+
+        // A connection that never sent anything is closed without a response
+        let (client, server) = tokio::io::duplex(1024);
+        let mut io = HeaderTimeoutIo::new(
+            server,
+            timeout,
+            IdleTracker::default(),
+            Dispatch::none(),
+        );
+        assert_eq!(io.read(&mut buf).await.unwrap(), 0);
+        drop(io);
+        let mut client = client;
+        assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_header_timeout_waits_while_requests_are_in_flight() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let tracker = IdleTracker::default();
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut io = HeaderTimeoutIo::new(
+            server,
+            Some(Duration::from_millis(30)),
+            tracker.clone(),
+            Dispatch::none(),
+        );
+
+        let request = tracker.begin();
+        let mut buf = vec![0; 64];
+        let read = tokio::time::timeout(Duration::from_millis(100), io.read(&mut buf)).await;
+        assert!(read.is_err());
+
+        drop(request);
+        client.write_all(b"GET").await.unwrap();
+        assert_eq!(io.read(&mut buf).await.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_idle_tracker_waits_for_in_flight_requests() {
+        let tracker = IdleTracker::default();
+        let timeout = Duration::from_millis(10);
+
+        let request = tracker.begin();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(tracker.remaining(timeout).is_some());
+
+        drop(request);
+        assert!(tracker.remaining(timeout).is_some());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(tracker.remaining(timeout).is_none());
+    }
+}
@@ -1,11 +1,25 @@
-use crate::{openapi::OpenAPIValidator, router::Router, settings::AuthorizationSpanMode};
-use hyper::{Request, body::Incoming, service::Service};
+use crate::{
+    compression::CompressionConfig, limits::LimitsConfig, openapi::OpenAPIValidator,
+    router::Router, settings::AuthorizationSpanMode, static_files::StaticConfig,
+};
+use hyper::{Request, Version, service::Service};
 use phlow_sdk::{
     prelude::*,
     tracing::{Dispatch, Level, field},
 };
 
-use std::{future::Future, pin::Pin};
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+/// Collects the W3C trace context headers sent by the caller
+fn trace_context(headers: &hyper::HeaderMap) -> HashMap<String, String> {
+    ["traceparent", "tracestate"]
+        .into_iter()
+        .filter_map(|name| {
+            let value = headers.get(name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
 
 #[derive(Debug, Clone)]
 pub struct RequestContext {
@@ -18,6 +32,9 @@ pub struct RequestContext {
     pub router: Router,
     pub openapi_validator: Option<OpenAPIValidator>,
     pub cors: Option<crate::setup::CorsConfig>,
+    pub static_files: Arc<StaticConfig>,
+    pub compression: Arc<CompressionConfig>,
+    pub limits: Arc<LimitsConfig>,
 }
 
 #[derive(Debug, Clone)]
@@ -31,11 +48,14 @@ pub struct TracingMiddleware<S> {
     pub router: Router,
     pub openapi_validator: Option<OpenAPIValidator>,
     pub cors: Option<crate::setup::CorsConfig>,
+    pub static_files: Arc<StaticConfig>,
+    pub compression: Arc<CompressionConfig>,
+    pub limits: Arc<LimitsConfig>,
 }
 
-impl<S> Service<Request<Incoming>> for TracingMiddleware<S>
+impl<S, B> Service<Request<B>> for TracingMiddleware<S>
 where
-    S: Service<Request<Incoming>> + Clone + Send + 'static,
+    S: Service<Request<B>> + Clone + Send + 'static,
     S::Response: Send + 'static,
     S::Error: Send + 'static,
     S::Future: Send + 'static,
@@ -44,7 +64,7 @@ where
     type Error = S::Error;
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
-    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+    fn call(&self, mut req: Request<B>) -> Self::Future {
         log::debug!(
             "Incoming request: method={} path={} from={}",
             req.method(),
@@ -52,7 +72,7 @@ where
             self.peer_addr
         );
         if req.method() == hyper::Method::GET && req.uri().path() == "/health" {
-            let fut: <S as Service<Request<Incoming>>>::Future = self.inner.call(req);
+            let fut: <S as Service<Request<B>>>::Future = self.inner.call(req);
             return Box::pin(async move { fut.await });
         }
 
@@ -66,6 +86,7 @@ where
                 http.request.method = field::Empty,
                 http.request.size = field::Empty,
                 http.connection.state = field::Empty,
+                network.protocol.version = field::Empty,
                 "http.request.method-original" = field::Empty,
                 "http.request.resend-count" = field::Empty,
                 "http.response.body.size" = field::Empty,
@@ -84,6 +105,7 @@ where
                 "http.request.header.x-forwarded-for" = field::Empty,
                 "http.request.header.cache-control" = field::Empty,
                 "http.request.header.accept-encoding" = field::Empty,
+                "http.request.header.content-encoding" = field::Empty,
                 "http.request.header.authorization" = field::Empty, // Obscure this header
                 "http.request.header.accept-language" = field::Empty,
                 "http.request.header.connection" = field::Empty,
@@ -112,6 +134,7 @@ where
                 "http.response.header.x-forwarded-for" = field::Empty,
                 "http.response.header.cache-control" = field::Empty,
                 "http.response.header.accept-encoding" = field::Empty,
+                "http.response.header.content-encoding" = field::Empty,
                 "http.response.header.authorization" = field::Empty, // Obscure this header
                 "http.response.header.accept-language" = field::Empty,
                 "http.response.header.connection" = field::Empty,
@@ -130,8 +153,21 @@ where
                 "http.response.header.via" = field::Empty,
             );
 
+            phlow_sdk::propagation::extract(&span, &trace_context(req.headers()));
+
             span_enter!(span);
 
+            span.record(
+                "network.protocol.version",
+                match req.version() {
+                    Version::HTTP_09 => "0.9",
+                    Version::HTTP_10 => "1.0",
+                    Version::HTTP_2 => "2",
+                    Version::HTTP_3 => "3",
+                    _ => "1.1",
+                },
+            );
+
             let context = RequestContext {
                 id: self.id,
                 sender: self.sender.clone(),
@@ -142,11 +178,14 @@ where
                 router: self.router.clone(),
                 openapi_validator: self.openapi_validator.clone(),
                 cors: self.cors.clone(),
+                static_files: self.static_files.clone(),
+                compression: self.compression.clone(),
+                limits: self.limits.clone(),
             };
 
             req.extensions_mut().insert(context);
 
-            let fut: <S as Service<Request<Incoming>>>::Future = self.inner.call(req);
+            let fut: <S as Service<Request<B>>>::Future = self.inner.call(req);
 
             Box::pin(async move {
                 let res = fut.await;
@@ -1,12 +1,15 @@
+use crate::compression::{self, Algorithm, CompressionConfig};
+use crate::limits::Rejection;
 use crate::settings::AuthorizationSpanMode;
+use crate::static_files::StaticResponse;
 use crate::{middleware::RequestContext, response::ResponseHandler, router::Router};
 use bytes::Bytes;
-use http_body_util::{BodyExt, Full};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
 use hyper::body::Body;
 use hyper::{HeaderMap, Request, Response};
 use phlow_sdk::span_enter;
 use phlow_sdk::{prelude::*, tracing::Span};
-use std::{collections::HashMap, convert::Infallible};
+use std::{collections::HashMap, convert::Infallible, time::Duration};
 
 macro_rules! to_span_record {
     ($span:expr, $target:expr, $key:expr, $value:expr) => {{
@@ -15,9 +18,11 @@ macro_rules! to_span_record {
     }};
 }
 
-pub async fn proxy(
-    req: Request<hyper::body::Incoming>,
-) -> Result<Response<Full<Bytes>>, Infallible> {
+pub async fn proxy<B>(req: Request<B>) -> Result<Response<Full<Bytes>>, Infallible>
+where
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: std::fmt::Debug + std::error::Error + Send + Sync + 'static,
+{
     // Handle fixed routes first
     if req.method() == hyper::Method::GET && req.uri().path() == "/health" {
         log::debug!("/health probe received");
@@ -29,6 +34,24 @@ pub async fn proxy(
         return Ok(response);
     }
 
+    // Serve static mounts directly, bypassing the flow
+    if let Some(context) = req.extensions().get::<RequestContext>()
+        && !context.static_files.is_empty()
+        && let Some(static_response) = context
+            .static_files
+            .serve(req.method(), req.uri().path(), req.headers())
+            .await
+    {
+        return Ok(static_reply(
+            context,
+            req.method(),
+            req.uri().path(),
+            req.headers(),
+            static_response,
+        )
+        .await);
+    }
+
     // Handle CORS preflight requests (OPTIONS)
     if req.method() == hyper::Method::OPTIONS {
         log::debug!(
@@ -104,7 +127,8 @@ pub async fn proxy(
     span_enter!(context.span);
 
     let path = req.uri().path().to_string();
-    let method = req.method().to_string();
+    let request_method = req.method().clone();
+    let method = request_method.to_string();
     let body_size = req.body().size_hint().lower();
     let request_size = req.size_hint().lower();
     let query = req.uri().query().unwrap_or_default().to_string();
@@ -178,11 +202,17 @@ pub async fn proxy(
     }
 
     let headers = resolve_headers(
-        headers_clone,
+        headers_clone.clone(),
         &context.span,
         &context.authorization_span_mode,
     );
-    let body = resolve_body(req);
+    let max_body_size = context.limits.max_body_size_for(&method, &path);
+    let body = resolve_body(
+        req,
+        &context.compression,
+        max_body_size,
+        context.limits.body_read_timeout,
+    );
     let query_params = resolve_query_params(&query);
 
     context
@@ -194,7 +224,44 @@ pub async fn proxy(
     context.span.record("http.request.path", &path);
 
     let query_params = query_params.await;
-    let body = body.await;
+    let body = match body.await {
+        Ok(body) => body,
+        Err((status_code, message)) => {
+            let rejection = Rejection::from_status(status_code);
+            let mut error_handler = ResponseHandler::from(
+                HashMap::from([
+                    ("status_code", status_code.to_value()),
+                    (
+                        "body",
+                        HashMap::from([
+                            ("error", "Invalid request body".to_value()),
+                            ("message", message.to_value()),
+                        ])
+                        .to_value(),
+                    ),
+                ])
+                .to_value(),
+            );
+
+            // The rest of the body may still be in flight, so the connection is not reused
+            if let Some(rejection) = rejection {
+                rejection.record(&context.dispatch);
+                error_handler
+                    .headers
+                    .insert("connection".to_string(), "close".to_string());
+            }
+
+            context
+                .span
+                .record("http.response.status_code", error_handler.status_code);
+            context
+                .span
+                .record("http.response.body.size", error_handler.body.len());
+
+            log::debug!("Rejected request body: status={} {}", status_code, message);
+            return Ok(error_handler.build());
+        }
+    };
     let headers = headers.await;
     log::debug!(
         "Resolved request parts: headers={} query_params={} body_kind={}",
@@ -238,6 +305,24 @@ pub async fn proxy(
         validation_error.is_some()
     );
 
+    // No route matched: a SPA mount may answer browser navigations instead of the 404
+    if validation_error.is_some()
+        && original_path.is_none()
+        && let Some(static_response) = context
+            .static_files
+            .fallback(&request_method, &path, &headers_clone)
+            .await
+    {
+        return Ok(static_reply(
+            &context,
+            &request_method,
+            &path,
+            &headers_clone,
+            static_response,
+        )
+        .await);
+    }
+
     // If validation failed, return error response immediately
     if let Some(error_response) = validation_error {
         let error_handler = ResponseHandler::from(error_response);
@@ -301,6 +386,24 @@ pub async fn proxy(
         response.headers.len()
     );
 
+    // Without an OpenAPI spec the flow is the router, so its 404 means no route matched
+    if response.status_code == 404
+        && context.router.openapi_validator.is_none()
+        && let Some(static_response) = context
+            .static_files
+            .fallback(&request_method, &path, &headers_clone)
+            .await
+    {
+        return Ok(static_reply(
+            &context,
+            &request_method,
+            &path,
+            &headers_clone,
+            static_response,
+        )
+        .await);
+    }
+
     // Apply CORS headers to the response
     let origin = data_map
         .get("headers")
@@ -321,12 +424,66 @@ pub async fn proxy(
         to_span_record!(context.span, "http.response.header.{}", key, value);
     });
 
+    let (http_response, encoding) = context
+        .compression
+        .encode_response(&headers_clone, response.build())
+        .await;
+    record_response_encoding(&context.span, encoding);
+
     log::debug!(
-        "Sending final HTTP response: status={} headers={}",
+        "Sending final HTTP response: status={} headers={} encoding={:?}",
         response.status_code,
-        response.headers.len()
+        response.headers.len(),
+        encoding
     );
-    Ok(response.build())
+    Ok(http_response)
+}
+
+async fn static_reply(
+    context: &RequestContext,
+    method: &hyper::Method,
+    path: &str,
+    headers: &HeaderMap,
+    static_response: StaticResponse,
+) -> Response<Full<Bytes>> {
+    context
+        .span
+        .record("otel.name", format!("{} {}", method, path));
+    context.span.record("http.request.method", method.as_str());
+    context
+        .span
+        .record("http.response.status_code", static_response.status_code);
+    context
+        .span
+        .record("http.response.body.size", static_response.body_size);
+    static_response
+        .response
+        .headers()
+        .iter()
+        .for_each(|(key, value)| {
+            if let Ok(value) = value.to_str() {
+                to_span_record!(context.span, "http.response.header.{}", key, value);
+            }
+        });
+
+    let (response, encoding) = context
+        .compression
+        .encode_response(headers, static_response.response)
+        .await;
+    record_response_encoding(&context.span, encoding);
+
+    log::debug!(
+        "Static response: path={} status={}",
+        path,
+        static_response.status_code
+    );
+    response
+}
+
+fn record_response_encoding(span: &Span, encoding: Option<Algorithm>) {
+    if let Some(encoding) = encoding {
+        span.record("http.response.header.content-encoding", encoding.name());
+    }
 }
 
 async fn resolve_query_params(query: &str) -> Value {
@@ -343,15 +500,100 @@ async fn resolve_query_params(query: &str) -> Value {
     map.to_value()
 }
 
-async fn resolve_body(req: Request<hyper::body::Incoming>) -> Value {
-    let body_bytes: Bytes = match req.into_body().collect().await {
+async fn resolve_body<B>(
+    req: Request<B>,
+    compression: &CompressionConfig,
+    max_body_size: Option<usize>,
+    body_read_timeout: Option<Duration>,
+) -> Result<Value, (u16, String)>
+where
+    B: Body<Data = Bytes>,
+    B::Error: std::fmt::Debug + std::error::Error + Send + Sync + 'static,
+{
+    let content_encoding = req
+        .headers()
+        .get("content-encoding")
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.trim().to_lowercase())
+        .filter(|h| !h.is_empty() && h != "identity");
+
+    let too_large = |limit: usize| {
+        (
+            413,
+            format!("Request body exceeds the limit of {} bytes", limit),
+        )
+    };
+
+    // Refuse declared oversize bodies before reading anything
+    let content_length = req
+        .headers()
+        .get("content-length")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().parse::<u64>().ok());
+    if let (Some(length), Some(limit)) = (content_length, max_body_size)
+        && length > limit as u64
+    {
+        return Err(too_large(limit));
+    }
+
+    let collect = Limited::new(req.into_body(), max_body_size.unwrap_or(usize::MAX)).collect();
+    let collected = match body_read_timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, collect).await {
+            Ok(collected) => collected,
+            Err(_) => {
+                return Err((
+                    408,
+                    format!("Timed out after {:?} reading request body", timeout),
+                ));
+            }
+        },
+        None => collect.await,
+    };
+
+    let body_bytes: Bytes = match collected {
         Ok(full_body) => full_body.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return Err(too_large(max_body_size.unwrap_or(usize::MAX)));
+        }
         Err(e) => {
             log::debug!("Error reading request body: {:?}", e);
             Bytes::new()
         }
     };
 
+    let body_bytes = match content_encoding {
+        Some(encoding) if compression.decompress_requests && !body_bytes.is_empty() => {
+            let Some(algorithm) = Algorithm::from_token(&encoding) else {
+                return Err((415, format!("Unsupported Content-Encoding: {}", encoding)));
+            };
+
+            // The route limit applies to the decoded body too, or a small compressed
+            // body could inflate far past it
+            let max_size = max_body_size.map_or(compression.max_decompressed_size, |limit| {
+                limit.min(compression.max_decompressed_size)
+            });
+
+            match compression::decompress(&body_bytes, algorithm, max_size) {
+                Ok(decoded) => {
+                    log::debug!(
+                        "Request body decoded from {}: {} -> {} bytes",
+                        algorithm.name(),
+                        body_bytes.len(),
+                        decoded.len()
+                    );
+                    Bytes::from(decoded)
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => {
+                    return Err(too_large(max_size));
+                }
+                Err(e) => {
+                    return Err((400, format!("Invalid {} request body: {}", encoding, e)));
+                }
+            }
+        }
+        _ => body_bytes,
+    };
+
     let body = match std::str::from_utf8(&body_bytes) {
         Ok(s) => {
             let s = s.trim().to_string();
@@ -381,7 +623,7 @@ async fn resolve_body(req: Request<hyper::body::Incoming>) -> Value {
             _ => "other",
         }
     );
-    body
+    Ok(body)
 }
 
 fn resolve_authorization(authorization: &str, mode: &AuthorizationSpanMode) -> String {
@@ -484,3 +726,35 @@ fn validate_request_and_extract_params(
 
     (path_params, original_path, None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gzip_request(body: Vec<u8>) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method("POST")
+            .uri("/avatar")
+            .header("content-encoding", "gzip")
+            .body(Full::new(Bytes::from(body)))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_route_limit_applies_to_decompressed_body() {
+        let compression = CompressionConfig::default();
+        let bomb = compression::compress(&vec![0u8; 8 * 1024 * 1024], Algorithm::Gzip).unwrap();
+        let limit = 16 * 1024;
+        assert!(bomb.len() < limit);
+
+        let result = resolve_body(gzip_request(bomb), &compression, Some(limit), None).await;
+        assert_eq!(result.unwrap_err().0, 413);
+
+        let small = compression::compress(br#"{"ok":true}"#, Algorithm::Gzip).unwrap();
+        let result = resolve_body(gzip_request(small), &compression, Some(limit), None).await;
+        assert_eq!(
+            result.unwrap().get("ok").and_then(|v| v.as_bool()),
+            Some(&true)
+        );
+    }
+}
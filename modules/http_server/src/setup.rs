@@ -1,5 +1,9 @@
-use crate::{openapi::OpenAPIValidator, router::Router};
+use crate::{
+    compression::CompressionConfig, limits::LimitsConfig, openapi::OpenAPIValidator,
+    router::Router, static_files::StaticConfig, transport::ProtocolConfig,
+};
 use phlow_sdk::prelude::*;
+use std::sync::Arc;
 
 #[derive(Clone, Debug)]
 pub struct CorsConfig {
@@ -134,6 +138,10 @@ pub struct Config {
     pub host: String,
     pub router: Router,
     pub cors: Option<CorsConfig>,
+    pub static_files: Arc<StaticConfig>,
+    pub compression: Arc<CompressionConfig>,
+    pub protocols: ProtocolConfig,
+    pub limits: Arc<LimitsConfig>,
 }
 
 impl From<Value> for Config {
@@ -145,6 +153,10 @@ impl From<Value> for Config {
                 host: "0.0.0.0".to_string(),
                 router: Router::from(Value::Null),
                 cors: None,
+                static_files: Arc::new(StaticConfig::default()),
+                compression: Arc::new(CompressionConfig::default()),
+                protocols: ProtocolConfig::default(),
+                limits: Arc::new(LimitsConfig::default()),
             };
         }
 
@@ -184,6 +196,30 @@ impl From<Value> for Config {
             CorsConfig::from(cors_value.clone())
         });
 
+        let static_files = match value.get("static") {
+            Some(static_value) => {
+                let static_config = StaticConfig::from(static_value);
+                log::info!(
+                    "Static file serving enabled for {} mount(s)",
+                    static_config.mounts.len()
+                );
+                static_config
+            }
+            None => StaticConfig::default(),
+        };
+
+        let compression = match value.get("compression") {
+            Some(compression_value) => CompressionConfig::from(compression_value),
+            None => CompressionConfig::default(),
+        };
+
+        let protocols = ProtocolConfig::from(&value);
+
+        let limits = match value.get("limits") {
+            Some(limits_value) => LimitsConfig::from(limits_value),
+            None => LimitsConfig::default(),
+        };
+
         log::debug!("HTTP server will bind to {}:{}", host, port);
 
         Config {
@@ -191,6 +227,10 @@ impl From<Value> for Config {
             host,
             router,
             cors,
+            static_files: Arc::new(static_files),
+            compression: Arc::new(compression),
+            protocols,
+            limits: Arc::new(limits),
         }
     }
 }
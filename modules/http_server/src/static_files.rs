@@ -0,0 +1,796 @@
+use crate::compression::accepts;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{HeaderMap, Method, Response};
+use phlow_sdk::prelude::*;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A directory mounted under a URL prefix and served without going through the flow
+#[derive(Clone, Debug)]
+pub struct StaticMount {
+    pub path: String,
+    pub dir: PathBuf,
+    pub index: String,
+    pub fallback: Option<String>,
+    pub precompressed: bool,
+    pub cache_control: Option<String>,
+}
+
+impl StaticMount {
+    fn from_value(value: &Value) -> Option<Self> {
+        if !value.is_object() {
+            log::error!("Static mount ignored: expected an object, got {}", value);
+            return None;
+        }
+
+        let dir = match value.get("dir") {
+            Some(dir) => dir.to_string(),
+            None => {
+                log::error!("Static mount ignored: missing 'dir'");
+                return None;
+            }
+        };
+
+        let mut path = value
+            .get("path")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "/".to_string());
+        if !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        if path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+
+        let index = value
+            .get("index")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "index.html".to_string());
+
+        // `spa: true` falls back to the index file, `spa: "200.html"` to a custom file
+        let fallback = match value.get("spa") {
+            Some(Value::Boolean(true)) => Some(index.clone()),
+            Some(Value::String(file)) => Some(file.as_string()),
+            _ => None,
+        };
+
+        let precompressed = value
+            .get("precompressed")
+            .and_then(|v| v.as_bool())
+            .copied()
+            .unwrap_or(true);
+
+        let cache_control = value.get("cache_control").map(|v| v.to_string());
+
+        log::debug!(
+            "Static mount configured: path={} dir={} index={} fallback={:?} precompressed={}",
+            path,
+            dir,
+            index,
+            fallback,
+            precompressed
+        );
+
+        Some(Self {
+            path,
+            dir: PathBuf::from(dir),
+            index,
+            fallback,
+            precompressed,
+            cache_control,
+        })
+    }
+
+    /// Returns the part of the request path below this mount, if it matches
+    fn strip<'a>(&self, request_path: &'a str) -> Option<&'a str> {
+        if self.path == "/" {
+            return Some(request_path);
+        }
+
+        let rest = request_path.strip_prefix(self.path.as_str())?;
+        if rest.is_empty() || rest.starts_with('/') {
+            Some(rest)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StaticConfig {
+    pub mounts: Vec<StaticMount>,
+}
+
+impl From<&Value> for StaticConfig {
+    fn from(value: &Value) -> Self {
+        let mut mounts: Vec<StaticMount> = match value {
+            Value::Array(items) => items
+                .values
+                .iter()
+                .filter_map(StaticMount::from_value)
+                .collect(),
+            Value::Object(_) => StaticMount::from_value(value).into_iter().collect(),
+            _ => Vec::new(),
+        };
+
+        // Longest prefix wins
+        mounts.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+
+        Self { mounts }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Satisfiable(u64, u64),
+    Unsatisfiable,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+pub struct StaticResponse {
+    pub status_code: u16,
+    pub body_size: usize,
+    pub response: Response<Full<Bytes>>,
+}
+
+impl StaticConfig {
+    pub fn is_empty(&self) -> bool {
+        self.mounts.is_empty()
+    }
+
+    /// Tries to answer the request with a file from a static mount. Returns `None`
+    /// when the request must continue to the flow. The SPA fallback is not applied
+    /// here, see [`StaticConfig::fallback`].
+    pub async fn serve(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Option<StaticResponse> {
+        if method != Method::GET && method != Method::HEAD {
+            return None;
+        }
+
+        for mount in self.mounts.iter() {
+            let Some(relative) = mount.strip(path) else {
+                continue;
+            };
+
+            let Some(relative) = sanitize_path(relative) else {
+                log::debug!("Static request rejected, unsafe path: {}", path);
+                return Some(build_status(403));
+            };
+
+            let mut file_path = mount.dir.join(&relative);
+
+            if let Ok(meta) = tokio::fs::metadata(&file_path).await
+                && meta.is_dir()
+            {
+                if !path.ends_with('/') && !relative.as_os_str().is_empty() {
+                    return Some(redirect(&format!("{}/", path)));
+                }
+                file_path = file_path.join(&mount.index);
+            }
+
+            if tokio::fs::metadata(&file_path)
+                .await
+                .map(|m| m.is_file())
+                .unwrap_or(false)
+            {
+                return Some(serve_file(mount, &file_path, method, headers).await);
+            }
+        }
+
+        None
+    }
+
+    /// Serves the SPA fallback of the mount matching the request. Called only once
+    /// no route matched, so it never shadows the API, and only for navigations that
+    /// explicitly ask for HTML.
+    pub async fn fallback(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Option<StaticResponse> {
+        if method != Method::GET && method != Method::HEAD {
+            return None;
+        }
+        if !accepts_html(path, headers) {
+            return None;
+        }
+
+        let mount = self.mounts.iter().find(|mount| mount.strip(path).is_some())?;
+        let fallback_path = mount.dir.join(mount.fallback.as_ref()?);
+        log::debug!(
+            "No route matched, serving SPA fallback {}",
+            fallback_path.display()
+        );
+        Some(serve_file(mount, &fallback_path, method, headers).await)
+    }
+}
+
+/// Resolves symlinks and returns the real path only when it stays inside the mount
+async fn resolve_within(mount: &StaticMount, path: &Path) -> Option<PathBuf> {
+    let root = tokio::fs::canonicalize(&mount.dir).await.ok()?;
+    let real = tokio::fs::canonicalize(path).await.ok()?;
+
+    if real.starts_with(&root) {
+        Some(real)
+    } else {
+        log::debug!(
+            "Static request rejected, {} resolves outside {}",
+            path.display(),
+            root.display()
+        );
+        None
+    }
+}
+
+async fn read_range(path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+
+    let mut content = vec![0; (end - start + 1) as usize];
+    file.read_exact(&mut content).await?;
+    Ok(content)
+}
+
+/// Joins only normal components so a request can never escape the mounted directory
+fn sanitize_path(relative: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(relative)?;
+    let mut result = PathBuf::new();
+
+    for component in Path::new(&decoded).components() {
+        match component {
+            Component::Normal(part) => result.push(part),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(result)
+}
+
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    let decoded = String::from_utf8(out).ok()?;
+    if decoded.contains('\0') {
+        return None;
+    }
+    Some(decoded)
+}
+
+/// SPA fallback only applies to browser navigations, never to missing assets or to
+/// clients sending `*/*` or no `Accept` at all
+fn accepts_html(path: &str, headers: &HeaderMap) -> bool {
+    let last_segment = path.rsplit('/').next().unwrap_or_default();
+    if last_segment.contains('.') {
+        return false;
+    }
+
+    headers
+        .get("accept")
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|accept| accepts(accept, "text/html"))
+}
+
+fn negotiate_encoding(headers: &HeaderMap) -> Vec<Encoding> {
+    let accept = headers
+        .get("accept-encoding")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let mut encodings = Vec::new();
+    if accepts(accept, "br") {
+        encodings.push(Encoding::Brotli);
+    }
+    if accepts(accept, "gzip") {
+        encodings.push(Encoding::Gzip);
+    }
+    encodings
+}
+
+fn parse_range(header: &str, size: u64) -> Option<ByteRange> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+
+    // Multipart ranges are not supported, the full body is returned instead
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || size == 0 {
+            return Some(ByteRange::Unsatisfiable);
+        }
+        let suffix = suffix.min(size);
+        return Some(ByteRange::Satisfiable(size - suffix, size - 1));
+    }
+
+    let start: u64 = start.parse().ok()?;
+    let end: u64 = if end.is_empty() {
+        size.saturating_sub(1)
+    } else {
+        end.parse::<u64>().ok()?.min(size.saturating_sub(1))
+    };
+
+    if start >= size || start > end {
+        return Some(ByteRange::Unsatisfiable);
+    }
+
+    Some(ByteRange::Satisfiable(start, end))
+}
+
+fn entity_tag(len: u64, modified: Option<SystemTime>) -> String {
+    let mtime = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    format!("W/\"{:x}-{:x}\"", len, mtime)
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get("if-none-match").and_then(|h| h.to_str().ok()) {
+        let weak = etag.trim_start_matches("W/");
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == weak);
+    }
+
+    if let (Some(since), Some(modified)) = (
+        headers
+            .get("if-modified-since")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| httpdate::parse_http_date(h).ok()),
+        modified,
+    ) {
+        let modified_secs = modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let since_secs = since
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        return modified_secs <= since_secs;
+    }
+
+    false
+}
+
+async fn serve_file(
+    mount: &StaticMount,
+    file_path: &Path,
+    method: &Method,
+    headers: &HeaderMap,
+) -> StaticResponse {
+    let Some(real_path) = resolve_within(mount, file_path).await else {
+        return build_status(403);
+    };
+    let meta = match tokio::fs::metadata(&real_path).await {
+        Ok(meta) if meta.is_file() => meta,
+        _ => return build_status(404),
+    };
+
+    let modified = meta.modified().ok();
+    let etag = entity_tag(meta.len(), modified);
+    let mime = mime_guess::from_path(file_path)
+        .first_or_octet_stream()
+        .to_string();
+
+    let mut builder = Response::builder()
+        .header("content-type", mime)
+        .header("etag", &etag)
+        .header("accept-ranges", "bytes");
+
+    if let Some(modified) = modified {
+        builder = builder.header("last-modified", httpdate::fmt_http_date(modified));
+    }
+    if let Some(cache_control) = &mount.cache_control {
+        builder = builder.header("cache-control", cache_control);
+    }
+    if mount.precompressed {
+        builder = builder.header("vary", "accept-encoding");
+    }
+
+    if not_modified(headers, &etag, modified) {
+        log::debug!("Static file not modified: {}", file_path.display());
+        return finish(builder.status(304), Bytes::new(), false);
+    }
+
+    let range = headers
+        .get("range")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| parse_range(h, meta.len()));
+
+    // Precompressed variants are only used for full responses
+    if mount.precompressed && range.is_none() {
+        for encoding in negotiate_encoding(headers) {
+            let mut variant = file_path.as_os_str().to_owned();
+            variant.push(".");
+            variant.push(encoding.extension());
+
+            let Some(variant) = resolve_within(mount, Path::new(&variant)).await else {
+                continue;
+            };
+            if let Ok(content) = tokio::fs::read(&variant).await {
+                log::debug!(
+                    "Serving precompressed {} variant of {}",
+                    encoding.name(),
+                    file_path.display()
+                );
+                return finish(
+                    builder
+                        .status(200)
+                        .header("content-encoding", encoding.name()),
+                    Bytes::from(content),
+                    method == Method::HEAD,
+                );
+            }
+        }
+    }
+
+    // Only the requested bytes are read, so small ranges of large files stay cheap
+    let content = match range {
+        Some(ByteRange::Satisfiable(start, end)) => read_range(&real_path, start, end).await,
+        Some(ByteRange::Unsatisfiable) => Ok(Vec::new()),
+        None => tokio::fs::read(&real_path).await,
+    };
+    let content = match content {
+        Ok(content) => Bytes::from(content),
+        Err(e) => {
+            log::error!("Error reading static file {}: {}", file_path.display(), e);
+            return build_status(500);
+        }
+    };
+
+    match range {
+        Some(ByteRange::Satisfiable(start, end)) => finish(
+            builder.status(206).header(
+                "content-range",
+                format!("bytes {}-{}/{}", start, end, meta.len()),
+            ),
+            content,
+            method == Method::HEAD,
+        ),
+        Some(ByteRange::Unsatisfiable) => finish(
+            builder
+                .status(416)
+                .header("content-range", format!("bytes */{}", meta.len())),
+            Bytes::new(),
+            false,
+        ),
+        None => finish(builder.status(200), content, method == Method::HEAD),
+    }
+}
+
+fn finish(builder: hyper::http::response::Builder, body: Bytes, head: bool) -> StaticResponse {
+    let body_size = body.len();
+    let builder = builder.header("content-length", body_size);
+    let body = if head { Bytes::new() } else { body };
+
+    match builder.body(Full::new(body)) {
+        Ok(response) => StaticResponse {
+            status_code: response.status().as_u16(),
+            body_size,
+            response,
+        },
+        Err(e) => {
+            log::error!("Error creating static response: {:?}", e);
+            build_status(500)
+        }
+    }
+}
+
+fn build_status(status: u16) -> StaticResponse {
+    StaticResponse {
+        status_code: status,
+        body_size: 0,
+        response: Response::builder()
+            .status(status)
+            .body(Full::new(Bytes::new()))
+            .expect("Failed to build response"),
+    }
+}
+
+fn redirect(location: &str) -> StaticResponse {
+    StaticResponse {
+        status_code: 301,
+        body_size: 0,
+        response: Response::builder()
+            .status(301)
+            .header("location", location)
+            .body(Full::new(Bytes::new()))
+            .expect("Failed to build response"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("phlow_static_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(dir: &Path, spa: bool) -> StaticConfig {
+        let mut mount = std::collections::HashMap::from([
+            ("path", "/app".to_value()),
+            ("dir", dir.to_string_lossy().to_string().to_value()),
+        ]);
+        if spa {
+            mount.insert("spa", true.to_value());
+        }
+        StaticConfig::from(&vec![mount.to_value()].to_value())
+    }
+
+    #[test]
+    fn test_sanitize_path_rejects_traversal() {
+        assert_eq!(
+            sanitize_path("/css/app.css"),
+            Some(PathBuf::from("css/app.css"))
+        );
+        assert_eq!(sanitize_path("/../etc/passwd"), None);
+        assert_eq!(sanitize_path("/%2e%2e/etc/passwd"), None);
+        assert_eq!(sanitize_path("/a%20b.txt"), Some(PathBuf::from("a b.txt")));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("bytes=0-9", 100),
+            Some(ByteRange::Satisfiable(0, 9))
+        );
+        assert_eq!(
+            parse_range("bytes=90-", 100),
+            Some(ByteRange::Satisfiable(90, 99))
+        );
+        assert_eq!(
+            parse_range("bytes=-10", 100),
+            Some(ByteRange::Satisfiable(90, 99))
+        );
+        assert_eq!(
+            parse_range("bytes=50-500", 100),
+            Some(ByteRange::Satisfiable(50, 99))
+        );
+        assert_eq!(
+            parse_range("bytes=200-", 100),
+            Some(ByteRange::Unsatisfiable)
+        );
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), None);
+        assert_eq!(parse_range("items=0-1", 100), None);
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-encoding", "gzip, deflate, br".parse().unwrap());
+        assert_eq!(
+            negotiate_encoding(&headers),
+            vec![Encoding::Brotli, Encoding::Gzip]
+        );
+
+        headers.insert("accept-encoding", "gzip, br;q=0".parse().unwrap());
+        assert_eq!(negotiate_encoding(&headers), vec![Encoding::Gzip]);
+    }
+
+    #[test]
+    fn test_mounts_sorted_by_prefix_length() {
+        let value = vec![
+            std::collections::HashMap::from([("path", "/"), ("dir", "./public")]).to_value(),
+            std::collections::HashMap::from([("path", "/docs/"), ("dir", "./docs")]).to_value(),
+        ]
+        .to_value();
+        let config = StaticConfig::from(&value);
+        assert_eq!(config.mounts[0].path, "/docs");
+        assert_eq!(config.mounts[1].path, "/");
+        assert!(config.mounts[0].strip("/docsx").is_none());
+        assert_eq!(config.mounts[0].strip("/docs/a.html"), Some("/a.html"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_file_with_etag_and_range() {
+        let dir = temp_dir("etag");
+        std::fs::write(dir.join("hello.txt"), "hello world").unwrap();
+        let config = config(&dir, false);
+
+        let headers = HeaderMap::new();
+        let res = config
+            .serve(&Method::GET, "/app/hello.txt", &headers)
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.response.headers().get("content-type").unwrap(),
+            "text/plain"
+        );
+        let etag = res.response.headers().get("etag").unwrap().clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("if-none-match", etag);
+        let res = config
+            .serve(&Method::GET, "/app/hello.txt", &headers)
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, 304);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("range", "bytes=0-4".parse().unwrap());
+        let res = config
+            .serve(&Method::GET, "/app/hello.txt", &headers)
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, 206);
+        assert_eq!(res.body_size, 5);
+        assert_eq!(
+            res.response.headers().get("content-range").unwrap(),
+            "bytes 0-4/11"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_serve_precompressed_and_index() {
+        let dir = temp_dir("precompressed");
+        std::fs::write(dir.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.join("index.html.gz"), "gzipped").unwrap();
+        let config = config(&dir, false);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-encoding", "gzip".parse().unwrap());
+        let res = config.serve(&Method::GET, "/app", &headers).await.unwrap();
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.response.headers().get("content-encoding").unwrap(),
+            "gzip"
+        );
+        assert_eq!(
+            res.response.headers().get("content-type").unwrap(),
+            "text/html"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_spa_fallback_and_passthrough() {
+        let dir = temp_dir("spa");
+        std::fs::write(dir.join("index.html"), "<html></html>").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "text/html".parse().unwrap());
+
+        let without_spa = config(&dir, false);
+        assert!(
+            without_spa
+                .serve(&Method::GET, "/app/users/1", &headers)
+                .await
+                .is_none()
+        );
+
+        assert!(
+            without_spa
+                .fallback(&Method::GET, "/app/users/1", &headers)
+                .await
+                .is_none()
+        );
+
+        // The fallback is left to the resolver, after routing
+        let with_spa = config(&dir, true);
+        assert!(
+            with_spa
+                .serve(&Method::GET, "/app/users/1", &headers)
+                .await
+                .is_none()
+        );
+        let res = with_spa
+            .fallback(&Method::GET, "/app/users/1", &headers)
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, 200);
+
+        // Missing assets never fall back to the index
+        assert!(
+            with_spa
+                .fallback(&Method::GET, "/app/missing.js", &headers)
+                .await
+                .is_none()
+        );
+        assert!(
+            with_spa
+                .fallback(&Method::POST, "/app/users", &headers)
+                .await
+                .is_none()
+        );
+
+        // API clients sending */* or no Accept header are not navigations
+        let mut any = HeaderMap::new();
+        any.insert("accept", "*/*".parse().unwrap());
+        for headers in [any, HeaderMap::new()] {
+            assert!(
+                with_spa
+                    .fallback(&Method::GET, "/app/users/1", &headers)
+                    .await
+                    .is_none()
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinks_cannot_escape_mount() {
+        let dir = temp_dir("symlink");
+        let outside = temp_dir("symlink_outside");
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        std::fs::write(dir.join("inside.txt"), "inside").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), dir.join("secret.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.join("inside.txt"), dir.join("alias.txt")).unwrap();
+        let config = config(&dir, false);
+
+        let headers = HeaderMap::new();
+        let res = config
+            .serve(&Method::GET, "/app/secret.txt", &headers)
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, 403);
+
+        let res = config
+            .serve(&Method::GET, "/app/alias.txt", &headers)
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, 200);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+}
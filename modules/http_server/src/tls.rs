@@ -0,0 +1,70 @@
+use phlow_sdk::prelude::*;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+impl TryFrom<&Value> for TlsConfig {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let cert = value
+            .get("cert")
+            .map(|v| v.to_string())
+            .ok_or("Missing 'cert' in tls configuration")?;
+        let key = value
+            .get("key")
+            .map(|v| v.to_string())
+            .ok_or("Missing 'key' in tls configuration")?;
+
+        Ok(TlsConfig { cert, key })
+    }
+}
+
+impl TlsConfig {
+    /// Loads the certificate chain and private key into a rustls server config
+    /// advertising the given ALPN protocols.
+    pub fn server_config(&self, alpn: Vec<Vec<u8>>) -> Result<rustls::ServerConfig, String> {
+        let cert_pem = std::fs::read(&self.cert)
+            .map_err(|e| format!("Failed to read TLS certificate {}: {}", self.cert, e))?;
+        let key_pem = std::fs::read(&self.key)
+            .map_err(|e| format!("Failed to read TLS key {}: {}", self.key, e))?;
+
+        let certs = CertificateDer::pem_slice_iter(&cert_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid TLS certificate {}: {:?}", self.cert, e))?;
+        if certs.is_empty() {
+            return Err(format!("No certificate found in {}", self.cert));
+        }
+
+        let key = PrivateKeyDer::from_pem_slice(&key_pem)
+            .map_err(|e| format!("Invalid TLS key {}: {:?}", self.key, e))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Invalid TLS protocol configuration: {}", e))?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("Invalid TLS certificate/key pair: {}", e))?;
+
+        config.alpn_protocols = alpn;
+
+        log::debug!(
+            "TLS configuration loaded: cert={} alpn={:?}",
+            self.cert,
+            config
+                .alpn_protocols
+                .iter()
+                .map(|p| String::from_utf8_lossy(p).to_string())
+                .collect::<Vec<String>>()
+        );
+
+        Ok(config)
+    }
+}
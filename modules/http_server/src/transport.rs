@@ -0,0 +1,288 @@
+use crate::limits::{LimitsConfig, MIN_HTTP1_BUF_SIZE};
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto;
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct Http1Config {
+    pub enabled: bool,
+    pub keep_alive: bool,
+    pub max_headers: Option<usize>,
+    pub max_buf_size: Option<usize>,
+}
+
+impl Default for Http1Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep_alive: true,
+            max_headers: None,
+            max_buf_size: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Http2Config {
+    pub enabled: bool,
+    pub max_concurrent_streams: Option<u32>,
+    pub max_header_list_size: Option<u32>,
+    pub initial_stream_window_size: Option<u32>,
+    pub initial_connection_window_size: Option<u32>,
+    pub keep_alive_interval: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Http3Config {
+    pub port: Option<u16>,
+    pub max_concurrent_streams: u32,
+    pub max_field_section_size: Option<u64>,
+    pub idle_timeout: u64,
+}
+
+impl Default for Http3Config {
+    fn default() -> Self {
+        Self {
+            port: None,
+            max_concurrent_streams: 100,
+            max_field_section_size: None,
+            idle_timeout: 30,
+        }
+    }
+}
+
+/// Which HTTP versions the server speaks and the limits applied to each of them
+#[derive(Clone, Debug, Default)]
+pub struct ProtocolConfig {
+    pub http1: Http1Config,
+    pub http2: Http2Config,
+    pub http3: Option<Http3Config>,
+}
+
+/// A protocol block may be written as `true`/`false` or as an object of options
+fn protocol_enabled(value: Option<&Value>) -> Option<(bool, Value)> {
+    match value {
+        Some(Value::Boolean(enabled)) => {
+            Some((*enabled, HashMap::<String, Value>::new().to_value()))
+        }
+        Some(Value::Object(_)) => {
+            let options = value.cloned().unwrap_or(Value::Null);
+            let enabled = options
+                .get("enabled")
+                .and_then(|v| v.as_bool())
+                .copied()
+                .unwrap_or(true);
+            Some((enabled, options))
+        }
+        _ => None,
+    }
+}
+
+fn get_u32(value: &Value, key: &str) -> Option<u32> {
+    value.get(key).and_then(|v| v.to_u64()).map(|v| v as u32)
+}
+
+impl From<&Value> for ProtocolConfig {
+    fn from(value: &Value) -> Self {
+        let mut config = ProtocolConfig::default();
+
+        if !value.is_object() {
+            return config;
+        }
+
+        if let Some((enabled, options)) = protocol_enabled(value.get("http1")) {
+            config.http1.enabled = enabled;
+            if let Some(keep_alive) = options.get("keep_alive").and_then(|v| v.as_bool()) {
+                config.http1.keep_alive = *keep_alive;
+            }
+            config.http1.max_headers = options
+                .get("max_headers")
+                .and_then(|v| v.to_u64())
+                .map(|v| v as usize);
+            config.http1.max_buf_size = options
+                .get("max_buf_size")
+                .and_then(|v| v.to_u64())
+                .map(|v| v as usize);
+        }
+
+        if let Some((enabled, options)) = protocol_enabled(value.get("http2")) {
+            config.http2 = Http2Config {
+                enabled,
+                max_concurrent_streams: get_u32(&options, "max_concurrent_streams"),
+                max_header_list_size: get_u32(&options, "max_header_list_size"),
+                initial_stream_window_size: get_u32(&options, "initial_stream_window_size"),
+                initial_connection_window_size: get_u32(&options, "initial_connection_window_size"),
+                keep_alive_interval: options.get("keep_alive_interval").and_then(|v| v.to_u64()),
+            };
+        }
+
+        if let Some((true, options)) = protocol_enabled(value.get("http3")) {
+            let defaults = Http3Config::default();
+            config.http3 = Some(Http3Config {
+                port: options
+                    .get("port")
+                    .and_then(|v| v.to_u64())
+                    .map(|v| v as u16),
+                max_concurrent_streams: get_u32(&options, "max_concurrent_streams")
+                    .unwrap_or(defaults.max_concurrent_streams),
+                max_field_section_size: options
+                    .get("max_field_section_size")
+                    .and_then(|v| v.to_u64()),
+                idle_timeout: options
+                    .get("idle_timeout")
+                    .and_then(|v| v.to_u64())
+                    .unwrap_or(defaults.idle_timeout),
+            });
+        }
+
+        if !config.http1.enabled && !config.http2.enabled {
+            log::warn!("Both http1 and http2 are disabled; falling back to HTTP/1.1");
+            config.http1.enabled = true;
+        }
+
+        log::debug!(
+            "Protocol configuration parsed: http1={} http2={} http3={}",
+            config.http1.enabled,
+            config.http2.enabled,
+            config.http3.is_some()
+        );
+
+        config
+    }
+}
+
+impl ProtocolConfig {
+    /// Builds the connection builder used for every TCP (or TLS) connection.
+    /// With HTTP/2 enabled on plain TCP, clients using prior knowledge (h2c) are detected
+    /// from the connection preface.
+    /// Header limits and the header read timeout from `limits` apply unless a
+    /// protocol block sets its own value.
+    pub fn connection_builder(&self, limits: &LimitsConfig) -> auto::Builder<TokioExecutor> {
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+
+        {
+            let mut http1 = builder.http1();
+            http1.keep_alive(self.http1.keep_alive);
+            http1.timer(TokioTimer::new());
+            // Enforced by `limits::HeaderTimeoutIo`, which answers 408 instead of dropping
+            http1.header_read_timeout(None);
+            if let Some(max_headers) = self.http1.max_headers {
+                http1.max_headers(max_headers);
+            }
+            if let Some(max_buf_size) = self.http1.max_buf_size.or(limits.http1_max_buf_size()) {
+                http1.max_buf_size(max_buf_size.max(MIN_HTTP1_BUF_SIZE));
+            }
+        }
+
+        {
+            let mut http2 = builder.http2();
+            http2.timer(TokioTimer::new());
+            http2.max_concurrent_streams(self.http2.max_concurrent_streams);
+            http2.initial_stream_window_size(self.http2.initial_stream_window_size);
+            http2.initial_connection_window_size(self.http2.initial_connection_window_size);
+            if let Some(max_header_list_size) = self
+                .http2
+                .max_header_list_size
+                .or(limits.max_header_size.map(|size| size as u32))
+            {
+                http2.max_header_list_size(max_header_list_size);
+            }
+            if let Some(interval) = self.http2.keep_alive_interval {
+                http2.keep_alive_interval(Duration::from_secs(interval));
+            }
+        }
+
+        match (self.http1.enabled, self.http2.enabled) {
+            (true, false) => builder.http1_only(),
+            (false, true) => builder.http2_only(),
+            _ => builder,
+        }
+    }
+
+    /// ALPN identifiers advertised on TLS connections, in preference order
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        let mut protocols = Vec::new();
+        if self.http2.enabled {
+            protocols.push(b"h2".to_vec());
+        }
+        if self.http1.enabled {
+            protocols.push(b"http/1.1".to_vec());
+        }
+        protocols
+    }
+
+    /// `Alt-Svc` value announcing the HTTP/3 endpoint to TCP clients
+    pub fn alt_svc(&self, tcp_port: u16) -> Option<String> {
+        self.http3
+            .as_ref()
+            .map(|http3| format!("h3=\":{}\"; ma=86400", http3.port.unwrap_or(tcp_port)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_serve_http1_only() {
+        let config = ProtocolConfig::from(&Value::Null);
+        assert!(config.http1.enabled);
+        assert!(!config.http2.enabled);
+        assert!(config.http3.is_none());
+        assert_eq!(config.alpn_protocols(), vec![b"http/1.1".to_vec()]);
+        assert_eq!(config.alt_svc(8080), None);
+    }
+
+    #[test]
+    fn test_http2_boolean_and_object_forms() {
+        let config = ProtocolConfig::from(&HashMap::from([("http2", true)]).to_value());
+        assert!(config.http2.enabled);
+        assert_eq!(
+            config.alpn_protocols(),
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+
+        let config = ProtocolConfig::from(
+            &HashMap::from([(
+                "http2",
+                HashMap::from([
+                    ("max_concurrent_streams", 50.to_value()),
+                    ("max_header_list_size", 8192.to_value()),
+                ])
+                .to_value(),
+            )])
+            .to_value(),
+        );
+        assert!(config.http2.enabled);
+        assert_eq!(config.http2.max_concurrent_streams, Some(50));
+        assert_eq!(config.http2.max_header_list_size, Some(8192));
+    }
+
+    #[test]
+    fn test_cannot_disable_every_tcp_protocol() {
+        let config = ProtocolConfig::from(&HashMap::from([("http1", false)]).to_value());
+        assert!(config.http1.enabled);
+    }
+
+    #[test]
+    fn test_http3_alt_svc() {
+        let config = ProtocolConfig::from(
+            &HashMap::from([(
+                "http3",
+                HashMap::from([("port", 8443.to_value())]).to_value(),
+            )])
+            .to_value(),
+        );
+        let http3 = config.http3.as_ref().unwrap();
+        assert_eq!(http3.max_concurrent_streams, 100);
+        assert_eq!(
+            config.alt_svc(443),
+            Some("h3=\":8443\"; ma=86400".to_string())
+        );
+
+        let config = ProtocolConfig::from(&HashMap::from([("http3", false)]).to_value());
+        assert!(config.http3.is_none());
+    }
+}
@@ -0,0 +1,613 @@
+use phlow_sdk::delivery::{LAST_ERROR_HEADER, RETRY_COUNT_HEADER, outcome};
+use phlow_sdk::prelude::*;
+use phlow_sdk::tracing::{Dispatch, Level, field};
+use rdkafka::consumer::{
+    BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer,
+};
+use rdkafka::message::{Header, Headers, Message, OwnedHeaders, OwnedMessage};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{ClientContext, Offset, TopicPartitionList};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::produce::create_producer;
+use crate::setup::Config;
+
+/// Messages a partition buffers while its previous one runs the flow. Past it, the
+/// partition is paused until its worker catches up.
+const PARTITION_BUFFER: usize = 64;
+
+/// Time a revocation waits, in total, for the messages the revoked partitions are
+/// running. The wait blocks the rebalance callback, which runs on the thread polling
+/// the consumer, so the whole group waits with it: keep it far below
+/// `session.timeout.ms` (45s by default).
+const REVOKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+type PartitionKey = (String, i32);
+
+/// Shared by the consumer loop, the worker of the partition and the rebalance callback
+#[derive(Default)]
+struct PartitionState {
+    /// Messages dispatched to the worker and not processed yet
+    queued: AtomicUsize,
+    paused: AtomicBool,
+    /// Set when the partition is revoked or a message could not be dead-lettered.
+    /// The worker stops and the messages after it are left uncommitted.
+    stopped: AtomicBool,
+    /// Held while a message runs, so a revocation waits for its commit
+    busy: tokio::sync::Mutex<()>,
+}
+
+struct Partition {
+    sender: mpsc::UnboundedSender<OwnedMessage>,
+    state: Arc<PartitionState>,
+}
+
+type Partitions = Arc<Mutex<HashMap<PartitionKey, Partition>>>;
+
+/// Drops the workers of revoked partitions once the message each one is running is
+/// committed, so the next owner starts right after it and buffered messages are not
+/// processed twice
+pub struct PartitionContext {
+    partitions: Partitions,
+}
+
+impl ClientContext for PartitionContext {}
+
+impl ConsumerContext for PartitionContext {
+    fn pre_rebalance(&self, _: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        let Rebalance::Revoke(list) = rebalance else {
+            return;
+        };
+
+        let revoked: Vec<Arc<PartitionState>> = {
+            let mut partitions = self.partitions.lock().unwrap();
+            list.elements()
+                .iter()
+                .filter_map(|element| {
+                    partitions.remove(&(element.topic().to_string(), element.partition()))
+                })
+                .map(|partition| {
+                    partition.state.stopped.store(true, Ordering::SeqCst);
+                    partition.state
+                })
+                .collect()
+        };
+
+        let deadline = Instant::now() + REVOKE_TIMEOUT;
+        for state in revoked {
+            while state.busy.try_lock().is_err() {
+                if Instant::now() >= deadline {
+                    log::warn!("Revoking partitions before their running messages finished");
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+/// Consumes the topics of the module as a member of `group_id`. Each partition is
+/// processed in order, one message at a time, while partitions run concurrently.
+pub struct KafkaConsumer {
+    id: ModuleId,
+    main_sender: MainRuntimeSender,
+    config: Config,
+    consumer: StreamConsumer<PartitionContext>,
+    partitions: Partitions,
+    /// Producer of `dlq_topic`
+    dead_letters: Option<FutureProducer>,
+    dispatch: Dispatch,
+}
+
+impl KafkaConsumer {
+    pub fn new(
+        id: ModuleId,
+        main_sender: MainRuntimeSender,
+        config: Config,
+        dispatch: Dispatch,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let group_id = config
+            .group_id
+            .clone()
+            .ok_or("group_id is required to consume as main")?;
+        if config.topics.is_empty() {
+            return Err("topics is required to consume as main".into());
+        }
+
+        // Offsets are committed by hand, only after the flow handled the message
+        let partitions = Partitions::default();
+        let consumer: StreamConsumer<PartitionContext> = config
+            .client_config()
+            .set("group.id", &group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", &config.auto_offset_reset)
+            .create_with_context(PartitionContext {
+                partitions: Arc::clone(&partitions),
+            })?;
+
+        let dead_letters = match &config.dlq_topic {
+            Some(_) => Some(create_producer(&config)?),
+            None => None,
+        };
+
+        Ok(Self {
+            id,
+            main_sender,
+            config,
+            consumer,
+            partitions,
+            dead_letters,
+            dispatch,
+        })
+    }
+
+    pub async fn run(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let topics: Vec<&str> = self.config.topics.iter().map(String::as_str).collect();
+        self.consumer.subscribe(&topics)?;
+        log::info!(
+            "Consuming {} as group {}",
+            topics.join(", "),
+            self.config.group_id.as_deref().unwrap_or_default()
+        );
+
+        let this = Arc::new(self);
+
+        loop {
+            let message = match this.consumer.recv().await {
+                Ok(message) => message.detach(),
+                Err(e) => {
+                    log::error!("Kafka consumer error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            this.dispatch(message);
+        }
+    }
+
+    /// Queues the message on the worker of its partition without waiting, as `recv`
+    /// serves every partition. A partition with a full buffer is paused instead.
+    fn dispatch(self: &Arc<Self>, message: OwnedMessage) {
+        let key = (message.topic().to_string(), message.partition());
+        let mut partitions = self.partitions.lock().unwrap();
+        let partition = partitions
+            .entry(key.clone())
+            .or_insert_with(|| self.spawn_worker(key.clone()));
+
+        // Fetched before the partition stopped, and read again by its next owner
+        if partition.state.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let queued = partition.state.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        if partition.sender.send(message).is_err() {
+            log::error!("Partition worker stopped");
+            return;
+        }
+        if queued >= PARTITION_BUFFER && !partition.state.paused.swap(true, Ordering::SeqCst) {
+            self.set_paused(&key, true);
+        }
+    }
+
+    fn spawn_worker(self: &Arc<Self>, key: PartitionKey) -> Partition {
+        let (sender, mut messages) = mpsc::unbounded_channel::<OwnedMessage>();
+        let state = Arc::new(PartitionState::default());
+        let this = Arc::clone(self);
+        let worker_state = Arc::clone(&state);
+
+        tokio::spawn(async move {
+            while let Some(message) = messages.recv().await {
+                let _busy = worker_state.busy.lock().await;
+                if worker_state.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                if !this.process(message).await {
+                    // Nothing after the message is committed, so it is read again
+                    // once the partition is assigned anew
+                    worker_state.stopped.store(true, Ordering::SeqCst);
+                    this.set_paused(&key, true);
+                    log::error!(
+                        "Stopped partition {} [{}] until it is assigned again",
+                        key.0,
+                        key.1
+                    );
+                    break;
+                }
+
+                let queued = worker_state.queued.fetch_sub(1, Ordering::SeqCst) - 1;
+                if queued <= PARTITION_BUFFER / 2 {
+                    // Under the lock of `dispatch`, so a pause is never undone before it happens
+                    let _partitions = this.partitions.lock().unwrap();
+                    if worker_state.paused.swap(false, Ordering::SeqCst) {
+                        this.set_paused(&key, false);
+                    }
+                }
+            }
+        });
+
+        Partition { sender, state }
+    }
+
+    fn set_paused(&self, (topic, partition): &PartitionKey, paused: bool) {
+        let mut list = TopicPartitionList::new();
+        list.add_partition(topic, *partition);
+        let result = if paused {
+            self.consumer.pause(&list)
+        } else {
+            self.consumer.resume(&list)
+        };
+        if let Err(e) = result {
+            log::warn!(
+                "Failed to {} {} [{}]: {}",
+                if paused { "pause" } else { "resume" },
+                topic,
+                partition,
+                e
+            );
+        }
+    }
+
+    /// Runs the flow for the message, retrying it while it fails, and commits it.
+    /// Returns false when the message ran out of retries and could not be
+    /// dead-lettered, leaving it uncommitted.
+    async fn process(&self, message: OwnedMessage) -> bool {
+        let mut attempt = 0;
+        let mut last_error = None;
+
+        loop {
+            match self
+                .run_flow(&message, attempt, last_error.as_deref())
+                .await
+            {
+                Ok(()) => break,
+                Err(error) if attempt < self.config.max_retry => {
+                    attempt += 1;
+                    let delay = self.config.retry_delay(attempt);
+                    log::debug!(
+                        "Retrying message {} [{}] @ {} in {}ms ({}/{}): {}",
+                        message.topic(),
+                        message.partition(),
+                        message.offset(),
+                        delay.as_millis(),
+                        attempt,
+                        self.config.max_retry,
+                        error
+                    );
+                    last_error = Some(error);
+                    tokio::time::sleep(delay).await;
+                }
+                Err(error) => {
+                    if let Err(e) = self.dead_letter(&message, attempt, &error).await {
+                        log::error!(
+                            "Failed to dead-letter {} [{}] @ {}: {}",
+                            message.topic(),
+                            message.partition(),
+                            message.offset(),
+                            e
+                        );
+                        return false;
+                    }
+                    break;
+                }
+            }
+        }
+
+        let mut offsets = TopicPartitionList::new();
+        let committed = offsets
+            .add_partition_offset(
+                message.topic(),
+                message.partition(),
+                Offset::Offset(message.offset() + 1),
+            )
+            .and_then(|_| self.consumer.commit(&offsets, CommitMode::Async));
+        if let Err(e) = committed {
+            log::error!(
+                "Failed to commit {} [{}] @ {}: {}",
+                message.topic(),
+                message.partition(),
+                message.offset(),
+                e
+            );
+        }
+        true
+    }
+
+    async fn run_flow(
+        &self,
+        message: &OwnedMessage,
+        attempt: i64,
+        last_error: Option<&str>,
+    ) -> Result<(), String> {
+        let _guard = phlow_sdk::tracing::dispatcher::set_default(&self.dispatch);
+        use_log!();
+
+        let span = tracing::span!(
+            Level::INFO,
+            "message_receive",
+            "messaging.system" = "kafka",
+            "messaging.destination.name" = message.topic(),
+            "messaging.operation" = "receive",
+            "messaging.kafka.consumer.group" = self.config.group_id.as_deref(),
+            "messaging.kafka.destination.partition" = message.partition(),
+            "messaging.kafka.message.offset" = message.offset(),
+            "messaging.kafka.message.key" = field::Empty,
+            "messaging.message.payload_size_bytes" = message.payload().map_or(0, <[u8]>::len),
+        );
+        if let Some(key) = message.key() {
+            span.record(
+                "messaging.kafka.message.key",
+                String::from_utf8_lossy(key).as_ref(),
+            );
+        }
+        phlow_sdk::propagation::extract(&span, &trace_context(message));
+        span_enter!(span);
+
+        let data = message_value(message, attempt, self.config.max_retry, last_error);
+        let response = sender_package!(
+            span.clone(),
+            self.dispatch.clone(),
+            self.id,
+            self.main_sender.clone(),
+            Some(data)
+        )
+        .await
+        .ok();
+        log::debug!("Response: {:?}", response);
+
+        outcome(response.as_ref())
+    }
+
+    /// Publishes a message out of retries to `dlq_topic`, with the last error in its
+    /// headers. Without it, the message is skipped.
+    async fn dead_letter(
+        &self,
+        message: &OwnedMessage,
+        attempts: i64,
+        error: &str,
+    ) -> Result<(), String> {
+        let (Some(producer), Some(topic)) = (&self.dead_letters, &self.config.dlq_topic) else {
+            log::warn!(
+                "Skipping message {} [{}] @ {} after {} retries: {}",
+                message.topic(),
+                message.partition(),
+                message.offset(),
+                attempts,
+                error
+            );
+            return Ok(());
+        };
+
+        let original_partition = message.partition().to_string();
+        let original_offset = message.offset().to_string();
+        let attempts = attempts.to_string();
+        let mut headers = message
+            .headers()
+            .cloned()
+            .unwrap_or_else(|| OwnedHeaders::new_with_capacity(5));
+        for (key, value) in [
+            (LAST_ERROR_HEADER, error),
+            (RETRY_COUNT_HEADER, attempts.as_str()),
+            ("x-original-topic", message.topic()),
+            ("x-original-partition", original_partition.as_str()),
+            ("x-original-offset", original_offset.as_str()),
+        ] {
+            headers = headers.insert(Header {
+                key,
+                value: Some(value),
+            });
+        }
+
+        let mut record = FutureRecord::<[u8], [u8]>::to(topic).headers(headers);
+        if let Some(key) = message.key() {
+            record = record.key(key);
+        }
+        if let Some(payload) = message.payload() {
+            record = record.payload(payload);
+        }
+
+        producer
+            .send(record, self.config.message_timeout)
+            .await
+            .map_err(|(e, _)| format!("Failed to send message to {}: {}", topic, e))?;
+        log::debug!(
+            "Message {} [{}] @ {} sent to {}: {}",
+            message.topic(),
+            message.partition(),
+            message.offset(),
+            topic,
+            error
+        );
+        Ok(())
+    }
+}
+
+fn headers(message: &OwnedMessage) -> HashMap<String, String> {
+    let Some(headers) = message.headers() else {
+        return HashMap::new();
+    };
+    headers
+        .iter()
+        .map(|header| {
+            let value = header
+                .value
+                .map(|value| String::from_utf8_lossy(value).to_string())
+                .unwrap_or_default();
+            (header.key.to_string(), value)
+        })
+        .collect()
+}
+
+/// Collects the W3C trace context headers sent by the producer
+fn trace_context(message: &OwnedMessage) -> HashMap<String, String> {
+    headers(message)
+        .into_iter()
+        .filter(|(name, _)| name == "traceparent" || name == "tracestate")
+        .collect()
+}
+
+/// Message as the flow receives it. The value is parsed when it is JSON.
+fn message_value(
+    message: &OwnedMessage,
+    attempt: i64,
+    max_retry: i64,
+    last_error: Option<&str>,
+) -> Value {
+    let value = match message.payload() {
+        Some(payload) => {
+            let text = String::from_utf8_lossy(payload);
+            Value::json_to_value(&text).unwrap_or_else(|_| text.to_value())
+        }
+        None => Value::Null,
+    };
+    let key = message
+        .key()
+        .map(|key| String::from_utf8_lossy(key).to_string());
+    let headers: HashMap<String, Value> = headers(message)
+        .into_iter()
+        .map(|(name, value)| (name, value.to_value()))
+        .collect();
+
+    let retry = json!({
+        "count": attempt,
+        "max": max_retry,
+        "last_error": last_error.map(str::to_string),
+    });
+
+    json!({
+        "topic": message.topic().to_string(),
+        "partition": message.partition(),
+        "offset": message.offset(),
+        "key": key,
+        "value": value,
+        "headers": headers,
+        "timestamp": message.timestamp().to_millis(),
+        "retry": retry,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::produce::produce;
+    use rdkafka::mocking::MockCluster;
+
+    /// A message out of retries that can not be dead-lettered is left uncommitted
+    #[tokio::test]
+    async fn test_failed_dead_letter_is_not_committed() {
+        let config = Config::try_from(
+            &Value::json_to_value(
+                r#"{
+                    "brokers": "127.0.0.1:1",
+                    "topics": "orders",
+                    "group_id": "billing",
+                    "max_retry": 0,
+                    "dlq_topic": "orders.dlq",
+                    "message_timeout": 200
+                }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        // The flow fails: packages are dropped without a response
+        let (main_sender, main_receiver) = channel::unbounded::<Package>();
+        std::thread::spawn(move || for _ in main_receiver {});
+
+        let consumer = KafkaConsumer::new(0, main_sender, config, Dispatch::none()).unwrap();
+        let message = OwnedMessage::new(
+            Some(b"{}".to_vec()),
+            None,
+            "orders".to_string(),
+            rdkafka::Timestamp::NotAvailable,
+            0,
+            7,
+            None,
+        );
+        assert!(!consumer.process(message).await);
+    }
+
+    /// Produces through a step and consumes as main against librdkafka's in-process
+    /// mock cluster, failing the first delivery to see it retried
+    #[tokio::test]
+    async fn test_produce_and_consume() {
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic("orders", 2, 1).unwrap();
+
+        let config = Config::try_from(
+            &Value::json_to_value(&format!(
+                r#"{{
+                    "brokers": "{}",
+                    "topics": "orders",
+                    "group_id": "billing",
+                    "auto_offset_reset": "earliest",
+                    "retry_delay": 10
+                }}"#,
+                cluster.bootstrap_servers()
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let producer = create_producer(&config).unwrap();
+        let input = Value::json_to_value(
+            r#"{ "key": "42", "message": { "id": 42 }, "headers": { "source": "test" } }"#,
+        )
+        .unwrap();
+        let response = produce(&producer, &config, Some(input), HashMap::new()).await;
+        assert!(response.success, "{:?}", response.error_message);
+
+        let (main_sender, main_receiver) = channel::unbounded::<Package>();
+        let consumer = KafkaConsumer::new(0, main_sender, config, Dispatch::none()).unwrap();
+        tokio::spawn(consumer.run());
+
+        let receive = || {
+            let receiver = main_receiver.clone();
+            tokio::task::spawn_blocking(move || {
+                receiver.recv_timeout(Duration::from_secs(20)).unwrap()
+            })
+        };
+
+        let mut package = receive().await.unwrap();
+        let data = package.request_data.clone().unwrap();
+        assert_eq!(data.get("key"), Some(&"42".to_value()));
+        assert_eq!(
+            data.get("value")
+                .and_then(|v| v.get("id"))
+                .and_then(Value::to_i64),
+            Some(42)
+        );
+        assert_eq!(
+            data.get("headers").and_then(|h| h.get("source")),
+            Some(&"test".to_value())
+        );
+        package
+            .response
+            .take()
+            .unwrap()
+            .send(Value::json_to_value(r#"{ "ack": false, "error": "busy" }"#).unwrap())
+            .unwrap();
+
+        let mut package = receive().await.unwrap();
+        let retry = package
+            .request_data
+            .clone()
+            .unwrap()
+            .get("retry")
+            .cloned()
+            .unwrap();
+        assert_eq!(retry.get("count").and_then(Value::to_i64), Some(1));
+        assert_eq!(retry.get("last_error"), Some(&"busy".to_value()));
+        package
+            .response
+            .take()
+            .unwrap()
+            .send(Value::Boolean(true))
+            .unwrap();
+    }
+}
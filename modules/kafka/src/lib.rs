@@ -0,0 +1,56 @@
+mod consumer;
+mod produce;
+mod setup;
+
+use consumer::KafkaConsumer;
+use phlow_sdk::prelude::*;
+use produce::{create_producer, produce};
+use setup::Config;
+use std::sync::Arc;
+
+create_main!(start_kafka(setup));
+
+pub async fn start_kafka(
+    setup: ModuleSetup,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::try_from(&setup.with).map_err(|e| format!("{}", e))?;
+
+    log::debug!("Starting Kafka module with config: {:?}", config);
+
+    if setup.is_main() {
+        // Without a main sender (--migrate, --var-main) only the producer runs
+        if let Some(main_sender) = setup.main_sender.clone() {
+            log::info!("Starting Kafka consumer as main module");
+            let consumer = KafkaConsumer::new(
+                setup.id,
+                main_sender,
+                config.clone(),
+                setup.dispatch.clone(),
+            )?;
+
+            tokio::task::spawn(async move {
+                if let Err(e) = consumer.run().await {
+                    log::error!("Kafka consumer error: {}", e);
+                }
+            });
+        }
+    }
+
+    let producer = create_producer(&config)?;
+    let config = Arc::new(config);
+    let rx = module_channel!(setup);
+
+    for package in rx {
+        let producer = producer.clone();
+        let config = Arc::clone(&config);
+        let trace_headers = package.trace_headers();
+
+        tokio::task::spawn(async move {
+            let response = produce(&producer, &config, package.input(), trace_headers).await;
+            sender_safe!(package.sender, response.to_value().into());
+        });
+    }
+
+    log::debug!("Kafka module finished");
+    Ok(())
+}
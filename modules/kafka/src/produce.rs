@@ -0,0 +1,202 @@
+use phlow_sdk::prelude::*;
+use rdkafka::error::KafkaResult;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use std::collections::HashMap;
+
+use crate::setup::Config;
+
+pub fn create_producer(config: &Config) -> KafkaResult<FutureProducer> {
+    let mut client = config.client_config();
+    client.set(
+        "message.timeout.ms",
+        config.message_timeout.as_millis().to_string(),
+    );
+    if config.idempotence {
+        client.set("enable.idempotence", "true");
+    }
+    client.create()
+}
+
+#[derive(Debug)]
+pub struct Input {
+    pub topic: String,
+    pub key: Option<String>,
+    /// None produces a tombstone, which deletes the key of a compacted topic
+    pub payload: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub partition: Option<i32>,
+}
+
+impl Input {
+    pub fn parse(value: &Value, config: &Config) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err("Input must be an object".to_string());
+        }
+
+        let topic = value
+            .get("topic")
+            .map(|v| v.to_string())
+            .or_else(|| config.default_topic().map(str::to_string))
+            .ok_or_else(|| {
+                "topic is required when the module has not exactly one topic".to_string()
+            })?;
+
+        let payload = match value.get("message") {
+            None | Some(Value::Null) | Some(Value::Undefined) => None,
+            Some(Value::String(message)) => Some(message.as_string()),
+            Some(message) => Some(message.to_json(JsonMode::Inline)),
+        };
+
+        let headers = match value.get("headers") {
+            Some(Value::Object(headers)) => headers
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => return Err("headers must be an object".to_string()),
+        };
+
+        let partition = match value.get("partition") {
+            None | Some(Value::Null) => None,
+            Some(partition) => Some(
+                partition
+                    .to_i64()
+                    .and_then(|p| i32::try_from(p).ok())
+                    .ok_or_else(|| "partition must be an integer".to_string())?,
+            ),
+        };
+
+        Ok(Self {
+            topic,
+            key: value.get("key").map(|v| v.to_string()),
+            payload,
+            headers,
+            partition,
+        })
+    }
+
+    /// Adds trace context headers to the message, keeping any the flow set explicitly
+    fn propagate_trace(&mut self, trace_headers: HashMap<String, String>) {
+        for (key, value) in trace_headers {
+            if !self.headers.iter().any(|(name, _)| *name == key) {
+                self.headers.push((key, value));
+            }
+        }
+    }
+}
+
+#[derive(Debug, ToValue)]
+pub struct ProducerResponse {
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub topic: Option<String>,
+    pub partition: Option<i32>,
+    pub offset: Option<i64>,
+}
+
+impl ProducerResponse {
+    pub fn from_error(error_message: &str) -> Self {
+        Self {
+            success: false,
+            error_message: Some(error_message.to_string()),
+            topic: None,
+            partition: None,
+            offset: None,
+        }
+    }
+}
+
+/// Produces the message of a step and waits for the broker to acknowledge it
+pub async fn produce(
+    producer: &FutureProducer,
+    config: &Config,
+    input: Option<Value>,
+    trace_headers: HashMap<String, String>,
+) -> ProducerResponse {
+    let mut input = match input.as_ref().map(|input| Input::parse(input, config)) {
+        Some(Ok(input)) => input,
+        Some(Err(e)) => return ProducerResponse::from_error(&e),
+        None => return ProducerResponse::from_error("No input provided"),
+    };
+    input.propagate_trace(trace_headers);
+
+    let mut headers = OwnedHeaders::new_with_capacity(input.headers.len());
+    for (key, value) in &input.headers {
+        headers = headers.insert(Header {
+            key,
+            value: Some(value),
+        });
+    }
+
+    let mut record = FutureRecord::<String, String>::to(&input.topic).headers(headers);
+    if let Some(key) = &input.key {
+        record = record.key(key);
+    }
+    if let Some(payload) = &input.payload {
+        record = record.payload(payload);
+    }
+    if let Some(partition) = input.partition {
+        record = record.partition(partition);
+    }
+
+    match producer.send(record, config.message_timeout).await {
+        Ok(delivery) => {
+            log::debug!(
+                "Produced message to {} [{}] at offset {}",
+                input.topic,
+                delivery.partition,
+                delivery.offset
+            );
+            ProducerResponse {
+                success: true,
+                error_message: None,
+                topic: Some(input.topic),
+                partition: Some(delivery.partition),
+                offset: Some(delivery.offset),
+            }
+        }
+        Err((e, _)) => ProducerResponse::from_error(&format!("Produce error: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config::try_from(
+            &Value::json_to_value(r#"{ "brokers": "localhost:9092", "topics": "orders" }"#)
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn parse(json: &str) -> Result<Input, String> {
+        Input::parse(&Value::json_to_value(json).unwrap(), &config())
+    }
+
+    #[test]
+    fn test_input() {
+        let input =
+            parse(r#"{ "key": "42", "message": { "id": 42 }, "headers": { "source": "api" } }"#)
+                .unwrap();
+        assert_eq!(input.topic, "orders");
+        assert_eq!(input.key.as_deref(), Some("42"));
+        assert_eq!(input.payload.as_deref(), Some(r#"{"id":42}"#));
+        assert_eq!(
+            input.headers,
+            vec![("source".to_string(), "api".to_string())]
+        );
+
+        let input = parse(r#"{ "topic": "users", "key": "7", "partition": 2 }"#).unwrap();
+        assert_eq!(input.topic, "users");
+        assert_eq!(input.payload, None);
+        assert_eq!(input.partition, Some(2));
+
+        assert_eq!(
+            parse(r#"{ "message": "a", "partition": "first" }"#).unwrap_err(),
+            "partition must be an integer"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use phlow_sdk::prelude::*;
+use rdkafka::ClientConfig;
+use std::fmt::Display;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    MissingBrokers,
+    InvalidOffsetReset(String),
+    InvalidProperties,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingBrokers => write!(f, "`brokers` is required, such as localhost:9092"),
+            Self::InvalidOffsetReset(value) => write!(
+                f,
+                "Invalid auto_offset_reset: {}. Use earliest or latest",
+                value
+            ),
+            Self::InvalidProperties => {
+                write!(f, "`properties` must be an object of librdkafka settings")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Bootstrap servers, comma separated
+    pub brokers: String,
+    pub client_id: Option<String>,
+    /// Topics consumed as main. A single topic is also the default of the producer.
+    pub topics: Vec<String>,
+    pub group_id: Option<String>,
+    /// Where a group without committed offsets starts: earliest or latest
+    pub auto_offset_reset: String,
+    /// Producer with exactly-once delivery per partition, which implies acks=all
+    pub idempotence: bool,
+    /// Time a produced message may take to be delivered, retries included
+    pub message_timeout: Duration,
+    /// Attempts of a failed message after the first, before it is skipped
+    pub max_retry: i64,
+    /// Delay before the first retry, doubled on each further one
+    pub retry_delay: Duration,
+    /// Topic that receives messages out of retries, with the last error
+    pub dlq_topic: Option<String>,
+    /// librdkafka settings passed as they are, such as security.protocol
+    pub properties: Vec<(String, String)>,
+}
+
+impl Config {
+    /// Settings shared by the consumer and the producer
+    pub fn client_config(&self) -> ClientConfig {
+        let mut client = ClientConfig::new();
+        client.set("bootstrap.servers", &self.brokers);
+        if let Some(client_id) = &self.client_id {
+            client.set("client.id", client_id);
+        }
+        for (key, value) in &self.properties {
+            client.set(key, value);
+        }
+        client
+    }
+
+    /// Delay before the retry number `attempt`, starting at 1, up to 30 seconds
+    pub fn retry_delay(&self, attempt: i64) -> Duration {
+        let exponent = (attempt - 1).clamp(0, 16) as u32;
+        (self.retry_delay * 2u32.pow(exponent)).min(Duration::from_secs(30))
+    }
+
+    /// Topic of messages produced without one
+    pub fn default_topic(&self) -> Option<&str> {
+        match self.topics.as_slice() {
+            [topic] => Some(topic),
+            _ => None,
+        }
+    }
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(array)) => array.values.iter().map(|v| v.to_string()).collect(),
+        Some(Value::Null) | Some(Value::Undefined) | None => Vec::new(),
+        Some(value) => value
+            .to_string()
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    }
+}
+
+impl TryFrom<&Value> for Config {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Error> {
+        let brokers = strings(value.get("brokers"));
+        if brokers.is_empty() {
+            return Err(Error::MissingBrokers);
+        }
+
+        let auto_offset_reset = value
+            .get("auto_offset_reset")
+            .map(|v| v.to_string().to_lowercase())
+            .unwrap_or_else(|| "latest".to_string());
+        if auto_offset_reset != "earliest" && auto_offset_reset != "latest" {
+            return Err(Error::InvalidOffsetReset(auto_offset_reset));
+        }
+
+        let properties = match value.get("properties") {
+            Some(Value::Object(object)) => object
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => return Err(Error::InvalidProperties),
+        };
+
+        Ok(Self {
+            brokers: brokers.join(","),
+            client_id: value.get("client_id").map(|v| v.to_string()),
+            topics: strings(value.get("topics")),
+            group_id: value.get("group_id").map(|v| v.to_string()),
+            auto_offset_reset,
+            idempotence: *value
+                .get("idempotence")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(&true),
+            message_timeout: Duration::from_millis(
+                value
+                    .get("message_timeout")
+                    .and_then(|v| v.to_u64())
+                    .unwrap_or(30000),
+            ),
+            max_retry: value
+                .get("max_retry")
+                .and_then(|v| v.to_i64())
+                .unwrap_or(3)
+                .max(0),
+            retry_delay: Duration::from_millis(
+                value
+                    .get("retry_delay")
+                    .and_then(|v| v.to_u64())
+                    .unwrap_or(1000),
+            ),
+            dlq_topic: value
+                .get("dlq_topic")
+                .map(|v| v.to_string())
+                .filter(|topic| !topic.is_empty()),
+            properties,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Config, Error> {
+        Config::try_from(&Value::json_to_value(json).unwrap())
+    }
+
+    #[test]
+    fn test_config() {
+        let config = parse(
+            r#"{
+                "brokers": ["kafka-1:9092", "kafka-2:9092"],
+                "topics": "orders",
+                "group_id": "billing",
+                "properties": { "security.protocol": "SASL_SSL" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.brokers, "kafka-1:9092,kafka-2:9092");
+        assert_eq!(config.default_topic(), Some("orders"));
+        assert_eq!(config.auto_offset_reset, "latest");
+        assert!(config.idempotence);
+        assert_eq!(
+            config.client_config().get("security.protocol"),
+            Some("SASL_SSL")
+        );
+
+        let config = parse(r#"{ "brokers": "localhost:9092", "topics": "a, b" }"#).unwrap();
+        assert_eq!(config.topics, vec!["a", "b"]);
+        assert_eq!(config.default_topic(), None);
+
+        assert!(matches!(
+            parse(r#"{ "topics": "a" }"#),
+            Err(Error::MissingBrokers)
+        ));
+        assert!(matches!(
+            parse(r#"{ "brokers": "localhost:9092", "auto_offset_reset": "newest" }"#),
+            Err(Error::InvalidOffsetReset(_))
+        ));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let config = parse(r#"{ "brokers": "localhost:9092", "retry_delay": 500 }"#).unwrap();
+        let delays: Vec<u128> = (1..=4)
+            .map(|attempt| config.retry_delay(attempt).as_millis())
+            .collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000]);
+        assert_eq!(config.retry_delay(20), Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,199 @@
+use phlow_sdk::prelude::*;
+use phlow_sdk::tracing::{Dispatch, Level};
+use rumqttc::{AsyncClient, Event, EventLoop, Packet, Publish, QoS};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::setup::Config;
+
+/// Delivers the messages of the subscribed topic filters to the flow
+pub struct Subscriber {
+    pub id: ModuleId,
+    pub main_sender: MainRuntimeSender,
+    pub dispatch: Dispatch,
+}
+
+/// Drives the connection: publishes queued by steps and messages of the subscriptions
+/// only flow while it runs. A lost connection is reopened with backoff, and the topic
+/// filters are subscribed again unless the broker resumed the session.
+pub async fn run(
+    mut eventloop: EventLoop,
+    client: AsyncClient,
+    config: Arc<Config>,
+    subscriber: Option<Subscriber>,
+) {
+    let subscriber = subscriber.map(Arc::new);
+    let acks = spawn_acks(client.clone());
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(ack))) => {
+                log::info!("Connected to MQTT broker {}:{}", config.host, config.port);
+                backoff = Duration::from_secs(1);
+
+                let resubscribe =
+                    subscriber.is_some() && !config.topics.is_empty() && !ack.session_present;
+                let subscribed = match resubscribe {
+                    true => client.try_subscribe_many(config.topics.clone()),
+                    false => Ok(()),
+                };
+                if let Err(e) = subscribed {
+                    log::error!("Failed to subscribe: {}", e);
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                // Without a subscriber, `done` is dropped and the message acknowledged
+                let (done, finished) = oneshot::channel();
+                let _ = acks.send((publish.clone(), finished));
+                if let Some(subscriber) = &subscriber {
+                    let subscriber = Arc::clone(subscriber);
+                    tokio::spawn(async move {
+                        deliver(&subscriber, publish).await;
+                        let _ = done.send(());
+                    });
+                }
+            }
+            Ok(Event::Incoming(Packet::SubAck(ack))) => {
+                log::debug!("Subscribed: {:?}", ack.return_codes);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!(
+                    "MQTT connection error: {}. Reconnecting in {}s",
+                    e,
+                    backoff.as_secs()
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_secs(30));
+            }
+        }
+    }
+}
+
+/// Acknowledges each received message once its flow returns. Flows run in parallel,
+/// but the acks go out in the order the messages arrived, as MQTT requires.
+fn spawn_acks(client: AsyncClient) -> mpsc::UnboundedSender<(Publish, oneshot::Receiver<()>)> {
+    let (acks, mut pending) = mpsc::unbounded_channel::<(Publish, oneshot::Receiver<()>)>();
+    tokio::spawn(async move {
+        while let Some((publish, finished)) = pending.recv().await {
+            let _ = finished.await;
+            if let Err(e) = client.ack(&publish).await {
+                log::error!("Failed to acknowledge message on {}: {}", publish.topic, e);
+            }
+        }
+    });
+    acks
+}
+
+async fn deliver(subscriber: &Subscriber, publish: Publish) {
+    let _guard = phlow_sdk::tracing::dispatcher::set_default(&subscriber.dispatch);
+    use_log!();
+
+    let span = tracing::span!(
+        Level::INFO,
+        "message_receive",
+        "messaging.system" = "mqtt",
+        "messaging.destination.name" = publish.topic.as_str(),
+        "messaging.operation" = "receive",
+        "messaging.message.payload_size_bytes" = publish.payload.len(),
+        "messaging.mqtt.qos" = qos_level(publish.qos),
+        "messaging.mqtt.retain" = publish.retain,
+    );
+    span_enter!(span);
+
+    let data = message_value(&publish);
+    let response = sender_package!(
+        span.clone(),
+        subscriber.dispatch.clone(),
+        subscriber.id,
+        subscriber.main_sender.clone(),
+        Some(data)
+    )
+    .await;
+    log::debug!("Response: {:?}", response);
+}
+
+fn qos_level(qos: QoS) -> u8 {
+    match qos {
+        QoS::AtMostOnce => 0,
+        QoS::AtLeastOnce => 1,
+        QoS::ExactlyOnce => 2,
+    }
+}
+
+/// Message as the flow receives it. The payload is parsed when it is JSON.
+fn message_value(publish: &Publish) -> Value {
+    let text = String::from_utf8_lossy(&publish.payload);
+    let payload = Value::json_to_value(&text).unwrap_or_else(|_| text.to_value());
+
+    json!({
+        "topic": publish.topic.clone(),
+        "payload": payload,
+        "qos": qos_level(publish.qos),
+        "retain": publish.retain,
+        "dup": publish.dup,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acks_follow_arrival_order() {
+        let (requests, acked) = flume::unbounded();
+        let acks = spawn_acks(AsyncClient::from_senders(requests));
+
+        let mut first = Publish::new("a", QoS::AtLeastOnce, "1");
+        first.pkid = 1;
+        let mut second = Publish::new("b", QoS::AtLeastOnce, "2");
+        second.pkid = 2;
+        let (first_done, first_finished) = oneshot::channel();
+        let (second_done, second_finished) = oneshot::channel();
+        acks.send((first, first_finished)).unwrap();
+        acks.send((second, second_finished)).unwrap();
+
+        // The second flow returns first, but is only acknowledged after the first
+        second_done.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(acked.is_empty());
+
+        first_done.send(()).unwrap();
+        let pkids: Vec<u16> = [
+            acked.recv_async().await.unwrap(),
+            acked.recv_async().await.unwrap(),
+        ]
+        .into_iter()
+        .map(|request| match request {
+            rumqttc::Request::PubAck(ack) => ack.pkid,
+            other => panic!("expected a PubAck, got {:?}", other),
+        })
+        .collect();
+        assert_eq!(pkids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_message_value() {
+        let mut publish = Publish::new("sensors/1", QoS::AtLeastOnce, r#"{ "celsius": 21.5 }"#);
+        publish.retain = true;
+        let value = message_value(&publish);
+        assert_eq!(value.get("topic"), Some(&"sensors/1".to_value()));
+        assert_eq!(
+            value
+                .get("payload")
+                .and_then(|p| p.get("celsius"))
+                .and_then(Value::to_f64),
+            Some(21.5)
+        );
+        assert_eq!(value.get("qos").and_then(Value::to_i64), Some(1));
+        assert_eq!(value.get("retain"), Some(&Value::Boolean(true)));
+
+        let publish = Publish::new("status", QoS::AtMostOnce, "online");
+        assert_eq!(
+            message_value(&publish).get("payload"),
+            Some(&"online".to_value())
+        );
+    }
+}
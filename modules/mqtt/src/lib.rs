@@ -0,0 +1,58 @@
+mod connection;
+mod publish;
+mod setup;
+
+use connection::Subscriber;
+use phlow_sdk::prelude::*;
+use publish::publish;
+use rumqttc::AsyncClient;
+use setup::Config;
+use std::sync::Arc;
+
+/// Requests, such as publishes, queued while the connection is busy or down
+const REQUEST_CAPACITY: usize = 1000;
+
+create_main!(start_mqtt(setup));
+
+pub async fn start_mqtt(
+    setup: ModuleSetup,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::try_from(&setup.with).map_err(|e| format!("{}", e))?;
+
+    log::debug!("Starting MQTT module with config: {:?}", config);
+
+    // Without a main sender (--test, --migrate, --var-main) only the publisher runs
+    if setup.main_sender.is_some() && config.topics.is_empty() {
+        return Err("topics is required to subscribe as main".into());
+    }
+
+    let subscriber = setup.main_sender.clone().map(|main_sender| Subscriber {
+        id: setup.id,
+        main_sender,
+        dispatch: setup.dispatch.clone(),
+    });
+
+    let (client, eventloop) = AsyncClient::new(config.mqtt_options(), REQUEST_CAPACITY);
+    let config = Arc::new(config);
+    tokio::task::spawn(connection::run(
+        eventloop,
+        client.clone(),
+        Arc::clone(&config),
+        subscriber,
+    ));
+
+    let rx = module_channel!(setup);
+
+    for package in rx {
+        let client = client.clone();
+        let config = Arc::clone(&config);
+
+        tokio::task::spawn(async move {
+            let response = publish(&client, &config, package.input()).await;
+            sender_safe!(package.sender, response.to_value().into());
+        });
+    }
+
+    log::debug!("MQTT module finished");
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use phlow_sdk::prelude::*;
+use rumqttc::{AsyncClient, QoS};
+
+use crate::setup::{Config, parse_qos};
+
+#[derive(Debug)]
+pub struct Input {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QoS,
+    /// The broker keeps the message and delivers it to every new subscriber of the topic
+    pub retain: bool,
+}
+
+impl Input {
+    pub fn parse(value: &Value, config: &Config) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err("Input must be an object".to_string());
+        }
+
+        let topic = value
+            .get("topic")
+            .map(|v| v.to_string())
+            .ok_or_else(|| "topic is required".to_string())?;
+        if topic.is_empty() || topic.contains('+') || topic.contains('#') {
+            return Err(format!(
+                "Invalid topic: {}. Wildcards are only valid to subscribe",
+                topic
+            ));
+        }
+
+        // An empty retained message clears the message retained on the topic
+        let payload = match value.get("message") {
+            None | Some(Value::Null) | Some(Value::Undefined) => Vec::new(),
+            Some(Value::String(message)) => message.as_string().into_bytes(),
+            Some(message) => message.to_json(JsonMode::Inline).into_bytes(),
+        };
+
+        let qos = match value.get("qos") {
+            Some(qos) => parse_qos(qos).map_err(|e| e.to_string())?,
+            None => config.qos,
+        };
+
+        Ok(Self {
+            topic,
+            payload,
+            qos,
+            retain: *value
+                .get("retain")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(&false),
+        })
+    }
+}
+
+#[derive(Debug, ToValue)]
+pub struct PublishResponse {
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+impl PublishResponse {
+    pub fn from_error(error_message: &str) -> Self {
+        Self {
+            success: false,
+            error_message: Some(error_message.to_string()),
+        }
+    }
+}
+
+/// Queues the message of a step on the connection, which delivers it with its QoS,
+/// also across reconnections
+pub async fn publish(
+    client: &AsyncClient,
+    config: &Config,
+    input: Option<Value>,
+) -> PublishResponse {
+    let input = match input.as_ref().map(|input| Input::parse(input, config)) {
+        Some(Ok(input)) => input,
+        Some(Err(e)) => return PublishResponse::from_error(&e),
+        None => return PublishResponse::from_error("No input provided"),
+    };
+
+    match client
+        .publish(&input.topic, input.qos, input.retain, input.payload)
+        .await
+    {
+        Ok(()) => {
+            log::debug!("Published message to {}", input.topic);
+            PublishResponse {
+                success: true,
+                error_message: None,
+            }
+        }
+        Err(e) => PublishResponse::from_error(&format!("Publish error: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Input, String> {
+        let config = Config::try_from(&Value::json_to_value(r#"{ "qos": 0 }"#).unwrap()).unwrap();
+        Input::parse(&Value::json_to_value(json).unwrap(), &config)
+    }
+
+    #[test]
+    fn test_input() {
+        let input =
+            parse(r#"{ "topic": "devices/1/cmd", "message": { "on": true }, "retain": true }"#)
+                .unwrap();
+        assert_eq!(input.payload, br#"{"on":true}"#);
+        assert_eq!(input.qos, QoS::AtMostOnce);
+        assert!(input.retain);
+
+        let input = parse(r#"{ "topic": "status", "message": "online", "qos": 2 }"#).unwrap();
+        assert_eq!(input.payload, b"online");
+        assert_eq!(input.qos, QoS::ExactlyOnce);
+
+        assert_eq!(
+            parse(r#"{ "topic": "devices/+/cmd" }"#).unwrap_err(),
+            "Invalid topic: devices/+/cmd. Wildcards are only valid to subscribe"
+        );
+        assert_eq!(
+            parse(r#"{ "message": "a" }"#).unwrap_err(),
+            "topic is required"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use phlow_sdk::prelude::*;
+use rumqttc::{MqttOptions, QoS, SubscribeFilter, Transport};
+use std::fmt::Display;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidQos(String),
+    InvalidTopic(String),
+    MissingClientId,
+    Certificate(String, std::io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidQos(value) => write!(f, "Invalid qos: {}. Use 0, 1 or 2", value),
+            Self::InvalidTopic(value) => write!(
+                f,
+                "Invalid topic: {}. Use a topic filter or an object with topic and qos",
+                value
+            ),
+            Self::MissingClientId => write!(
+                f,
+                "client_id is required with clean_session: false, so the broker can resume the session"
+            ),
+            Self::Certificate(path, e) => write!(f, "Failed to read {}: {}", path, e),
+        }
+    }
+}
+
+pub fn parse_qos(value: &Value) -> Result<QoS, Error> {
+    match value.to_i64() {
+        Some(0) => Ok(QoS::AtMostOnce),
+        Some(1) => Ok(QoS::AtLeastOnce),
+        Some(2) => Ok(QoS::ExactlyOnce),
+        _ => Err(Error::InvalidQos(value.to_string())),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tls {
+    /// CA certificate of the broker; the platform's roots when not set
+    pub ca_cert: Option<Vec<u8>>,
+    /// Certificate and key of the client, for brokers that authenticate clients by TLS
+    pub client_auth: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keep_alive: Duration,
+    /// false keeps the session and its subscriptions on the broker while the client
+    /// is offline, so QoS 1 and 2 messages published meanwhile are delivered on return
+    pub clean_session: bool,
+    /// QoS of subscriptions and publishes that don't set their own
+    pub qos: QoS,
+    /// Topic filters subscribed as main
+    pub topics: Vec<SubscribeFilter>,
+    pub tls: Option<Tls>,
+}
+
+impl Config {
+    pub fn mqtt_options(&self) -> MqttOptions {
+        let mut options = MqttOptions::new(&self.client_id, &self.host, self.port);
+        // Received messages are acknowledged once their flow returns
+        options
+            .set_keep_alive(self.keep_alive)
+            .set_clean_session(self.clean_session)
+            .set_manual_acks(true);
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+        match &self.tls {
+            Some(Tls {
+                ca_cert: Some(ca),
+                client_auth,
+            }) => {
+                options.set_transport(Transport::tls(ca.clone(), client_auth.clone(), None));
+            }
+            Some(Tls { ca_cert: None, .. }) => {
+                options.set_transport(Transport::tls_with_default_config());
+            }
+            None => {}
+        }
+        options
+    }
+}
+
+fn read(value: Option<&Value>) -> Result<Option<Vec<u8>>, Error> {
+    match value {
+        Some(path) => {
+            let path = path.to_string();
+            std::fs::read(&path)
+                .map(Some)
+                .map_err(|e| Error::Certificate(path, e))
+        }
+        None => Ok(None),
+    }
+}
+
+fn topics(value: Option<&Value>, qos: QoS) -> Result<Vec<SubscribeFilter>, Error> {
+    let items = match value {
+        Some(Value::Array(array)) => array.values.clone(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(value) => vec![value.clone()],
+    };
+
+    items
+        .iter()
+        .map(|item| match item {
+            Value::String(topic) => Ok(SubscribeFilter::new(topic.as_string(), qos)),
+            Value::Object(_) => {
+                let topic = item
+                    .get("topic")
+                    .map(|v| v.to_string())
+                    .ok_or_else(|| Error::InvalidTopic(item.to_string()))?;
+                let qos = match item.get("qos") {
+                    Some(value) => parse_qos(value)?,
+                    None => qos,
+                };
+                Ok(SubscribeFilter::new(topic, qos))
+            }
+            item => Err(Error::InvalidTopic(item.to_string())),
+        })
+        .collect()
+}
+
+impl TryFrom<&Value> for Config {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Error> {
+        let qos = match value.get("qos") {
+            Some(qos) => parse_qos(qos)?,
+            None => QoS::AtLeastOnce,
+        };
+
+        let clean_session = *value
+            .get("clean_session")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(&true);
+
+        let client_id = match value.get("client_id") {
+            Some(client_id) => client_id.to_string(),
+            None if !clean_session => return Err(Error::MissingClientId),
+            None => {
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.subsec_nanos())
+                    .unwrap_or_default();
+                format!("phlow-{}-{:x}", std::process::id(), nanos)
+            }
+        };
+
+        let ca_cert = read(value.get("ca_cert"))?;
+        let client_cert = read(value.get("client_cert"))?;
+        let client_key = read(value.get("client_key"))?;
+        let tls_enabled = *value.get("tls").and_then(|v| v.as_bool()).unwrap_or(&false)
+            || ca_cert.is_some()
+            || client_cert.is_some();
+        let tls = tls_enabled.then(|| Tls {
+            ca_cert,
+            client_auth: client_cert.zip(client_key),
+        });
+
+        let port = value
+            .get("port")
+            .and_then(|v| v.to_i64())
+            .map(|port| port as u16)
+            .unwrap_or(if tls.is_some() { 8883 } else { 1883 });
+
+        Ok(Self {
+            host: value
+                .get("host")
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "localhost".to_string()),
+            port,
+            client_id,
+            username: value.get("username").map(|v| v.to_string()),
+            password: value.get("password").map(|v| v.to_string()),
+            keep_alive: Duration::from_secs(
+                value
+                    .get("keep_alive")
+                    .and_then(|v| v.to_u64())
+                    .unwrap_or(30),
+            ),
+            clean_session,
+            qos,
+            topics: topics(value.get("topics"), qos)?,
+            tls,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Config, Error> {
+        Config::try_from(&Value::json_to_value(json).unwrap())
+    }
+
+    #[test]
+    fn test_config() {
+        let config = parse(
+            r#"{
+                "host": "broker",
+                "client_id": "gateway",
+                "clean_session": false,
+                "topics": ["sensors/+/temperature", { "topic": "alerts/#", "qos": 2 }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.port, 1883);
+        assert_eq!(config.qos, QoS::AtLeastOnce);
+        assert!(config.tls.is_none());
+        assert_eq!(
+            config.topics,
+            vec![
+                SubscribeFilter::new("sensors/+/temperature".to_string(), QoS::AtLeastOnce),
+                SubscribeFilter::new("alerts/#".to_string(), QoS::ExactlyOnce),
+            ]
+        );
+
+        let config = parse(r#"{ "tls": true, "topics": "devices/#", "qos": 0 }"#).unwrap();
+        assert_eq!(config.port, 8883);
+        assert!(config.client_id.starts_with("phlow-"));
+        assert_eq!(config.topics[0].qos, QoS::AtMostOnce);
+
+        assert!(matches!(
+            parse(r#"{ "clean_session": false }"#),
+            Err(Error::MissingClientId)
+        ));
+        assert!(matches!(
+            parse(r#"{ "qos": 3 }"#),
+            Err(Error::InvalidQos(_))
+        ));
+        assert!(matches!(
+            parse(r#"{ "topics": [{ "qos": 1 }] }"#),
+            Err(Error::InvalidTopic(_))
+        ));
+    }
+}
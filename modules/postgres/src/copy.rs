@@ -0,0 +1,176 @@
+use bytes::Bytes;
+use deadpool_postgres::Client;
+use futures::{SinkExt, StreamExt, pin_mut};
+use phlow_sdk::prelude::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::types::{ToSql, Type};
+
+use crate::execute::QueryError;
+use crate::input::{CopyIn, CopyOut, CopySource};
+use crate::types;
+
+fn io_error(path: &str) -> impl Fn(std::io::Error) -> QueryError + '_ {
+    move |e| QueryError::Other(format!("{}: {}", path, e))
+}
+
+/// Streams the rows of the query to the file, so exports never sit in memory
+pub async fn copy_out(client: &Client, copy: &CopyOut) -> Result<Value, QueryError> {
+    let statement = format!(
+        "COPY ({}) TO STDOUT WITH ({})",
+        copy.query.trim().trim_end_matches(';'),
+        copy.format.options(copy.header)
+    );
+
+    let stream = client.copy_out(&statement).await?;
+    pin_mut!(stream);
+
+    let mut file = tokio::fs::File::create(&copy.path)
+        .await
+        .map_err(io_error(&copy.path))?;
+    let mut bytes = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        bytes += chunk.len();
+        file.write_all(&chunk).await.map_err(io_error(&copy.path))?;
+    }
+    file.flush().await.map_err(io_error(&copy.path))?;
+
+    Ok(json!({
+        "path": copy.path.clone(),
+        "bytes": bytes,
+    }))
+}
+
+/// Columns to load from rows given as objects: their keys, in sorted order
+fn object_columns(rows: &[Value]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    for row in rows {
+        if let Value::Object(object) = row {
+            for (key, _) in object.iter() {
+                let key = key.to_string();
+                if !columns.contains(&key) {
+                    columns.push(key);
+                }
+            }
+        }
+    }
+    columns.sort();
+    columns
+}
+
+pub async fn copy_in(client: &Client, copy: &CopyIn) -> Result<Value, QueryError> {
+    let count = match &copy.source {
+        CopySource::File {
+            path,
+            format,
+            header,
+        } => {
+            let columns = if copy.columns.is_empty() {
+                String::new()
+            } else {
+                format!("({})", copy.columns.join(", "))
+            };
+            let statement = format!(
+                "COPY {} {} FROM STDIN WITH ({})",
+                copy.table,
+                columns,
+                format.options(*header)
+            );
+
+            let mut file = tokio::fs::File::open(path).await.map_err(io_error(path))?;
+            let sink = client.copy_in::<_, Bytes>(&statement).await?;
+            pin_mut!(sink);
+
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = file.read(&mut buffer).await.map_err(io_error(path))?;
+                if read == 0 {
+                    break;
+                }
+                sink.send(Bytes::copy_from_slice(&buffer[..read])).await?;
+            }
+            sink.finish().await?
+        }
+        CopySource::Rows(rows) => {
+            let columns = if copy.columns.is_empty() {
+                object_columns(rows)
+            } else {
+                copy.columns.clone()
+            };
+            let list = if columns.is_empty() {
+                "*".to_string()
+            } else {
+                columns.join(", ")
+            };
+
+            // The column types tell how to convert each value, as with parameters
+            let probe = client
+                .prepare(&format!("SELECT {} FROM {} LIMIT 0", list, copy.table))
+                .await
+                .map_err(QueryError::Prepare)?;
+            let names: Vec<String> = probe
+                .columns()
+                .iter()
+                .map(|c| c.name().to_string())
+                .collect();
+            let column_types: Vec<Type> =
+                probe.columns().iter().map(|c| c.type_().clone()).collect();
+
+            let sink = client
+                .copy_in(&format!(
+                    "COPY {} ({}) FROM STDIN BINARY",
+                    copy.table,
+                    names.join(", ")
+                ))
+                .await?;
+            let writer = BinaryCopyInWriter::new(sink, &column_types);
+            pin_mut!(writer);
+
+            for (index, row) in rows.iter().enumerate() {
+                let values: Vec<&Value> = match row {
+                    Value::Array(array) => array.values.iter().collect(),
+                    row if row.is_object() => names
+                        .iter()
+                        .map(|name| row.get(name.as_str()).unwrap_or(&Value::Null))
+                        .collect(),
+                    _ => {
+                        return Err(QueryError::Param(format!(
+                            "rows[{}]: expected an object or an array",
+                            index
+                        )));
+                    }
+                };
+
+                if values.len() != names.len() {
+                    return Err(QueryError::Param(format!(
+                        "rows[{}]: expected {} values, found {}",
+                        index,
+                        names.len(),
+                        values.len()
+                    )));
+                }
+
+                let params = values
+                    .iter()
+                    .zip(names.iter().zip(&column_types))
+                    .map(|(value, (name, ty))| {
+                        types::to_sql(value, ty).map_err(|e| {
+                            QueryError::Param(format!("rows[{}].{}: {}", index, name, e))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let param_refs: Vec<&(dyn ToSql + Sync)> = params
+                    .iter()
+                    .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+                    .collect();
+
+                writer.as_mut().write(&param_refs).await?;
+            }
+
+            writer.finish().await?
+        }
+    };
+
+    Ok(json!({ "count": count }))
+}
@@ -0,0 +1,219 @@
+use deadpool_postgres::{Client, Pool};
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::execute::{self, QueryError};
+use crate::input::{Cursor, Page, Query};
+use crate::response::QueryResult;
+
+/// Each cursor has its own connection, so the SQL name can be fixed
+const CURSOR_NAME: &str = "phlow_cursor";
+
+struct OpenCursor {
+    client: Client,
+    fetch_size: i64,
+    last_used: Instant,
+}
+
+/// Server-side cursors kept open between steps. A cursor holds a pooled connection
+/// inside a transaction until its last batch, `close` or the idle timeout.
+#[derive(Clone)]
+pub struct Cursors {
+    open: Arc<Mutex<HashMap<String, OpenCursor>>>,
+    timeout: Duration,
+}
+
+fn batch(result: QueryResult, cursor: Option<&str>) -> Value {
+    let mut data = result.to_value();
+    if let Value::Object(object) = &mut data {
+        object.insert("cursor".to_string(), cursor.to_value());
+        object.insert("done".to_string(), cursor.is_none().to_value());
+    }
+    data
+}
+
+async fn fetch_batch(client: &Client, fetch_size: i64) -> Result<QueryResult, QueryError> {
+    let rows = client
+        .query(&format!("FETCH {} FROM {}", fetch_size, CURSOR_NAME), &[])
+        .await?;
+    Ok(QueryResult::from(rows))
+}
+
+impl Cursors {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            open: Arc::new(Mutex::new(HashMap::new())),
+            timeout,
+        }
+    }
+
+    /// Closes the cursors idle for longer than the timeout, releasing their connections
+    pub fn expire(&self) {
+        let cursors = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(cursors.timeout.min(Duration::from_secs(10)));
+            loop {
+                interval.tick().await;
+                let expired: Vec<(String, OpenCursor)> = {
+                    let mut open = cursors.open.lock().unwrap();
+                    let ids: Vec<String> = open
+                        .iter()
+                        .filter(|(_, cursor)| cursor.last_used.elapsed() > cursors.timeout)
+                        .map(|(id, _)| id.clone())
+                        .collect();
+                    ids.into_iter()
+                        .filter_map(|id| open.remove(&id).map(|cursor| (id, cursor)))
+                        .collect()
+                };
+
+                for (id, cursor) in expired {
+                    log::warn!("Cursor {} expired before its last batch", id);
+                    let _ = cursor.client.batch_execute("ROLLBACK").await;
+                }
+            }
+        });
+    }
+
+    /// Declares the cursor and returns its first batch. A result that fits in one
+    /// batch is done right away and no cursor stays open.
+    pub async fn open(&self, pool: &Pool, cursor: &Cursor) -> Result<Value, QueryError> {
+        let client = pool
+            .get()
+            .await
+            .map_err(|e| QueryError::Other(format!("Failed to get client from pool: {}", e)))?;
+
+        let declare = Query {
+            query: format!(
+                "DECLARE {} NO SCROLL CURSOR FOR {}",
+                CURSOR_NAME,
+                cursor.query.query.trim().trim_end_matches(';')
+            ),
+            params: cursor.query.params.clone(),
+            types: cursor.query.types.clone(),
+            batch: true,
+            cache_query: false,
+            savepoint: None,
+        };
+
+        client.batch_execute("BEGIN").await?;
+        let result = match execute::rows(&client, &declare).await {
+            Ok(_) => fetch_batch(&client, cursor.fetch_size).await,
+            Err(e) => Err(e),
+        };
+
+        self.keep(None, client, cursor.fetch_size, result).await
+    }
+
+    pub async fn fetch(&self, id: &str) -> Result<Value, QueryError> {
+        // Taken out while in use, so a cursor is never fetched twice at once
+        let cursor = self.open.lock().unwrap().remove(id).ok_or_else(|| {
+            QueryError::Other(format!(
+                "Unknown cursor: {}. It was closed, fully read or expired",
+                id
+            ))
+        })?;
+
+        let result = fetch_batch(&cursor.client, cursor.fetch_size).await;
+        self.keep(
+            Some(id.to_string()),
+            cursor.client,
+            cursor.fetch_size,
+            result,
+        )
+        .await
+    }
+
+    pub async fn close(&self, id: &str) -> Result<Value, QueryError> {
+        let cursor = self.open.lock().unwrap().remove(id);
+        if let Some(cursor) = cursor {
+            cursor.client.batch_execute("ROLLBACK").await?;
+        }
+        Ok(batch(QueryResult::from(Vec::new()), None))
+    }
+
+    /// Keeps the cursor open while batches come full, and ends the transaction on
+    /// the last batch or on an error
+    async fn keep(
+        &self,
+        id: Option<String>,
+        client: Client,
+        fetch_size: i64,
+        result: Result<QueryResult, QueryError>,
+    ) -> Result<Value, QueryError> {
+        match result {
+            Ok(result) if (result.count as i64) < fetch_size => {
+                client.batch_execute("COMMIT").await?;
+                Ok(batch(result, None))
+            }
+            Ok(result) => {
+                let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                let data = batch(result, Some(&id));
+                self.open.lock().unwrap().insert(
+                    id,
+                    OpenCursor {
+                        client,
+                        fetch_size,
+                        last_used: Instant::now(),
+                    },
+                );
+                Ok(data)
+            }
+            Err(e) => {
+                let _ = client.batch_execute("ROLLBACK").await;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Runs a keyset page: the query wrapped with a filter on `key` and a limit. `next`
+/// is the key of the last row to pass as `after`, or null on the last page.
+pub async fn page(client: &Client, page: &Page) -> Result<Value, QueryError> {
+    let mut params = page.query.params.clone();
+    let mut types = page.query.types.clone();
+    let (operator, order) = if page.desc {
+        ("<", "DESC")
+    } else {
+        (">", "ASC")
+    };
+
+    let filter = if page.after.is_null() {
+        String::new()
+    } else {
+        params.push(page.after.clone());
+        types.resize(params.len(), None);
+        format!("WHERE {} {} ${}", page.key, operator, params.len())
+    };
+
+    let query = Query {
+        query: format!(
+            "SELECT * FROM ({}) AS phlow_page {} ORDER BY {} {} LIMIT {}",
+            page.query.query.trim().trim_end_matches(';'),
+            filter,
+            page.key,
+            order,
+            page.limit
+        ),
+        params,
+        types,
+        batch: true,
+        cache_query: page.query.cache_query,
+        savepoint: None,
+    };
+
+    let result = execute::rows(client, &query).await?;
+    let next = match result.rows.last() {
+        Some(row) if result.count as i64 == page.limit => {
+            row.get(page.key.as_str()).cloned().unwrap_or(Value::Null)
+        }
+        _ => Value::Null,
+    };
+
+    let mut data = result.to_value();
+    if let Value::Object(object) = &mut data {
+        object.insert("next".to_string(), next);
+    }
+    Ok(data)
+}
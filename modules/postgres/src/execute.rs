@@ -0,0 +1,207 @@
+use deadpool_postgres::GenericClient;
+use phlow_sdk::prelude::*;
+use std::error::Error;
+use tokio_postgres::types::{ToSql, Type};
+
+use crate::input::{Query, Transaction};
+use crate::response::QueryResult;
+use crate::types::{self, unspecified};
+
+pub enum QueryError {
+    /// A parameter could not be converted to the type of its placeholder
+    Param(String),
+    /// The statement could not be prepared, such as a syntax error or an unknown table
+    Prepare(tokio_postgres::Error),
+    /// An error reported by the server while running the statement
+    Database(tokio_postgres::Error),
+    /// The connection failed before the server could answer
+    Connection(tokio_postgres::Error),
+    /// Failures outside the database, like the file of a COPY or an unknown cursor
+    Other(String),
+}
+
+impl From<tokio_postgres::Error> for QueryError {
+    fn from(err: tokio_postgres::Error) -> Self {
+        if err.as_db_error().is_some() {
+            QueryError::Database(err)
+        } else {
+            QueryError::Connection(err)
+        }
+    }
+}
+
+impl QueryError {
+    /// Message of the errors that fail the step
+    fn message(self) -> String {
+        match self {
+            QueryError::Prepare(e) => format!("Failed to prepare statement: {}", e),
+            QueryError::Database(e) => e.to_string(),
+            QueryError::Connection(e) => format!("Connection failed: {}", e),
+            QueryError::Param(e) | QueryError::Other(e) => e,
+        }
+    }
+}
+
+fn database_error(err: &tokio_postgres::Error) -> Value {
+    let code = err.code().map(|c| c.code()).unwrap_or("UNKNOWN");
+    let message = err
+        .as_db_error()
+        .map(|db| db.message().to_string())
+        .unwrap_or_else(|| err.to_string());
+    let cause = err.source().map(|s| s.to_string()).unwrap_or_default();
+
+    json!({
+        "code": code,
+        "cause": cause,
+        "message": message,
+    })
+}
+
+/// Response of a query that reached the database: its result, or the database error
+pub fn outcome(result: Result<Value, tokio_postgres::Error>) -> Value {
+    match result {
+        Ok(data) => json!({ "success": true, "data": data }),
+        Err(err) => json!({ "success": false, "error": database_error(&err) }),
+    }
+}
+
+/// Step response of an action: errors the server reports while running a statement,
+/// like a violated constraint, are part of the output. Statements that can not be
+/// prepared, lost connections, invalid parameters and other failures fail the step.
+pub fn respond(result: Result<Value, QueryError>) -> ModuleResponse {
+    match result {
+        Ok(data) => ModuleResponse::from_success(outcome(Ok(data))),
+        Err(QueryError::Database(e)) => ModuleResponse::from_success(outcome(Err(e))),
+        Err(e) => ModuleResponse::from_error(e.message()),
+    }
+}
+
+/// Prepares the query with its type hints and binds each parameter to the type the
+/// server resolved for it. Queries with `batch: false` run as a simple query.
+pub async fn run<C: GenericClient>(client: &C, query: &Query) -> Result<Value, QueryError> {
+    if !query.batch {
+        client.batch_execute(&query.query).await?;
+        return Ok("OK".to_value());
+    }
+
+    rows(client, query).await.map(|result| result.to_value())
+}
+
+pub async fn rows<C: GenericClient>(client: &C, query: &Query) -> Result<QueryResult, QueryError> {
+    let stmt = if query.types.iter().all(Option::is_none) {
+        if query.cache_query {
+            client.prepare_cached(&query.query).await
+        } else {
+            client.prepare(&query.query).await
+        }
+    } else {
+        let hints: Vec<Type> = query
+            .types
+            .iter()
+            .map(|ty| ty.clone().unwrap_or_else(unspecified))
+            .collect();
+        if query.cache_query {
+            client.prepare_typed_cached(&query.query, &hints).await
+        } else {
+            client.prepare_typed(&query.query, &hints).await
+        }
+    }
+    .map_err(QueryError::Prepare)?;
+
+    if stmt.params().len() != query.params.len() {
+        return Err(QueryError::Param(format!(
+            "expected {} parameters, found {}",
+            stmt.params().len(),
+            query.params.len()
+        )));
+    }
+
+    let params = stmt
+        .params()
+        .iter()
+        .zip(&query.params)
+        .enumerate()
+        .map(|(i, (ty, value))| {
+            types::to_sql(value, ty)
+                .map_err(|e| QueryError::Param(format!("Invalid parameter ${}: {}", i + 1, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let param_refs: Vec<&(dyn ToSql + Sync)> = params
+        .iter()
+        .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+        .collect();
+
+    let rows = client.query(&stmt, &param_refs[..]).await?;
+    Ok(QueryResult::from(rows))
+}
+
+/// Runs the queries in order on one connection and commits when they all succeed.
+/// Queries with a savepoint roll back to it on a database error and the transaction
+/// goes on; any other failure rolls back the whole transaction.
+pub async fn transaction(
+    client: &mut deadpool_postgres::Client,
+    transaction: &Transaction,
+) -> Result<Value, String> {
+    let mut builder = client
+        .build_transaction()
+        .read_only(transaction.read_only)
+        .deferrable(transaction.deferrable);
+    if let Some(isolation) = transaction.isolation {
+        builder = builder.isolation_level(isolation);
+    }
+
+    let mut tx = match builder.start().await {
+        Ok(tx) => tx,
+        Err(e) => return Ok(outcome(Err(e))),
+    };
+
+    let mut results = Vec::new();
+
+    for (index, query) in transaction.queries.iter().enumerate() {
+        let result = match &query.savepoint {
+            Some(name) => {
+                let savepoint = match tx.savepoint(name).await {
+                    Ok(savepoint) => savepoint,
+                    Err(e) => return Ok(failed(outcome(Err(e)), index)),
+                };
+                match run(&savepoint, query).await {
+                    Ok(data) => savepoint.commit().await.map(|_| data),
+                    Err(QueryError::Database(e)) => {
+                        if let Err(e) = savepoint.rollback().await {
+                            return Ok(failed(outcome(Err(e)), index));
+                        }
+                        results.push(outcome(Err(e)));
+                        continue;
+                    }
+                    Err(e) => return Err(format!("queries[{}]: {}", index, e.message())),
+                }
+            }
+            None => match run(&tx, query).await {
+                Ok(data) => Ok(data),
+                Err(QueryError::Database(e)) => Err(e),
+                Err(e) => return Err(format!("queries[{}]: {}", index, e.message())),
+            },
+        };
+
+        match result {
+            Ok(data) => results.push(outcome(Ok(data))),
+            // Dropping the transaction rolls it back
+            Err(e) => return Ok(failed(outcome(Err(e)), index)),
+        }
+    }
+
+    Ok(match tx.commit().await {
+        Ok(_) => json!({ "success": true, "data": results.to_value() }),
+        Err(e) => outcome(Err(e)),
+    })
+}
+
+/// Adds the position of the query that failed the transaction to its error
+fn failed(response: Value, index: usize) -> Value {
+    let mut error = response.get("error").cloned().unwrap_or(Value::Null);
+    if let Value::Object(object) = &mut error {
+        object.insert("index".to_string(), index.to_value());
+    }
+    json!({ "success": false, "error": error })
+}
@@ -1,53 +1,53 @@
 use phlow_sdk::prelude::*;
-use tokio_postgres::types::ToSql;
+use tokio_postgres::IsolationLevel;
+use tokio_postgres::types::Type;
 
 use crate::postgres::PostgresConfig;
+use crate::types::parse_type;
 
 #[derive(Debug)]
-pub struct Input {
+pub struct Query {
     pub query: String,
-    pub params: Vec<Box<dyn ToSql + Sync + Send>>,
+    /// Converted once the statement is prepared, to the types the server expects
+    pub params: Vec<Value>,
+    /// Type hints from `types`, by parameter position
+    pub types: Vec<Option<Type>>,
     pub batch: bool,
     pub cache_query: bool,
+    /// Inside a transaction, runs the query in a savepoint so its failure is recorded
+    /// instead of rolling back the transaction
+    pub savepoint: Option<String>,
 }
 
-impl TryFrom<(Option<Value>, &PostgresConfig)> for Input {
-    type Error = String;
-
-    fn try_from((value, config): (Option<Value>, &PostgresConfig)) -> Result<Self, Self::Error> {
-        let value = value.ok_or_else(|| "Input value is None".to_string())?;
+impl Query {
+    fn parse(value: &Value, config: &PostgresConfig) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err("Query must be an object".to_string());
+        }
 
         let query = value
             .get("query")
-            .clone()
-            .and_then(|v| Some(v.to_string()))
-            .ok_or_else(|| "Query not found or not a string".to_string())?
-            .to_string();
-
-        let params = value
-            .get("params")
-            .clone()
-            .and_then(|v| v.as_array().cloned()) // <- clona os valores do array
-            .map(|arr| {
-                arr.into_iter()
-                    .map(|v| {
-                        if let Some(n) = v.to_i64() {
-                            if n >= i32::MIN as i64 && n <= i32::MAX as i64 {
-                                Ok::<_, String>(Box::new(n as i32) as Box<dyn ToSql + Sync + Send>)
-                            } else {
-                                Ok::<_, String>(Box::new(n) as Box<dyn ToSql + Sync + Send>)
-                            }
-                        } else if let Some(f) = v.to_f64() {
-                            Ok::<_, String>(Box::new(f) as Box<dyn ToSql + Sync + Send>)
-                        } else if let Some(b) = v.as_bool() {
-                            Ok::<_, String>(Box::new(b.clone()) as Box<dyn ToSql + Sync + Send>)
-                        } else {
-                            Ok::<_, String>(Box::new(v.to_string()) as Box<dyn ToSql + Sync + Send>)
-                        }
-                    })
-                    .collect::<Result<Vec<_>, _>>()
-            })
-            .unwrap_or_else(|| Ok(vec![]))?;
+            .map(|v| v.to_string())
+            .ok_or_else(|| "Query not found or not a string".to_string())?;
+
+        let params = match value.get("params") {
+            Some(Value::Array(array)) => array.values.clone(),
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => return Err("Params must be an array".to_string()),
+        };
+
+        let types = match value.get("types") {
+            Some(Value::Array(array)) => array
+                .values
+                .iter()
+                .map(|ty| match ty {
+                    Value::Null | Value::Undefined => Ok(None),
+                    ty => parse_type(&ty.to_string()).map(Some),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => return Err("Types must be an array".to_string()),
+        };
 
         let prepare_statements = *value
             .get("batch")
@@ -59,11 +59,407 @@ impl TryFrom<(Option<Value>, &PostgresConfig)> for Input {
             .and_then(Value::as_bool)
             .unwrap_or(&config.cache_query);
 
-        Ok(Input {
+        Ok(Query {
             query,
             params,
+            types,
             batch: prepare_statements,
             cache_query,
+            savepoint: None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Transaction {
+    pub queries: Vec<Query>,
+    pub isolation: Option<IsolationLevel>,
+    pub read_only: bool,
+    pub deferrable: bool,
+}
+
+impl Transaction {
+    fn parse(value: &Value, config: &PostgresConfig) -> Result<Self, String> {
+        let items = match value.get("queries") {
+            Some(Value::Array(array)) if !array.values.is_empty() => &array.values,
+            _ => return Err("Transaction needs a non-empty queries array".to_string()),
+        };
+
+        let mut queries = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let mut query =
+                Query::parse(item, config).map_err(|e| format!("queries[{}]: {}", index, e))?;
+            query.savepoint = match item.get("savepoint") {
+                Some(Value::Boolean(true)) => Some(format!("phlow_savepoint_{}", index)),
+                Some(Value::Boolean(false)) | Some(Value::Null) | None => None,
+                Some(name) => Some(name.to_string()),
+            };
+            queries.push(query);
+        }
+
+        let isolation = match value.get("isolation").map(Value::to_string) {
+            None => None,
+            Some(level) => Some(match level.to_lowercase().replace(' ', "_").as_str() {
+                "read_uncommitted" => IsolationLevel::ReadUncommitted,
+                "read_committed" => IsolationLevel::ReadCommitted,
+                "repeatable_read" => IsolationLevel::RepeatableRead,
+                "serializable" => IsolationLevel::Serializable,
+                _ => {
+                    return Err(format!(
+                        "Invalid isolation level: {}. Use read_uncommitted, read_committed, repeatable_read or serializable",
+                        level
+                    ));
+                }
+            }),
+        };
+
+        Ok(Transaction {
+            queries,
+            isolation,
+            read_only: *value
+                .get("read_only")
+                .and_then(Value::as_bool)
+                .unwrap_or(&false),
+            deferrable: *value
+                .get("deferrable")
+                .and_then(Value::as_bool)
+                .unwrap_or(&false),
+        })
+    }
+}
+
+/// Names that go into the SQL as is, like tables and columns, optionally schema qualified
+pub fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+fn identifier(value: &Value, key: &str) -> Result<String, String> {
+    let name = value
+        .get(key)
+        .map(Value::to_string)
+        .ok_or_else(|| format!("{} is required", key))?;
+    if !is_identifier(&name) {
+        return Err(format!("Invalid {}: {}", key, name));
+    }
+    Ok(name)
+}
+
+fn positive(value: &Value, key: &str, default: i64) -> Result<i64, String> {
+    match value.get(key) {
+        None | Some(Value::Null) => Ok(default),
+        Some(number) => match number.to_i64() {
+            Some(number) if number > 0 => Ok(number),
+            _ => Err(format!("{} must be a positive number", key)),
+        },
+    }
+}
+
+#[derive(Debug)]
+pub struct Cursor {
+    pub query: Query,
+    pub fetch_size: i64,
+}
+
+/// Keyset pagination: the rows after `after` in `key` order
+#[derive(Debug)]
+pub struct Page {
+    pub query: Query,
+    /// Column name only: the page filters and orders the query wrapped as a subquery,
+    /// where `u.id` is just `id`
+    pub key: String,
+    pub after: Value,
+    pub limit: i64,
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyFormat {
+    Csv,
+    Text,
+}
+
+impl CopyFormat {
+    fn parse(value: &Value) -> Result<Self, String> {
+        match value.get("format").map(Value::to_string).as_deref() {
+            None | Some("csv") => Ok(CopyFormat::Csv),
+            Some("text") => Ok(CopyFormat::Text),
+            Some(format) => Err(format!("Invalid format: {}. Use csv or text", format)),
+        }
+    }
+
+    /// Options of the COPY command. Only CSV has a header.
+    pub fn options(&self, header: bool) -> String {
+        match self {
+            CopyFormat::Csv => format!("FORMAT csv, HEADER {}", header),
+            CopyFormat::Text => "FORMAT text".to_string(),
+        }
+    }
+}
+
+/// Writes the rows of a query to a file with COPY TO
+#[derive(Debug)]
+pub struct CopyOut {
+    pub query: String,
+    pub path: String,
+    pub format: CopyFormat,
+    pub header: bool,
+}
+
+#[derive(Debug)]
+pub enum CopySource {
+    Rows(Vec<Value>),
+    File {
+        path: String,
+        format: CopyFormat,
+        header: bool,
+    },
+}
+
+/// Bulk loads a table with COPY FROM
+#[derive(Debug)]
+pub struct CopyIn {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub source: CopySource,
+}
+
+impl CopyIn {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let table = identifier(value, "table")?;
+
+        let columns = match value.get("columns") {
+            Some(Value::Array(array)) => array
+                .values
+                .iter()
+                .map(|column| {
+                    let column = column.to_string();
+                    if is_identifier(&column) {
+                        Ok(column)
+                    } else {
+                        Err(format!("Invalid column: {}", column))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => return Err("Columns must be an array".to_string()),
+        };
+
+        let header = *value
+            .get("header")
+            .and_then(Value::as_bool)
+            .unwrap_or(&true);
+
+        let source = match (value.get("rows"), value.get("path")) {
+            (Some(Value::Array(array)), None) => CopySource::Rows(array.values.clone()),
+            (None, Some(path)) => CopySource::File {
+                path: path.to_string(),
+                format: CopyFormat::parse(value)?,
+                header,
+            },
+            _ => return Err("copy_in needs either a rows array or a path".to_string()),
+        };
+
+        Ok(CopyIn {
+            table,
+            columns,
+            source,
         })
     }
 }
+
+#[derive(Debug)]
+pub enum Input {
+    Query(Query),
+    Transaction(Transaction),
+    /// Opens a server-side cursor and returns its first batch
+    Cursor(Cursor),
+    /// Next batch of an open cursor
+    Fetch(String),
+    Close(String),
+    Page(Page),
+    CopyOut(CopyOut),
+    CopyIn(CopyIn),
+}
+
+impl TryFrom<(Option<Value>, &PostgresConfig)> for Input {
+    type Error = String;
+
+    fn try_from((value, config): (Option<Value>, &PostgresConfig)) -> Result<Self, Self::Error> {
+        let value = value.ok_or_else(|| "Input value is None".to_string())?;
+        if !value.is_object() {
+            return Err("Input must be an object".to_string());
+        }
+
+        match value.get("action").map(Value::to_string).as_deref() {
+            None | Some("query") => Query::parse(&value, config).map(Input::Query),
+            Some("transaction") => Transaction::parse(&value, config).map(Input::Transaction),
+            Some("cursor") => Ok(Input::Cursor(Cursor {
+                query: Query::parse(&value, config)?,
+                fetch_size: positive(&value, "fetch_size", 1000)?,
+            })),
+            Some(action @ ("fetch" | "close")) => {
+                let cursor = value
+                    .get("cursor")
+                    .map(Value::to_string)
+                    .ok_or_else(|| "cursor is required".to_string())?;
+                Ok(if action == "fetch" {
+                    Input::Fetch(cursor)
+                } else {
+                    Input::Close(cursor)
+                })
+            }
+            Some("page") => Ok(Input::Page(Page {
+                query: Query::parse(&value, config)?,
+                key: identifier(&value, "key")?
+                    .rsplit('.')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                after: value.get("after").cloned().unwrap_or(Value::Null),
+                limit: positive(&value, "limit", 100)?,
+                desc: *value.get("desc").and_then(Value::as_bool).unwrap_or(&false),
+            })),
+            Some("copy_out") => Ok(Input::CopyOut(CopyOut {
+                query: value
+                    .get("query")
+                    .map(Value::to_string)
+                    .ok_or_else(|| "Query not found or not a string".to_string())?,
+                path: value
+                    .get("path")
+                    .map(Value::to_string)
+                    .ok_or_else(|| "path is required".to_string())?,
+                format: CopyFormat::parse(&value)?,
+                header: *value
+                    .get("header")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(&true),
+            })),
+            Some("copy_in") => CopyIn::parse(&value).map(Input::CopyIn),
+            Some(action) => Err(format!(
+                "Unknown action: {}. Use query, transaction, cursor, fetch, close, page, copy_out or copy_in",
+                action
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PostgresConfig {
+        PostgresConfig::try_from(Value::json_to_value("{}").unwrap()).unwrap()
+    }
+
+    fn parse(json: &str) -> Result<Input, String> {
+        Input::try_from((Some(Value::json_to_value(json).unwrap()), &config()))
+    }
+
+    #[test]
+    fn test_query() {
+        let input =
+            parse(r#"{ "query": "SELECT $1, $2", "params": [1, "a"], "types": [null, "uuid"] }"#)
+                .unwrap();
+        match input {
+            Input::Query(query) => {
+                assert_eq!(query.params.len(), 2);
+                assert_eq!(query.types, vec![None, Some(Type::UUID)]);
+                assert!(query.batch);
+            }
+            _ => panic!("expected a query"),
+        }
+    }
+
+    #[test]
+    fn test_transaction() {
+        let input = parse(
+            r#"{
+                "action": "transaction",
+                "isolation": "serializable",
+                "queries": [
+                    { "query": "INSERT INTO a VALUES ($1)", "params": [1] },
+                    { "query": "INSERT INTO b VALUES ($1)", "params": [2], "savepoint": true }
+                ]
+            }"#,
+        )
+        .unwrap();
+        match input {
+            Input::Transaction(transaction) => {
+                assert_eq!(transaction.queries.len(), 2);
+                assert!(matches!(
+                    transaction.isolation,
+                    Some(IsolationLevel::Serializable)
+                ));
+                assert_eq!(transaction.queries[0].savepoint, None);
+                assert_eq!(
+                    transaction.queries[1].savepoint.as_deref(),
+                    Some("phlow_savepoint_1")
+                );
+            }
+            _ => panic!("expected a transaction"),
+        }
+
+        assert_eq!(
+            parse(r#"{ "action": "transaction", "queries": [{ "params": [] }] }"#).unwrap_err(),
+            "queries[0]: Query not found or not a string"
+        );
+        assert_eq!(
+            parse(r#"{ "action": "transaction", "isolation": "snapshot", "queries": [{ "query": "SELECT 1" }] }"#)
+                .unwrap_err(),
+            "Invalid isolation level: snapshot. Use read_uncommitted, read_committed, repeatable_read or serializable"
+        );
+    }
+
+    #[test]
+    fn test_streaming_actions() {
+        match parse(
+            r#"{ "action": "page", "query": "SELECT * FROM users", "key": "id", "after": 10 }"#,
+        )
+        .unwrap()
+        {
+            Input::Page(page) => {
+                assert_eq!(page.key, "id");
+                assert_eq!(page.limit, 100);
+                assert_eq!(page.after.to_i64(), Some(10));
+            }
+            _ => panic!("expected a page"),
+        }
+
+        match parse(r#"{ "action": "page", "query": "SELECT u.id FROM users u", "key": "u.id" }"#)
+            .unwrap()
+        {
+            Input::Page(page) => assert_eq!(page.key, "id"),
+            _ => panic!("expected a page"),
+        }
+
+        match parse(r#"{ "action": "copy_in", "table": "public.users", "path": "users.csv" }"#)
+            .unwrap()
+        {
+            Input::CopyIn(copy) => {
+                assert_eq!(copy.table, "public.users");
+                assert!(matches!(
+                    copy.source,
+                    CopySource::File {
+                        format: CopyFormat::Csv,
+                        header: true,
+                        ..
+                    }
+                ));
+            }
+            _ => panic!("expected a copy_in"),
+        }
+
+        assert_eq!(
+            parse(r#"{ "action": "copy_in", "table": "users; DROP TABLE users", "rows": [] }"#)
+                .unwrap_err(),
+            "Invalid table: users; DROP TABLE users"
+        );
+        assert_eq!(
+            parse(r#"{ "action": "cursor", "query": "SELECT 1", "fetch_size": 0 }"#).unwrap_err(),
+            "fetch_size must be a positive number"
+        );
+    }
+}
@@ -1,27 +1,61 @@
+mod copy;
+mod cursor;
+mod execute;
 mod input;
+mod listen;
+mod migrations;
 mod postgres;
 mod response;
+mod types;
 use std::sync::Arc;
 
+use cursor::Cursors;
+use deadpool_postgres::Pool;
+use execute::{QueryError, respond};
 use input::Input;
 use phlow_sdk::prelude::*;
 use postgres::PostgresConfig;
-use response::QueryResult;
-use std::error::Error;
-use tokio_postgres::types::ToSql;
 
-create_step!(postgres(setup));
+create_main!(postgres(setup));
 
 pub async fn postgres(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let rx = module_channel!(setup);
     let config = PostgresConfig::try_from(setup.with.clone())?;
     let pool = Arc::new(config.create_pool()?);
+
+    // Migrations run before the module registers, so the flow only starts on an
+    // up-to-date schema and a failed migration stops the runtime
+    if let Some(migrations) = &config.migrations
+        && let Err(e) = migrations::run(&pool, migrations).await
+    {
+        log::error!("{}", e);
+        return Err(e.into());
+    }
+
+    // Without a sender, as with --migrate or in tests, postgres is only used as a step
+    if let Some(main_sender) = setup.main_sender.clone() {
+        log::info!("Main module started");
+        if config.listen.is_empty() {
+            return Err("listen is required when postgres is the main module".into());
+        }
+
+        tokio::task::spawn(listen::listen(
+            setup.id,
+            main_sender,
+            config.clone(),
+            setup.dispatch.clone(),
+        ));
+    }
+
+    let rx = module_channel!(setup);
     println!("Postgres module initialized with config: {:?}", config);
+    let cursors = Cursors::new(config.cursor_timeout);
+    cursors.expire();
     let mut handles = Vec::new();
 
     for package in rx {
         let pool = pool.clone();
         let config = config.clone();
+        let cursors = cursors.clone();
 
         let handle = tokio::spawn(async move {
             let input = match Input::try_from((package.input, &config)) {
@@ -35,98 +69,8 @@ pub async fn postgres(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Erro
                 }
             };
 
-            let client = match pool.get().await {
-                Ok(client) => client,
-                Err(e) => {
-                    let response = ModuleResponse::from_error(format!(
-                        "Failed to get client from pool: {}",
-                        e
-                    ));
-
-                    sender_safe!(package.sender, response.into());
-                    return;
-                }
-            };
-
-            if input.batch {
-                let stmt = if input.cache_query {
-                    match client.prepare_cached(input.query.as_str()).await {
-                        Ok(stmt) => stmt,
-                        Err(e) => {
-                            let response = ModuleResponse::from_error(format!(
-                                "Failed to prepare statement: {}",
-                                e
-                            ));
-
-                            sender_safe!(package.sender, response.into());
-                            return;
-                        }
-                    }
-                } else {
-                    match client.prepare(input.query.as_str()).await {
-                        Ok(stmt) => stmt,
-                        Err(e) => {
-                            let response = ModuleResponse::from_error(format!(
-                                "Failed to prepare statement: {}",
-                                e
-                            ));
-
-                            sender_safe!(package.sender, response.into());
-                            return;
-                        }
-                    }
-                };
-
-                let param_refs: Vec<&(dyn ToSql + Sync)> = input
-                    .params
-                    .iter()
-                    .map(|p| p.as_ref() as &(dyn ToSql + Sync))
-                    .collect();
-
-                match client.query(&stmt, &param_refs[..]).await {
-                    Ok(rows) => {
-                        let result = QueryResult::from(rows);
-                        let response: ModuleResponse = ModuleResponse::from_success(json!({
-                            "success": true,
-                            "data": result.to_value()
-                        }));
-
-                        sender_safe!(package.sender, response.into());
-                    }
-                    Err(e) => {
-                        let code = e.code().map(|c| c.code()).unwrap_or("UNKNOWN");
-                        let message = e
-                            .as_db_error()
-                            .map(|db| db.message().to_string())
-                            .unwrap_or_else(|| e.to_string());
-                        let cause = e.source().map(|s| s.to_string()).unwrap_or_default();
-
-                        let response = ModuleResponse::from_success(json!({
-                            "success": false,
-                            "error": {
-                                "code": code,
-                                "cause": cause,
-                                "message": message,
-                            }
-                        }));
-
-                        sender_safe!(package.sender, response.into());
-                        return;
-                    }
-                };
-            } else {
-                match client.batch_execute(&input.query).await {
-                    Ok(_) => {
-                        let response = "OK".to_value().into();
-                        sender_safe!(package.sender, response);
-                    }
-                    Err(e) => {
-                        let response =
-                            ModuleResponse::from_error(format!("Batch execution failed: {}", e));
-                        sender_safe!(package.sender, response.into());
-                    }
-                }
-            }
+            let response = handle(input, &pool, &cursors).await;
+            sender_safe!(package.sender, response);
         });
 
         handles.push(handle);
@@ -140,3 +84,85 @@ pub async fn postgres(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Erro
 
     Ok(())
 }
+
+async fn handle(input: Input, pool: &Pool, cursors: &Cursors) -> ModuleResponse {
+    // Cursors keep their own connections
+    let mut client = match &input {
+        Input::Cursor(cursor) => return respond(cursors.open(pool, cursor).await),
+        Input::Fetch(id) => return respond(cursors.fetch(id).await),
+        Input::Close(id) => return respond(cursors.close(id).await),
+        _ => match pool.get().await {
+            Ok(client) => client,
+            Err(e) => {
+                return ModuleResponse::from_error(format!(
+                    "Failed to get client from pool: {}",
+                    e
+                ));
+            }
+        },
+    };
+
+    match input {
+        Input::Query(query) => match execute::run(&client, &query).await {
+            Ok(data) if !query.batch => data.into(),
+            Err(QueryError::Database(e) | QueryError::Connection(e)) if !query.batch => {
+                ModuleResponse::from_error(format!("Batch execution failed: {}", e))
+            }
+            result => respond(result),
+        },
+        Input::Transaction(transaction) => {
+            match execute::transaction(&mut client, &transaction).await {
+                Ok(response) => ModuleResponse::from_success(response),
+                Err(e) => ModuleResponse::from_error(format!("Transaction rolled back: {}", e)),
+            }
+        }
+        Input::Page(page) => respond(cursor::page(&client, &page).await),
+        Input::CopyOut(copy) => respond(copy::copy_out(&client, &copy).await),
+        Input::CopyIn(copy) => respond(copy::copy_in(&client, &copy).await),
+        Input::Cursor(_) | Input::Fetch(_) | Input::Close(_) => {
+            ModuleResponse::from_error("Cursor actions do not use a pooled connection".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phlow_sdk::tokio;
+    use phlow_sdk::tokio::sync::oneshot;
+
+    /// Under `phlow --test` every module is in test mode without a main sender
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_registers_in_test_mode_without_listen() {
+        let (setup_sender, setup_receiver) = oneshot::channel();
+        let setup = ModuleSetup {
+            id: 0,
+            setup_sender,
+            main_sender: None,
+            with: json!({ "ssl_mode": "disable" }),
+            dispatch: phlow_sdk::tracing::Dispatch::default(),
+            app_data: ApplicationData {
+                name: None,
+                version: None,
+                environment: None,
+                description: None,
+                author: None,
+                license: None,
+                repository: None,
+                homepage: None,
+            },
+            is_test_mode: true,
+            step_ids: Vec::new(),
+            script_arg_index: None,
+            shutdown: ShutdownHooks::default(),
+        };
+
+        let module = tokio::spawn(postgres(setup));
+        let sender = setup_receiver.await.unwrap();
+        assert!(sender.is_some());
+
+        // Closing the channel ends the module loop
+        drop(sender);
+        assert!(module.await.unwrap().is_ok());
+    }
+}
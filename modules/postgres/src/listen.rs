@@ -0,0 +1,119 @@
+use phlow_sdk::prelude::*;
+use phlow_sdk::tracing::{Dispatch, Level, field};
+use std::time::Duration;
+use tokio_postgres::{AsyncMessage, Notification};
+
+use crate::postgres::PostgresConfig;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Notification as delivered to the flow. A payload that is valid JSON arrives
+/// parsed, any other payload as its text.
+fn notification_value(notification: &Notification) -> Value {
+    let payload = notification.payload();
+    let payload = Value::json_to_value(payload).unwrap_or_else(|_| payload.to_value());
+
+    json!({
+        "channel": notification.channel(),
+        "payload": payload,
+        "process_id": notification.process_id(),
+    })
+}
+
+/// Listens to the channels on a dedicated connection and runs the flow for each
+/// notification. When the connection drops it reconnects with an increasing delay
+/// and listens again; notifications sent while disconnected are lost.
+pub async fn listen(
+    id: ModuleId,
+    main_sender: MainRuntimeSender,
+    config: PostgresConfig,
+    dispatch: Dispatch,
+) {
+    let _guard = phlow_sdk::tracing::dispatcher::set_default(&dispatch);
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        match subscribe(&config).await {
+            Ok((client, mut messages)) => {
+                log::info!("Listening to {}", config.listen.join(", "));
+                backoff = Duration::from_secs(1);
+
+                while let Some(message) = messages.recv().await {
+                    match message {
+                        AsyncMessage::Notification(notification) => {
+                            deliver(id, main_sender.clone(), dispatch.clone(), notification)
+                        }
+                        AsyncMessage::Notice(notice) => {
+                            log::debug!("Postgres notice: {}", notice.message())
+                        }
+                        _ => {}
+                    }
+                }
+
+                // Kept until here, as dropping the client closes the connection
+                drop(client);
+                log::warn!("Lost the listen connection, reconnecting");
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to listen to {}: {}. Retrying in {}s",
+                    config.listen.join(", "),
+                    e,
+                    backoff.as_secs()
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+async fn subscribe(
+    config: &PostgresConfig,
+) -> Result<
+    (
+        tokio_postgres::Client,
+        tokio::sync::mpsc::UnboundedReceiver<AsyncMessage>,
+    ),
+    tokio_postgres::Error,
+> {
+    let (client, messages) = config.connect().await?;
+    let statements: Vec<String> = config
+        .listen
+        .iter()
+        .map(|channel| format!("LISTEN \"{}\";", channel))
+        .collect();
+    client.batch_execute(&statements.join(" ")).await?;
+    Ok((client, messages))
+}
+
+fn deliver(
+    id: ModuleId,
+    main_sender: MainRuntimeSender,
+    dispatch: Dispatch,
+    notification: Notification,
+) {
+    tokio::spawn(async move {
+        let _guard = phlow_sdk::tracing::dispatcher::set_default(&dispatch);
+
+        let span = tracing::span!(
+            Level::INFO,
+            "notification_receive",
+            "messaging.system" = "postgresql",
+            "messaging.destination.name" = notification.channel(),
+            "messaging.operation" = "receive",
+            "messaging.message.payload_size_bytes" = notification.payload().len(),
+            "messaging.message.conversation_id" = field::Empty,
+        );
+        span_enter!(span);
+        span.record("messaging.message.conversation_id", id.to_string());
+
+        let data = notification_value(&notification);
+        log::debug!("Received notification {:?}", data);
+
+        let response = sender_package!(span.clone(), dispatch.clone(), id, main_sender, Some(data))
+            .await
+            .unwrap_or(Value::Null);
+        log::debug!("Response: {:?}", response);
+    });
+}
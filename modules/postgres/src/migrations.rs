@@ -0,0 +1,136 @@
+use deadpool_postgres::Pool;
+use phlow_sdk::migrations::{Migration, MigrationsConfig, load, pending};
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Applies the pending migrations, each in its own transaction together with its
+/// history row. An advisory lock keeps concurrent instances from migrating at once.
+pub async fn run(pool: &Pool, config: &MigrationsConfig) -> Result<usize, String> {
+    let migrations = load(&config.path)?;
+    let mut client = pool
+        .get()
+        .await
+        .map_err(|e| format!("Failed to get client from pool: {}", e))?;
+
+    client
+        .execute("SELECT pg_advisory_lock(hashtext($1))", &[&config.table])
+        .await
+        .map_err(|e| format!("Failed to lock migrations: {}", e))?;
+
+    let result = apply(&mut client, config, &migrations).await;
+
+    if let Err(e) = client
+        .execute("SELECT pg_advisory_unlock(hashtext($1))", &[&config.table])
+        .await
+    {
+        log::warn!("Failed to unlock migrations: {}", e);
+    }
+
+    result
+}
+
+async fn apply(
+    client: &mut deadpool_postgres::Client,
+    config: &MigrationsConfig,
+    migrations: &[Migration],
+) -> Result<usize, String> {
+    let table = &config.table;
+
+    let exists: bool = client
+        .query_one("SELECT to_regclass($1) IS NOT NULL", &[table])
+        .await
+        .map_err(|e| e.to_string())?
+        .get(0);
+
+    if !exists && !config.dry_run {
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    version BIGINT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    checksum TEXT NOT NULL,
+                    applied_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                    duration_ms BIGINT NOT NULL
+                )",
+                table
+            ))
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", table, e))?;
+    }
+
+    let mut applied = HashMap::new();
+    if exists {
+        let rows = client
+            .query(
+                &format!("SELECT version, name, checksum FROM {}", table),
+                &[],
+            )
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+        for row in rows {
+            applied.insert(row.get::<_, i64>(0), (row.get(1), row.get(2)));
+        }
+    }
+
+    let pending = pending(migrations, &applied)?;
+    if pending.is_empty() {
+        log::info!("Migrations up to date");
+        return Ok(0);
+    }
+
+    if config.dry_run {
+        for migration in &pending {
+            log::info!(
+                "Pending migration {} ({})",
+                migration.version,
+                migration.name
+            );
+        }
+        return Ok(0);
+    }
+
+    let insert = format!(
+        "INSERT INTO {} (version, name, checksum, duration_ms) VALUES ($1, $2, $3, $4)",
+        table
+    );
+
+    for migration in &pending {
+        let started = Instant::now();
+        let failed = |e: tokio_postgres::Error| {
+            format!(
+                "Migration {} ({}) failed: {}",
+                migration.version,
+                migration.name,
+                e.as_db_error()
+                    .map(|db| db.message().to_string())
+                    .unwrap_or_else(|| e.to_string())
+            )
+        };
+
+        let tx = client.transaction().await.map_err(failed)?;
+        tx.batch_execute(&migration.sql).await.map_err(failed)?;
+        let duration = started.elapsed().as_millis() as i64;
+        tx.execute(
+            &insert,
+            &[
+                &migration.version,
+                &migration.name,
+                &migration.checksum,
+                &duration,
+            ],
+        )
+        .await
+        .map_err(failed)?;
+        tx.commit().await.map_err(failed)?;
+
+        log::info!(
+            "Applied migration {} ({}) in {}ms",
+            migration.version,
+            migration.name,
+            duration
+        );
+    }
+
+    Ok(pending.len())
+}
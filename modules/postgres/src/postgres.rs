@@ -1,12 +1,19 @@
 use deadpool_postgres::{Pool, Runtime};
+use futures::StreamExt;
 use phlow_sdk::prelude::*;
 use rustls::ClientConfig;
 use rustls::client::danger::{ServerCertVerified, ServerCertVerifier};
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
 use std::sync::Arc;
-use tokio_postgres::NoTls;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
+use tokio_postgres::{AsyncMessage, Client, Connection, NoTls};
 use tokio_postgres_rustls::MakeRustlsConnect;
 
+use crate::input::is_identifier;
+use phlow_sdk::migrations::MigrationsConfig;
+
 #[derive(Debug)]
 struct NoVerifier;
 
@@ -87,6 +94,11 @@ pub struct PostgresConfig {
     pub batch: bool,
     pub cache_query: bool,
     pub max_size: usize,
+    pub migrations: Option<MigrationsConfig>,
+    /// Idle time after which an open cursor is closed
+    pub cursor_timeout: Duration,
+    /// Channels the main module listens to
+    pub listen: Vec<String>,
 }
 
 impl PostgresConfig {
@@ -117,21 +129,80 @@ impl PostgresConfig {
             cfg.create_pool(Some(Runtime::Tokio1), NoTls)
                 .map_err(PostgresConfigError::PoolError)
         } else {
-            // Create rustls config that accepts any certificate
-            // This is necessary for some cloud providers like DigitalOcean
-            let tls_config = ClientConfig::builder()
-                .dangerous()
-                .with_custom_certificate_verifier(Arc::new(NoVerifier))
-                .with_no_client_auth();
+            cfg.create_pool(Some(Runtime::Tokio1), tls())
+                .map_err(PostgresConfigError::PoolError)
+        }
+    }
 
-            let tls = MakeRustlsConnect::new(tls_config);
+    /// Opens a connection outside the pool, for sessions that must outlive a step,
+    /// like the LISTEN of the main module. The receiver yields the notifications and
+    /// notices of the connection and ends when it closes.
+    pub async fn connect(
+        &self,
+    ) -> Result<(Client, mpsc::UnboundedReceiver<AsyncMessage>), tokio_postgres::Error> {
+        let mut cfg = tokio_postgres::Config::new();
+        cfg.host(&self.host)
+            .port(self.port)
+            .user(&self.user)
+            .password(&self.password)
+            .dbname(&self.database)
+            .ssl_mode(match self.ssl_mode.as_str() {
+                "require" => tokio_postgres::config::SslMode::Require,
+                "disable" => tokio_postgres::config::SslMode::Disable,
+                _ => tokio_postgres::config::SslMode::Prefer,
+            });
 
-            cfg.create_pool(Some(Runtime::Tokio1), tls)
-                .map_err(PostgresConfigError::PoolError)
+        if self.ssl_mode == "disable" {
+            let (client, connection) = cfg.connect(NoTls).await?;
+            Ok((client, drive(connection)))
+        } else {
+            let (client, connection) = cfg.connect(tls()).await?;
+            Ok((client, drive(connection)))
         }
     }
 }
 
+/// Creates a rustls config that accepts any certificate.
+/// This is necessary for some cloud providers like DigitalOcean
+fn tls() -> MakeRustlsConnect {
+    let tls_config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoVerifier))
+        .with_no_client_auth();
+
+    MakeRustlsConnect::new(tls_config)
+}
+
+/// Polls the connection on its own task, forwarding its asynchronous messages
+fn drive<S, T>(mut connection: Connection<S, T>) -> mpsc::UnboundedReceiver<AsyncMessage>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(message) => {
+                    if tx.send(message).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let reason = e
+                        .as_db_error()
+                        .map(|db| db.message().to_string())
+                        .unwrap_or_else(|| e.to_string());
+                    log::warn!("Postgres connection closed: {}", reason);
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
 impl TryFrom<Value> for PostgresConfig {
     type Error = String;
 
@@ -181,6 +252,30 @@ impl TryFrom<Value> for PostgresConfig {
             .and_then(Value::to_i64)
             .unwrap_or(10) as usize;
 
+        let migrations = match value.get("migrations") {
+            None | Some(Value::Null) => None,
+            Some(migrations) => Some(MigrationsConfig::try_from(migrations)?),
+        };
+
+        let cursor_timeout = Duration::from_secs(
+            value
+                .get("cursor_timeout")
+                .and_then(Value::to_u64)
+                .unwrap_or(60),
+        );
+
+        let listen = match value.get("listen") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(channels)) => channels.values.iter().map(Value::to_string).collect(),
+            Some(channel) => vec![channel.to_string()],
+        };
+        if let Some(channel) = listen
+            .iter()
+            .find(|channel| channel.contains('.') || !is_identifier(channel))
+        {
+            return Err(format!("Invalid listen channel: {}", channel));
+        }
+
         Ok(PostgresConfig {
             host,
             port,
@@ -191,6 +286,9 @@ impl TryFrom<Value> for PostgresConfig {
             cache_query,
             ssl_mode,
             max_size,
+            migrations,
+            cursor_timeout,
+            listen,
         })
     }
 }
@@ -1,7 +1,99 @@
 use std::collections::HashMap;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use phlow_sdk::prelude::*;
-use tokio_postgres::{types::Type, Row};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use tokio_postgres::types::{FromSql, Kind};
+use tokio_postgres::{Row, types::Type};
+
+use crate::types::RawText;
+
+fn get<'a, T, F>(row: &'a Row, i: usize, convert: F) -> Value
+where
+    T: FromSql<'a>,
+    F: Fn(T) -> Value,
+{
+    row.try_get::<_, Option<T>>(i)
+        .ok()
+        .flatten()
+        .map_or(Value::Null, convert)
+}
+
+fn json(value: serde_json::Value) -> Value {
+    Value::json_to_value(&value.to_string()).unwrap_or(Value::Null)
+}
+
+fn decimal(value: Decimal) -> Value {
+    value.to_f64().map_or(Value::Null, Value::from)
+}
+
+/// Converts a column to a value. NUMERIC becomes a float, timestamps and dates their
+/// text, JSON a value and BYTEA base64.
+fn column_value(row: &Row, i: usize, ty: &Type) -> Value {
+    if let Kind::Array(member) = ty.kind() {
+        return array_value(row, i, member);
+    }
+
+    if matches!(ty.kind(), Kind::Enum(_)) {
+        return get(row, i, |v: RawText| Value::from(v.0));
+    }
+
+    match *ty {
+        Type::INT2 => get(row, i, |v: i16| Value::from(v)),
+        Type::INT4 => get(row, i, |v: i32| Value::from(v)),
+        Type::INT8 => get(row, i, |v: i64| Value::from(v)),
+        Type::OID => get(row, i, |v: u32| Value::from(v)),
+        Type::FLOAT4 => get(row, i, |v: f32| Value::from(v)),
+        Type::FLOAT8 => get(row, i, |v: f64| Value::from(v)),
+        Type::NUMERIC => get(row, i, decimal),
+        Type::BOOL => get(row, i, |v: bool| Value::from(v)),
+        Type::UUID => get(row, i, |v: uuid::Uuid| Value::from(v.to_string())),
+        Type::JSON | Type::JSONB => get(row, i, json),
+        Type::DATE => get(row, i, |v: NaiveDate| Value::from(v.to_string())),
+        Type::TIME => get(row, i, |v: NaiveTime| Value::from(v.to_string())),
+        Type::TIMESTAMP => get(row, i, |v: NaiveDateTime| Value::from(v.to_string())),
+        Type::TIMESTAMPTZ => get(row, i, |v: DateTime<Utc>| Value::from(v.to_rfc3339())),
+        Type::BYTEA => get(row, i, |v: Vec<u8>| Value::from(BASE64.encode(v))),
+        _ => get(row, i, |v: String| Value::from(v)),
+    }
+}
+
+fn array_value(row: &Row, i: usize, member: &Type) -> Value {
+    fn items<T, F: Fn(T) -> Value>(items: Vec<Option<T>>, convert: F) -> Value {
+        items
+            .into_iter()
+            .map(|item| item.map_or(Value::Null, &convert))
+            .collect::<Vec<_>>()
+            .to_value()
+    }
+
+    match *member {
+        Type::INT2 => get(row, i, |v: Vec<Option<i16>>| items(v, Value::from)),
+        Type::INT4 => get(row, i, |v: Vec<Option<i32>>| items(v, Value::from)),
+        Type::INT8 => get(row, i, |v: Vec<Option<i64>>| items(v, Value::from)),
+        Type::FLOAT4 => get(row, i, |v: Vec<Option<f32>>| items(v, Value::from)),
+        Type::FLOAT8 => get(row, i, |v: Vec<Option<f64>>| items(v, Value::from)),
+        Type::NUMERIC => get(row, i, |v: Vec<Option<Decimal>>| items(v, decimal)),
+        Type::BOOL => get(row, i, |v: Vec<Option<bool>>| items(v, Value::from)),
+        Type::UUID => get(row, i, |v: Vec<Option<uuid::Uuid>>| {
+            items(v, |v| Value::from(v.to_string()))
+        }),
+        Type::JSON | Type::JSONB => get(row, i, |v: Vec<Option<serde_json::Value>>| items(v, json)),
+        Type::DATE => get(row, i, |v: Vec<Option<NaiveDate>>| {
+            items(v, |v| Value::from(v.to_string()))
+        }),
+        Type::TIMESTAMP => get(row, i, |v: Vec<Option<NaiveDateTime>>| {
+            items(v, |v| Value::from(v.to_string()))
+        }),
+        Type::TIMESTAMPTZ => get(row, i, |v: Vec<Option<DateTime<Utc>>>| {
+            items(v, |v| Value::from(v.to_rfc3339()))
+        }),
+        _ => get(row, i, |v: Vec<Option<String>>| items(v, Value::from)),
+    }
+}
 
 #[derive(Debug, Clone, ToValue)]
 pub struct QueryResult {
@@ -22,22 +114,7 @@ impl From<Vec<Row>> for QueryResult {
             for (i, column) in row.columns().iter().enumerate() {
                 let name = column.name();
 
-                let value = match column.type_() {
-                    &Type::INT2 => row.try_get::<_, i16>(i).map_or(Value::Null, Value::from),
-                    &Type::INT4 => row.try_get::<_, i32>(i).map_or(Value::Null, Value::from),
-                    &Type::INT8 => row.try_get::<_, i64>(i).map_or(Value::Null, Value::from),
-                    &Type::FLOAT4 => row.try_get::<_, f32>(i).map_or(Value::Null, Value::from),
-                    &Type::FLOAT8 => row.try_get::<_, f64>(i).map_or(Value::Null, Value::from),
-                    &Type::NUMERIC => row.try_get::<_, f64>(i).map_or(Value::Null, Value::from),
-                    &Type::BOOL => row.try_get::<_, bool>(i).map_or(Value::Null, Value::from),
-                    &Type::DATE => row
-                        .try_get::<_, chrono::NaiveDate>(i)
-                        .map_or(Value::Null, |v| Value::from(v.to_string())),
-                    &Type::TIMESTAMP | &Type::TIMESTAMPTZ => row
-                        .try_get::<_, chrono::NaiveDateTime>(i)
-                        .map_or(Value::Null, |v| Value::from(v.to_string())),
-                    _ => row.try_get::<_, String>(i).map_or(Value::Null, Value::from),
-                };
+                let value = column_value(&row, i, column.type_());
 
                 map.insert(name.to_string(), value.to_value());
             }
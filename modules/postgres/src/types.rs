@@ -0,0 +1,344 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bytes::BytesMut;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use phlow_sdk::prelude::*;
+use postgres_protocol::types::{self as protocol, ArrayDimension};
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::str::FromStr;
+use tokio_postgres::types::{FromSql, IsNull, Kind, ToSql, Type, to_sql_checked};
+
+pub type Param = Box<dyn ToSql + Sync + Send>;
+
+/// Placeholder for parameters without a type hint. OID 0 lets the server infer the type.
+pub fn unspecified() -> Type {
+    Type::new(
+        "unspecified".to_string(),
+        0,
+        Kind::Pseudo,
+        "pg_catalog".to_string(),
+    )
+}
+
+/// Type named by a hint in `types`, e.g. `uuid`, `jsonb` or `int8[]`
+pub fn parse_type(name: &str) -> Result<Type, String> {
+    let name = name.trim().to_lowercase();
+    let (base, array) = match name.strip_suffix("[]") {
+        Some(base) => (base.trim(), true),
+        None => (name.as_str(), false),
+    };
+
+    let (ty, array_ty) = match base {
+        "bool" | "boolean" => (Type::BOOL, Type::BOOL_ARRAY),
+        "int2" | "smallint" => (Type::INT2, Type::INT2_ARRAY),
+        "int4" | "int" | "integer" => (Type::INT4, Type::INT4_ARRAY),
+        "int8" | "bigint" => (Type::INT8, Type::INT8_ARRAY),
+        "float4" | "real" => (Type::FLOAT4, Type::FLOAT4_ARRAY),
+        "float8" | "double" | "double precision" => (Type::FLOAT8, Type::FLOAT8_ARRAY),
+        "numeric" | "decimal" => (Type::NUMERIC, Type::NUMERIC_ARRAY),
+        "text" => (Type::TEXT, Type::TEXT_ARRAY),
+        "varchar" => (Type::VARCHAR, Type::VARCHAR_ARRAY),
+        "uuid" => (Type::UUID, Type::UUID_ARRAY),
+        "json" => (Type::JSON, Type::JSON_ARRAY),
+        "jsonb" => (Type::JSONB, Type::JSONB_ARRAY),
+        "date" => (Type::DATE, Type::DATE_ARRAY),
+        "time" => (Type::TIME, Type::TIME_ARRAY),
+        "timestamp" => (Type::TIMESTAMP, Type::TIMESTAMP_ARRAY),
+        "timestamptz" => (Type::TIMESTAMPTZ, Type::TIMESTAMPTZ_ARRAY),
+        "bytea" => (Type::BYTEA, Type::BYTEA_ARRAY),
+        _ => return Err(format!("Unknown parameter type: {}", name)),
+    };
+
+    Ok(if array { array_ty } else { ty })
+}
+
+/// SQL NULL for a parameter of any type
+#[derive(Debug)]
+struct Null;
+
+impl ToSql for Null {
+    fn to_sql(&self, _: &Type, _: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        Ok(IsNull::Yes)
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// Text sent as is, for types whose binary format is their text, like enums
+#[derive(Debug)]
+pub struct RawText(pub String);
+
+impl ToSql for RawText {
+    fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        out.extend_from_slice(self.0.as_bytes());
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty.kind(), Kind::Enum(_)) || ty.name() == "citext"
+    }
+
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for RawText {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(RawText(String::from_utf8(raw.to_vec())?))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty.kind(), Kind::Enum(_)) || ty.name() == "citext"
+    }
+}
+
+/// One-dimensional array of converted elements
+#[derive(Debug)]
+struct Array(Vec<Param>);
+
+impl ToSql for Array {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let member = match ty.kind() {
+            Kind::Array(member) => member,
+            _ => return Err(format!("{} is not an array type", ty).into()),
+        };
+
+        let dimension = ArrayDimension {
+            len: i32::try_from(self.0.len())?,
+            lower_bound: 1,
+        };
+
+        protocol::array_to_sql(
+            Some(dimension),
+            member.oid(),
+            self.0.iter(),
+            |element, out| match element.to_sql_checked(member, out)? {
+                IsNull::No => Ok(postgres_protocol::IsNull::No),
+                IsNull::Yes => Ok(postgres_protocol::IsNull::Yes),
+            },
+            out,
+        )?;
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty.kind(), Kind::Array(_))
+    }
+
+    to_sql_checked!();
+}
+
+fn parse<T: FromStr>(value: &Value, ty: &Type) -> Result<T, String> {
+    value
+        .to_string()
+        .trim()
+        .parse::<T>()
+        .map_err(|_| format!("expected {}, found {}", ty, value))
+}
+
+fn integer(value: &Value, ty: &Type, min: i64, max: i64) -> Result<i64, String> {
+    let number = match value.to_i64() {
+        Some(number) if value.is_number() => number,
+        _ => parse::<i64>(value, ty)?,
+    };
+
+    if number < min || number > max {
+        return Err(format!("{} is out of range for {}", number, ty));
+    }
+    Ok(number)
+}
+
+fn timestamp(value: &Value, ty: &Type) -> Result<NaiveDateTime, String> {
+    let text = value.to_string();
+    let text = text.trim();
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Ok(datetime.naive_utc());
+    }
+
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| format!("expected {}, found {}", ty, value))
+}
+
+fn json(value: &Value) -> Result<serde_json::Value, String> {
+    // Strings holding a JSON object or array are sent as that document
+    let text = match value {
+        Value::String(string) => {
+            let trimmed = string.as_str().trim();
+            if (trimmed.starts_with('{') || trimmed.starts_with('['))
+                && let Ok(document) = serde_json::from_str(trimmed)
+            {
+                return Ok(document);
+            }
+            return Ok(serde_json::Value::String(string.as_string()));
+        }
+        value => value.to_json(JsonMode::Inline),
+    };
+
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// Converts a value to the Rust type that binds to the parameter type `ty`
+pub fn to_sql(value: &Value, ty: &Type) -> Result<Param, String> {
+    if matches!(value, Value::Null | Value::Undefined) {
+        return Ok(Box::new(Null));
+    }
+
+    if let Kind::Array(member) = ty.kind() {
+        return match value {
+            Value::Array(array) => Ok(Box::new(Array(
+                array
+                    .values
+                    .iter()
+                    .map(|item| to_sql(item, member))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ))),
+            _ => Err(format!("expected an array for {}, found {}", ty, value)),
+        };
+    }
+
+    if <RawText as ToSql>::accepts(ty) {
+        return Ok(Box::new(RawText(value.to_string())));
+    }
+
+    let param: Param = match *ty {
+        Type::BOOL => match value.as_bool() {
+            Some(boolean) => Box::new(*boolean),
+            None => Box::new(parse::<bool>(value, ty)?),
+        },
+        Type::INT2 => Box::new(integer(value, ty, i16::MIN as i64, i16::MAX as i64)? as i16),
+        Type::INT4 => Box::new(integer(value, ty, i32::MIN as i64, i32::MAX as i64)? as i32),
+        Type::INT8 | Type::OID => Box::new(integer(value, ty, i64::MIN, i64::MAX)?),
+        Type::FLOAT4 => Box::new(match value.to_f64() {
+            Some(number) if value.is_number() => number as f32,
+            _ => parse::<f32>(value, ty)?,
+        }),
+        Type::FLOAT8 => Box::new(match value.to_f64() {
+            Some(number) if value.is_number() => number,
+            _ => parse::<f64>(value, ty)?,
+        }),
+        Type::NUMERIC => Box::new(parse::<Decimal>(value, ty)?),
+        Type::UUID => Box::new(parse::<uuid::Uuid>(value, ty)?),
+        Type::JSON | Type::JSONB => Box::new(json(value)?),
+        Type::DATE => Box::new(
+            NaiveDate::parse_from_str(value.to_string().trim(), "%Y-%m-%d")
+                .map_err(|_| format!("expected {}, found {}", ty, value))?,
+        ),
+        Type::TIME => Box::new(parse::<NaiveTime>(value, ty)?),
+        Type::TIMESTAMP => Box::new(timestamp(value, ty)?),
+        Type::TIMESTAMPTZ => Box::new(DateTime::<Utc>::from_naive_utc_and_offset(
+            timestamp(value, ty)?,
+            Utc,
+        )),
+        Type::BYTEA => Box::new(
+            BASE64
+                .decode(value.to_string().trim())
+                .map_err(|e| format!("expected base64 for {}: {}", ty, e))?,
+        ),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+            Box::new(match value {
+                Value::Object(_) | Value::Array(_) => value.to_json(JsonMode::Inline),
+                value => value.to_string(),
+            })
+        }
+        _ => {
+            return Err(format!(
+                "unsupported type {}. Cast the parameter in the query, e.g. $1::text::{}",
+                ty, ty
+            ));
+        }
+    };
+
+    Ok(param)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: &Value, ty: &Type) -> Result<Vec<u8>, String> {
+        let param = to_sql(value, ty)?;
+        let mut out = BytesMut::new();
+        param
+            .to_sql_checked(ty, &mut out)
+            .map_err(|e| e.to_string())?;
+        Ok(out.to_vec())
+    }
+
+    #[test]
+    fn test_parse_type() {
+        assert_eq!(parse_type("UUID").unwrap(), Type::UUID);
+        assert_eq!(parse_type("bigint[]").unwrap(), Type::INT8_ARRAY);
+        assert_eq!(
+            parse_type("money").unwrap_err(),
+            "Unknown parameter type: money"
+        );
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(
+            encode(&Value::from(7i64), &Type::INT2).unwrap(),
+            7i16.to_be_bytes()
+        );
+        assert_eq!(
+            encode(&"42".to_value(), &Type::INT8).unwrap(),
+            42i64.to_be_bytes()
+        );
+        assert_eq!(
+            encode(&Value::from(70000i64), &Type::INT2).unwrap_err(),
+            "70000 is out of range for int2"
+        );
+        assert!(encode(&"12.50".to_value(), &Type::NUMERIC).is_ok());
+        assert!(encode(&"2024-05-01T10:00:00Z".to_value(), &Type::TIMESTAMPTZ).is_ok());
+        assert!(encode(&"2024-05-01 10:00:00".to_value(), &Type::TIMESTAMP).is_ok());
+        assert_eq!(
+            encode(&"not-a-uuid".to_value(), &Type::UUID).unwrap_err(),
+            "expected uuid, found not-a-uuid"
+        );
+    }
+
+    #[test]
+    fn test_json_and_arrays() {
+        let object = Value::json_to_value(r#"{"a": 1}"#).unwrap();
+        assert_eq!(json(&object).unwrap(), serde_json::json!({"a": 1}));
+        assert_eq!(
+            json(&r#"{"a": 1}"#.to_value()).unwrap(),
+            serde_json::json!({"a": 1})
+        );
+        assert_eq!(
+            json(&"hello".to_value()).unwrap(),
+            serde_json::json!("hello")
+        );
+
+        let array = Value::json_to_value("[1, null, 3]").unwrap();
+        assert!(encode(&array, &Type::INT4_ARRAY).is_ok());
+        assert!(encode(&Value::from(1i64), &Type::INT4_ARRAY).is_err());
+    }
+
+    #[test]
+    fn test_null() {
+        let param = to_sql(&Value::Null, &Type::UUID).unwrap();
+        let mut out = BytesMut::new();
+        assert!(matches!(
+            param.to_sql_checked(&Type::UUID, &mut out).unwrap(),
+            IsNull::Yes
+        ));
+    }
+}
@@ -0,0 +1,406 @@
+use phlow_sdk::prelude::*;
+use redis::aio::ConnectionManager;
+use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, Client, ExistenceCheck, SetExpiry, SetOptions};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::input::RedisInput;
+use crate::setup::Config;
+
+/// Interval between attempts while a lock waits to be released
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Releases the lock only when it still holds the caller's token, so a lock that
+/// expired and was taken by someone else is not released by mistake
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+pub struct Redis {
+    pub client: Client,
+    pub connection: ConnectionManager,
+    pub config: Config,
+}
+
+/// Strings are stored as they are, so other clients can read them; other values as JSON
+pub fn encode(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.as_string(),
+        value => value.to_json(JsonMode::Inline),
+    }
+}
+
+/// Values are parsed when they are JSON, and returned as strings otherwise
+pub fn decode(text: &str) -> Value {
+    Value::json_to_value(text).unwrap_or_else(|_| text.to_value())
+}
+
+fn fields(value: &Value) -> Vec<(String, String)> {
+    match value {
+        Value::Object(object) => object
+            .iter()
+            .map(|(field, value)| (field.to_string(), encode(value)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn response(entries: Vec<(&str, Value)>) -> Value {
+    let mut map: HashMap<&str, Value> = HashMap::from([("success", true.to_value())]);
+    map.extend(entries);
+    map.to_value()
+}
+
+fn to_error(e: redis::RedisError) -> String {
+    format!("Redis error: {}", e)
+}
+
+impl Redis {
+    pub async fn execute(&self, input: RedisInput) -> Result<Value, String> {
+        let mut con = self.connection.clone();
+
+        match input {
+            RedisInput::Set { key, value, ttl } => {
+                let name = self.config.key(&key);
+                let payload = encode(&value);
+                match ttl.or(self.config.default_ttl) {
+                    Some(ttl) => con.set_ex::<_, _, ()>(&name, payload, ttl).await,
+                    None => con.set::<_, _, ()>(&name, payload).await,
+                }
+                .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("cached", true.to_value()),
+                ]))
+            }
+            RedisInput::Get { key } => {
+                let value: Option<String> =
+                    con.get(self.config.key(&key)).await.map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("found", value.is_some().to_value()),
+                    ("key", key.to_value()),
+                    ("value", value.as_deref().map(decode).unwrap_or(Value::Null)),
+                ]))
+            }
+            RedisInput::Remove { key } => {
+                let removed: u64 = con.del(self.config.key(&key)).await.map_err(to_error)?;
+
+                let mut entries = vec![
+                    ("key", key.to_value()),
+                    ("removed", (removed > 0).to_value()),
+                ];
+                if removed == 0 {
+                    entries.push(("error", "Key not found".to_value()));
+                }
+                Ok(response(entries))
+            }
+            RedisInput::Exists { key } => {
+                let found: bool = con.exists(self.config.key(&key)).await.map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("found", found.to_value()),
+                ]))
+            }
+            RedisInput::Ttl { key } => {
+                // -2: the key does not exist, -1: it never expires
+                let ttl: i64 = con.ttl(self.config.key(&key)).await.map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("found", (ttl != -2).to_value()),
+                    (
+                        "ttl",
+                        if ttl >= 0 {
+                            ttl.to_value()
+                        } else {
+                            Value::Null
+                        },
+                    ),
+                ]))
+            }
+            RedisInput::Expire { key, ttl } => {
+                let updated: bool = con
+                    .expire(self.config.key(&key), ttl as i64)
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("found", updated.to_value()),
+                ]))
+            }
+            RedisInput::Incr { key, by, ttl } => {
+                let name = self.config.key(&key);
+                let value: i64 = con.incr(&name, by).await.map_err(to_error)?;
+
+                // The ttl starts with the key, making fixed windows such as rate limits
+                if let Some(ttl) = ttl.filter(|_| value == by) {
+                    con.expire::<_, ()>(&name, ttl as i64)
+                        .await
+                        .map_err(to_error)?;
+                }
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("value", value.to_value()),
+                ]))
+            }
+            RedisInput::HSet {
+                key,
+                fields: values,
+            } => {
+                let added: u64 = redis::cmd("HSET")
+                    .arg(self.config.key(&key))
+                    .arg(fields(&values))
+                    .query_async(&mut con)
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("added", added.to_value()),
+                ]))
+            }
+            RedisInput::HGet { key, field } => {
+                let value: Option<String> = con
+                    .hget(self.config.key(&key), &field)
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("found", value.is_some().to_value()),
+                    ("key", key.to_value()),
+                    ("field", field.to_value()),
+                    ("value", value.as_deref().map(decode).unwrap_or(Value::Null)),
+                ]))
+            }
+            RedisInput::HGetAll { key } => {
+                let values: HashMap<String, String> =
+                    con.hgetall(self.config.key(&key)).await.map_err(to_error)?;
+                let value = values
+                    .iter()
+                    .map(|(field, value)| (field.clone(), decode(value)))
+                    .collect::<HashMap<String, Value>>();
+
+                Ok(response(vec![
+                    ("found", (!value.is_empty()).to_value()),
+                    ("key", key.to_value()),
+                    ("value", value.to_value()),
+                ]))
+            }
+            RedisInput::HDel { key, fields } => {
+                let removed: u64 = con
+                    .hdel(self.config.key(&key), &fields)
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("removed", removed.to_value()),
+                ]))
+            }
+            RedisInput::Publish { channel, message } => {
+                let receivers: u64 = con
+                    .publish(&channel, encode(&message))
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("channel", channel.to_value()),
+                    ("receivers", receivers.to_value()),
+                ]))
+            }
+            RedisInput::XAdd {
+                stream,
+                fields: values,
+                maxlen,
+            } => {
+                let name = self.config.key(&stream);
+                let items = fields(&values);
+                let id: String = match maxlen {
+                    Some(maxlen) => {
+                        con.xadd_maxlen(&name, StreamMaxlen::Approx(maxlen as usize), "*", &items)
+                            .await
+                    }
+                    None => con.xadd(&name, "*", &items).await,
+                }
+                .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("stream", stream.to_value()),
+                    ("id", id.to_value()),
+                ]))
+            }
+            RedisInput::XGroupCreate {
+                stream,
+                group,
+                start,
+            } => {
+                let created = match con
+                    .xgroup_create_mkstream::<_, _, _, ()>(self.config.key(&stream), &group, &start)
+                    .await
+                {
+                    Ok(()) => true,
+                    Err(e) if e.code() == Some("BUSYGROUP") => false,
+                    Err(e) => return Err(to_error(e)),
+                };
+
+                Ok(response(vec![
+                    ("stream", stream.to_value()),
+                    ("group", group.to_value()),
+                    ("created", created.to_value()),
+                ]))
+            }
+            RedisInput::XReadGroup {
+                stream,
+                group,
+                consumer,
+                count,
+                block,
+                id,
+            } => {
+                let mut options = StreamReadOptions::default()
+                    .group(&group, &consumer)
+                    .count(count as usize);
+                let name = self.config.key(&stream);
+
+                let reply: Option<StreamReadReply> = match block {
+                    // A blocking read would hold up every command sharing the connection
+                    Some(block) => {
+                        options = options.block(block as usize);
+                        let mut dedicated = self
+                            .client
+                            .get_multiplexed_async_connection()
+                            .await
+                            .map_err(to_error)?;
+                        dedicated.xread_options(&[&name], &[&id], &options).await
+                    }
+                    None => con.xread_options(&[&name], &[&id], &options).await,
+                }
+                .map_err(to_error)?;
+
+                let messages: Vec<Value> = reply
+                    .map(|reply| reply.keys)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .flat_map(|key| key.ids)
+                    .map(|entry| {
+                        let fields = entry
+                            .map
+                            .iter()
+                            .map(|(field, value)| {
+                                let text: String =
+                                    redis::from_redis_value(value).unwrap_or_default();
+                                (field.clone(), decode(&text))
+                            })
+                            .collect::<HashMap<String, Value>>();
+
+                        HashMap::from([("id", entry.id.to_value()), ("fields", fields.to_value())])
+                            .to_value()
+                    })
+                    .collect();
+
+                Ok(response(vec![
+                    ("stream", stream.to_value()),
+                    ("count", messages.len().to_value()),
+                    ("messages", messages.to_value()),
+                ]))
+            }
+            RedisInput::XAck { stream, group, ids } => {
+                let acked: u64 = con
+                    .xack(self.config.key(&stream), &group, &ids)
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("stream", stream.to_value()),
+                    ("acked", acked.to_value()),
+                ]))
+            }
+            RedisInput::Lock { key, ttl, wait } => {
+                let name = self.config.key(&key);
+                let token = uuid::Uuid::new_v4().to_string();
+                let options = SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(ttl));
+                let deadline = Instant::now() + Duration::from_millis(wait);
+
+                let acquired = loop {
+                    let set: Option<String> = con
+                        .set_options(&name, &token, options)
+                        .await
+                        .map_err(to_error)?;
+                    if set.is_some() {
+                        break true;
+                    }
+                    if Instant::now() >= deadline {
+                        break false;
+                    }
+                    tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+                };
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("acquired", acquired.to_value()),
+                    (
+                        "token",
+                        if acquired {
+                            token.to_value()
+                        } else {
+                            Value::Null
+                        },
+                    ),
+                ]))
+            }
+            RedisInput::Unlock { key, token } => {
+                let released: u64 = redis::Script::new(UNLOCK_SCRIPT)
+                    .key(self.config.key(&key))
+                    .arg(&token)
+                    .invoke_async(&mut con)
+                    .await
+                    .map_err(to_error)?;
+
+                Ok(response(vec![
+                    ("key", key.to_value()),
+                    ("released", (released > 0).to_value()),
+                ]))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(encode(&"online".to_value()), "online");
+        assert_eq!(decode("online"), "online".to_value());
+
+        let value = Value::json_to_value(r#"{ "id": 1, "tags": ["a"] }"#).unwrap();
+        assert_eq!(decode(&encode(&value)), value);
+        assert_eq!(decode(&encode(&42.to_value())), 42.to_value());
+    }
+
+    #[test]
+    fn test_fields() {
+        let value = Value::json_to_value(r#"{ "name": "Ana", "age": 30 }"#).unwrap();
+        let mut items = fields(&value);
+        items.sort();
+        assert_eq!(
+            items,
+            vec![
+                ("age".to_string(), "30".to_string()),
+                ("name".to_string(), "Ana".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use phlow_sdk::prelude::*;
+
+/// Redis input actions. set, get, remove and exists take the same input as the cache module.
+#[derive(Debug, Clone)]
+pub enum RedisInput {
+    Set {
+        key: String,
+        value: Value,
+        ttl: Option<u64>,
+    },
+    Get {
+        key: String,
+    },
+    Remove {
+        key: String,
+    },
+    Exists {
+        key: String,
+    },
+    Ttl {
+        key: String,
+    },
+    Expire {
+        key: String,
+        ttl: u64,
+    },
+    Incr {
+        key: String,
+        by: i64,
+        ttl: Option<u64>,
+    },
+    HSet {
+        key: String,
+        fields: Value,
+    },
+    HGet {
+        key: String,
+        field: String,
+    },
+    HGetAll {
+        key: String,
+    },
+    HDel {
+        key: String,
+        fields: Vec<String>,
+    },
+    Publish {
+        channel: String,
+        message: Value,
+    },
+    XAdd {
+        stream: String,
+        fields: Value,
+        maxlen: Option<u64>,
+    },
+    XGroupCreate {
+        stream: String,
+        group: String,
+        start: String,
+    },
+    XReadGroup {
+        stream: String,
+        group: String,
+        consumer: String,
+        count: u64,
+        block: Option<u64>,
+        id: String,
+    },
+    XAck {
+        stream: String,
+        group: String,
+        ids: Vec<String>,
+    },
+    Lock {
+        key: String,
+        ttl: u64,
+        wait: u64,
+    },
+    Unlock {
+        key: String,
+        token: String,
+    },
+}
+
+fn required(input: &Value, field: &str, action: &str) -> Result<String, String> {
+    let value = input
+        .get(field)
+        .ok_or(format!("Missing '{}' field for {} action", field, action))?
+        .to_string();
+
+    if value.is_empty() {
+        return Err(format!("'{}' cannot be empty for {} action", field, action));
+    }
+
+    Ok(value)
+}
+
+fn key(input: &Value, action: &str) -> Result<String, String> {
+    let key = input
+        .get("key")
+        .ok_or(format!("Missing 'key' field for {} action", action))?
+        .to_string();
+
+    if key.is_empty() {
+        return Err(format!("Key cannot be empty for {} action", action));
+    }
+
+    Ok(key)
+}
+
+fn object(input: &Value, field: &str, action: &str) -> Result<Value, String> {
+    match input.get(field) {
+        Some(value) if value.is_object() => Ok(value.clone()),
+        Some(_) => Err(format!(
+            "'{}' must be an object for {} action",
+            field, action
+        )),
+        None => Err(format!("Missing '{}' field for {} action", field, action)),
+    }
+}
+
+fn list(input: &Value, field: &str, single: &str, action: &str) -> Result<Vec<String>, String> {
+    let items = match (input.get(field), input.get(single)) {
+        (Some(Value::Array(array)), _) => array.values.iter().map(|v| v.to_string()).collect(),
+        (Some(value), _) | (None, Some(value)) => vec![value.to_string()],
+        (None, None) => Vec::new(),
+    };
+
+    if items.is_empty() {
+        return Err(format!("Missing '{}' field for {} action", field, action));
+    }
+
+    Ok(items)
+}
+
+impl TryFrom<Option<Value>> for RedisInput {
+    type Error = String;
+
+    fn try_from(input_value: Option<Value>) -> Result<Self, Self::Error> {
+        let input = input_value.ok_or("Missing input for redis module")?;
+
+        if !input.is_object() {
+            return Err("Redis input must be an object".to_string());
+        }
+
+        let action = match input.get("action") {
+            Some(Value::String(s)) => s.as_string(),
+            Some(v) => v.to_string(),
+            None => return Err("Missing required 'action' field in redis input".to_string()),
+        };
+        let action = action.as_str();
+
+        match action {
+            "set" => Ok(RedisInput::Set {
+                key: key(&input, action)?,
+                value: input
+                    .get("value")
+                    .ok_or("Missing 'value' field for set action")?
+                    .clone(),
+                ttl: input.get("ttl").and_then(|v| v.to_u64()),
+            }),
+            "get" => Ok(RedisInput::Get {
+                key: key(&input, action)?,
+            }),
+            "remove" => Ok(RedisInput::Remove {
+                key: key(&input, action)?,
+            }),
+            "exists" => Ok(RedisInput::Exists {
+                key: key(&input, action)?,
+            }),
+            "ttl" => Ok(RedisInput::Ttl {
+                key: key(&input, action)?,
+            }),
+            "expire" => Ok(RedisInput::Expire {
+                key: key(&input, action)?,
+                ttl: input
+                    .get("ttl")
+                    .and_then(|v| v.to_u64())
+                    .ok_or("Missing 'ttl' field for expire action")?,
+            }),
+            "incr" => Ok(RedisInput::Incr {
+                key: key(&input, action)?,
+                by: input.get("by").and_then(|v| v.to_i64()).unwrap_or(1),
+                ttl: input.get("ttl").and_then(|v| v.to_u64()),
+            }),
+            "hset" => Ok(RedisInput::HSet {
+                key: key(&input, action)?,
+                fields: object(&input, "fields", action)?,
+            }),
+            "hget" => Ok(RedisInput::HGet {
+                key: key(&input, action)?,
+                field: required(&input, "field", action)?,
+            }),
+            "hgetall" => Ok(RedisInput::HGetAll {
+                key: key(&input, action)?,
+            }),
+            "hdel" => Ok(RedisInput::HDel {
+                key: key(&input, action)?,
+                fields: list(&input, "fields", "field", action)?,
+            }),
+            "publish" => Ok(RedisInput::Publish {
+                channel: required(&input, "channel", action)?,
+                message: input.get("message").cloned().unwrap_or(Value::Null),
+            }),
+            "xadd" => Ok(RedisInput::XAdd {
+                stream: required(&input, "stream", action)?,
+                fields: object(&input, "fields", action)?,
+                maxlen: input.get("maxlen").and_then(|v| v.to_u64()),
+            }),
+            "xgroup_create" => Ok(RedisInput::XGroupCreate {
+                stream: required(&input, "stream", action)?,
+                group: required(&input, "group", action)?,
+                start: input
+                    .get("start")
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "$".to_string()),
+            }),
+            "xreadgroup" => Ok(RedisInput::XReadGroup {
+                stream: required(&input, "stream", action)?,
+                group: required(&input, "group", action)?,
+                consumer: required(&input, "consumer", action)?,
+                count: input.get("count").and_then(|v| v.to_u64()).unwrap_or(10),
+                block: input.get("block").and_then(|v| v.to_u64()),
+                id: input
+                    .get("id")
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| ">".to_string()),
+            }),
+            "xack" => Ok(RedisInput::XAck {
+                stream: required(&input, "stream", action)?,
+                group: required(&input, "group", action)?,
+                ids: list(&input, "ids", "id", action)?,
+            }),
+            "lock" => Ok(RedisInput::Lock {
+                key: key(&input, action)?,
+                ttl: input.get("ttl").and_then(|v| v.to_u64()).unwrap_or(30),
+                wait: input.get("wait").and_then(|v| v.to_u64()).unwrap_or(0),
+            }),
+            "unlock" => Ok(RedisInput::Unlock {
+                key: key(&input, action)?,
+                token: required(&input, "token", action)?,
+            }),
+            _ => Err(format!(
+                "Invalid action '{}'. Must be one of: set, get, remove, exists, ttl, expire, incr, hset, hget, hgetall, hdel, publish, xadd, xgroup_create, xreadgroup, xack, lock, unlock",
+                action
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<RedisInput, String> {
+        RedisInput::try_from(Some(Value::json_to_value(json).unwrap()))
+    }
+
+    #[test]
+    fn test_redis_input_cache_shape() {
+        match parse(
+            r#"{ "action": "set", "key": "user:1", "value": { "name": "Ana" }, "ttl": 60 }"#,
+        )
+        .unwrap()
+        {
+            RedisInput::Set { key, value, ttl } => {
+                assert_eq!(key, "user:1");
+                assert!(value.is_object());
+                assert_eq!(ttl, Some(60));
+            }
+            _ => panic!("Expected Set variant"),
+        }
+
+        assert!(matches!(
+            parse(r#"{ "action": "get", "key": "user:1" }"#).unwrap(),
+            RedisInput::Get { key } if key == "user:1"
+        ));
+        assert_eq!(
+            parse(r#"{ "action": "remove" }"#).unwrap_err(),
+            "Missing 'key' field for remove action"
+        );
+    }
+
+    #[test]
+    fn test_redis_input_streams_and_locks() {
+        match parse(r#"{ "action": "xreadgroup", "stream": "orders", "group": "billing", "consumer": "c1" }"#)
+            .unwrap()
+        {
+            RedisInput::XReadGroup {
+                count, block, id, ..
+            } => {
+                assert_eq!(count, 10);
+                assert_eq!(block, None);
+                assert_eq!(id, ">");
+            }
+            _ => panic!("Expected XReadGroup variant"),
+        }
+
+        assert!(matches!(
+            parse(r#"{ "action": "xack", "stream": "orders", "group": "billing", "id": "1-0" }"#)
+                .unwrap(),
+            RedisInput::XAck { ids, .. } if ids == vec!["1-0".to_string()]
+        ));
+        assert!(matches!(
+            parse(r#"{ "action": "lock", "key": "job" }"#).unwrap(),
+            RedisInput::Lock {
+                ttl: 30,
+                wait: 0,
+                ..
+            }
+        ));
+        assert_eq!(
+            parse(r#"{ "action": "hset", "key": "h", "fields": 1 }"#).unwrap_err(),
+            "'fields' must be an object for hset action"
+        );
+        assert!(
+            parse(r#"{ "action": "clear" }"#)
+                .unwrap_err()
+                .contains("Invalid action 'clear'")
+        );
+    }
+}
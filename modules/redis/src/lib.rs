@@ -0,0 +1,75 @@
+mod commands;
+mod input;
+mod setup;
+mod subscriber;
+
+use commands::Redis;
+use input::RedisInput;
+use phlow_sdk::prelude::*;
+use redis::aio::ConnectionManager;
+use setup::Config;
+use std::sync::Arc;
+use subscriber::Subscriber;
+
+create_main!(start_redis(setup));
+
+pub async fn start_redis(
+    setup: ModuleSetup,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::try_from(&setup.with).map_err(|e| format!("{}", e))?;
+
+    log::debug!("Starting Redis module with config: {:?}", config);
+
+    // Without a main sender (--test, --migrate, --var-main) only the commands run
+    if setup.main_sender.is_some() && config.channels.is_empty() && config.patterns.is_empty() {
+        return Err("channels or patterns is required to subscribe as main".into());
+    }
+
+    let client = config.client().map_err(|e| format!("{}", e))?;
+
+    if let Some(main_sender) = setup.main_sender.clone() {
+        let subscriber = Subscriber {
+            id: setup.id,
+            main_sender,
+            dispatch: setup.dispatch.clone(),
+        };
+        tokio::task::spawn(subscriber::run(
+            client.clone(),
+            Arc::new(config.clone()),
+            subscriber,
+        ));
+    }
+
+    let connection = ConnectionManager::new(client.clone()).await?;
+    let redis = Arc::new(Redis {
+        client,
+        connection,
+        config,
+    });
+
+    let rx = module_channel!(setup);
+
+    for package in rx {
+        let redis = Arc::clone(&redis);
+
+        tokio::task::spawn(async move {
+            let result = match RedisInput::try_from(package.input()) {
+                Ok(input) => redis.execute(input).await,
+                Err(e) => Err(format!("Invalid input: {}", e)),
+            };
+
+            let response = result.unwrap_or_else(|e| {
+                log::error!("Redis operation failed: {}", e);
+                std::collections::HashMap::from([
+                    ("success", false.to_value()),
+                    ("error", e.to_value()),
+                ])
+                .to_value()
+            });
+            sender_safe!(package.sender, response.into());
+        });
+    }
+
+    log::debug!("Redis module finished");
+    Ok(())
+}
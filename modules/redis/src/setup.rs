@@ -0,0 +1,124 @@
+use phlow_sdk::prelude::*;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum Error {
+    Url(String),
+    DefaultTtl,
+    Channels,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Url(e) => write!(f, "Invalid url: {}", e),
+            Self::DefaultTtl => write!(f, "default_ttl must be a positive number of seconds"),
+            Self::Channels => write!(
+                f,
+                "channels and patterns must be strings or arrays of strings"
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// redis:// or rediss:// (TLS) url, with credentials and database when needed
+    pub url: String,
+    /// Prepended to keys, streams and locks, so flows can share a server without clashing
+    pub prefix: Option<String>,
+    /// Seconds a key set without ttl lives; forever when not set
+    pub default_ttl: Option<u64>,
+    /// Channels subscribed as main
+    pub channels: Vec<String>,
+    /// Channel patterns, such as `orders.*`, subscribed as main
+    pub patterns: Vec<String>,
+}
+
+impl Config {
+    pub fn client(&self) -> Result<redis::Client, Error> {
+        redis::Client::open(self.url.as_str()).map_err(|e| Error::Url(e.to_string()))
+    }
+
+    pub fn key(&self, key: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}{}", prefix, key),
+            None => key.to_string(),
+        }
+    }
+}
+
+fn strings(value: Option<&Value>) -> Result<Vec<String>, Error> {
+    match value {
+        Some(Value::Array(array)) => array
+            .values
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s.as_string()),
+                _ => Err(Error::Channels),
+            })
+            .collect(),
+        Some(Value::String(s)) => Ok(vec![s.as_string()]),
+        Some(Value::Null) | None => Ok(Vec::new()),
+        Some(_) => Err(Error::Channels),
+    }
+}
+
+impl TryFrom<&Value> for Config {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Error> {
+        let default_ttl = match value.get("default_ttl") {
+            Some(ttl) => match ttl.to_i64() {
+                Some(ttl) if ttl > 0 => Some(ttl as u64),
+                _ => return Err(Error::DefaultTtl),
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            url: value
+                .get("url")
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "redis://localhost:6379".to_string()),
+            prefix: value.get("prefix").map(|v| v.to_string()),
+            default_ttl,
+            channels: strings(value.get("channels"))?,
+            patterns: strings(value.get("patterns"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Config, Error> {
+        Config::try_from(&Value::json_to_value(json).unwrap())
+    }
+
+    #[test]
+    fn test_config() {
+        let config =
+            parse(r#"{ "prefix": "billing:", "channels": "orders", "patterns": ["events.*"] }"#)
+                .unwrap();
+        assert_eq!(config.url, "redis://localhost:6379");
+        assert_eq!(config.key("user:1"), "billing:user:1");
+        assert_eq!(config.channels, vec!["orders".to_string()]);
+        assert_eq!(config.patterns, vec!["events.*".to_string()]);
+        assert!(config.client().is_ok());
+
+        assert!(matches!(
+            parse(r#"{ "default_ttl": 0 }"#),
+            Err(Error::DefaultTtl)
+        ));
+        assert!(matches!(
+            parse(r#"{ "channels": [1] }"#),
+            Err(Error::Channels)
+        ));
+        assert!(matches!(
+            parse(r#"{ "url": "http://localhost" }"#).unwrap().client(),
+            Err(Error::Url(_))
+        ));
+    }
+}
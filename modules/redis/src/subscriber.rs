@@ -0,0 +1,127 @@
+use futures_util::StreamExt;
+use phlow_sdk::prelude::*;
+use phlow_sdk::tracing::{Dispatch, Level};
+use redis::Msg;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::decode;
+use crate::setup::Config;
+
+pub struct Subscriber {
+    pub id: ModuleId,
+    pub main_sender: MainRuntimeSender,
+    pub dispatch: Dispatch,
+}
+
+/// Subscribes to the channels and patterns of the config and runs the flow for each
+/// message. Pub/sub keeps no messages: those published while the connection is down
+/// are lost, so it reconnects with backoff and subscribes again.
+pub async fn run(client: redis::Client, config: Arc<Config>, subscriber: Subscriber) {
+    let subscriber = Arc::new(subscriber);
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        match subscribe(&client, &config).await {
+            Ok(mut pubsub) => {
+                log::info!(
+                    "Subscribed to channels {:?} and patterns {:?}",
+                    config.channels,
+                    config.patterns
+                );
+                backoff = Duration::from_secs(1);
+
+                let mut messages = pubsub.on_message();
+                while let Some(msg) = messages.next().await {
+                    let subscriber = Arc::clone(&subscriber);
+                    tokio::spawn(async move { deliver(&subscriber, msg).await });
+                }
+                log::warn!("Redis subscription connection closed");
+            }
+            Err(e) => log::error!("Failed to subscribe: {}", e),
+        }
+
+        log::info!("Resubscribing in {}s", backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(Duration::from_secs(30));
+    }
+}
+
+async fn subscribe(
+    client: &redis::Client,
+    config: &Config,
+) -> redis::RedisResult<redis::aio::PubSub> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    if !config.channels.is_empty() {
+        pubsub.subscribe(&config.channels).await?;
+    }
+    if !config.patterns.is_empty() {
+        pubsub.psubscribe(&config.patterns).await?;
+    }
+    Ok(pubsub)
+}
+
+async fn deliver(subscriber: &Subscriber, msg: Msg) {
+    let _guard = phlow_sdk::tracing::dispatcher::set_default(&subscriber.dispatch);
+    use_log!();
+
+    let span = tracing::span!(
+        Level::INFO,
+        "message_receive",
+        "messaging.system" = "redis",
+        "messaging.destination.name" = msg.get_channel_name(),
+        "messaging.operation" = "receive",
+        "messaging.message.payload_size_bytes" = msg.get_payload_bytes().len(),
+    );
+    span_enter!(span);
+
+    let data = message_value(&msg);
+    let response = sender_package!(
+        span.clone(),
+        subscriber.dispatch.clone(),
+        subscriber.id,
+        subscriber.main_sender.clone(),
+        Some(data)
+    )
+    .await;
+    log::debug!("Response: {:?}", response);
+}
+
+/// Message as the flow receives it. The message is parsed when it is JSON.
+fn message_value(msg: &Msg) -> Value {
+    let pattern: Option<String> = msg.get_pattern().unwrap_or_default();
+    let text = String::from_utf8_lossy(msg.get_payload_bytes());
+
+    json!({
+        "channel": msg.get_channel_name().to_string(),
+        "pattern": pattern.to_value(),
+        "message": decode(&text),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_value() {
+        let msg = Msg::from_owned_value(redis::Value::Array(vec![
+            redis::Value::BulkString(b"pmessage".to_vec()),
+            redis::Value::BulkString(b"orders.*".to_vec()),
+            redis::Value::BulkString(b"orders.created".to_vec()),
+            redis::Value::BulkString(br#"{ "id": 7 }"#.to_vec()),
+        ]))
+        .unwrap();
+
+        let value = message_value(&msg);
+        assert_eq!(value.get("channel"), Some(&"orders.created".to_value()));
+        assert_eq!(value.get("pattern"), Some(&"orders.*".to_value()));
+        assert_eq!(
+            value
+                .get("message")
+                .and_then(|m| m.get("id"))
+                .and_then(Value::to_i64),
+            Some(7)
+        );
+    }
+}
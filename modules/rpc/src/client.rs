@@ -1,28 +1,62 @@
-use crate::service::{PhlowRpcClient, RpcRequest};
+use crate::pool::ConnectionPool;
+use crate::service::RpcRequest;
 use crate::setup::Config;
 use phlow_sdk::prelude::*;
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
-use tarpc::{client, context, tokio_serde::formats::Json};
+use std::sync::Arc;
+use std::time::Duration;
 
+/// `upstream` in the input targets a named upstream instead of load balancing
+fn upstream_name(input: &Value) -> Option<String> {
+    if !input.is_object() {
+        return None;
+    }
+    input.get("upstream").map(|v| v.to_string())
+}
+
+/// `params`, or the whole input when absent, without the `upstream` used for routing
+fn params(input: &Value) -> Value {
+    if let Some(params) = input.get("params") {
+        return params.clone();
+    }
+
+    let mut params = input.clone();
+    if params.is_object() {
+        params.remove(&"upstream");
+    }
+    params
+}
+
+#[derive(Clone)]
 pub struct RpcClient {
-    config: Config,
+    pool: Arc<ConnectionPool>,
+    service_name: String,
 }
 
 impl RpcClient {
-    pub fn new(config: Config) -> Self {
-        Self { config }
+    pub fn new(config: &Config) -> Self {
+        let pool = Arc::new(ConnectionPool::new(config));
+
+        if config.health_check_interval_ms > 0 && pool.needs_health_checks() {
+            tokio::task::spawn(
+                pool.clone()
+                    .health_check_loop(Duration::from_millis(config.health_check_interval_ms)),
+            );
+        }
+
+        Self {
+            pool,
+            service_name: config.service_name.clone(),
+        }
     }
 
     pub async fn execute_call(
         &self,
         input: Value,
+        trace_headers: HashMap<String, String>,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        log::debug!("Executing RPC call to {}", self.config.server_address());
-
-        let server_addr: std::net::SocketAddr = self.config.server_address().parse()?;
-        let transport = tarpc::serde_transport::tcp::connect(server_addr, Json::default).await?;
-        let client = PhlowRpcClient::new(client::Config::default(), transport).spawn();
+        let upstream = self.pool.select(upstream_name(&input).as_deref())?;
+        log::debug!("Executing RPC call to {}", upstream.upstream.address());
 
         // Extract method and params from input
         let method = match input.get("method") {
@@ -32,9 +66,9 @@ impl RpcClient {
         .to_string();
 
         // Convert phlow_sdk::Value to serde_json::Value
-        let params = input.get("params").cloned().unwrap_or(input.clone());
+        let params = params(&input);
 
-        let headers: HashMap<String, String> = input
+        let mut headers: HashMap<String, String> = input
             .get("headers")
             .and_then(|v| {
                 if let Value::Object(obj) = v {
@@ -49,6 +83,11 @@ impl RpcClient {
             })
             .unwrap_or_else(HashMap::new);
 
+        // Headers set by the flow win over the propagated trace context
+        for (key, value) in trace_headers {
+            headers.entry(key).or_insert(value);
+        }
+
         let request = RpcRequest {
             method,
             params,
@@ -57,37 +96,37 @@ impl RpcClient {
 
         log::debug!("Sending RPC request: {:?}", request);
 
-        let mut ctx = context::current();
-        ctx.deadline = Instant::now() + Duration::from_millis(self.config.timeout_ms);
-
-        let response = client.call(ctx, request).await?;
+        let response = upstream
+            .call(self.pool.timeout(), |client, ctx| async move {
+                client.call(ctx, request).await
+            })
+            .await?;
 
         log::debug!("Received RPC response: {:?}", response);
 
         Ok(response.to_value())
     }
 
-    pub async fn health_check(&self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        log::debug!(
-            "Performing health check on {}",
-            self.config.server_address()
-        );
-
-        let server_addr: std::net::SocketAddr = self.config.server_address().parse()?;
-        let transport = tarpc::serde_transport::tcp::connect(server_addr, Json::default).await?;
-        let client = PhlowRpcClient::new(client::Config::default(), transport).spawn();
-
-        let mut ctx = context::current();
-        ctx.deadline = Instant::now() + Duration::from_millis(self.config.timeout_ms);
+    pub async fn health_check(
+        &self,
+        input: &Value,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let upstream = self.pool.select(upstream_name(input).as_deref())?;
+        log::debug!("Performing health check on {}", upstream.upstream.address());
 
-        let is_healthy = client.health(ctx).await?;
+        let is_healthy = upstream
+            .call(self.pool.timeout(), |client, ctx| async move {
+                client.health(ctx).await
+            })
+            .await?;
 
         let mut result = HashMap::new();
         result.insert("healthy".to_string(), is_healthy.to_value());
-        result.insert("service".to_string(), self.config.service_name.to_value());
+        result.insert("service".to_string(), self.service_name.to_value());
+        result.insert("upstream".to_string(), upstream.upstream.name.to_value());
         result.insert(
             "address".to_string(),
-            self.config.server_address().to_value(),
+            upstream.upstream.address().to_value(),
         );
 
         Ok(result.to_value())
@@ -95,18 +134,62 @@ impl RpcClient {
 
     pub async fn get_service_info(
         &self,
+        input: &Value,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let upstream = self.pool.select(upstream_name(input).as_deref())?;
+        log::debug!("Getting service info from {}", upstream.upstream.address());
+
+        let info = upstream
+            .call(self.pool.timeout(), |client, ctx| async move {
+                client.info(ctx).await
+            })
+            .await?;
+
+        Ok(info.to_value())
+    }
+
+    pub async fn get_methods(
+        &self,
+        input: &Value,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        log::debug!("Getting service info from {}", self.config.server_address());
+        let upstream = self.pool.select(upstream_name(input).as_deref())?;
+        log::debug!("Getting methods from {}", upstream.upstream.address());
 
-        let server_addr: std::net::SocketAddr = self.config.server_address().parse()?;
-        let transport = tarpc::serde_transport::tcp::connect(server_addr, Json::default).await?;
-        let client = PhlowRpcClient::new(client::Config::default(), transport).spawn();
+        let methods = upstream
+            .call(self.pool.timeout(), |client, ctx| async move {
+                client.methods(ctx).await
+            })
+            .await?;
 
-        let mut ctx = context::current();
-        ctx.deadline = Instant::now() + Duration::from_millis(self.config.timeout_ms);
+        let mut result = HashMap::new();
+        result.insert("methods".to_string(), methods);
 
-        let info = client.info(ctx).await?;
+        Ok(result.to_value())
+    }
+}
 
-        Ok(info.to_value())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_without_upstream() {
+        let input = HashMap::from([
+            ("upstream", "a".to_value()),
+            ("user_id", 7.to_value()),
+        ])
+        .to_value();
+        assert_eq!(upstream_name(&input), Some("a".to_string()));
+
+        let call_params = params(&input);
+        assert!(call_params.get("upstream").is_none());
+        assert_eq!(call_params.get("user_id"), Some(&7.to_value()));
+
+        let input = HashMap::from([
+            ("upstream", "a".to_value()),
+            ("params", HashMap::from([("upstream", "b")]).to_value()),
+        ])
+        .to_value();
+        assert_eq!(params(&input).get("upstream"), Some(&"b".to_value()));
     }
 }
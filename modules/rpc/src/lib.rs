@@ -1,4 +1,6 @@
 mod client;
+mod methods;
+mod pool;
 mod server;
 mod service;
 mod setup;
@@ -19,6 +21,15 @@ pub async fn start_rpc_module(
 
     if setup.is_main() {
         log::info!("Starting RPC server as main module");
+        // Fail setup instead of answering every call to the method with an error
+        if let Some(method) = config.methods.unknown_step(|step| setup.has_step(step)) {
+            return Err(setup::Error::UnknownMethodStep {
+                method: method.name.clone(),
+                step: method.step.clone().unwrap_or_default(),
+            }
+            .to_string()
+            .into());
+        }
         let dispatch = setup.dispatch.clone();
         let config_clone = config.clone();
         let main_sender = match setup.main_sender.clone() {
@@ -55,21 +66,25 @@ async fn handle_rpc_client(
 
     log::debug!("RPC client handler started");
 
+    // One client per module, so connections are reused across steps and calls
+    let client = RpcClient::new(&config);
+
     for package in rx {
         log::debug!("Received RPC client request: {:?}", package);
 
-        let config = config.clone();
-        let client = RpcClient::new(config);
+        let client = client.clone();
+        let trace_headers = package.trace_headers();
 
         tokio::task::spawn(async move {
             let result = match package.input {
                 Some(input) => match input.get("action") {
                     Some(Value::String(action)) => match action.as_str() {
-                        "health" => client.health_check().await,
-                        "info" => client.get_service_info().await,
-                        _ => client.execute_call(input).await,
+                        "health" => client.health_check(&input).await,
+                        "info" => client.get_service_info(&input).await,
+                        "methods" => client.get_methods(&input).await,
+                        _ => client.execute_call(input, trace_headers).await,
                     },
-                    _ => client.execute_call(input).await,
+                    _ => client.execute_call(input, trace_headers).await,
                 },
                 None => {
                     let error_msg = "No input provided";
@@ -0,0 +1,286 @@
+use phlow_sdk::prelude::*;
+use std::collections::HashMap;
+
+pub const METHOD_NOT_FOUND: &str = "method_not_found";
+pub const INVALID_PARAMS: &str = "invalid_params";
+pub const INVALID_RESULT: &str = "invalid_result";
+pub const FLOW_ERROR: &str = "flow_error";
+
+/// A method exposed by the rpc server. Schemas use the same shape as module schemas
+/// (`type`, `required`, `properties`, `items`, `enum`).
+#[derive(Clone, Debug)]
+pub struct MethodDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    pub params: Value,
+    pub result: Value,
+    /// Id of the step the flow starts at for this method
+    pub step: Option<String>,
+}
+
+impl MethodDefinition {
+    pub fn parse(value: &Value) -> Option<Self> {
+        if !value.is_object() {
+            return None;
+        }
+
+        Some(Self {
+            name: value.get("name")?.to_string(),
+            description: value.get("description").map(|v| v.to_string()),
+            params: value.get("params").cloned().unwrap_or(Value::Null),
+            result: value.get("result").cloned().unwrap_or(Value::Null),
+            step: value.get("step").map(|v| v.to_string()),
+        })
+    }
+
+    /// Entry of the catalogue returned by `methods()`
+    pub fn describe(&self) -> Value {
+        let mut description = HashMap::from([
+            ("name", self.name.to_value()),
+            ("params", self.params.clone()),
+            ("result", self.result.clone()),
+        ]);
+        if let Some(text) = &self.description {
+            description.insert("description", text.to_value());
+        }
+        description.to_value()
+    }
+}
+
+/// Methods declared in `with.methods`. An empty registry accepts any method and leaves
+/// dispatch to the flow.
+#[derive(Clone, Debug, Default)]
+pub struct MethodRegistry {
+    methods: Vec<MethodDefinition>,
+}
+
+impl MethodRegistry {
+    pub fn new(methods: Vec<MethodDefinition>) -> Self {
+        Self { methods }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MethodDefinition> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    /// First method whose `step` is not one of the flow's steps
+    pub fn unknown_step(&self, has_step: impl Fn(&str) -> bool) -> Option<&MethodDefinition> {
+        self.methods
+            .iter()
+            .find(|method| method.step.as_deref().is_some_and(|step| !has_step(step)))
+    }
+
+    pub fn catalogue(&self) -> Value {
+        self.methods
+            .iter()
+            .map(MethodDefinition::describe)
+            .collect::<Vec<_>>()
+            .to_value()
+    }
+}
+
+/// Checks `value` against `schema`, returning one message per violation.
+/// A null schema accepts anything.
+pub fn validate(schema: &Value, value: &Value, path: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_into(schema, value, path, &mut errors);
+    errors
+}
+
+fn is_missing(value: &Value) -> bool {
+    matches!(value, Value::Null | Value::Undefined)
+}
+
+fn validate_into(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if !schema.is_object() {
+        return;
+    }
+
+    if is_missing(value) {
+        if let Some(Value::Boolean(true)) = schema.get("required") {
+            errors.push(format!("{}: is required", path));
+        }
+        return;
+    }
+
+    let expected = schema
+        .get("type")
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "any".to_string());
+    let matches_type = match expected.as_str() {
+        "string" => matches!(value, Value::String(_)),
+        "number" => matches!(value, Value::Number(_)),
+        "integer" => value.to_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => matches!(value, Value::Boolean(_)),
+        "object" => matches!(value, Value::Object(_)),
+        "array" => matches!(value, Value::Array(_)),
+        _ => true,
+    };
+    if !matches_type {
+        errors.push(format!("{}: expected {}", path, expected));
+        return;
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum")
+        && !allowed.values.iter().any(|item| item == value)
+    {
+        errors.push(format!(
+            "{}: must be one of {}",
+            path,
+            allowed.to_value().to_json(JsonMode::Inline)
+        ));
+    }
+
+    if let (Some(Value::Object(properties)), Value::Object(_)) = (schema.get("properties"), value) {
+        for (key, property) in properties.iter() {
+            let field = value.get(key.to_string().as_str()).unwrap_or(&Value::Null);
+            validate_into(property, field, &format!("{}.{}", path, key), errors);
+        }
+    }
+
+    if let (Some(items), Value::Array(array)) = (schema.get("items"), value) {
+        for (index, item) in array.values.iter().enumerate() {
+            validate_into(items, item, &format!("{}[{}]", path, index), errors);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Value {
+        HashMap::from([
+            ("type", "object".to_value()),
+            ("required", true.to_value()),
+            (
+                "properties",
+                HashMap::from([
+                    (
+                        "user_id",
+                        HashMap::from([
+                            ("type", "number".to_value()),
+                            ("required", true.to_value()),
+                        ])
+                        .to_value(),
+                    ),
+                    (
+                        "status",
+                        HashMap::from([
+                            ("type", "string".to_value()),
+                            ("enum", vec!["active", "blocked"].to_value()),
+                        ])
+                        .to_value(),
+                    ),
+                    (
+                        "tags",
+                        HashMap::from([
+                            ("type", "array".to_value()),
+                            ("items", HashMap::from([("type", "string")]).to_value()),
+                        ])
+                        .to_value(),
+                    ),
+                ])
+                .to_value(),
+            ),
+        ])
+        .to_value()
+    }
+
+    #[test]
+    fn test_valid_params() {
+        let params = HashMap::from([
+            ("user_id", 1.to_value()),
+            ("status", "active".to_value()),
+            ("tags", vec!["a"].to_value()),
+        ])
+        .to_value();
+        assert!(validate(&schema(), &params, "params").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let params = HashMap::from([
+            ("status", "deleted".to_value()),
+            ("tags", vec![1.to_value()].to_value()),
+        ])
+        .to_value();
+        let mut errors = validate(&schema(), &params, "params");
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                "params.status: must be one of [\"active\",\"blocked\"]",
+                "params.tags[0]: expected string",
+                "params.user_id: is required",
+            ]
+        );
+
+        assert_eq!(
+            validate(&schema(), &Value::Null, "params"),
+            vec!["params: is required"]
+        );
+    }
+
+    #[test]
+    fn test_null_schema_accepts_anything() {
+        assert!(validate(&Value::Null, &"anything".to_value(), "params").is_empty());
+    }
+
+    #[test]
+    fn test_registry_catalogue() {
+        let method = MethodDefinition::parse(
+            &HashMap::from([
+                ("name", "get_user".to_value()),
+                ("step", "get_user".to_value()),
+                ("params", schema()),
+            ])
+            .to_value(),
+        )
+        .unwrap();
+        let registry = MethodRegistry::new(vec![method]);
+
+        assert!(registry.get("get_user").is_some());
+        assert!(registry.get("delete_user").is_none());
+
+        let catalogue = registry.catalogue();
+        let entry = catalogue.get(0).unwrap();
+        assert_eq!(entry.get("name").unwrap().to_string(), "get_user");
+        assert!(entry.get("params").unwrap().is_object());
+    }
+
+    #[test]
+    fn test_integer_rejects_fractions() {
+        let schema = HashMap::from([("type", "integer")]).to_value();
+        assert!(validate(&schema, &2.to_value(), "params").is_empty());
+        assert!(validate(&schema, &2.0.to_value(), "params").is_empty());
+        assert_eq!(
+            validate(&schema, &2.5.to_value(), "params"),
+            vec!["params: expected integer"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_step() {
+        let method = |name: &str, step: Option<&str>| MethodDefinition {
+            name: name.to_string(),
+            description: None,
+            params: Value::Null,
+            result: Value::Null,
+            step: step.map(|step| step.to_string()),
+        };
+        let registry = MethodRegistry::new(vec![
+            method("ping", None),
+            method("get_user", Some("get_user")),
+            method("delete_user", Some("delete")),
+        ]);
+
+        let unknown = registry.unknown_step(|step| step == "get_user").unwrap();
+        assert_eq!(unknown.name, "delete_user");
+        assert!(registry.unknown_step(|_| true).is_none());
+    }
+}
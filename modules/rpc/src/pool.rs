@@ -0,0 +1,356 @@
+use crate::service::PhlowRpcClient;
+use crate::setup::{Config, LoadBalancing, Upstream};
+use phlow_sdk::prelude::*;
+use socket2::{SockRef, TcpKeepalive};
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tarpc::client::RpcError;
+use tarpc::{client, context, tokio_serde::formats::Json};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+pub enum PoolError {
+    UnknownUpstream(String),
+    Connect(String, std::io::Error),
+    Rpc(String, RpcError),
+}
+
+impl Display for PoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownUpstream(name) => write!(f, "Unknown upstream: {}", name),
+            Self::Connect(address, e) => write!(f, "Failed to connect to {}: {}", address, e),
+            Self::Rpc(address, e) => write!(f, "RPC call to {} failed: {}", address, e),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/// Errors after which the connection can no longer be used and must be reopened
+fn is_connection_error(error: &RpcError) -> bool {
+    matches!(
+        error,
+        RpcError::Shutdown | RpcError::Send(_) | RpcError::Channel(_)
+    )
+}
+
+/// Connections to a single upstream. tarpc clients multiplex requests, so each slot is
+/// shared by concurrent calls and only reopened after the transport fails.
+pub struct UpstreamPool {
+    pub upstream: Upstream,
+    slots: Vec<Mutex<Option<PhlowRpcClient>>>,
+    next_slot: AtomicUsize,
+    in_flight: AtomicUsize,
+    healthy: AtomicBool,
+    keep_alive: Option<Duration>,
+    connect_timeout: Duration,
+}
+
+impl UpstreamPool {
+    fn new(
+        upstream: Upstream,
+        pool_size: usize,
+        keep_alive: Option<Duration>,
+        connect_timeout: Duration,
+    ) -> Self {
+        Self {
+            upstream,
+            slots: (0..pool_size).map(|_| Mutex::new(None)).collect(),
+            next_slot: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            healthy: AtomicBool::new(true),
+            keep_alive,
+            connect_timeout,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    async fn connect(&self) -> Result<PhlowRpcClient, PoolError> {
+        let address = self.upstream.address();
+        log::debug!("Opening RPC connection to {}", address);
+
+        let connect_error = |e| PoolError::Connect(address.clone(), e);
+        let stream = tokio::time::timeout(self.connect_timeout, TcpStream::connect(&address))
+            .await
+            .map_err(|_| {
+                connect_error(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("timed out after {:?}", self.connect_timeout),
+                ))
+            })?
+            .map_err(connect_error)?;
+        stream.set_nodelay(true).map_err(connect_error)?;
+        if let Some(keep_alive) = self.keep_alive {
+            SockRef::from(&stream)
+                .set_tcp_keepalive(&TcpKeepalive::new().with_time(keep_alive))
+                .map_err(connect_error)?;
+        }
+
+        let transport = tarpc::serde_transport::Transport::from((stream, Json::default()));
+        Ok(PhlowRpcClient::new(client::Config::default(), transport).spawn())
+    }
+
+    /// The slot is not locked while connecting, so an unreachable upstream never
+    /// blocks the other calls on the slot. When two calls connect at once, the
+    /// first stored connection wins and the other one is dropped.
+    async fn client(&self, slot: usize) -> Result<PhlowRpcClient, PoolError> {
+        if let Some(client) = self.slots[slot].lock().await.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let client = self.connect().await?;
+        let mut slot = self.slots[slot].lock().await;
+        Ok(slot.get_or_insert(client).clone())
+    }
+
+    async fn reset(&self, slot: usize) {
+        *self.slots[slot].lock().await = None;
+    }
+
+    async fn reset_all(&self) {
+        for slot in 0..self.slots.len() {
+            self.reset(slot).await;
+        }
+    }
+
+    /// Runs `call` on one of the pooled connections, reopening it when the transport failed
+    pub async fn call<T, F, Fut>(&self, timeout: Duration, call: F) -> Result<T, PoolError>
+    where
+        F: FnOnce(PhlowRpcClient, context::Context) -> Fut,
+        Fut: Future<Output = Result<T, RpcError>>,
+    {
+        let slot = self.next_slot.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+
+        let result = async {
+            let client = self.client(slot).await?;
+            let mut ctx = context::current();
+            ctx.deadline = Instant::now() + timeout;
+
+            match call(client, ctx).await {
+                Ok(value) => Ok(value),
+                Err(e) => {
+                    if is_connection_error(&e) {
+                        log::debug!(
+                            "Dropping RPC connection to {}: {}",
+                            self.upstream.address(),
+                            e
+                        );
+                        self.reset(slot).await;
+                    }
+                    Err(PoolError::Rpc(self.upstream.address(), e))
+                }
+            }
+        }
+        .await;
+
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+}
+
+pub struct ConnectionPool {
+    upstreams: Vec<Arc<UpstreamPool>>,
+    load_balancing: LoadBalancing,
+    next_upstream: AtomicUsize,
+    timeout: Duration,
+}
+
+impl ConnectionPool {
+    pub fn new(config: &Config) -> Self {
+        let keep_alive =
+            (config.keep_alive_ms > 0).then(|| Duration::from_millis(config.keep_alive_ms));
+        let connect_timeout = Duration::from_millis(config.connect_timeout_ms);
+
+        Self {
+            upstreams: config
+                .upstreams
+                .iter()
+                .map(|upstream| {
+                    Arc::new(UpstreamPool::new(
+                        upstream.clone(),
+                        config.pool_size,
+                        keep_alive,
+                        connect_timeout,
+                    ))
+                })
+                .collect(),
+            load_balancing: config.load_balancing.clone(),
+            next_upstream: AtomicUsize::new(0),
+            timeout: Duration::from_millis(config.timeout_ms),
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Ejecting the only upstream changes nothing, as selection falls back to all of them
+    pub fn needs_health_checks(&self) -> bool {
+        self.upstreams.len() > 1
+    }
+
+    /// Picks the named upstream, or one of the healthy upstreams per the load balancing policy.
+    /// When every upstream is ejected all of them are considered, so calls still get a chance.
+    pub fn select(&self, name: Option<&str>) -> Result<Arc<UpstreamPool>, PoolError> {
+        if let Some(name) = name {
+            return self
+                .upstreams
+                .iter()
+                .find(|pool| pool.upstream.name == name)
+                .cloned()
+                .ok_or_else(|| PoolError::UnknownUpstream(name.to_string()));
+        }
+
+        let healthy: Vec<&Arc<UpstreamPool>> = self
+            .upstreams
+            .iter()
+            .filter(|pool| pool.is_healthy())
+            .collect();
+        let candidates = if healthy.is_empty() {
+            self.upstreams.iter().collect()
+        } else {
+            healthy
+        };
+
+        let selected = match self.load_balancing {
+            LoadBalancing::RoundRobin => {
+                let next = self.next_upstream.fetch_add(1, Ordering::Relaxed);
+                candidates[next % candidates.len()]
+            }
+            LoadBalancing::LeastLoaded => candidates
+                .into_iter()
+                .min_by_key(|pool| pool.in_flight())
+                .expect("at least one upstream is configured"),
+        };
+
+        Ok(selected.clone())
+    }
+
+    /// Periodically calls `health` on every upstream, ejecting the ones that fail
+    /// from selection until they answer again.
+    pub async fn health_check_loop(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            for pool in &self.upstreams {
+                let healthy = matches!(
+                    pool.call(self.timeout, |client, ctx| async move {
+                        client.health(ctx).await
+                    })
+                    .await,
+                    Ok(true)
+                );
+
+                let was_healthy = pool.healthy.swap(healthy, Ordering::Relaxed);
+                if was_healthy && !healthy {
+                    log::warn!(
+                        "RPC upstream {} ({}) failed its health check and was ejected",
+                        pool.upstream.name,
+                        pool.upstream.address()
+                    );
+                    pool.reset_all().await;
+                } else if !was_healthy && healthy {
+                    log::info!(
+                        "RPC upstream {} ({}) is healthy again",
+                        pool.upstream.name,
+                        pool.upstream.address()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn pool(load_balancing: &str) -> ConnectionPool {
+        let config = Config::try_from(
+            &HashMap::from([
+                (
+                    "upstreams",
+                    vec![
+                        HashMap::from([("name", "a"), ("host", "127.0.0.1")]),
+                        HashMap::from([("name", "b"), ("host", "127.0.0.2")]),
+                    ]
+                    .to_value(),
+                ),
+                ("load_balancing", load_balancing.to_value()),
+            ])
+            .to_value(),
+        )
+        .unwrap();
+        ConnectionPool::new(&config)
+    }
+
+    fn names(pool: &ConnectionPool, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| pool.select(None).unwrap().upstream.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin_skips_ejected_upstreams() {
+        let pool = pool("round_robin");
+        assert_eq!(names(&pool, 4), vec!["a", "b", "a", "b"]);
+
+        pool.upstreams[0].healthy.store(false, Ordering::Relaxed);
+        assert_eq!(names(&pool, 2), vec!["b", "b"]);
+
+        // With every upstream ejected, calls are still spread over all of them
+        pool.upstreams[1].healthy.store(false, Ordering::Relaxed);
+        assert_eq!(names(&pool, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_least_loaded() {
+        let pool = pool("least_loaded");
+        pool.upstreams[0].in_flight.store(3, Ordering::Relaxed);
+        assert_eq!(names(&pool, 2), vec!["b", "b"]);
+    }
+
+    #[test]
+    fn test_connect_timeout_and_health_checks() {
+        let config = Config::try_from(
+            &HashMap::from([
+                ("upstreams", vec!["10.0.0.1:9000"].to_value()),
+                ("connect_timeout_ms", 100.to_value()),
+            ])
+            .to_value(),
+        )
+        .unwrap();
+        let single = ConnectionPool::new(&config);
+        assert_eq!(
+            single.upstreams[0].connect_timeout,
+            Duration::from_millis(100)
+        );
+        assert!(!single.needs_health_checks());
+        assert!(pool("round_robin").needs_health_checks());
+    }
+
+    #[test]
+    fn test_select_by_name() {
+        let pool = pool("round_robin");
+        assert_eq!(pool.select(Some("b")).unwrap().upstream.name, "b");
+        assert!(matches!(
+            pool.select(Some("c")),
+            Err(PoolError::UnknownUpstream(_))
+        ));
+    }
+}
@@ -2,6 +2,7 @@ use crate::service::{PhlowRpc, PhlowRpcServer};
 use crate::setup::Config;
 use futures::StreamExt;
 use phlow_sdk::prelude::*;
+use std::sync::Arc;
 use tarpc::server::Channel;
 use tarpc::{server, tokio_serde::formats::Json};
 use tracing::Dispatch;
@@ -19,6 +20,7 @@ pub async fn start_rpc_server(
     let server = PhlowRpcServer {
         dispatch: dispatch.clone(),
         service_name: config.service_name.clone(),
+        methods: Arc::new(config.methods.clone()),
         main_sender: main_sender.clone(),
         id: id.clone(),
     };
@@ -1,6 +1,10 @@
+use crate::methods::{
+    FLOW_ERROR, INVALID_PARAMS, INVALID_RESULT, METHOD_NOT_FOUND, MethodRegistry, validate,
+};
 use phlow_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tarpc::context;
 use tracing::{Dispatch, Level};
 
@@ -16,9 +20,22 @@ pub struct RpcRequest {
 pub struct RpcResponse {
     pub result: Value,
     pub error: Option<String>,
+    /// Machine-readable kind of `error`, such as `method_not_found` or `invalid_params`
+    pub error_code: Option<String>,
     pub headers: HashMap<String, String>,
 }
 
+impl RpcResponse {
+    fn error(code: &str, message: String) -> Self {
+        Self {
+            result: Value::Null,
+            error: Some(message),
+            error_code: Some(code.to_string()),
+            headers: HashMap::new(),
+        }
+    }
+}
+
 #[tarpc::service]
 pub trait PhlowRpc {
     /// Execute a remote procedure call
@@ -29,6 +46,10 @@ pub trait PhlowRpc {
 
     /// Get service information
     async fn info() -> HashMap<String, String>;
+
+    /// Catalogue of the declared methods. A call of its own, so `info()` keeps
+    /// answering the string map older clients decode.
+    async fn methods() -> Value;
 }
 
 #[derive(Clone)]
@@ -36,6 +57,7 @@ pub struct PhlowRpcServer {
     #[allow(dead_code)] // Used in tracing and sender_package! macro
     pub dispatch: Dispatch,
     pub service_name: String,
+    pub methods: Arc<MethodRegistry>,
     pub main_sender: MainRuntimeSender,
     pub id: ModuleId,
 }
@@ -48,8 +70,34 @@ impl PhlowRpc for PhlowRpcServer {
             request.params
         );
 
+        // With declared methods, unknown names and invalid params never reach the flow
+        let method = if self.methods.is_empty() {
+            None
+        } else {
+            match self.methods.get(&request.method) {
+                Some(method) => Some(method.clone()),
+                None => {
+                    log::debug!("Unknown RPC method: {}", request.method);
+                    return RpcResponse::error(
+                        METHOD_NOT_FOUND,
+                        format!("Unknown method '{}'", request.method),
+                    );
+                }
+            }
+        };
+
+        if let Some(method) = &method {
+            let errors = validate(&method.params, &request.params, "params");
+            if !errors.is_empty() {
+                return RpcResponse::error(INVALID_PARAMS, errors.join("; "));
+            }
+        }
+
         log::debug!("Sending RPC request to steps: {:?}", request);
 
+        let start_step = method.as_ref().and_then(|method| method.step.clone());
+        let method_name = request.method.clone();
+
         // Execute the request through the phlow pipeline system
         // This integrates with the steps defined in the YAML configuration
         let response_value =
@@ -60,6 +108,7 @@ impl PhlowRpc for PhlowRpcServer {
                     "rpc.method" = request.method.clone(),
                     "rpc.service" = self.service_name.clone(),
                 );
+                phlow_sdk::propagation::extract(&span, &request.headers);
 
                 span_enter!(span);
 
@@ -69,10 +118,10 @@ impl PhlowRpc for PhlowRpcServer {
                         self.dispatch.clone(),
                         self.id.clone(),
                         self.main_sender.clone(),
-                        Some(request.to_value())
+                        Some(request.to_value()),
+                        start_step
                     )
-                    .await
-                    .unwrap_or(Value::Null);
+                    .await;
 
                     log::debug!("Received response from steps: {:?}", response_value);
                     response_value
@@ -80,11 +129,36 @@ impl PhlowRpc for PhlowRpcServer {
             })
             .await;
 
+        // The runtime drops the request without a response when the flow fails
+        let response_value = match response_value {
+            Ok(response_value) => response_value,
+            Err(_) => {
+                log::warn!("RPC method {} failed in the flow", method_name);
+                return RpcResponse::error(
+                    FLOW_ERROR,
+                    format!("Method '{}' failed to execute", method_name),
+                );
+            }
+        };
+
         log::debug!("Final response from steps: {:?}", response_value);
 
+        if let Some(method) = &method {
+            let errors = validate(&method.result, &response_value, "result");
+            if !errors.is_empty() {
+                log::warn!(
+                    "RPC method {} returned an invalid result: {}",
+                    method.name,
+                    errors.join("; ")
+                );
+                return RpcResponse::error(INVALID_RESULT, errors.join("; "));
+            }
+        }
+
         let response = RpcResponse {
             result: response_value.to_value(),
             error: None,
+            error_code: None,
             headers: HashMap::new(),
         };
 
@@ -111,4 +185,62 @@ impl PhlowRpc for PhlowRpcServer {
 
         info
     }
+
+    async fn methods(self, _: context::Context) -> Value {
+        log::debug!("Method catalogue requested");
+        self.methods.catalogue()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::methods::MethodDefinition;
+    use futures::StreamExt;
+    use tarpc::server::{BaseChannel, Channel};
+    use tarpc::tokio_serde::formats::Json;
+
+    #[tokio::test]
+    async fn test_info_and_methods_over_the_wire() {
+        let method =
+            MethodDefinition::parse(&HashMap::from([("name", "ping".to_value())]).to_value())
+                .unwrap();
+        let (main_sender, _main_receiver) = channel::unbounded();
+        let server = PhlowRpcServer {
+            dispatch: Dispatch::none(),
+            service_name: "user-service".to_string(),
+            methods: Arc::new(MethodRegistry::new(vec![method])),
+            main_sender,
+            id: 0,
+        };
+
+        let mut listener = tarpc::serde_transport::tcp::listen("127.0.0.1:0", Json::default)
+            .await
+            .unwrap();
+        let address = listener.local_addr();
+        tokio::spawn(async move {
+            let transport = listener.next().await.unwrap().unwrap();
+            BaseChannel::with_defaults(transport)
+                .execute(server.serve())
+                .for_each(|response| response)
+                .await;
+        });
+
+        let transport = tarpc::serde_transport::tcp::connect(address, Json::default)
+            .await
+            .unwrap();
+        let client = PhlowRpcClient::new(tarpc::client::Config::default(), transport).spawn();
+
+        // Clients built against the string map keep decoding `info()`
+        let info = client.info(context::current()).await.unwrap();
+        assert_eq!(
+            info.get("service_name").map(String::as_str),
+            Some("user-service")
+        );
+        assert!(!info.contains_key("methods"));
+
+        let methods = client.methods(context::current()).await.unwrap();
+        let entry = methods.get(0).unwrap();
+        assert_eq!(entry.get("name").unwrap().to_string(), "ping");
+    }
 }
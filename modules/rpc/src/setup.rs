@@ -1,11 +1,15 @@
+use crate::methods::{MethodDefinition, MethodRegistry};
 use phlow_sdk::prelude::*;
 use std::fmt::Display;
 
 #[derive(Debug)]
-#[allow(dead_code)] // Future use for validation
+#[allow(dead_code)] // InvalidPort is reserved for future validation
 pub enum Error {
     InvalidAddress,
     InvalidPort,
+    MissingMethodName,
+    DuplicateMethodName(String),
+    UnknownMethodStep { method: String, step: String },
 }
 
 impl Display for Error {
@@ -13,10 +17,77 @@ impl Display for Error {
         match self {
             Self::InvalidAddress => write!(f, "Invalid address format"),
             Self::InvalidPort => write!(f, "Invalid port number"),
+            Self::MissingMethodName => write!(f, "Invalid method definition, a name is required"),
+            Self::DuplicateMethodName(name) => write!(f, "Method '{}' is declared twice", name),
+            Self::UnknownMethodStep { method, step } => {
+                write!(
+                    f,
+                    "Method '{}' starts at step '{}', which does not exist",
+                    method, step
+                )
+            }
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadBalancing {
+    RoundRobin,
+    LeastLoaded,
+}
+
+impl LoadBalancing {
+    fn from_str(value: &str) -> Self {
+        match value {
+            "least_loaded" => LoadBalancing::LeastLoaded,
+            _ => LoadBalancing::RoundRobin,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Upstream {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Upstream {
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// An upstream is either `"host:port"` or `{ name, host, port }`
+    fn parse(value: &Value, index: usize, default_port: u16) -> Option<Self> {
+        let (name, host, port) = match value {
+            Value::String(address) => {
+                let address = address.as_str();
+                let (host, port) = match address.rsplit_once(':') {
+                    Some((host, port)) => (host, port.parse().ok()?),
+                    None => (address, default_port),
+                };
+                (None, host.to_string(), port)
+            }
+            Value::Object(_) => (
+                value.get("name").map(|v| v.to_string()),
+                value.get("host")?.to_string(),
+                value
+                    .get("port")
+                    .and_then(|v| v.to_i64())
+                    .map(|v| v as u16)
+                    .unwrap_or(default_port),
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            name: name.unwrap_or_else(|| format!("upstream-{}", index)),
+            host,
+            port,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub host: String,
@@ -24,6 +95,13 @@ pub struct Config {
     pub timeout_ms: u64,
     pub max_connections: usize,
     pub service_name: String,
+    pub upstreams: Vec<Upstream>,
+    pub pool_size: usize,
+    pub keep_alive_ms: u64,
+    pub connect_timeout_ms: u64,
+    pub load_balancing: LoadBalancing,
+    pub health_check_interval_ms: u64,
+    pub methods: MethodRegistry,
 }
 
 impl Config {
@@ -64,12 +142,156 @@ impl TryFrom<&Value> for Config {
             .map(|v| v.to_string())
             .unwrap_or_else(|| "default".to_string());
 
+        // Without `upstreams` the client talks to `host`/`port`, as a single upstream
+        let upstreams = match value.get("upstreams") {
+            Some(Value::Array(array)) if !array.values.is_empty() => array
+                .values
+                .iter()
+                .enumerate()
+                .map(|(index, upstream)| {
+                    Upstream::parse(upstream, index, port).ok_or(Error::InvalidAddress)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => vec![Upstream {
+                name: service_name.clone(),
+                host: host.clone(),
+                port,
+            }],
+        };
+
+        let pool_size = value
+            .get("pool_size")
+            .and_then(|v| v.to_i64())
+            .map(|v| v.max(1) as usize)
+            .unwrap_or(4);
+
+        let keep_alive_ms = value
+            .get("keep_alive_ms")
+            .and_then(|v| v.to_i64())
+            .map(|v| v as u64)
+            .unwrap_or(30000);
+
+        let connect_timeout_ms = value
+            .get("connect_timeout_ms")
+            .and_then(|v| v.to_i64())
+            .map(|v| v.max(1) as u64)
+            .unwrap_or(3000);
+
+        let load_balancing = value
+            .get("load_balancing")
+            .map(|v| LoadBalancing::from_str(&v.to_string()))
+            .unwrap_or(LoadBalancing::RoundRobin);
+
+        let health_check_interval_ms = value
+            .get("health_check_interval_ms")
+            .and_then(|v| v.to_i64())
+            .map(|v| v as u64)
+            .unwrap_or(10000);
+
+        let methods = match value.get("methods") {
+            Some(Value::Array(array)) => array
+                .values
+                .iter()
+                .map(|method| MethodDefinition::parse(method).ok_or(Error::MissingMethodName))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => Vec::new(),
+        };
+        for (index, method) in methods.iter().enumerate() {
+            if methods[..index]
+                .iter()
+                .any(|other| other.name == method.name)
+            {
+                return Err(Error::DuplicateMethodName(method.name.clone()));
+            }
+        }
+
         Ok(Self {
             host,
             port,
             timeout_ms,
             max_connections,
             service_name,
+            upstreams,
+            pool_size,
+            keep_alive_ms,
+            connect_timeout_ms,
+            load_balancing,
+            health_check_interval_ms,
+            methods: MethodRegistry::new(methods),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_single_upstream_from_host_and_port() {
+        let config = Config::try_from(
+            &HashMap::from([
+                ("host", "10.0.0.1".to_value()),
+                ("port", 9000.to_value()),
+                ("service_name", "users".to_value()),
+            ])
+            .to_value(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.upstreams,
+            vec![Upstream {
+                name: "users".to_string(),
+                host: "10.0.0.1".to_string(),
+                port: 9000,
+            }]
+        );
+        assert_eq!(config.pool_size, 4);
+        assert_eq!(config.load_balancing, LoadBalancing::RoundRobin);
+    }
+
+    #[test]
+    fn test_upstream_forms() {
+        let config = Config::try_from(
+            &HashMap::from([
+                (
+                    "upstreams",
+                    vec![
+                        "10.0.0.1:9000".to_value(),
+                        HashMap::from([("name", "b".to_value()), ("host", "10.0.0.2".to_value())])
+                            .to_value(),
+                    ]
+                    .to_value(),
+                ),
+                ("load_balancing", "least_loaded".to_value()),
+            ])
+            .to_value(),
+        )
+        .unwrap();
+
+        assert_eq!(config.upstreams[0].name, "upstream-0");
+        assert_eq!(config.upstreams[0].address(), "10.0.0.1:9000");
+        assert_eq!(config.upstreams[1].name, "b");
+        assert_eq!(config.upstreams[1].address(), "10.0.0.2:8080");
+        assert_eq!(config.load_balancing, LoadBalancing::LeastLoaded);
+    }
+
+    #[test]
+    fn test_invalid_upstream() {
+        let config = Config::try_from(
+            &HashMap::from([("upstreams", vec!["10.0.0.1:not-a-port"].to_value())]).to_value(),
+        );
+        assert!(matches!(config, Err(Error::InvalidAddress)));
+    }
+
+    #[test]
+    fn test_duplicate_method_names() {
+        let method = HashMap::from([("name", "ping")]).to_value();
+        let result = Config::try_from(
+            &HashMap::from([("methods", vec![method.clone(), method].to_value())]).to_value(),
+        );
+
+        assert!(matches!(result, Err(Error::DuplicateMethodName(name)) if name == "ping"));
+    }
+}
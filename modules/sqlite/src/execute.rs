@@ -0,0 +1,360 @@
+use phlow_sdk::prelude::*;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, Statement};
+use std::collections::HashMap;
+
+use crate::input::{Query, Transaction};
+use crate::response::{QueryResult, column_value};
+
+#[derive(Debug)]
+pub enum QueryError {
+    /// A parameter could not be bound to its placeholder
+    Param(String),
+    Database(rusqlite::Error),
+}
+
+impl From<rusqlite::Error> for QueryError {
+    fn from(err: rusqlite::Error) -> Self {
+        QueryError::Database(err)
+    }
+}
+
+fn database_error(err: &rusqlite::Error) -> Value {
+    let (code, cause, message) = match err {
+        rusqlite::Error::SqliteFailure(failure, message) => (
+            format!("{:?}", failure.code),
+            failure.to_string(),
+            message.clone().unwrap_or_else(|| failure.to_string()),
+        ),
+        err => ("UNKNOWN".to_string(), String::new(), err.to_string()),
+    };
+
+    json!({
+        "code": code,
+        "cause": cause,
+        "message": message,
+    })
+}
+
+/// Response of a query that reached the database: its result, or the database error
+pub fn outcome(result: Result<Value, rusqlite::Error>) -> Value {
+    match result {
+        Ok(data) => json!({ "success": true, "data": data }),
+        Err(err) => json!({ "success": false, "error": database_error(&err) }),
+    }
+}
+
+/// Converts a parameter to the SQLite value stored for it. Booleans become 0 or 1,
+/// and objects and arrays their JSON text.
+fn to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null | Value::Undefined => SqlValue::Null,
+        Value::Boolean(v) => SqlValue::Integer(*v as i64),
+        Value::Number(number) if !number.is_float() => match number.to_i64() {
+            Some(v) => SqlValue::Integer(v),
+            None => SqlValue::Real(number.to_f64().unwrap_or_default()),
+        },
+        Value::Number(number) => SqlValue::Real(number.to_f64().unwrap_or_default()),
+        Value::Array(_) | Value::Object(_) => SqlValue::Text(value.to_json(JsonMode::Inline)),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
+
+/// Position in `params` of a placeholder. `$1` and `?1` name their position, as in
+/// postgres, so the same query runs on both; a bare `?` takes the next one.
+fn position(name: Option<&str>, index: usize) -> Result<usize, String> {
+    match name {
+        None => Ok(index - 1),
+        Some(name) => name
+            .strip_prefix(['$', '?'])
+            .and_then(|number| number.parse::<usize>().ok())
+            .filter(|number| *number > 0)
+            .map(|number| number - 1)
+            .ok_or_else(|| format!("Unsupported parameter {}. Use $1, ?1 or ?", name)),
+    }
+}
+
+fn bind(stmt: &mut Statement, params: &[Value]) -> Result<(), QueryError> {
+    let count = stmt.parameter_count();
+    let positions = (1..=count)
+        .map(|index| position(stmt.parameter_name(index), index))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(QueryError::Param)?;
+
+    let expected = positions.iter().max().map_or(0, |max| max + 1);
+    if expected != params.len() {
+        return Err(QueryError::Param(format!(
+            "expected {} parameters, found {}",
+            expected,
+            params.len()
+        )));
+    }
+
+    for (index, position) in positions.into_iter().enumerate() {
+        stmt.raw_bind_parameter(index + 1, to_sql(&params[position]))?;
+    }
+    Ok(())
+}
+
+/// Runs the query with its parameters. Queries with `batch: false` run as a script of
+/// statements, without parameters.
+pub fn run(conn: &Connection, query: &Query) -> Result<Value, QueryError> {
+    if !query.batch {
+        conn.execute_batch(&query.query)?;
+        return Ok("OK".to_value());
+    }
+
+    rows(conn, query).map(|result| result.to_value())
+}
+
+pub fn rows(conn: &Connection, query: &Query) -> Result<QueryResult, QueryError> {
+    if query.cache_query {
+        collect(&mut *conn.prepare_cached(&query.query)?, &query.params)
+    } else {
+        collect(&mut conn.prepare(&query.query)?, &query.params)
+    }
+}
+
+fn collect(stmt: &mut Statement, params: &[Value]) -> Result<QueryResult, QueryError> {
+    bind(stmt, params)?;
+
+    let columns: Vec<(String, Option<String>)> = stmt
+        .columns()
+        .iter()
+        .map(|column| {
+            (
+                column.name().to_string(),
+                column.decl_type().map(str::to_string),
+            )
+        })
+        .collect();
+
+    let mut result = QueryResult {
+        rows: Vec::new(),
+        count: 0,
+    };
+
+    let mut rows = stmt.raw_query();
+    while let Some(row) = rows.next()? {
+        let mut map = HashMap::new();
+        for (i, (name, decltype)) in columns.iter().enumerate() {
+            map.insert(
+                name.clone(),
+                column_value(row.get_ref(i)?, decltype.as_deref()),
+            );
+        }
+        result.rows.push(map);
+    }
+    result.count = result.rows.len();
+
+    Ok(result)
+}
+
+/// Runs the queries in order on one connection and commits when they all succeed.
+/// Queries with a savepoint roll back to it on a database error and the transaction
+/// goes on; any other failure rolls back the whole transaction.
+pub fn transaction(conn: &mut Connection, transaction: &Transaction) -> Result<Value, String> {
+    if transaction.read_only
+        && let Err(e) = conn.execute_batch("PRAGMA query_only = ON")
+    {
+        return Ok(outcome(Err(e)));
+    }
+
+    let result = queries(conn, transaction);
+
+    if transaction.read_only
+        && let Err(e) = conn.execute_batch("PRAGMA query_only = OFF")
+    {
+        log::warn!("Failed to reset query_only: {}", e);
+    }
+
+    result
+}
+
+fn queries(conn: &mut Connection, transaction: &Transaction) -> Result<Value, String> {
+    let mut tx = match conn.transaction_with_behavior(transaction.behavior.into()) {
+        Ok(tx) => tx,
+        Err(e) => return Ok(outcome(Err(e))),
+    };
+
+    let mut results = Vec::new();
+
+    for (index, query) in transaction.queries.iter().enumerate() {
+        let result = match &query.savepoint {
+            Some(name) => {
+                let savepoint = match tx.savepoint_with_name(name) {
+                    Ok(savepoint) => savepoint,
+                    Err(e) => return Ok(failed(outcome(Err(e)), index)),
+                };
+                match run(&savepoint, query) {
+                    Ok(data) => savepoint.commit().map(|_| data),
+                    Err(QueryError::Database(e)) => {
+                        // Dropping the savepoint rolls it back
+                        drop(savepoint);
+                        results.push(outcome(Err(e)));
+                        continue;
+                    }
+                    Err(QueryError::Param(e)) => {
+                        return Err(format!("queries[{}]: {}", index, e));
+                    }
+                }
+            }
+            None => match run(&tx, query) {
+                Ok(data) => Ok(data),
+                Err(QueryError::Database(e)) => Err(e),
+                Err(QueryError::Param(e)) => {
+                    return Err(format!("queries[{}]: {}", index, e));
+                }
+            },
+        };
+
+        match result {
+            Ok(data) => results.push(outcome(Ok(data))),
+            // Dropping the transaction rolls it back
+            Err(e) => return Ok(failed(outcome(Err(e)), index)),
+        }
+    }
+
+    Ok(match tx.commit() {
+        Ok(_) => json!({ "success": true, "data": results.to_value() }),
+        Err(e) => outcome(Err(e)),
+    })
+}
+
+/// Adds the position of the query that failed the transaction to its error
+fn failed(response: Value, index: usize) -> Value {
+    let mut error = response.get("error").cloned().unwrap_or(Value::Null);
+    if let Value::Object(object) = &mut error {
+        object.insert("index".to_string(), index.to_value());
+    }
+    json!({ "success": false, "error": error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Behavior;
+
+    fn query(sql: &str, params: Vec<Value>) -> Query {
+        Query {
+            query: sql.to_string(),
+            params,
+            batch: true,
+            cache_query: true,
+            savepoint: None,
+        }
+    }
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT UNIQUE, active BOOLEAN, meta JSON)",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_params() {
+        let conn = connection();
+        let meta = Value::json_to_value(r#"{ "role": "admin" }"#).unwrap();
+        run(
+            &conn,
+            &query(
+                "INSERT INTO users (name, active, meta) VALUES ($2, $3, $1)",
+                vec![meta, "ana".to_value(), true.to_value()],
+            ),
+        )
+        .unwrap();
+
+        let result = rows(
+            &conn,
+            &query(
+                "SELECT * FROM users WHERE name = ?1 OR name = ?1",
+                vec!["ana".to_value()],
+            ),
+        )
+        .unwrap();
+        assert_eq!(result.count, 1);
+        let row = &result.rows[0];
+        assert_eq!(row.get("active"), Some(&Value::Boolean(true)));
+        assert_eq!(
+            row.get("meta").unwrap().get("role"),
+            Some(&"admin".to_value())
+        );
+
+        match rows(&conn, &query("SELECT $1, $2", vec![1.to_value()])) {
+            Err(QueryError::Param(e)) => assert_eq!(e, "expected 2 parameters, found 1"),
+            _ => panic!("expected a parameter error"),
+        }
+        match rows(&conn, &query("SELECT :name", vec![1.to_value()])) {
+            Err(QueryError::Param(e)) => {
+                assert_eq!(e, "Unsupported parameter :name. Use $1, ?1 or ?")
+            }
+            _ => panic!("expected a parameter error"),
+        }
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut conn = connection();
+        let mut duplicate = query("INSERT INTO users (name) VALUES ('ana')", Vec::new());
+        duplicate.savepoint = Some("dup".to_string());
+
+        let response = super::transaction(
+            &mut conn,
+            &Transaction {
+                queries: vec![
+                    query("INSERT INTO users (name) VALUES ('ana')", Vec::new()),
+                    duplicate,
+                    query("INSERT INTO users (name) VALUES ('bia')", Vec::new()),
+                ],
+                behavior: Behavior::Immediate,
+                read_only: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(response.get("success"), Some(&Value::Boolean(true)));
+        let count = rows(&conn, &query("SELECT * FROM users", Vec::new()))
+            .unwrap()
+            .count;
+        assert_eq!(count, 2);
+
+        let response = super::transaction(
+            &mut conn,
+            &Transaction {
+                queries: vec![
+                    query("INSERT INTO users (name) VALUES ('caio')", Vec::new()),
+                    query("INSERT INTO users (name) VALUES ('ana')", Vec::new()),
+                ],
+                behavior: Behavior::Immediate,
+                read_only: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(response.get("success"), Some(&Value::Boolean(false)));
+        let error = response.get("error").unwrap();
+        assert_eq!(error.get("index").and_then(Value::to_i64), Some(1));
+        assert_eq!(error.get("code"), Some(&"ConstraintViolation".to_value()));
+        let count = rows(&conn, &query("SELECT * FROM users", Vec::new()))
+            .unwrap()
+            .count;
+        assert_eq!(count, 2);
+
+        let response = super::transaction(
+            &mut conn,
+            &Transaction {
+                queries: vec![query("DELETE FROM users", Vec::new())],
+                behavior: Behavior::Deferred,
+                read_only: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(response.get("success"), Some(&Value::Boolean(false)));
+        run(
+            &conn,
+            &query("DELETE FROM users WHERE name = 'bia'", Vec::new()),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use phlow_sdk::prelude::*;
+use rusqlite::TransactionBehavior;
+
+use crate::sqlite::SqliteConfig;
+
+#[derive(Debug)]
+pub struct Query {
+    pub query: String,
+    pub params: Vec<Value>,
+    pub batch: bool,
+    pub cache_query: bool,
+    /// Inside a transaction, runs the query in a savepoint so its failure is recorded
+    /// instead of rolling back the transaction
+    pub savepoint: Option<String>,
+}
+
+impl Query {
+    fn parse(value: &Value, config: &SqliteConfig) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err("Query must be an object".to_string());
+        }
+
+        let query = value
+            .get("query")
+            .map(|v| v.to_string())
+            .ok_or_else(|| "Query not found or not a string".to_string())?;
+
+        let params = match value.get("params") {
+            Some(Value::Array(array)) => array.values.clone(),
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => return Err("Params must be an array".to_string()),
+        };
+
+        let batch = *value
+            .get("batch")
+            .and_then(Value::as_bool)
+            .unwrap_or(&config.batch);
+
+        let cache_query = *value
+            .get("cache_query")
+            .and_then(Value::as_bool)
+            .unwrap_or(&config.cache_query);
+
+        Ok(Query {
+            query,
+            params,
+            batch,
+            cache_query,
+            savepoint: None,
+        })
+    }
+}
+
+/// When a transaction takes the write lock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Behavior {
+    Deferred,
+    Immediate,
+    Exclusive,
+}
+
+impl From<Behavior> for TransactionBehavior {
+    fn from(behavior: Behavior) -> Self {
+        match behavior {
+            Behavior::Deferred => TransactionBehavior::Deferred,
+            Behavior::Immediate => TransactionBehavior::Immediate,
+            Behavior::Exclusive => TransactionBehavior::Exclusive,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Transaction {
+    pub queries: Vec<Query>,
+    /// Immediate by default, so a transaction never fails halfway waiting to
+    /// upgrade a read lock
+    pub behavior: Behavior,
+    pub read_only: bool,
+}
+
+impl Transaction {
+    fn parse(value: &Value, config: &SqliteConfig) -> Result<Self, String> {
+        let items = match value.get("queries") {
+            Some(Value::Array(array)) if !array.values.is_empty() => &array.values,
+            _ => return Err("Transaction needs a non-empty queries array".to_string()),
+        };
+
+        let mut queries = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let mut query =
+                Query::parse(item, config).map_err(|e| format!("queries[{}]: {}", index, e))?;
+            query.savepoint = match item.get("savepoint") {
+                Some(Value::Boolean(true)) => Some(format!("phlow_savepoint_{}", index)),
+                Some(Value::Boolean(false)) | Some(Value::Null) | None => None,
+                Some(name) => Some(name.to_string()),
+            };
+            queries.push(query);
+        }
+
+        let read_only = *value
+            .get("read_only")
+            .and_then(Value::as_bool)
+            .unwrap_or(&false);
+
+        let behavior = match value.get("behavior").map(Value::to_string) {
+            None if read_only => Behavior::Deferred,
+            None => Behavior::Immediate,
+            Some(behavior) => match behavior.to_lowercase().as_str() {
+                "deferred" => Behavior::Deferred,
+                "immediate" => Behavior::Immediate,
+                "exclusive" => Behavior::Exclusive,
+                _ => {
+                    return Err(format!(
+                        "Invalid transaction behavior: {}. Use deferred, immediate or exclusive",
+                        behavior
+                    ));
+                }
+            },
+        };
+
+        Ok(Transaction {
+            queries,
+            behavior,
+            read_only,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Input {
+    Query(Query),
+    Transaction(Transaction),
+}
+
+impl TryFrom<(Option<Value>, &SqliteConfig)> for Input {
+    type Error = String;
+
+    fn try_from((value, config): (Option<Value>, &SqliteConfig)) -> Result<Self, Self::Error> {
+        let value = value.ok_or_else(|| "Input value is None".to_string())?;
+        if !value.is_object() {
+            return Err("Input must be an object".to_string());
+        }
+
+        match value.get("action").map(Value::to_string).as_deref() {
+            None | Some("query") => Query::parse(&value, config).map(Input::Query),
+            Some("transaction") => Transaction::parse(&value, config).map(Input::Transaction),
+            Some(action) => Err(format!(
+                "Unknown action: {}. Use query or transaction",
+                action
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SqliteConfig {
+        SqliteConfig::try_from(Value::json_to_value(r#"{ "path": ":memory:" }"#).unwrap()).unwrap()
+    }
+
+    fn parse(json: &str) -> Result<Input, String> {
+        Input::try_from((Some(Value::json_to_value(json).unwrap()), &config()))
+    }
+
+    #[test]
+    fn test_query() {
+        let input = parse(r#"{ "query": "SELECT $1, $2", "params": [1, "a"] }"#).unwrap();
+        match input {
+            Input::Query(query) => {
+                assert_eq!(query.params.len(), 2);
+                assert!(query.batch);
+                assert!(query.cache_query);
+            }
+            _ => panic!("expected a query"),
+        }
+
+        assert_eq!(
+            parse(r#"{ "query": "SELECT 1", "params": 1 }"#).unwrap_err(),
+            "Params must be an array"
+        );
+        assert_eq!(
+            parse(r#"{ "action": "cursor", "query": "SELECT 1" }"#).unwrap_err(),
+            "Unknown action: cursor. Use query or transaction"
+        );
+    }
+
+    #[test]
+    fn test_transaction() {
+        let input = parse(
+            r#"{
+                "action": "transaction",
+                "queries": [
+                    { "query": "INSERT INTO a VALUES ($1)", "params": [1] },
+                    { "query": "INSERT INTO b VALUES ($1)", "params": [2], "savepoint": true }
+                ]
+            }"#,
+        )
+        .unwrap();
+        match input {
+            Input::Transaction(transaction) => {
+                assert_eq!(transaction.queries.len(), 2);
+                assert_eq!(transaction.behavior, Behavior::Immediate);
+                assert_eq!(transaction.queries[0].savepoint, None);
+                assert_eq!(
+                    transaction.queries[1].savepoint.as_deref(),
+                    Some("phlow_savepoint_1")
+                );
+            }
+            _ => panic!("expected a transaction"),
+        }
+
+        match parse(r#"{ "action": "transaction", "read_only": true, "queries": [{ "query": "SELECT 1" }] }"#)
+            .unwrap()
+        {
+            Input::Transaction(transaction) => {
+                assert_eq!(transaction.behavior, Behavior::Deferred)
+            }
+            _ => panic!("expected a transaction"),
+        }
+
+        assert_eq!(
+            parse(r#"{ "action": "transaction", "queries": [{ "params": [] }] }"#).unwrap_err(),
+            "queries[0]: Query not found or not a string"
+        );
+        assert_eq!(
+            parse(r#"{ "action": "transaction", "behavior": "lazy", "queries": [{ "query": "SELECT 1" }] }"#)
+                .unwrap_err(),
+            "Invalid transaction behavior: lazy. Use deferred, immediate or exclusive"
+        );
+    }
+}
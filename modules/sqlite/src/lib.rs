@@ -0,0 +1,95 @@
+mod execute;
+mod input;
+mod migrations;
+mod response;
+mod sqlite;
+use std::sync::Arc;
+
+use execute::QueryError;
+use input::Input;
+use phlow_sdk::prelude::*;
+use sqlite::{SqliteConfig, SqlitePool};
+
+create_step!(sqlite(setup));
+
+pub async fn sqlite(setup: ModuleSetup) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = SqliteConfig::try_from(setup.with.clone())?;
+    let pool = Arc::new(config.create_pool().map_err(|e| {
+        let message = format!("Failed to open {}: {}", config.path, e);
+        log::error!("{}", message);
+        message
+    })?);
+
+    // Migrations run before the module registers, so the flow only starts on an
+    // up-to-date schema and a failed migration stops the runtime
+    if let Some(migrations) = &config.migrations
+        && let Err(e) = migrations::run(&pool, migrations)
+    {
+        log::error!("{}", e);
+        return Err(e.into());
+    }
+
+    let rx = module_channel!(setup);
+    log::debug!("SQLite module initialized with config: {:?}", config);
+    let mut handles = Vec::new();
+
+    for package in rx {
+        let pool = pool.clone();
+        let config = config.clone();
+
+        // SQLite calls block, so each package runs on the blocking thread pool
+        let handle = tokio::task::spawn_blocking(move || {
+            let input = match Input::try_from((package.input, &config)) {
+                Ok(input) => input,
+                Err(e) => {
+                    let response =
+                        ModuleResponse::from_error(format!("Failed to parse input: {}", e));
+
+                    sender_safe!(package.sender, response);
+                    return;
+                }
+            };
+
+            let response = handle(input, &pool);
+            sender_safe!(package.sender, response);
+        });
+
+        handles.push(handle);
+    }
+
+    for handle in handles {
+        if let Err(e) = handle.await {
+            log::error!("Error in task: {:?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle(input: Input, pool: &SqlitePool) -> ModuleResponse {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => {
+            return ModuleResponse::from_error(format!(
+                "Failed to get connection from pool: {}",
+                e
+            ));
+        }
+    };
+
+    match input {
+        Input::Query(query) => match execute::run(&conn, &query) {
+            Ok(data) if !query.batch => data.into(),
+            Err(QueryError::Database(e)) if !query.batch => {
+                ModuleResponse::from_error(format!("Batch execution failed: {}", e))
+            }
+            Ok(data) => ModuleResponse::from_success(execute::outcome(Ok(data))),
+            Err(QueryError::Database(e)) => ModuleResponse::from_success(execute::outcome(Err(e))),
+            Err(QueryError::Param(e)) => ModuleResponse::from_error(e),
+        },
+        Input::Transaction(transaction) => match execute::transaction(&mut conn, &transaction) {
+            Ok(response) => ModuleResponse::from_success(response),
+            Err(e) => ModuleResponse::from_error(format!("Transaction rolled back: {}", e)),
+        },
+    }
+}
@@ -0,0 +1,173 @@
+use phlow_sdk::migrations::{MigrationsConfig, load, pending};
+use phlow_sdk::prelude::*;
+use rusqlite::{Connection, TransactionBehavior, params};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::sqlite::SqlitePool;
+
+/// Applies the pending migrations, each in its own immediate transaction together
+/// with its history row. The history is read again inside each transaction, so
+/// concurrent instances never apply a migration twice.
+pub fn run(pool: &SqlitePool, config: &MigrationsConfig) -> Result<usize, String> {
+    let migrations = load(&config.path)?;
+    let mut conn = pool
+        .get()
+        .map_err(|e| format!("Failed to get connection from pool: {}", e))?;
+    let table = &config.table;
+
+    if !config.dry_run {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                duration_ms INTEGER NOT NULL
+            )",
+            table
+        ))
+        .map_err(|e| format!("Failed to create {}: {}", table, e))?;
+    }
+
+    let pending: Vec<i64> = pending(&migrations, &applied(&conn, table)?)?
+        .iter()
+        .map(|m| m.version)
+        .collect();
+    if pending.is_empty() {
+        log::info!("Migrations up to date");
+        return Ok(0);
+    }
+
+    if config.dry_run {
+        for migration in migrations.iter().filter(|m| pending.contains(&m.version)) {
+            log::info!(
+                "Pending migration {} ({})",
+                migration.version,
+                migration.name
+            );
+        }
+        return Ok(0);
+    }
+
+    let insert = format!(
+        "INSERT INTO {} (version, name, checksum, duration_ms) VALUES (?1, ?2, ?3, ?4)",
+        table
+    );
+
+    let mut count = 0;
+    for migration in migrations.iter().filter(|m| pending.contains(&m.version)) {
+        let started = Instant::now();
+        let failed = |e: rusqlite::Error| {
+            format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            )
+        };
+
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(failed)?;
+        if applied(&tx, table)?.contains_key(&migration.version) {
+            continue;
+        }
+        tx.execute_batch(&migration.sql).map_err(failed)?;
+        let duration = started.elapsed().as_millis() as i64;
+        tx.execute(
+            &insert,
+            params![
+                migration.version,
+                migration.name,
+                migration.checksum,
+                duration
+            ],
+        )
+        .map_err(failed)?;
+        tx.commit().map_err(failed)?;
+        count += 1;
+
+        log::info!(
+            "Applied migration {} ({}) in {}ms",
+            migration.version,
+            migration.name,
+            duration
+        );
+    }
+
+    Ok(count)
+}
+
+fn applied(conn: &Connection, table: &str) -> Result<HashMap<i64, (String, String)>, String> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut applied = HashMap::new();
+    if !exists {
+        return Ok(applied);
+    }
+
+    let read = |e: rusqlite::Error| format!("Failed to read {}: {}", table, e);
+    let mut stmt = conn
+        .prepare(&format!("SELECT version, name, checksum FROM {}", table))
+        .map_err(read)?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+        .map_err(read)?;
+    for row in rows {
+        let (version, entry) = row.map_err(read)?;
+        applied.insert(version, entry);
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("phlow_sqlite_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("migrations")).unwrap();
+        std::fs::write(
+            dir.join("migrations/0001_create_users.sql"),
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("migrations/0002_add_email.sql"),
+            "ALTER TABLE users ADD COLUMN email TEXT;",
+        )
+        .unwrap();
+
+        let with = format!(
+            r#"{{ "path": "{}", "migrations": "{}" }}"#,
+            dir.join("app.db").display(),
+            dir.join("migrations").display()
+        );
+        let config =
+            crate::sqlite::SqliteConfig::try_from(Value::json_to_value(&with).unwrap()).unwrap();
+        let pool = config.create_pool().unwrap();
+        let migrations = config.migrations.unwrap();
+
+        assert_eq!(run(&pool, &migrations), Ok(2));
+        assert_eq!(run(&pool, &migrations), Ok(0));
+
+        std::fs::write(
+            dir.join("migrations/0001_create_users.sql"),
+            "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+        assert!(
+            run(&pool, &migrations)
+                .unwrap_err()
+                .starts_with("Checksum mismatch")
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
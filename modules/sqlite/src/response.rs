@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use phlow_sdk::prelude::*;
+use rusqlite::types::ValueRef;
+
+/// Converts a column to a value from its storage class. Columns declared as JSON come
+/// back parsed, BOOLEAN as a boolean and BLOBs as base64, as with postgres.
+pub fn column_value(value: ValueRef, decltype: Option<&str>) -> Value {
+    let decltype = decltype.unwrap_or_default().to_uppercase();
+
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(v) if decltype.starts_with("BOOL") => Value::from(v != 0),
+        ValueRef::Integer(v) => Value::from(v),
+        ValueRef::Real(v) => Value::from(v),
+        ValueRef::Text(v) => {
+            let text = String::from_utf8_lossy(v);
+            if decltype.starts_with("JSON") {
+                Value::json_to_value(&text).unwrap_or_else(|_| text.to_value())
+            } else {
+                text.to_value()
+            }
+        }
+        ValueRef::Blob(v) => Value::from(BASE64.encode(v)),
+    }
+}
+
+#[derive(Debug, Clone, ToValue)]
+pub struct QueryResult {
+    pub rows: Vec<HashMap<String, Value>>,
+    pub count: usize,
+}
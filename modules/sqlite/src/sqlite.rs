@@ -0,0 +1,180 @@
+use phlow_sdk::prelude::*;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use std::time::Duration;
+
+use phlow_sdk::migrations::MigrationsConfig;
+
+pub type SqlitePool = Pool<SqliteConnectionManager>;
+
+const JOURNAL_MODES: [&str; 6] = ["delete", "truncate", "persist", "memory", "wal", "off"];
+const SYNCHRONOUS: [&str; 4] = ["off", "normal", "full", "extra"];
+
+#[derive(Clone, Debug)]
+pub struct SqliteConfig {
+    /// Database file, created when missing. `:memory:` keeps the database in memory.
+    pub path: String,
+    pub journal_mode: String,
+    pub synchronous: String,
+    /// Time a connection waits for a lock held by another one before failing
+    pub busy_timeout: Duration,
+    pub foreign_keys: bool,
+    pub batch: bool,
+    pub cache_query: bool,
+    pub max_size: u32,
+    pub migrations: Option<MigrationsConfig>,
+}
+
+impl SqliteConfig {
+    fn in_memory(&self) -> bool {
+        self.path == ":memory:"
+    }
+
+    /// PRAGMAs run on every new connection of the pool
+    fn pragmas(&self) -> String {
+        let mut pragmas = format!(
+            "PRAGMA busy_timeout = {}; PRAGMA synchronous = {}; PRAGMA foreign_keys = {};",
+            self.busy_timeout.as_millis(),
+            self.synchronous.to_uppercase(),
+            if self.foreign_keys { "ON" } else { "OFF" }
+        );
+        // An in-memory database has no journal file to configure
+        if !self.in_memory() {
+            pragmas.push_str(&format!(
+                " PRAGMA journal_mode = {};",
+                self.journal_mode.to_uppercase()
+            ));
+        }
+        pragmas
+    }
+
+    pub fn create_pool(&self) -> Result<SqlitePool, r2d2::Error> {
+        let manager = if self.in_memory() {
+            SqliteConnectionManager::memory()
+        } else {
+            SqliteConnectionManager::file(&self.path)
+        };
+        let pragmas = self.pragmas();
+        let manager = manager.with_init(move |conn| conn.execute_batch(&pragmas));
+
+        let builder = Pool::builder().min_idle(Some(1));
+        if self.in_memory() {
+            // Each connection to `:memory:` opens a database of its own, and closing the
+            // only one, idle or old, would drop every table
+            builder
+                .max_size(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .build(manager)
+        } else {
+            builder.max_size(self.max_size).build(manager)
+        }
+    }
+}
+
+impl TryFrom<Value> for SqliteConfig {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        if !value.is_object() {
+            return Err("with must be an object with the database path".to_string());
+        }
+
+        let path = value.get("path").map(Value::to_string).ok_or_else(|| {
+            "path is required. Use :memory: for an in-memory database".to_string()
+        })?;
+
+        let journal_mode = value
+            .get("journal_mode")
+            .map(|mode| mode.to_string().to_lowercase())
+            .unwrap_or_else(|| "wal".to_string());
+        if !JOURNAL_MODES.contains(&journal_mode.as_str()) {
+            return Err(format!(
+                "Invalid journal_mode: {}. Use {}",
+                journal_mode,
+                JOURNAL_MODES.join(", ")
+            ));
+        }
+
+        let synchronous = value
+            .get("synchronous")
+            .map(|mode| mode.to_string().to_lowercase())
+            .unwrap_or_else(|| "normal".to_string());
+        if !SYNCHRONOUS.contains(&synchronous.as_str()) {
+            return Err(format!(
+                "Invalid synchronous: {}. Use {}",
+                synchronous,
+                SYNCHRONOUS.join(", ")
+            ));
+        }
+
+        let busy_timeout = Duration::from_millis(
+            value
+                .get("busy_timeout")
+                .and_then(Value::to_u64)
+                .unwrap_or(5000),
+        );
+
+        let foreign_keys = *value
+            .get("foreign_keys")
+            .and_then(Value::as_bool)
+            .unwrap_or(&true);
+
+        let batch = *value.get("batch").and_then(Value::as_bool).unwrap_or(&true);
+
+        let cache_query = *value
+            .get("cache_query")
+            .and_then(Value::as_bool)
+            .unwrap_or(&true);
+
+        let max_size = value
+            .get("max_pool_size")
+            .and_then(Value::to_i64)
+            .unwrap_or(10)
+            .max(1) as u32;
+
+        let migrations = match value.get("migrations") {
+            None | Some(Value::Null) => None,
+            Some(migrations) => Some(MigrationsConfig::try_from(migrations)?),
+        };
+
+        Ok(SqliteConfig {
+            path,
+            journal_mode,
+            synchronous,
+            busy_timeout,
+            foreign_keys,
+            batch,
+            cache_query,
+            max_size,
+            migrations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_pool_keeps_its_connection() {
+        let config =
+            SqliteConfig::try_from(Value::json_to_value(r#"{ "path": ":memory:" }"#).unwrap())
+                .unwrap();
+        let pool = config.create_pool().unwrap();
+        assert_eq!(pool.max_size(), 1);
+        assert_eq!(pool.idle_timeout(), None);
+        assert_eq!(pool.max_lifetime(), None);
+
+        pool.get()
+            .unwrap()
+            .execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY)")
+            .unwrap();
+        let count: i64 = pool
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -67,6 +67,7 @@
 //! - [`engine`] - Configures and extends the scripting engine.
 //! - [`condition`] - Evaluates assert expressions for branching.
 //! - [`collector`] - Logs execution steps and tracks workflow state.
+//! - [`metrics`] - Counters and latency histograms exposed in the Prometheus format.
 //!
 //! ## Architecture Overview
 //!
@@ -130,6 +131,7 @@ pub mod condition;
 pub mod context;
 pub mod debug;
 pub mod id;
+pub mod metrics;
 pub mod phlow;
 pub mod pipeline;
 pub mod script;
@@ -0,0 +1,434 @@
+use once_cell::sync::OnceCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::time::Duration;
+
+static METRICS: OnceCell<Arc<Metrics>> = OnceCell::new();
+
+/// Latency buckets in seconds, same as the Prometheus client defaults
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Error,
+}
+
+impl Outcome {
+    pub fn from_result<T, E>(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Outcome::Success,
+            Err(_) => Outcome::Error,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Series {
+    success: u64,
+    error: u64,
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+}
+
+impl Series {
+    fn observe(&mut self, outcome: Outcome, elapsed: Duration) {
+        match outcome {
+            Outcome::Success => self.success += 1,
+            Outcome::Error => self.error += 1,
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.sum += seconds;
+        for (bucket, limit) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= limit {
+                *bucket += 1;
+            }
+        }
+    }
+
+    fn count(&self) -> u64 {
+        self.success + self.error
+    }
+}
+
+type QueueDepth = Box<dyn Fn() -> usize + Send + Sync>;
+
+/// In-process counters and latency histograms for flows, steps and modules,
+/// rendered in the Prometheus text format.
+pub struct Metrics {
+    flow: String,
+    flows: Mutex<Series>,
+    steps: Mutex<BTreeMap<(usize, String), Series>>,
+    modules: Mutex<BTreeMap<String, Series>>,
+    queues: Mutex<BTreeMap<String, QueueDepth>>,
+    main_queue: Mutex<Option<QueueDepth>>,
+    workers: AtomicUsize,
+    busy_workers: AtomicUsize,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").field("flow", &self.flow).finish()
+    }
+}
+
+/// Marks a worker as busy until dropped
+pub struct BusyWorker<'a> {
+    metrics: &'a Metrics,
+}
+
+impl Drop for BusyWorker<'_> {
+    fn drop(&mut self) {
+        self.metrics.busy_workers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new(flow: impl Into<String>) -> Self {
+        Self {
+            flow: flow.into(),
+            flows: Mutex::new(Series::default()),
+            steps: Mutex::new(BTreeMap::new()),
+            modules: Mutex::new(BTreeMap::new()),
+            queues: Mutex::new(BTreeMap::new()),
+            main_queue: Mutex::new(None),
+            workers: AtomicUsize::new(0),
+            busy_workers: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn record_flow(&self, outcome: Outcome, elapsed: Duration) {
+        if let Ok(mut flows) = self.flows.lock() {
+            flows.observe(outcome, elapsed);
+        }
+    }
+
+    pub fn record_step(&self, pipeline: usize, step: String, outcome: Outcome, elapsed: Duration) {
+        if let Ok(mut steps) = self.steps.lock() {
+            steps
+                .entry((pipeline, step))
+                .or_default()
+                .observe(outcome, elapsed);
+        }
+    }
+
+    pub fn record_module(&self, module: &str, outcome: Outcome, elapsed: Duration) {
+        if let Ok(mut modules) = self.modules.lock() {
+            modules
+                .entry(module.to_string())
+                .or_default()
+                .observe(outcome, elapsed);
+        }
+    }
+
+    /// Registers a probe read at scrape time, e.g. the length of a module channel
+    pub fn register_queue<F>(&self, name: &str, depth: F)
+    where
+        F: Fn() -> usize + Send + Sync + 'static,
+    {
+        if let Ok(mut queues) = self.queues.lock() {
+            queues.insert(name.to_string(), Box::new(depth));
+        }
+    }
+
+    /// Registers the probe for packages waiting for a free worker
+    pub fn register_main_queue<F>(&self, depth: F)
+    where
+        F: Fn() -> usize + Send + Sync + 'static,
+    {
+        if let Ok(mut main_queue) = self.main_queue.lock() {
+            *main_queue = Some(Box::new(depth));
+        }
+    }
+
+    pub fn set_workers(&self, workers: usize) {
+        self.workers.store(workers, Ordering::Relaxed);
+    }
+
+    pub fn busy_worker(&self) -> BusyWorker<'_> {
+        self.busy_workers.fetch_add(1, Ordering::Relaxed);
+        BusyWorker { metrics: self }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let flow = escape(&self.flow);
+
+        if let Ok(flows) = self.flows.lock() {
+            header(
+                &mut out,
+                "phlow_flow_executions_total",
+                "counter",
+                "Flow executions by outcome",
+            );
+            counter(
+                &mut out,
+                "phlow_flow_executions_total",
+                &format!("flow=\"{}\"", flow),
+                &flows,
+            );
+            header(
+                &mut out,
+                "phlow_flow_duration_seconds",
+                "histogram",
+                "Flow execution latency",
+            );
+            histogram(
+                &mut out,
+                "phlow_flow_duration_seconds",
+                &format!("flow=\"{}\"", flow),
+                &flows,
+            );
+        }
+
+        if let Ok(steps) = self.steps.lock() {
+            header(
+                &mut out,
+                "phlow_step_executions_total",
+                "counter",
+                "Step executions by outcome",
+            );
+            for ((pipeline, step), series) in steps.iter() {
+                let labels = format!(
+                    "flow=\"{}\",pipeline=\"{}\",step=\"{}\"",
+                    flow,
+                    pipeline,
+                    escape(step)
+                );
+                counter(&mut out, "phlow_step_executions_total", &labels, series);
+            }
+            header(
+                &mut out,
+                "phlow_step_duration_seconds",
+                "histogram",
+                "Step execution latency",
+            );
+            for ((pipeline, step), series) in steps.iter() {
+                let labels = format!(
+                    "flow=\"{}\",pipeline=\"{}\",step=\"{}\"",
+                    flow,
+                    pipeline,
+                    escape(step)
+                );
+                histogram(&mut out, "phlow_step_duration_seconds", &labels, series);
+            }
+        }
+
+        if let Ok(modules) = self.modules.lock() {
+            header(
+                &mut out,
+                "phlow_module_calls_total",
+                "counter",
+                "Module calls by outcome",
+            );
+            for (module, series) in modules.iter() {
+                let labels = format!("flow=\"{}\",module=\"{}\"", flow, escape(module));
+                counter(&mut out, "phlow_module_calls_total", &labels, series);
+            }
+            header(
+                &mut out,
+                "phlow_module_duration_seconds",
+                "histogram",
+                "Module call latency",
+            );
+            for (module, series) in modules.iter() {
+                let labels = format!("flow=\"{}\",module=\"{}\"", flow, escape(module));
+                histogram(&mut out, "phlow_module_duration_seconds", &labels, series);
+            }
+        }
+
+        if let Ok(queues) = self.queues.lock() {
+            header(
+                &mut out,
+                "phlow_module_queue_depth",
+                "gauge",
+                "Packages waiting in each module channel",
+            );
+            for (module, depth) in queues.iter() {
+                let _ = writeln!(
+                    out,
+                    "phlow_module_queue_depth{{flow=\"{}\",module=\"{}\"}} {}",
+                    flow,
+                    escape(module),
+                    depth()
+                );
+            }
+        }
+
+        if let Ok(main_queue) = self.main_queue.lock()
+            && let Some(depth) = main_queue.as_ref()
+        {
+            header(
+                &mut out,
+                "phlow_main_queue_depth",
+                "gauge",
+                "Packages from the main module waiting for a worker",
+            );
+            let _ = writeln!(
+                out,
+                "phlow_main_queue_depth{{flow=\"{}\"}} {}",
+                flow,
+                depth()
+            );
+        }
+
+        let workers = self.workers.load(Ordering::Relaxed);
+        let busy = self.busy_workers.load(Ordering::Relaxed);
+        header(
+            &mut out,
+            "phlow_workers",
+            "gauge",
+            "Package consumer workers",
+        );
+        let _ = writeln!(out, "phlow_workers{{flow=\"{}\"}} {}", flow, workers);
+        header(
+            &mut out,
+            "phlow_workers_busy",
+            "gauge",
+            "Package consumer workers executing a flow",
+        );
+        let _ = writeln!(out, "phlow_workers_busy{{flow=\"{}\"}} {}", flow, busy);
+        header(
+            &mut out,
+            "phlow_worker_utilization",
+            "gauge",
+            "Ratio of busy package consumer workers",
+        );
+        let utilization = if workers > 0 {
+            busy as f64 / workers as f64
+        } else {
+            0.0
+        };
+        let _ = writeln!(
+            out,
+            "phlow_worker_utilization{{flow=\"{}\"}} {}",
+            flow, utilization
+        );
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, labels: &str, series: &Series) {
+    let _ = writeln!(
+        out,
+        "{}{{{},status=\"success\"}} {}",
+        name, labels, series.success
+    );
+    let _ = writeln!(
+        out,
+        "{}{{{},status=\"error\"}} {}",
+        name, labels, series.error
+    );
+}
+
+fn histogram(out: &mut String, name: &str, labels: &str, series: &Series) {
+    for (limit, count) in BUCKETS.iter().zip(series.buckets) {
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            name, labels, limit, count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_bucket{{{},le=\"+Inf\"}} {}",
+        name,
+        labels,
+        series.count()
+    );
+    let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, series.sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, series.count());
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn set_metrics(metrics: Arc<Metrics>) -> Result<(), Arc<Metrics>> {
+    METRICS.set(metrics)
+}
+
+pub fn metrics() -> Option<&'static Arc<Metrics>> {
+    METRICS.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new("app");
+        metrics.record_module("http", Outcome::Success, Duration::from_millis(20));
+        metrics.record_module("http", Outcome::Error, Duration::from_secs(3));
+
+        let output = metrics.render();
+        assert!(output.contains(
+            "phlow_module_calls_total{flow=\"app\",module=\"http\",status=\"success\"} 1"
+        ));
+        assert!(
+            output.contains(
+                "phlow_module_calls_total{flow=\"app\",module=\"http\",status=\"error\"} 1"
+            )
+        );
+        assert!(output.contains(
+            "phlow_module_duration_seconds_bucket{flow=\"app\",module=\"http\",le=\"0.01\"} 0"
+        ));
+        assert!(output.contains(
+            "phlow_module_duration_seconds_bucket{flow=\"app\",module=\"http\",le=\"0.025\"} 1"
+        ));
+        assert!(output.contains(
+            "phlow_module_duration_seconds_bucket{flow=\"app\",module=\"http\",le=\"5\"} 2"
+        ));
+        assert!(
+            output.contains("phlow_module_duration_seconds_count{flow=\"app\",module=\"http\"} 2")
+        );
+    }
+
+    #[test]
+    fn test_queue_depth_and_worker_utilization() {
+        let metrics = Metrics::new("app");
+        metrics.set_workers(4);
+        metrics.register_queue("db", || 7);
+
+        let busy = metrics.busy_worker();
+        let output = metrics.render();
+        assert!(output.contains("phlow_module_queue_depth{flow=\"app\",module=\"db\"} 7"));
+        assert!(output.contains("phlow_workers_busy{flow=\"app\"} 1"));
+        assert!(output.contains("phlow_worker_utilization{flow=\"app\"} 0.25"));
+
+        drop(busy);
+        assert!(
+            metrics
+                .render()
+                .contains("phlow_workers_busy{flow=\"app\"} 0")
+        );
+    }
+
+    #[test]
+    fn test_labels_are_escaped() {
+        let metrics = Metrics::new("my \"app\"");
+        metrics.record_step(0, "step\\1".to_string(), Outcome::Success, Duration::ZERO);
+
+        let output = metrics.render();
+        assert!(output.contains(
+            "phlow_step_executions_total{flow=\"my \\\"app\\\"\",pipeline=\"0\",step=\"step\\\\1\",status=\"success\"} 1"
+        ));
+    }
+}
@@ -1,10 +1,12 @@
 use crate::{
     context::Context,
     debug::{DebugContext, DebugSnapshot, debug_controller},
+    metrics::{Outcome, metrics},
     step_worker::{NextStep, StepOutput, StepWorker, StepWorkerError},
 };
 use phlow_sdk::prelude::Value;
 use std::fmt::Display;
+use std::time::Instant;
 
 #[derive(Debug)]
 pub enum PipelineError {
@@ -58,7 +60,16 @@ impl Pipeline {
                 controller.before_step(snapshot).await;
             }
 
+            let started = Instant::now();
             let result = step.execute(&context).await;
+            if let Some(metrics) = metrics() {
+                metrics.record_step(
+                    self.id,
+                    step.metrics_name(step_index),
+                    Outcome::from_result(&result),
+                    started.elapsed(),
+                );
+            }
             if let Some(controller) = &controller {
                 controller.finish_step().await;
             }
@@ -3,6 +3,7 @@ use crate::{
     context::Context,
     debug::debug_controller,
     id::ID,
+    metrics::{Outcome, metrics},
     script::Script,
 };
 use once_cell::sync::Lazy;
@@ -12,7 +13,7 @@ use phlow_sdk::{
 };
 use rhai::Engine;
 use serde::Serialize;
-use std::{fmt::Display, sync::Arc};
+use std::{fmt::Display, sync::Arc, time::Instant};
 use uuid::Uuid;
 
 static PHLOW_TRUNCATE_SPAN_VALUE: Lazy<usize> =
@@ -258,6 +259,17 @@ impl StepWorker {
         })
     }
 
+    /// Label used for this step in metrics: its id, its label, or its position
+    pub(crate) fn metrics_name(&self, index: usize) -> String {
+        if self.id.is_some() {
+            self.id.to_string()
+        } else if let Some(label) = &self.label {
+            label.clone()
+        } else {
+            format!("#{}", index)
+        }
+    }
+
     pub fn get_id(&self) -> &ID {
         &self.id
     }
@@ -357,11 +369,20 @@ impl StepWorker {
                 context.clone()
             };
 
-            match self
+            let started = Instant::now();
+            let result = self
                 .modules
                 .execute(module, &context.get_input(), &context.get_payload())
-                .await
-            {
+                .await;
+            if let Some(metrics) = metrics() {
+                let outcome = match &result {
+                    Ok(response) if response.error.is_none() => Outcome::Success,
+                    _ => Outcome::Error,
+                };
+                metrics.record_module(module, outcome, started.elapsed());
+            }
+
+            match result {
                 Ok(response) => {
                     #[cfg(debug_assertions)]
                     log::debug!("Module response for step {}: {:?}", self.id, response);
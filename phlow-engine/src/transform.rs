@@ -48,6 +48,22 @@ pub(crate) fn value_to_pipelines(
     value_to_structs(engine, modules, &map)
 }
 
+/// Ids of every step in a raw flow, including the steps nested in `then` and `else`.
+/// These are the ids `Phlow::find_step_reference` can resolve.
+pub fn step_ids(input: &Value) -> Vec<String> {
+    let mut map = Vec::new();
+    process_raw_steps(input, &mut map);
+
+    map.iter()
+        .filter_map(|pipeline| match pipeline {
+            Value::Array(steps) => Some(steps.values.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|step| step.get("id").map(|id| id.to_string()))
+        .collect()
+}
+
 pub(crate) fn process_raw_steps(input: &Value, map: &mut Vec<Value>) -> Value {
     if let Value::Object(pipeline) = input {
         let mut new_pipeline = pipeline.clone();
@@ -481,3 +497,28 @@ fn build_parent_map(
 
     (parents, go_to_step_id)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use valu3::json;
+
+    #[test]
+    fn test_step_ids_include_nested_steps() {
+        let steps = json!({
+            "steps": [
+                {
+                    "id": "start",
+                    "assert": "{{ main.ok }}",
+                    "then": { "steps": [{ "id": "approved", "return": 1 }] },
+                    "else": [{ "id": "rejected", "return": 0 }]
+                },
+                { "return": 2 }
+            ]
+        });
+
+        let mut ids = step_ids(&steps);
+        ids.sort();
+        assert_eq!(ids, vec!["approved", "rejected", "start"]);
+    }
+}
@@ -3,6 +3,7 @@ pub mod debug_server;
 pub mod inline_module;
 pub mod loader;
 pub mod memory;
+pub mod metrics_server;
 pub mod package;
 pub mod preprocessor;
 pub mod runtime;
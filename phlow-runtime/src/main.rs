@@ -4,6 +4,7 @@ use phlow_runtime::{
     Settings,
     analyzer,
     loader::load_script_value,
+    runtime::shutdown_hooks,
     test_runner,
     Loader,
     Package,
@@ -11,6 +12,10 @@ use phlow_runtime::{
 use phlow_sdk::prelude::Value;
 use phlow_sdk::use_log;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long modules get to finish their shutdown hooks after a signal
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[cfg(all(feature = "mimalloc", target_env = "musl"))]
 #[global_allocator]
@@ -182,6 +187,10 @@ async fn main() {
                 Ok(Ok(_)) => {}
                 Ok(Err(err)) => {
                     log::error!("Runtime Error: {:?}", err);
+                    // Deploy jobs running only the migrations rely on the exit code
+                    if settings.migrate {
+                        std::process::exit(1);
+                    }
                 }
                 Err(err) => {
                     log::error!("Runtime task error: {:?}", err);
@@ -190,6 +199,7 @@ async fn main() {
         },
         _ = shutdown => {
             log::info!("Received shutdown signal. Bye bye!");
+            shutdown_hooks().run(SHUTDOWN_TIMEOUT).await;
             run_handle.abort();
             let _ = run_handle.await;
             // Sair com código 130 (Ctrl+C) para indicar interrupção pelo usuário
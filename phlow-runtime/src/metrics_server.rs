@@ -0,0 +1,215 @@
+use phlow_engine::metrics::Metrics;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head accepted from a scraper
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Time a client has to send the whole request head before its connection is closed,
+/// so idle or slow connections can not pile up on the metrics port
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn spawn(addr: String, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&addr).await?;
+    log::info!("Phlow metrics available at http://{}/metrics", addr);
+
+    tokio::spawn(async move {
+        if let Err(err) = serve(listener, metrics).await {
+            log::error!("Phlow metrics server failed: {}", err);
+        }
+    });
+
+    Ok(())
+}
+
+async fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        log::debug!("Metrics client connected: {}", addr);
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_client(stream, metrics).await {
+                log::debug!("Metrics client error: {}", err);
+            }
+        });
+    }
+}
+
+/// Reads up to the end of the request head, or `None` when the client closes the
+/// connection or sends more than `MAX_REQUEST_SIZE` first
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    while !buffer.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 || buffer.len() + read > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    Ok(Some(buffer))
+}
+
+async fn handle_client(mut stream: TcpStream, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    let buffer = match tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream)).await {
+        Ok(Ok(Some(buffer))) => buffer,
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(err)) => return Err(err),
+        Err(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "request head not received in time",
+            ));
+        }
+    };
+
+    let request = String::from_utf8_lossy(&buffer);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let mut body = metrics.render();
+            body.push_str(&process_metrics());
+            ("200 OK", "text/plain; version=0.0.4; charset=utf-8", body)
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Standard `process_*` metrics read from procfs
+#[cfg(target_os = "linux")]
+fn process_metrics() -> String {
+    let mut out = String::new();
+
+    let Ok(stat) = std::fs::read_to_string("/proc/self/stat") else {
+        return out;
+    };
+    // Fields after the command name, which is wrapped in parentheses and may contain spaces
+    let fields: Vec<&str> = match stat.rfind(')') {
+        Some(end) => stat[end + 1..].split_whitespace().collect(),
+        None => return out,
+    };
+    let field = |n: usize| -> f64 {
+        fields
+            .get(n - 3)
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or_default()
+    };
+
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as f64;
+    let boot_time = std::fs::read_to_string("/proc/stat")
+        .ok()
+        .and_then(|stat| {
+            stat.lines()
+                .find_map(|line| line.strip_prefix("btime "))
+                .and_then(|v| v.trim().parse::<f64>().ok())
+        })
+        .unwrap_or_default();
+    let open_fds = std::fs::read_dir("/proc/self/fd")
+        .map(|dir| dir.count())
+        .unwrap_or_default();
+
+    let mut gauge = |name: &str, kind: &str, help: &str, value: f64| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+
+    if ticks > 0.0 {
+        gauge(
+            "process_cpu_seconds_total",
+            "counter",
+            "Total user and system CPU time spent in seconds",
+            (field(14) + field(15)) / ticks,
+        );
+        gauge(
+            "process_start_time_seconds",
+            "gauge",
+            "Start time of the process since unix epoch in seconds",
+            boot_time + field(22) / ticks,
+        );
+    }
+    gauge(
+        "process_resident_memory_bytes",
+        "gauge",
+        "Resident memory size in bytes",
+        field(24) * page_size,
+    );
+    gauge(
+        "process_virtual_memory_bytes",
+        "gauge",
+        "Virtual memory size in bytes",
+        field(23),
+    );
+    gauge(
+        "process_threads",
+        "gauge",
+        "Number of OS threads in the process",
+        field(20),
+    );
+    gauge(
+        "process_open_fds",
+        "gauge",
+        "Number of open file descriptors",
+        open_fds as f64,
+    );
+
+    out
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_metrics() -> String {
+    String::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_idle_client_is_disconnected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(Metrics::new("metrics"))));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let started = std::time::Instant::now();
+        let mut buffer = [0u8; 16];
+        let read = stream.read(&mut buffer).await.unwrap_or_default();
+
+        assert_eq!(read, 0);
+        assert!(started.elapsed() < READ_TIMEOUT * 2);
+    }
+}
@@ -7,18 +7,21 @@ use crossbeam::channel;
 use futures::future::join_all;
 use log::{debug, error, info, warn};
 use phlow_engine::phs::{Script, ScriptError, build_engine};
+use phlow_engine::metrics::{Outcome, metrics};
+use phlow_engine::transform::step_ids;
 use phlow_engine::{Context, Phlow};
 use phlow_sdk::structs::Package;
 use phlow_sdk::tokio;
 use phlow_sdk::{
     prelude::{Array, Value},
-    structs::{ModulePackage, ModuleSetup, Modules},
+    structs::{ModulePackage, ModuleSetup, Modules, ShutdownHooks},
     tracing::{self, Dispatch, dispatcher},
 };
 use std::collections::HashSet;
 use std::fmt::Display;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
 use std::thread;
+use std::time::Instant;
 use tokio::sync::oneshot;
 
 #[derive(Debug)]
@@ -53,6 +56,13 @@ fn parse_cli_value(flag: &str, value: &str) -> Result<Value, RuntimeError> {
     }
 }
 
+static SHUTDOWN_HOOKS: LazyLock<ShutdownHooks> = LazyLock::new(ShutdownHooks::default);
+
+/// Hooks registered by the loaded modules, run before exiting on SIGINT/SIGTERM
+pub fn shutdown_hooks() -> &'static ShutdownHooks {
+    &SHUTDOWN_HOOKS
+}
+
 pub struct Runtime {}
 
 fn spawn_inline_module_worker(
@@ -103,6 +113,7 @@ impl Runtime {
         // -------------------------
         let app_data = loader.app_data.clone();
         let loader_main_id = loader.main.clone();
+        let step_ids = step_ids(&loader.get_steps());
         let mut unused_inline: HashSet<String> = inline_modules.keys().cloned().collect();
 
         for (id, module) in loader.modules.into_iter().enumerate() {
@@ -111,7 +122,7 @@ impl Runtime {
 
             let is_main = loader_main_id == id as i32;
             // Se --var-main foi especificado, não permitir que módulos principais sejam executados
-            let main_sender = if is_main && settings.var_main.is_none() {
+            let main_sender = if is_main && settings.var_main.is_none() && !settings.migrate {
                 Some(tx_main_package.clone())
             } else {
                 None
@@ -196,6 +207,9 @@ impl Runtime {
                     dispatch: dispatch.clone(),
                     app_data: app_data.clone(),
                     is_test_mode: false,
+                    step_ids: step_ids.clone(),
+                    script_arg_index: settings.script_arg_index,
+                    shutdown: shutdown_hooks().clone(),
                 };
 
                 let module_target = module_data.module.clone();
@@ -221,6 +235,10 @@ impl Runtime {
             match setup_receive.await {
                 Ok(Some(sender)) => {
                     debug!("Module {} registered", module_data.name);
+                    if let Some(metrics) = metrics() {
+                        let queue = sender.clone();
+                        metrics.register_queue(&module_data.name, move || queue.len());
+                    }
                     modules.register(module_data, sender);
                 }
                 Ok(None) => {
@@ -299,6 +317,12 @@ impl Runtime {
         let mut handles = Vec::new();
         let default_context = default_context.clone();
 
+        if let Some(metrics) = metrics() {
+            metrics.set_workers(settings.package_consumer_count.max(0) as usize);
+            let queue = rx_main_package.clone();
+            metrics.register_main_queue(move || queue.len());
+        }
+
         for _i in 0..settings.package_consumer_count {
             let rx_main_pkg = rx_main_package.clone();
             let phlow = phlow.clone();
@@ -332,7 +356,18 @@ impl Runtime {
                             Context::from_main(data)
                         }
                     };
-                    let start_step = start_step.clone();
+                    let start_step = match main_package.start_step.as_deref() {
+                        Some(step_id) => match phlow.find_step_reference(step_id) {
+                            Some(step_ref) => Some(step_ref),
+                            None => {
+                                // Dropping the package without a response reports a failure,
+                                // as a flow error does
+                                error!("Step id '{}' requested by main module not found", step_id);
+                                continue;
+                            }
+                        },
+                        None => start_step.clone(),
+                    };
 
                     tokio::task::block_in_place(move || {
                         dispatcher::with_default(&dispatch, || {
@@ -340,11 +375,19 @@ impl Runtime {
                             let rt = tokio::runtime::Handle::current();
 
                             rt.block_on(async {
+                                let _busy = metrics().map(|metrics| metrics.busy_worker());
+                                let started = Instant::now();
                                 let result = if let Some(step_ref) = start_step.clone() {
                                     phlow.execute_from(&mut context, step_ref).await
                                 } else {
                                     phlow.execute(&mut context).await
                                 };
+                                if let Some(metrics) = metrics() {
+                                    metrics.record_flow(
+                                        Outcome::from_result(&result),
+                                        started.elapsed(),
+                                    );
+                                }
                                 match result {
                                     Ok(result) => {
                                         let result_value = result.unwrap_or(Value::Undefined);
@@ -400,6 +443,11 @@ impl Runtime {
         )
         .await?;
 
+        if settings.migrate {
+            info!("Modules started with --migrate, exiting without running the flow");
+            return Ok(());
+        }
+
         // Se não há main definido ou --var-main foi especificado, forçar o início dos steps
         if no_main {
             // Criar um span padrão para o início dos steps
@@ -424,6 +472,7 @@ impl Runtime {
                 origin: 0,
                 span: Some(span),
                 dispatch: Some(dispatch.clone()),
+                start_step: None,
             };
 
             if let Err(err) = tx_main_package.send(package) {
@@ -516,6 +565,11 @@ impl Runtime {
 
         drop(tx_main_package);
 
+        if settings.migrate {
+            info!("Modules started with --migrate, exiting without running the flow");
+            return Ok(());
+        }
+
         Self::listener(rx_main_package, steps, modules, settings, Some(context))
             .await
             .map_err(|err| {
@@ -126,6 +126,7 @@
 //! # });
 //! ```
 use crate::debug_server;
+use crate::metrics_server;
 use crate::inline_module::{InlineModules, PhlowModule};
 use crate::loader::Loader;
 use crate::loader::error::Error as LoaderError;
@@ -443,12 +444,35 @@ impl PhlowRuntime {
             }
         }
 
+        if let Some(port) = self.settings.metrics_port
+            && phlow_engine::metrics::metrics().is_none()
+        {
+            let flow = loader
+                .app_data
+                .name
+                .clone()
+                .unwrap_or_else(|| "phlow".to_string());
+            let metrics = Arc::new(phlow_engine::metrics::Metrics::new(flow));
+            let addr = format!("{}:{}", self.settings.metrics_host, port);
+            match metrics_server::spawn(addr, metrics.clone()).await {
+                Ok(()) => {
+                    if phlow_engine::metrics::set_metrics(metrics).is_err() {
+                        log::warn!("Metrics registry already set");
+                    }
+                }
+                Err(err) => {
+                    log::error!("Failed to start metrics server: {}", err);
+                }
+            }
+        }
+
         let context = self.context.clone().unwrap_or_else(Context::new);
         let request_data = context.get_main();
         let context_for_runtime = context.clone();
-        let auto_start = self.settings.var_main.is_some()
-            || loader.main == -1
-            || context.get_main().is_some();
+        let auto_start = !self.settings.migrate
+            && (self.settings.var_main.is_some()
+                || loader.main == -1
+                || context.get_main().is_some());
 
         let app_name = loader
             .app_data
@@ -534,6 +558,7 @@ impl PhlowRuntime {
                 origin: 0,
                 span: Some(span),
                 dispatch: Some(dispatch.clone()),
+                start_step: None,
             }
         });
 
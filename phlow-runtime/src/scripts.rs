@@ -80,6 +80,7 @@ pub fn run_script(path: &str, setup: ModuleSetup, settings: &Settings) {
                         origin: 0,
                         span: Some(span),
                         dispatch: Some(dispatch.clone()),
+                        start_step: None,
                     };
 
                     debug!("Sending package to main loop: {:?}", runtime_package);
@@ -16,6 +16,8 @@ pub enum PrintOutput {
 #[derive(Debug)]
 pub struct Cli {
     pub main_target: Option<String>,
+    /// Position of `main_target` in the process arguments
+    pub main_target_index: Option<usize>,
     pub only_download_modules: bool,
     pub package_path: Option<String>,
     pub package_target: String,
@@ -25,6 +27,7 @@ pub struct Cli {
     pub print_yaml: bool,
     pub print_output: PrintOutput,
     pub test: bool,
+    pub migrate: bool,
     pub test_filter: Option<String>,
     pub var_main: Option<String>,
     pub var_payload: Option<String>,
@@ -123,6 +126,14 @@ impl Cli {
                     .action(ArgAction::SetTrue)
                     .default_value("false"),
             )
+            .arg(
+                Arg::new("migrate")
+                    .long("migrate")
+                    .help("Start the modules, applying database migrations, and exit without running the flow")
+                    .value_parser(clap::builder::BoolishValueParser::new())
+                    .action(ArgAction::SetTrue)
+                    .default_value("false"),
+            )
             .arg(
                 Arg::new("test_filter")
                     .long("test-filter")
@@ -223,6 +234,12 @@ impl Cli {
             Some(target) => Some(target.clone()),
             None => None,
         };
+        let main_target_index = main.as_ref().and_then(|main| {
+            env::args()
+                .skip(1)
+                .position(|arg| &arg == main)
+                .map(|index| index + 1)
+        });
 
         let install = *matches.get_one::<bool>("install").unwrap_or(&false);
         let package_path = matches.get_one::<String>("package").map(|s| s.to_string());
@@ -246,6 +263,7 @@ impl Cli {
             .unwrap_or(PrintOutput::Yaml);
 
         let test = *matches.get_one::<bool>("test").unwrap_or(&false);
+        let migrate = *matches.get_one::<bool>("migrate").unwrap_or(&false);
 
         let test_filter = matches
             .get_one::<String>("test_filter")
@@ -270,6 +288,7 @@ impl Cli {
         let analyzer_all = *matches.get_one::<bool>("all").unwrap_or(&false);
         Ok(Cli {
             main_target: main,
+            main_target_index,
             only_download_modules: install,
             package_path,
             package_target,
@@ -279,6 +298,7 @@ impl Cli {
             print_yaml,
             print_output,
             test,
+            migrate,
             test_filter,
             var_main,
             var_payload,
@@ -55,6 +55,22 @@ pub struct Envs {
      * Default: None
      */
     pub main: String,
+    /**
+     * Prometheus metrics port
+     *
+     * When set, the runtime serves Prometheus metrics on this port at `/metrics`.
+     * Environment variable: PHLOW_METRICS_PORT
+     * Default: None (disabled)
+     */
+    pub metrics_port: Option<u16>,
+    /**
+     * Prometheus metrics host
+     *
+     * This is the address the metrics endpoint binds to.
+     * Environment variable: PHLOW_METRICS_HOST
+     * Default: 0.0.0.0
+     */
+    pub metrics_host: String,
 }
 
 impl Envs {
@@ -88,6 +104,11 @@ impl Envs {
 
         let main = env::var("PHLOW_MAIN").unwrap_or(".".to_string());
 
+        let metrics_port = env::var("PHLOW_METRICS_PORT")
+            .ok()
+            .and_then(|v| v.parse::<u16>().ok());
+        let metrics_host = env::var("PHLOW_METRICS_HOST").unwrap_or("0.0.0.0".to_string());
+
         debug!("PHLOW_PACKAGE_CONSUMERS_COUNT = {}", package_consumer_count);
         #[cfg(target_env = "gnu")]
         debug!("PHLOW_MIN_ALLOCATED_MEMORY_MB = {}", min_allocated_memory);
@@ -102,6 +123,8 @@ impl Envs {
             "PHLOW_DEFAULT_PACKAGE_REPOSITORY_URL = {}",
             default_package_repository_url
         );
+        debug!("PHLOW_METRICS_PORT = {:?}", metrics_port);
+        debug!("PHLOW_METRICS_HOST = {}", metrics_host);
 
         Self {
             package_consumer_count,
@@ -113,6 +136,8 @@ impl Envs {
             garbage_collection_interval,
             default_package_repository_url,
             main,
+            metrics_port,
+            metrics_host,
         }
     }
 }
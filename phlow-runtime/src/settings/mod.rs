@@ -8,6 +8,8 @@ pub use cli::PrintOutput;
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub script_main_absolute_path: String,
+    /// Position of the flow file in the process arguments, when it was given there
+    pub script_arg_index: Option<usize>,
     pub only_download_modules: bool,
     pub package_path: Option<String>,
     pub package_target: String,
@@ -17,6 +19,8 @@ pub struct Settings {
     pub print_yaml: bool,
     pub print_output: PrintOutput,
     pub test: bool,
+    /// Only start the modules, so they apply their migrations, and exit
+    pub migrate: bool,
     pub test_filter: Option<String>,
     pub var_main: Option<String>,
     pub var_payload: Option<String>,
@@ -40,6 +44,8 @@ pub struct Settings {
     #[cfg(target_env = "gnu")]
     pub garbage_collection_interval: u64,
     pub default_package_repository_url: String,
+    pub metrics_port: Option<u16>,
+    pub metrics_host: String,
 }
 
 impl Settings {
@@ -62,6 +68,7 @@ impl Settings {
 
         let settings = Self {
             script_main_absolute_path,
+            script_arg_index: cli.main_target_index,
             only_download_modules: cli.only_download_modules,
             package_path: cli.package_path,
             package_target: cli.package_target,
@@ -83,10 +90,13 @@ impl Settings {
             #[cfg(target_env = "gnu")]
             garbage_collection_interval: envs.garbage_collection_interval,
             default_package_repository_url: envs.default_package_repository_url,
+            metrics_port: envs.metrics_port,
+            metrics_host: envs.metrics_host,
             download: cli.download,
             print_yaml: cli.print_yaml,
             print_output: cli.print_output,
             test: cli.test,
+            migrate: cli.migrate,
             test_filter: cli.test_filter,
             var_main: cli.var_main,
             var_payload: cli.var_payload,
@@ -113,6 +123,7 @@ impl Settings {
 
         Self {
             script_main_absolute_path,
+            script_arg_index: None,
             only_download_modules: false,
             package_path: None,
             package_target: "./phlow_packages".to_string(),
@@ -122,6 +133,7 @@ impl Settings {
             print_yaml: false,
             print_output: PrintOutput::Yaml,
             test: false,
+            migrate: false,
             test_filter: None,
             var_main: None,
             var_payload: None,
@@ -142,6 +154,8 @@ impl Settings {
             #[cfg(target_env = "gnu")]
             garbage_collection_interval: envs.garbage_collection_interval,
             default_package_repository_url: envs.default_package_repository_url,
+            metrics_port: envs.metrics_port,
+            metrics_host: envs.metrics_host,
         }
     }
 }
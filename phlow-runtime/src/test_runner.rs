@@ -4,10 +4,11 @@ use crossbeam::channel;
 use log::{debug, error};
 use phlow_engine::phs::{self, build_engine};
 use phlow_engine::script::Script;
+use phlow_engine::transform::step_ids;
 use phlow_engine::{Context, Phlow};
 use phlow_sdk::otel::init_tracing_subscriber;
 use phlow_sdk::prelude::json;
-use phlow_sdk::structs::{ModulePackage, ModuleSetup, Modules};
+use phlow_sdk::structs::{ModulePackage, ModuleSetup, Modules, ShutdownHooks};
 use phlow_sdk::valu3::prelude::*;
 use phlow_sdk::valu3::value::Value;
 use std::collections::HashMap;
@@ -764,6 +765,7 @@ async fn load_modules_like_runtime(
     let dispatch = guard.dispatch.clone();
 
     let engine = build_engine(None);
+    let step_ids = step_ids(&loader.get_steps());
 
     // Load modules exactly like Runtime::run does
     for (id, module) in loader.modules.iter().enumerate() {
@@ -793,6 +795,10 @@ async fn load_modules_like_runtime(
             dispatch: dispatch.clone(),
             app_data: loader.app_data.clone(),
             is_test_mode: true,
+            step_ids: step_ids.clone(),
+            script_arg_index: settings.script_arg_index,
+            // Tests never stop on a signal
+            shutdown: ShutdownHooks::default(),
         };
 
         let module_target = module.module.clone();
@@ -0,0 +1,50 @@
+use crate::prelude::*;
+
+/// Header with the number of times a message has been retried
+pub const RETRY_COUNT_HEADER: &str = "x-retry-count";
+/// Header with the error of the last failed attempt of a message
+pub const LAST_ERROR_HEADER: &str = "x-last-error";
+
+/// Reads the outcome of the flow for a consumed message: `false`,
+/// `{ ack: false, error }` or a failed flow fail it, anything else acknowledges it
+pub fn outcome(response: Option<&Value>) -> Result<(), String> {
+    match response {
+        None => Err("Flow failed without a response".to_string()),
+        Some(Value::Boolean(false)) => Err("Flow returned false".to_string()),
+        Some(response @ Value::Object(_))
+            if response.get("ack") == Some(&Value::Boolean(false)) =>
+        {
+            Err(response
+                .get("error")
+                .map(|error| error.to_string())
+                .unwrap_or_else(|| "Flow returned ack false".to_string()))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome() {
+        assert!(outcome(Some(&Value::Null)).is_ok());
+        assert!(outcome(Some(&Value::Boolean(true))).is_ok());
+        assert_eq!(
+            outcome(Some(&Value::Boolean(false))).unwrap_err(),
+            "Flow returned false"
+        );
+        assert_eq!(outcome(None).unwrap_err(), "Flow failed without a response");
+
+        let failed = Value::json_to_value(r#"{ "ack": false, "error": "timeout" }"#).unwrap();
+        assert_eq!(outcome(Some(&failed)).unwrap_err(), "timeout");
+        let done = Value::json_to_value(r#"{ "ack": true, "error": "ignored" }"#).unwrap();
+        assert!(outcome(Some(&done)).is_ok());
+        let no_error = Value::json_to_value(r#"{ "ack": false }"#).unwrap();
+        assert_eq!(
+            outcome(Some(&no_error)).unwrap_err(),
+            "Flow returned ack false"
+        );
+    }
+}
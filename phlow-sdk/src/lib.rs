@@ -1,10 +1,13 @@
 pub mod context;
 pub mod count;
+pub mod delivery;
 pub mod ext;
 pub mod id;
 pub mod macros;
+pub mod migrations;
 pub mod otel;
 pub mod prelude;
+pub mod propagation;
 pub mod structs;
 pub mod timer;
 
@@ -49,6 +49,7 @@ macro_rules! sender_package {
             origin: $id,
             span: None,
             dispatch: None,
+            start_step: None,
         };
 
         sender_safe!($sender, package);
@@ -64,6 +65,24 @@ macro_rules! sender_package {
             origin: $id,
             span: Some($span),
             dispatch: Some($dispatch),
+            start_step: None,
+        };
+
+        sender_safe!($sender, package);
+
+        rx
+    }};
+    // Starts the flow at the step with id `$start_step` instead of the first step
+    ($span:expr, $dispatch:expr, $id:expr, $sender:expr, $data:expr, $start_step:expr) => {{
+        let (tx, rx) = $crate::tokio::sync::oneshot::channel::<$crate::valu3::value::Value>();
+
+        let package = $crate::structs::Package {
+            response: Some(tx),
+            request_data: $data,
+            origin: $id,
+            span: Some($span),
+            dispatch: Some($dispatch),
+            start_step: $start_step,
         };
 
         sender_safe!($sender, package);
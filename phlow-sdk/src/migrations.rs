@@ -0,0 +1,222 @@
+use crate::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Plain or schema-qualified name, safe to interpolate into the history queries
+fn is_table_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// `migrations` setting of the SQL modules: a directory, or `{ path, table, dry_run }`
+#[derive(Clone, Debug)]
+pub struct MigrationsConfig {
+    /// Directory with the `<version>_<name>.sql` files
+    pub path: String,
+    /// History table of the applied migrations
+    pub table: String,
+    /// Only logs the pending migrations
+    pub dry_run: bool,
+}
+
+impl TryFrom<&Value> for MigrationsConfig {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let (path, table, dry_run) = match value {
+            Value::Object(_) => (
+                value
+                    .get("path")
+                    .map(Value::to_string)
+                    .ok_or_else(|| "migrations.path is required".to_string())?,
+                value.get("table").map(Value::to_string),
+                *value
+                    .get("dry_run")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(&false),
+            ),
+            value => (value.to_string(), None, false),
+        };
+
+        let table = table.unwrap_or_else(|| "phlow_migrations".to_string());
+        if !is_table_name(&table) {
+            return Err(format!("Invalid migrations table: {}", table));
+        }
+
+        Ok(MigrationsConfig {
+            path,
+            table,
+            dry_run,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub sql: String,
+    pub checksum: String,
+}
+
+/// Version and name of a file named `<version>_<name>.sql`. A `V` prefix and a double
+/// underscore, as in `V2__add_email.sql`, are accepted too.
+fn parse_file_name(file_name: &str) -> Option<(i64, String)> {
+    let stem = file_name.strip_suffix(".sql")?;
+    let stem = stem.strip_prefix(['V', 'v']).unwrap_or(stem);
+    let (version, name) = stem.split_once('_')?;
+    let version = version.parse::<i64>().ok()?;
+    let name = name.trim_start_matches('_');
+
+    if name.is_empty() {
+        return None;
+    }
+    Some((version, name.to_string()))
+}
+
+/// Hex SHA-256 of the file contents, stored with each applied migration
+pub fn checksum(sql: &str) -> String {
+    hex::encode(Sha256::digest(sql.as_bytes()))
+}
+
+/// Migrations of the directory, sorted by version
+pub fn load(path: &str) -> Result<Vec<Migration>, String> {
+    let entries = std::fs::read_dir(Path::new(path))
+        .map_err(|e| format!("Failed to read migrations directory {}: {}", path, e))?;
+
+    let mut migrations: Vec<Migration> = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !path.is_file() || !file_name.ends_with(".sql") {
+            continue;
+        }
+
+        let (version, name) = parse_file_name(&file_name).ok_or_else(|| {
+            format!(
+                "Invalid migration file name: {}. Use <version>_<name>.sql, e.g. 0001_create_users.sql",
+                file_name
+            )
+        })?;
+
+        if let Some(other) = migrations.iter().find(|m| m.version == version) {
+            return Err(format!(
+                "Duplicate migration version {}: {} and {}",
+                version, other.name, name
+            ));
+        }
+
+        let sql = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read migration {}: {}", file_name, e))?;
+
+        migrations.push(Migration {
+            version,
+            name,
+            checksum: checksum(&sql),
+            sql,
+        });
+    }
+
+    migrations.sort_by_key(|m| m.version);
+    Ok(migrations)
+}
+
+/// Migrations still to apply. Fails when an applied migration changed, or when a new
+/// one has a version older than the latest applied.
+pub fn pending<'a>(
+    migrations: &'a [Migration],
+    applied: &HashMap<i64, (String, String)>,
+) -> Result<Vec<&'a Migration>, String> {
+    let latest = applied.keys().max().copied();
+
+    for (version, (name, _)) in applied {
+        if !migrations.iter().any(|m| m.version == *version) {
+            log::warn!("Applied migration {} ({}) has no file", version, name);
+        }
+    }
+
+    let mut pending = Vec::new();
+    for migration in migrations {
+        match applied.get(&migration.version) {
+            Some((_, checksum)) if *checksum != migration.checksum => {
+                return Err(format!(
+                    "Checksum mismatch for migration {} ({}): the file changed after it was applied",
+                    migration.version, migration.name
+                ));
+            }
+            Some(_) => {}
+            None => match latest {
+                Some(latest) if migration.version < latest => {
+                    return Err(format!(
+                        "Migration {} ({}) is older than the latest applied migration {}",
+                        migration.version, migration.name, latest
+                    ));
+                }
+                _ => pending.push(migration),
+            },
+        }
+    }
+
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(version: i64, sql: &str) -> Migration {
+        Migration {
+            version,
+            name: format!("m{}", version),
+            sql: sql.to_string(),
+            checksum: checksum(sql),
+        }
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name("0001_create_users.sql"),
+            Some((1, "create_users".to_string()))
+        );
+        assert_eq!(
+            parse_file_name("V2__add_email.sql"),
+            Some((2, "add_email".to_string()))
+        );
+        assert_eq!(parse_file_name("create_users.sql"), None);
+        assert_eq!(parse_file_name("0003_.sql"), None);
+    }
+
+    #[test]
+    fn test_pending() {
+        let migrations = vec![migration(1, "A"), migration(2, "B"), migration(3, "C")];
+
+        let mut applied = HashMap::new();
+        applied.insert(1, ("m1".to_string(), checksum("A")));
+        let versions: Vec<i64> = pending(&migrations, &applied)
+            .unwrap()
+            .iter()
+            .map(|m| m.version)
+            .collect();
+        assert_eq!(versions, vec![2, 3]);
+
+        applied.insert(2, ("m2".to_string(), checksum("changed")));
+        assert_eq!(
+            pending(&migrations, &applied).unwrap_err(),
+            "Checksum mismatch for migration 2 (m2): the file changed after it was applied"
+        );
+
+        let mut applied = HashMap::new();
+        applied.insert(3, ("m3".to_string(), checksum("C")));
+        assert_eq!(
+            pending(&migrations, &applied).unwrap_err(),
+            "Migration 1 (m1) is older than the latest applied migration 3"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! W3C Trace Context (`traceparent` / `tracestate`) propagation between phlow services.
+//!
+//! Modules are loaded as separate libraries, so the OpenTelemetry global propagator is not
+//! shared with them; these helpers use a `TraceContextPropagator` directly instead.
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use std::collections::HashMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Writes the trace context of `span` into `carrier`.
+/// Nothing is written when the span is not recorded by OpenTelemetry.
+pub fn inject<I: Injector>(span: &tracing::Span, carrier: &mut I) {
+    TraceContextPropagator::new().inject_context(&span.context(), carrier);
+}
+
+/// Returns the trace context of `span` as lowercase header names and values.
+pub fn trace_headers(span: &tracing::Span) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    inject(span, &mut headers);
+    headers
+}
+
+/// Makes `span` a child of the remote trace found in `carrier`, if any.
+/// Must be called before `span` is entered for the first time.
+pub fn extract<E: Extractor>(span: &tracing::Span, carrier: &E) {
+    let context = TraceContextPropagator::new().extract(carrier);
+    if !opentelemetry::trace::TraceContextExt::span(&context)
+        .span_context()
+        .is_valid()
+    {
+        return;
+    }
+
+    if let Err(err) = span.set_parent(context) {
+        log::debug!("Unable to set remote trace parent: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn with_otel<F: FnOnce()>(f: F) {
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    #[test]
+    fn test_extract_and_inject_round_trip() {
+        with_otel(|| {
+            let carrier = HashMap::from([
+                ("traceparent".to_string(), TRACEPARENT.to_string()),
+                ("tracestate".to_string(), "vendor=value".to_string()),
+            ]);
+
+            let span = tracing::info_span!("server");
+            extract(&span, &carrier);
+            let headers = trace_headers(&span);
+
+            // Same trace, with this span as the new parent
+            let traceparent = headers.get("traceparent").unwrap();
+            let parts: Vec<&str> = traceparent.split('-').collect();
+            assert_eq!(parts[0], "00");
+            assert_eq!(parts[1], TRACE_ID);
+            assert_ne!(parts[2], "00f067aa0ba902b7");
+            assert_eq!(parts[3], "01");
+            assert_eq!(
+                headers.get("tracestate").map(String::as_str),
+                Some("vendor=value")
+            );
+
+            // A child of the span carries the trace on to the next hop
+            let child = span.in_scope(|| tracing::info_span!("client"));
+            let child_headers = trace_headers(&child);
+            assert!(child_headers["traceparent"].contains(TRACE_ID));
+            assert_ne!(child_headers["traceparent"], *traceparent);
+        });
+    }
+
+    #[test]
+    fn test_invalid_carrier_starts_a_new_trace() {
+        with_otel(|| {
+            let carrier = HashMap::from([("traceparent".to_string(), "garbage".to_string())]);
+
+            let span = tracing::info_span!("server");
+            extract(&span, &carrier);
+            let headers = trace_headers(&span);
+
+            assert!(headers.contains_key("traceparent"));
+            assert!(!headers["traceparent"].contains(TRACE_ID));
+        });
+    }
+
+    #[test]
+    fn test_nothing_injected_without_opentelemetry() {
+        let span = tracing::info_span!("server");
+        assert!(trace_headers(&span).is_empty());
+    }
+}
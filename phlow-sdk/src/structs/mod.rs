@@ -1,7 +1,9 @@
 pub mod modules;
+pub mod shutdown;
 use crate::sender_safe;
 use crossbeam::channel::{self, Receiver};
 pub use modules::*;
+pub use shutdown::*;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use tokio::sync::oneshot;
@@ -33,12 +35,23 @@ pub struct ModuleSetup {
     pub dispatch: tracing::Dispatch,
     pub app_data: ApplicationData,
     pub is_test_mode: bool,
+    /// Ids of the steps in the flow, so modules can validate a `start_step` at startup
+    pub step_ids: Vec<String>,
+    /// Position of the flow file in `std::env::args()` when it was given on the
+    /// command line, so modules can tell it apart from their own arguments
+    pub script_arg_index: Option<usize>,
+    /// Hooks the runtime runs before exiting on SIGINT/SIGTERM
+    pub shutdown: ShutdownHooks,
 }
 
 impl ModuleSetup {
     pub fn is_main(&self) -> bool {
         self.main_sender.is_some() || self.is_test_mode
     }
+
+    pub fn has_step(&self, id: &str) -> bool {
+        self.step_ids.iter().any(|step_id| step_id == id)
+    }
 }
 
 #[derive(Default)]
@@ -48,6 +61,8 @@ pub struct Package {
     pub origin: ModuleId,
     pub span: Option<tracing::Span>,
     pub dispatch: Option<tracing::Dispatch>,
+    /// Id of the step where the flow starts for this package, instead of the first one
+    pub start_step: Option<String>,
 }
 
 // Only production mode
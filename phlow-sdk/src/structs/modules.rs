@@ -217,6 +217,8 @@ pub struct ModulePackage {
     pub input: Option<Value>,
     pub payload: Option<Value>,
     pub sender: oneshot::Sender<ModuleResponse>,
+    /// Span active in the engine when the step was executed, used to propagate the trace
+    pub span: Option<tracing::Span>,
 }
 
 impl ModulePackage {
@@ -227,6 +229,14 @@ impl ModulePackage {
     pub fn payload(&self) -> Option<Value> {
         self.payload.clone()
     }
+
+    /// W3C trace context headers for outbound calls made on behalf of this step
+    pub fn trace_headers(&self) -> HashMap<String, String> {
+        match &self.span {
+            Some(span) => crate::propagation::trace_headers(span),
+            None => HashMap::new(),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -250,6 +260,7 @@ impl Module {
             input,
             payload,
             sender: package_sender,
+            span: Some(tracing::Span::current()),
         };
 
         let _ = self.sender.send(package);
@@ -0,0 +1,91 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Sender a module resolves, or drops, once its shutdown work is done
+pub type ShutdownDone = oneshot::Sender<()>;
+
+/// Work modules run before the runtime exits on a signal, like saving state to disk.
+///
+/// The runtime stops with `process::exit` on SIGINT/SIGTERM, so code after a
+/// module's package loop never runs then. A module that registers a hook gets a
+/// `ShutdownDone` when the signal arrives, and the runtime waits for it before
+/// exiting.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHooks {
+    hooks: Arc<Mutex<Vec<oneshot::Sender<ShutdownDone>>>>,
+}
+
+impl ShutdownHooks {
+    /// Resolves with a `ShutdownDone` when the runtime is stopping on a signal
+    pub fn register(&self) -> oneshot::Receiver<ShutdownDone> {
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut hooks) = self.hooks.lock() {
+            hooks.push(tx);
+        }
+        rx
+    }
+
+    /// Notifies every registered hook and waits until they are done, up to `timeout`
+    pub async fn run(&self, timeout: Duration) {
+        let hooks = match self.hooks.lock() {
+            Ok(mut hooks) => std::mem::take(&mut *hooks),
+            Err(_) => return,
+        };
+
+        let pending: Vec<_> = hooks
+            .into_iter()
+            .filter_map(|hook| {
+                let (done, wait) = oneshot::channel();
+                // A module that already stopped dropped its receiver
+                hook.send(done).ok().map(|_| wait)
+            })
+            .collect();
+
+        let wait_all = async {
+            for wait in pending {
+                let _ = wait.await;
+            }
+        };
+
+        if tokio::time::timeout(timeout, wait_all).await.is_err() {
+            log::warn!("Shutdown hooks did not finish within {:?}", timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_waits_for_hooks() {
+        let hooks = ShutdownHooks::default();
+        let hook = hooks.register();
+        let (saved_tx, saved_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let done = hook.await.unwrap();
+            saved_tx.send(()).unwrap();
+            let _ = done.send(());
+        });
+
+        hooks.run(Duration::from_secs(5)).await;
+        assert!(saved_rx.await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_gives_up_after_timeout() {
+        let hooks = ShutdownHooks::default();
+        let hook = hooks.register();
+
+        // Holds the done sender without ever resolving it
+        let stuck = tokio::spawn(async move {
+            let _done = hook.await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        hooks.run(Duration::from_millis(50)).await;
+        stuck.abort();
+    }
+}